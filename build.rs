@@ -1,7 +1,74 @@
 extern crate embed_resource;
-use std::{env, fs, path::Path};
+use std::{
+    env, fs,
+    path::Path,
+    process::Command,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// dependencies whose versions are shown on the about tab
+const KEY_DEPENDENCIES: [&str; 4] = ["native-windows-gui", "plotters", "packet", "nom"];
 
 fn main() {
     let dir = fs::canonicalize(env::var("CARGO_MANIFEST_DIR").unwrap()).unwrap();
     embed_resource::compile(Path::new(&dir).join("res").join("resources.rc"));
+
+    println!(
+        "cargo:rustc-env=BUILD_GIT_HASH={}",
+        git_hash().unwrap_or_else(|| "unknown".to_string())
+    );
+    println!("cargo:rustc-env=BUILD_DATE={}", build_date());
+    println!(
+        "cargo:rustc-env=BUILD_DEPENDENCIES={}",
+        dependency_versions(&dir)
+    );
+}
+
+fn git_hash() -> Option<String> {
+    let output = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8(output.stdout).ok()?.trim().to_string())
+}
+
+/// utc date of the build as `%Y-%m-%d`, without pulling chrono into the build dependencies
+fn build_date() -> String {
+    let days = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs() / 86400) as i64;
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// `name version` of the `KEY_DEPENDENCIES` locked in `Cargo.lock`
+fn dependency_versions(dir: &Path) -> String {
+    let lock = fs::read_to_string(dir.join("Cargo.lock")).unwrap_or_default();
+    let mut versions = Vec::new();
+    let mut name = None;
+    for line in lock.lines() {
+        if let Some(value) = line.strip_prefix("name = ") {
+            name = Some(value.trim_matches('"'));
+        } else if let Some(value) = line.strip_prefix("version = ") {
+            match name.take() {
+                Some(name) if KEY_DEPENDENCIES.contains(&name) => {
+                    versions.push(format!("{} {}", name, value.trim_matches('"')))
+                }
+                _ => {}
+            }
+        }
+    }
+    versions.join(", ")
 }
@@ -0,0 +1,235 @@
+use anyhow::Result;
+
+use crate::{
+    capture::CaptureMetrics,
+    socket::Capturer,
+    tr,
+    utils::{
+        display_width, format_byte_rate, format_bytes, interrupted, ip_packet_counts, pad_end,
+        process_cpu_time,
+    },
+};
+
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+/// how long a run lasts without --duration
+pub const DEFAULT_DURATION: Duration = Duration::from_secs(10);
+/// how often a polling run reads, as a reader driven by a timer would
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+/// how long a run waiting on socket events waits at most, so that Ctrl+C is noticed
+const WAIT_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// what a run of the capture loop kept up with
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BenchReport {
+    pub interface: String,
+    /// one read per `POLL_INTERVAL` on a non-blocking socket, otherwise reads as soon as the
+    /// socket signals it has something
+    pub polling: bool,
+    pub elapsed: Duration,
+    /// calls to read, counting the ones that came back empty
+    pub reads: u64,
+    pub packet_num: u64,
+    pub byte_num: u64,
+    /// the receive buffer of the socket, in bytes
+    pub recv_buffer: usize,
+    /// the counters the capturer kept meanwhile
+    pub metrics: CaptureMetrics,
+    /// processor time the process used meanwhile, `None` when it is not available
+    pub cpu_time: Option<Duration>,
+    /// packets the ip stack received and sent meanwhile, `None` when its counters are not
+    /// available
+    pub stack_packet_num: Option<u64>,
+}
+
+impl BenchReport {
+    /// packets the ip stack went through but the capture did not read; the counters are
+    /// system wide, so traffic of other adapters counts as dropped too
+    pub fn dropped(&self) -> Option<u64> {
+        self.stack_packet_num
+            .map(|stack| stack.saturating_sub(self.packet_num))
+    }
+
+    /// one `name value` line each, in the same order every run so that runs can be diffed
+    pub fn to_text(&self) -> String {
+        let secs = self.elapsed.as_secs_f64().max(0.001);
+        let mode = if self.polling {
+            tr!("polling", "轮询")
+        } else {
+            tr!("events", "事件")
+        };
+        let mut lines = vec![
+            (tr!("interface", "网卡"), self.interface.clone()),
+            (tr!("mode", "模式"), mode.to_string()),
+            (tr!("duration", "时长"), format!("{:.1} s", secs)),
+            (
+                tr!("reads", "读取次数"),
+                format!("{} ({:.0}/s)", self.reads, self.reads as f64 / secs),
+            ),
+            (
+                tr!("packets", "分组数"),
+                format!(
+                    "{} ({:.0}/s)",
+                    self.packet_num,
+                    self.packet_num as f64 / secs
+                ),
+            ),
+            (
+                tr!("bytes", "字节数"),
+                format!(
+                    "{} ({})",
+                    format_bytes(self.byte_num),
+                    format_byte_rate(self.byte_num as f64 / secs)
+                ),
+            ),
+            (
+                tr!("packets per read", "每次读取的分组数"),
+                format!("{:.2}", self.packet_num as f64 / self.reads.max(1) as f64),
+            ),
+            (
+                tr!("receive buffer", "接收缓冲区"),
+                tr!("{} bytes", "{} 字节", self.recv_buffer),
+            ),
+            (
+                tr!("empty reads", "无数据的读取"),
+                self.metrics.would_block.to_string(),
+            ),
+            (
+                tr!("largest datagram", "最大数据报"),
+                tr!("{} bytes", "{} 字节", self.metrics.max_datagram),
+            ),
+        ];
+        if let Some(cpu_time) = self.cpu_time {
+            let cpu_secs = cpu_time.as_secs_f64();
+            lines.push((
+                tr!("cpu time", "CPU 时间"),
+                tr!(
+                    "{:.2} s ({:.1}% of a core)",
+                    "{:.2} 秒（单核的 {:.1}%）",
+                    cpu_secs,
+                    cpu_secs * 100.0 / secs
+                ),
+            ));
+        }
+        match (self.stack_packet_num, self.dropped()) {
+            (Some(stack), Some(dropped)) => {
+                lines.push((
+                    tr!("ip stack packets", "协议栈分组数"),
+                    tr!("{}, system wide", "{}，全系统", stack),
+                ));
+                lines.push((
+                    tr!("dropped", "丢弃"),
+                    tr!(
+                        "{} ({:.1}%), estimated",
+                        "{} ({:.1}%)，估计值",
+                        dropped,
+                        dropped as f64 * 100.0 / stack.max(1) as f64
+                    ),
+                ));
+            }
+            _ => lines.push((
+                tr!("dropped", "丢弃"),
+                tr!("unknown, no ip statistics", "未知，无法获取 IP 统计").to_string(),
+            )),
+        }
+        let width = lines
+            .iter()
+            .map(|(name, _)| display_width(name))
+            .max()
+            .unwrap_or(0);
+        lines
+            .into_iter()
+            .map(|(name, value)| format!("{}  {}\n", pad_end(name, width), value))
+            .collect()
+    }
+}
+
+/// read from `capturer` for `duration` or until Ctrl+C, counting what is read without
+/// parsing it
+pub fn run(
+    capturer: &mut Capturer,
+    interface: &str,
+    polling: bool,
+    duration: Duration,
+) -> Result<BenchReport> {
+    let mut report = BenchReport {
+        interface: interface.to_string(),
+        polling,
+        recv_buffer: capturer.recv_buffer_size(),
+        ..Default::default()
+    };
+    let stack_before = ip_packet_counts().ok();
+    let cpu_before = process_cpu_time().ok();
+    let started = Instant::now();
+    while started.elapsed() < duration && !interrupted() {
+        let len = capturer.read()?.len();
+        report.reads += 1;
+        if len != 0 {
+            report.packet_num += 1;
+            report.byte_num += len as u64;
+        }
+        if polling {
+            thread::sleep(POLL_INTERVAL);
+        } else if len == 0 {
+            capturer.wait(Some(WAIT_TIMEOUT))?;
+        }
+    }
+    report.elapsed = started.elapsed();
+    report.metrics = capturer.metrics();
+    report.cpu_time = cpu_before
+        .zip(process_cpu_time().ok())
+        .map(|(before, after)| after.saturating_sub(before));
+    report.stack_packet_num = stack_before
+        .zip(ip_packet_counts().ok())
+        .map(|(before, after)| after.packets_since(&before));
+    Ok(report)
+}
+
+#[cfg(test)]
+mod bench_test {
+    use super::*;
+
+    #[test]
+    fn test_report_text() {
+        let mut report = BenchReport {
+            interface: "Ethernet".to_string(),
+            polling: false,
+            elapsed: Duration::from_secs(2),
+            reads: 400,
+            packet_num: 380,
+            byte_num: 380 * 1000,
+            recv_buffer: 65536,
+            metrics: CaptureMetrics {
+                reads: 400,
+                would_block: 20,
+                bytes: 380 * 1000,
+                max_datagram: 1500,
+                ..Default::default()
+            },
+            cpu_time: Some(Duration::from_millis(50)),
+            stack_packet_num: Some(400),
+        };
+        assert_eq!(report.dropped(), Some(20));
+        let text = report.to_text();
+        let lines = text.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], "interface         Ethernet");
+        assert_eq!(lines[1], "mode              events");
+        assert_eq!(lines[3], "reads             400 (200/s)");
+        assert_eq!(lines[6], "packets per read  0.95");
+        assert_eq!(lines[8], "empty reads       20");
+        assert_eq!(lines[9], "largest datagram  1500 bytes");
+        assert_eq!(lines[10], "cpu time          0.05 s (2.5% of a core)");
+        assert_eq!(lines[12], "dropped           20 (5.0%), estimated");
+
+        // packets read just before the counters are sampled may outnumber them
+        report.stack_packet_num = Some(300);
+        assert_eq!(report.dropped(), Some(0));
+        report.stack_packet_num = None;
+        assert!(report
+            .to_text()
+            .ends_with("dropped           unknown, no ip statistics\n"));
+    }
+}
@@ -0,0 +1,1099 @@
+use anyhow::{Error, Result};
+use chrono::{DateTime, Local};
+
+use crate::{
+    log::Level,
+    record::Record,
+    socket::{AdapterLost, CaptureError, CaptureProtocol},
+    tr,
+    utils::{privilege_message, IpCounts},
+};
+
+use std::{
+    cell::Cell,
+    net::Ipv4Addr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, TryRecvError},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+/// records the capture thread may get ahead of the gui by before it waits for it
+const CHANNEL_CAPACITY: usize = 65536;
+/// how long the capture thread waits for a packet before it checks whether to stop, unless
+/// the source is woken sooner
+const READ_TIMEOUT: Duration = Duration::from_millis(100);
+/// how often the capture thread publishes its read counters, a lock per packet costs the
+/// reader more than the gui gains from fresher numbers
+const PUBLISH_INTERVAL: Duration = Duration::from_millis(100);
+/// how long the capture thread sleeps after a failed read, before it tries again
+const FAILED_BACKOFF: Duration = Duration::from_millis(1);
+/// drains are counted by powers of two up to this many buckets, the last one taking in the
+/// larger ones as well
+const DRAIN_BUCKETS: usize = 16;
+
+/// a packet along with the time it was captured at, the local address and name of its
+/// adapter, if any, and whether only its start was read
+pub type ReadPacket<'a> = (
+    DateTime<Local>,
+    &'a mut [u8],
+    Option<(Ipv4Addr, &'a str)>,
+    bool,
+);
+
+/// cuts `PacketSource::read_timeout` short from another thread
+pub type Wake = Box<dyn Fn() + Send>;
+
+/// where packets are read from: the capturer, a saved capture, or canned packets in tests
+pub trait PacketSource: Send + 'static {
+    /// read the next packet, waiting for `timeout` at most for one to arrive; `None` when
+    /// none did, right away for a zero timeout
+    fn read_timeout(&mut self, timeout: Duration) -> Result<Option<ReadPacket<'_>>>;
+
+    /// whether there is anything left to read from, an adapter or the rest of a file
+    fn connected(&self) -> bool;
+
+    /// how much a single read takes in at most
+    fn buffer_size(&self) -> usize;
+
+    /// what ends `read_timeout` early once the thread is told to stop, for a source that may wait
+    /// longer than a moment
+    fn waker(&self) -> Option<Wake> {
+        None
+    }
+
+    /// the counters of its reads, for a source that keeps them
+    fn metrics(&self) -> CaptureMetrics {
+        CaptureMetrics::default()
+    }
+}
+
+/// how the reads of a capture went, to tell whether it kept up with the traffic
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReadStats {
+    pub packet_num: u64,
+    pub byte_num: u64,
+    /// reads that filled the whole buffer, so the packet may have been cut short
+    pub full_reads: u64,
+    /// the longest a packet was read before the next read started, while packets can only
+    /// queue up in the socket buffer
+    pub max_gap: Duration,
+}
+
+impl ReadStats {
+    /// take in the stats of an earlier capture thread of the same capture
+    pub fn merge(&mut self, other: &ReadStats) {
+        self.packet_num += other.packet_num;
+        self.byte_num += other.byte_num;
+        self.full_reads += other.full_reads;
+        self.max_gap = self.max_gap.max(other.max_gap);
+    }
+
+    /// a line on the packets likely lost, going by the ip stack counters taken before and
+    /// after; those are system wide, so traffic of other adapters counts as lost too, and
+    /// a capture of `only` one protocol has nothing to hold them against
+    pub fn drop_text(
+        &self,
+        stack: Option<(IpCounts, IpCounts)>,
+        only: Option<CaptureProtocol>,
+    ) -> String {
+        let gap = self.max_gap.as_secs_f64() * 1000.0;
+        match (stack, only) {
+            (_, Some(protocol)) => tr!(
+                "suspected drops: unknown, only {} was captured while the ip stack counts every protocol, {} full reads, longest gap between reads {:.1} ms",
+                "疑似丢包：未知，仅捕获了 {}，而协议栈统计全部协议，读满缓冲区 {} 次，读取最长间隔 {:.1} 毫秒",
+                protocol.name(),
+                self.full_reads,
+                gap
+            ),
+            (Some((before, after)), None) => {
+                let stack_packet_num = after.packets_since(&before);
+                let dropped = stack_packet_num.saturating_sub(self.packet_num);
+                tr!(
+                    "suspected drops: {} of {} ip stack packets ({:.1}%), {} discarded by the stack, {} full reads, longest gap between reads {:.1} ms",
+                    "疑似丢包：{} / {} 个协议栈分组（{:.1}%），协议栈丢弃 {} 个，读满缓冲区 {} 次，读取最长间隔 {:.1} 毫秒",
+                    dropped,
+                    stack_packet_num,
+                    dropped as f64 * 100.0 / stack_packet_num.max(1) as f64,
+                    after.discarded_since(&before),
+                    self.full_reads,
+                    gap
+                )
+            }
+            (None, None) => tr!(
+                "suspected drops: unknown, no ip statistics, {} full reads, longest gap between reads {:.1} ms",
+                "疑似丢包：未知，无法获取 IP 统计，读满缓冲区 {} 次，读取最长间隔 {:.1} 毫秒",
+                self.full_reads,
+                gap
+            ),
+        }
+    }
+}
+
+/// counters of the capture path itself, kept by the source on every read and by the capture
+/// thread on every drain, plain increments so that keeping them costs next to nothing
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CaptureMetrics {
+    /// reads of a socket, counting the ones that came back empty
+    pub reads: u64,
+    /// reads that found nothing to read
+    pub would_block: u64,
+    pub bytes: u64,
+    /// the largest single datagram read, in bytes
+    pub max_datagram: usize,
+    /// datagrams too large for the read buffer, of which only the start was read
+    pub truncated: u64,
+    /// packets of other protocols that a capture restricted to one read all the same, and left out
+    pub filtered: u64,
+    /// sockets bound for every protocol because one for the restricted protocol was refused
+    pub protocol_fallbacks: u64,
+    /// drains that took any packets, by how many: 1, 2 to 3, 4 to 7 and so on
+    pub drain_sizes: [u64; DRAIN_BUCKETS],
+}
+
+impl CaptureMetrics {
+    /// count in a read of `len` bytes, 0 when there was nothing to read
+    pub fn read(&mut self, len: usize) {
+        self.reads += 1;
+        if len == 0 {
+            self.would_block += 1;
+        }
+        self.bytes += len as u64;
+        self.max_datagram = self.max_datagram.max(len);
+    }
+
+    /// count in a drain of `count` packets; empty ones are left out, they are what an idle
+    /// capture does all the time
+    pub fn drained(&mut self, count: usize) {
+        if count == 0 {
+            return;
+        }
+        let bucket = (usize::BITS - 1 - count.leading_zeros()) as usize;
+        self.drain_sizes[bucket.min(DRAIN_BUCKETS - 1)] += 1;
+    }
+
+    /// take in the drains of an earlier capture thread of the same capture
+    pub fn merge_drains(&mut self, other: &CaptureMetrics) {
+        for (drains, other) in self.drain_sizes.iter_mut().zip(other.drain_sizes) {
+            *drains += other;
+        }
+    }
+
+    /// a line on the reads, and one on the drains when there were any
+    pub fn to_text(&self) -> String {
+        let mut text = tr!(
+            "reads: {} ({} found nothing), {} bytes, largest datagram {} bytes",
+            "读取：{} 次（其中 {} 次无数据），{} 字节，最大数据报 {} 字节",
+            self.reads,
+            self.would_block,
+            self.bytes,
+            self.max_datagram
+        );
+        if self.truncated != 0 {
+            text.push_str(&tr!(
+                ", {} cut short by the read buffer",
+                "，其中 {} 个超出读取缓冲区被截断",
+                self.truncated
+            ));
+        }
+        if self.filtered != 0 {
+            text.push_str(&tr!(
+                ", {} of other protocols left out",
+                "，其中 {} 个其他协议的分组被略过",
+                self.filtered
+            ));
+        }
+        if self.protocol_fallbacks != 0 {
+            text.push_str(&tr!(
+                ", {} sockets capture every protocol as the restricted one was refused",
+                "，{} 个套接字无法只捕获一种协议，改为捕获全部协议",
+                self.protocol_fallbacks
+            ));
+        }
+        let drains = self
+            .drain_sizes
+            .iter()
+            .enumerate()
+            .filter(|(_, &count)| count != 0)
+            .map(|(bucket, count)| {
+                let low = 1usize << bucket;
+                match bucket {
+                    0 => format!("1: {}", count),
+                    _ if bucket == DRAIN_BUCKETS - 1 => format!("{}+: {}", low, count),
+                    _ => format!("{}-{}: {}", low, low * 2 - 1, count),
+                }
+            })
+            .collect::<Vec<_>>();
+        if !drains.is_empty() {
+            text.push('\n');
+            text.push_str(&tr!(
+                "packets per drain: {}",
+                "每批分组数：{}",
+                drains.join(", ")
+            ));
+        }
+        text
+    }
+}
+
+/// keeps `ReadStats` up to date around each read
+#[derive(Debug, Default)]
+pub struct ReadMeter {
+    stats: ReadStats,
+    last_packet: Option<Instant>,
+}
+
+impl ReadMeter {
+    /// right before a read
+    pub fn start(&mut self) {
+        if let Some(last) = self.last_packet.take() {
+            self.stats.max_gap = self.stats.max_gap.max(last.elapsed());
+        }
+    }
+
+    /// right after it, with the length read into a buffer of `capacity`
+    pub fn end(&mut self, len: usize, capacity: usize) {
+        if len == 0 {
+            return;
+        }
+        self.stats.packet_num += 1;
+        self.stats.byte_num += len as u64;
+        if len >= capacity {
+            self.stats.full_reads += 1;
+        }
+        self.last_packet = Some(Instant::now());
+    }
+
+    pub fn stats(&self) -> ReadStats {
+        self.stats
+    }
+}
+
+impl<S: PacketSource + ?Sized> PacketSource for Box<S> {
+    fn read_timeout(&mut self, timeout: Duration) -> Result<Option<ReadPacket<'_>>> {
+        (**self).read_timeout(timeout)
+    }
+
+    fn connected(&self) -> bool {
+        (**self).connected()
+    }
+
+    fn buffer_size(&self) -> usize {
+        (**self).buffer_size()
+    }
+
+    fn waker(&self) -> Option<Wake> {
+        (**self).waker()
+    }
+
+    fn metrics(&self) -> CaptureMetrics {
+        (**self).metrics()
+    }
+}
+
+/// what the capture thread sends the gui
+#[derive(Debug)]
+pub enum CaptureEvent {
+    Record(Record),
+    /// an adapter stopped capturing for good; the thread ends when it was the last one
+    Lost(AdapterLost),
+    /// a read failed, the next one may well succeed
+    Failed(Error),
+}
+
+/// what binding other adapters does to the capture, e.g. on picking one from the list
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Handover {
+    /// nothing is being captured, the adapters are only bound for the next one
+    Idle,
+    /// the capture goes on from the new adapters, keeping its records, start time and timers
+    Continue,
+    /// none of the adapters could be bound, so the capture ends as if stopped
+    Stop,
+}
+
+impl Handover {
+    /// the handover of a capture, running or not, once `bound` adapters could be bound
+    pub fn plan(capturing: bool, bound: usize) -> Self {
+        match (capturing, bound) {
+            (false, _) => Self::Idle,
+            (true, 0) => Self::Stop,
+            (true, _) => Self::Continue,
+        }
+    }
+
+    /// bind the `(address, adapter name)` of each of `targets` in place of the adapters bound so far,
+    /// taking a capture that is `capturing` along: its thread is joined first, and then spawned
+    /// again on the new sockets or, with none bound, the capture stopped
+    pub fn run(
+        steps: &mut impl HandoverSteps,
+        capturing: bool,
+        targets: &[(Ipv4Addr, String)],
+    ) -> HandoverOutcome {
+        steps.join();
+        let failures = steps.bind(targets);
+        let handover = Self::plan(capturing, targets.len() - failures.len());
+        match handover {
+            Self::Idle => {}
+            Self::Continue => steps.respawn(),
+            Self::Stop => steps.stop(),
+        }
+        let bound = targets
+            .iter()
+            .map(|(address, _)| *address)
+            .filter(|address| failures.iter().all(|(failed, _)| failed != address))
+            .collect();
+        HandoverOutcome {
+            handover,
+            bound,
+            failures,
+        }
+    }
+}
+
+/// what `Handover::run` does to the capturer and the capture thread, left to the gui
+pub trait HandoverSteps {
+    /// end the capture thread, if there is one, and take the capturer back
+    fn join(&mut self);
+    /// bind the capturer to `targets` in place of what it was bound to, handing back the
+    /// addresses that could not be bound
+    fn bind(&mut self, targets: &[(Ipv4Addr, String)]) -> Vec<(Ipv4Addr, CaptureError)>;
+    /// go on capturing from the new sockets
+    fn respawn(&mut self);
+    /// end the capture as if stopped
+    fn stop(&mut self);
+}
+
+/// what came of handing over to other adapters
+#[derive(Debug)]
+pub struct HandoverOutcome {
+    pub handover: Handover,
+    /// the addresses that were bound, in the order they were given
+    pub bound: Vec<Ipv4Addr>,
+    /// the addresses that were not, with why
+    pub failures: Vec<(Ipv4Addr, CaptureError)>,
+}
+
+impl HandoverOutcome {
+    /// whether some target was refused for lack of administrator rights
+    pub fn access_denied(&self) -> bool {
+        self.failures
+            .iter()
+            .any(|(_, err)| matches!(err, CaptureError::AccessDenied))
+    }
+
+    /// the status line for the gui, `None` when every target was bound; `name` names the
+    /// adapter of an address, each address is named as adapters may have several
+    pub fn status(&self, name: impl Fn(Ipv4Addr) -> String) -> Option<(Level, String)> {
+        let stopped = if self.handover == Handover::Stop {
+            "，捕获已停止"
+        } else {
+            ""
+        };
+        let reason = |err: &CaptureError| match err {
+            CaptureError::AccessDenied => privilege_message().to_string(),
+            _ => format!("未知错误：{}", err),
+        };
+        match self.failures.as_slice() {
+            [] => None,
+            [(_, err)] if self.bound.is_empty() => {
+                Some((Level::Error, format!("{}{}", reason(err), stopped)))
+            }
+            failures => {
+                let details = failures
+                    .iter()
+                    .map(|(address, err)| format!("{}（{}）", name(*address), reason(err)))
+                    .collect::<Vec<_>>()
+                    .join("，");
+                let level = if self.handover == Handover::Stop {
+                    Level::Error
+                } else {
+                    Level::Warn
+                };
+                let message = format!(
+                    "已连接 {} 个网卡，以下网卡无法捕获：{}{}",
+                    self.bound.len(),
+                    details,
+                    stopped
+                );
+                Some((level, message))
+            }
+        }
+    }
+
+    /// the plot marker of a capture going on from the `previous` addresses, `None` unless it
+    /// went on from others; `name` names the adapter of an address
+    pub fn marker(
+        &self,
+        previous: &[Ipv4Addr],
+        name: impl Fn(Ipv4Addr) -> String,
+    ) -> Option<String> {
+        if self.handover != Handover::Continue || previous == self.bound.as_slice() {
+            return None;
+        }
+        let names = |addresses: &[Ipv4Addr]| {
+            addresses
+                .iter()
+                .map(|address| name(*address))
+                .collect::<Vec<_>>()
+                .join("，")
+        };
+        Some(match previous {
+            [] => format!("已切换到网卡 {}，捕获继续", names(&self.bound)),
+            previous => format!(
+                "已从网卡 {} 切换到网卡 {}，捕获继续",
+                names(previous),
+                names(&self.bound)
+            ),
+        })
+    }
+}
+
+/// reads, timestamps and parses packets on a thread of its own, so that the gui does not
+/// have to keep up with every packet from its timer
+pub struct CaptureThread<S: PacketSource> {
+    events: Receiver<CaptureEvent>,
+    /// set once draining finds the thread gone
+    ended: Cell<bool>,
+    stopped: Arc<AtomicBool>,
+    /// the reads so far and the counters of the source, published every `PUBLISH_INTERVAL`
+    /// and once more as the thread ends
+    reads: Arc<Mutex<(ReadStats, CaptureMetrics)>>,
+    /// the drains so far, the other counters are left at 0
+    drains: Cell<CaptureMetrics>,
+    wake: Option<Wake>,
+    handle: JoinHandle<S>,
+}
+
+/// what is left of a capture thread once it has been stopped
+pub struct StoppedCapture<S> {
+    pub source: S,
+    /// the events it sent that were not drained
+    pub events: Vec<CaptureEvent>,
+    pub reads: ReadStats,
+    /// the counters of the source, with the drains of the thread
+    pub metrics: CaptureMetrics,
+}
+
+impl<S: PacketSource> CaptureThread<S> {
+    pub fn spawn(mut source: S) -> Result<Self> {
+        let (sender, events) = mpsc::sync_channel(CHANNEL_CAPACITY);
+        let stopped = Arc::new(AtomicBool::new(false));
+        let stop = stopped.clone();
+        let reads = Arc::new(Mutex::new(Default::default()));
+        let shared_reads = reads.clone();
+        let wake = source.waker();
+        let handle = thread::Builder::new()
+            .name("capture".to_string())
+            .spawn(move || {
+                let mut meter = ReadMeter::default();
+                let capacity = source.buffer_size();
+                let mut published = Instant::now();
+                while !stop.load(Ordering::Relaxed) && source.connected() {
+                    meter.start();
+                    let event = match source.read_timeout(READ_TIMEOUT) {
+                        Ok(None) => None,
+                        Ok(Some((time, packet, local, truncated))) => {
+                            meter.end(packet.len(), capacity);
+                            let record = Record::from_packet(time, packet, local, truncated);
+                            Some(CaptureEvent::Record(record))
+                        }
+                        Err(err) => match err.downcast::<AdapterLost>() {
+                            Ok(lost) => Some(CaptureEvent::Lost(lost)),
+                            Err(err) => {
+                                thread::sleep(FAILED_BACKOFF);
+                                Some(CaptureEvent::Failed(err))
+                            }
+                        },
+                    };
+                    // also while idle, which would otherwise leave the counters of the last
+                    // burst until the next packet
+                    if published.elapsed() >= PUBLISH_INTERVAL {
+                        *shared_reads.lock().unwrap() = (meter.stats(), source.metrics());
+                        published = Instant::now();
+                    }
+                    let event = match event {
+                        Some(event) => event,
+                        None => continue,
+                    };
+                    // waits while the channel is full rather than dropping the record, the
+                    // socket buffers meanwhile; the gui is gone when sending fails
+                    if sender.send(event).is_err() {
+                        break;
+                    }
+                }
+                *shared_reads.lock().unwrap() = (meter.stats(), source.metrics());
+                source
+            })?;
+        Ok(Self {
+            events,
+            ended: Cell::new(false),
+            stopped,
+            reads,
+            drains: Cell::new(Default::default()),
+            wake,
+            handle,
+        })
+    }
+
+    /// the next of the events sent so far, if any
+    pub fn next(&self) -> Option<CaptureEvent> {
+        match self.events.try_recv() {
+            Ok(event) => Some(event),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => {
+                self.ended.set(true);
+                None
+            }
+        }
+    }
+
+    /// count in a drain of `count` events taken with `next`
+    pub fn drained(&self, count: usize) {
+        let mut drains = self.drains.get();
+        drains.drained(count);
+        self.drains.set(drains);
+    }
+
+    /// whether the thread has ended on its own, i.e. with its last adapter lost, and every
+    /// event of it has been taken
+    pub fn finished(&self) -> bool {
+        self.ended.get()
+    }
+
+    /// the reads of the thread so far, up to `PUBLISH_INTERVAL` behind
+    pub fn reads(&self) -> ReadStats {
+        self.reads.lock().unwrap().0
+    }
+
+    /// the counters of the source, up to `PUBLISH_INTERVAL` behind, with the drains so far
+    pub fn metrics(&self) -> CaptureMetrics {
+        let mut metrics = self.reads.lock().unwrap().1;
+        metrics.drain_sizes = self.drains.get().drain_sizes;
+        metrics
+    }
+
+    /// end the thread, giving back the source and what it left behind
+    pub fn stop(self) -> StoppedCapture<S> {
+        self.stopped.store(true, Ordering::Relaxed);
+        if let Some(wake) = &self.wake {
+            wake();
+        }
+        // the thread may be waiting for room in the channel, keep making some until it ends
+        let events = self.events.iter().collect();
+        let source = match self.handle.join() {
+            Ok(source) => source,
+            Err(panic) => std::panic::resume_unwind(panic),
+        };
+        let reads = self.reads.lock().unwrap().0;
+        let mut metrics = source.metrics();
+        metrics.drain_sizes = self.drains.get().drain_sizes;
+        StoppedCapture {
+            source,
+            events,
+            reads,
+            metrics,
+        }
+    }
+}
+
+#[cfg(test)]
+mod capture_test {
+    use super::*;
+
+    use crate::record::{PlotRecord, StatRecord};
+
+    use chrono::{Duration as TimeDelta, TimeZone};
+    use std::{collections::VecDeque, iter, time::Instant};
+
+    /// up to `max` of the events sent so far, counted as one drain like a gui tick
+    fn drain<S: PacketSource>(thread: &CaptureThread<S>, max: usize) -> Vec<CaptureEvent> {
+        let events = iter::from_fn(|| thread.next())
+            .take(max)
+            .collect::<Vec<_>>();
+        thread.drained(events.len());
+        events
+    }
+
+    /// udp packets numbered by their source port, sent at a fixed rate
+    struct PacedSource {
+        rate: u32,
+        count: u32,
+        sent: u32,
+        started: Option<Instant>,
+        buffer: Vec<u8>,
+    }
+
+    impl PacedSource {
+        fn new(rate: u32, count: u32) -> Self {
+            Self {
+                rate,
+                count,
+                sent: 0,
+                started: None,
+                buffer: Vec::new(),
+            }
+        }
+    }
+
+    fn udp_packet(src_port: u16) -> Vec<u8> {
+        let mut packet = vec![
+            0x45, 0, 0, 32, 0, 0, 0, 0, 64, 17, 0, 0, 10, 0, 0, 1, 10, 0, 0, 2,
+        ];
+        let sum = packet
+            .chunks(2)
+            .map(|word| u16::from_be_bytes([word[0], word[1]]) as u32)
+            .sum::<u32>();
+        let checksum = !((sum & 0xffff) + (sum >> 16)) as u16;
+        packet[10..12].copy_from_slice(&checksum.to_be_bytes());
+        packet.extend_from_slice(&src_port.to_be_bytes());
+        packet.extend_from_slice(&[0x1f, 0x90, 0, 12, 0, 0, b'p', b'i', b'n', b'g']);
+        packet
+    }
+
+    impl PacketSource for PacedSource {
+        /// does not wait, so that the thread is always there for the next packet
+        fn read_timeout(&mut self, _timeout: Duration) -> Result<Option<ReadPacket<'_>>> {
+            let started = *self.started.get_or_insert_with(Instant::now);
+            let due = started.elapsed().as_secs_f64() * self.rate as f64;
+            if (self.sent as f64) < due {
+                self.buffer = udp_packet(self.sent as u16);
+                self.sent += 1;
+                let local = Some((Ipv4Addr::new(10, 0, 0, 1), "test"));
+                Ok(Some((Local::now(), &mut self.buffer, local, false)))
+            } else {
+                Ok(None)
+            }
+        }
+
+        fn connected(&self) -> bool {
+            self.sent < self.count
+        }
+
+        fn buffer_size(&self) -> usize {
+            32
+        }
+    }
+
+    #[test]
+    fn test_no_records_lost() {
+        let thread = CaptureThread::spawn(PacedSource::new(50_000, 50_000)).unwrap();
+        let mut ports = Vec::new();
+        // drain on a timer as the gui does, by the batch its tick takes
+        while !thread.finished() {
+            thread::sleep(Duration::from_millis(10));
+            for event in drain(&thread, 5000) {
+                match event {
+                    CaptureEvent::Record(record) => ports.push(record.src_port.unwrap()),
+                    event => panic!("{:?}", event),
+                }
+            }
+        }
+        let stopped = thread.stop();
+        assert!(stopped.events.is_empty());
+        assert_eq!(stopped.source.sent, 50_000);
+        assert_eq!(ports.len(), 50_000);
+        assert!(ports.iter().enumerate().all(|(i, &port)| port == i as u16));
+        assert_eq!(stopped.reads.packet_num, 50_000);
+        assert_eq!(stopped.reads.byte_num, 50_000 * 32);
+        assert_eq!(stopped.reads.full_reads, 50_000);
+
+        // stopping with the channel full ends the thread all the same
+        let thread = CaptureThread::spawn(PacedSource::new(10_000_000, 1_000_000)).unwrap();
+        thread::sleep(Duration::from_secs(1));
+        let stopped = thread.stop();
+        assert!(stopped.events.len() > CHANNEL_CAPACITY);
+        assert_eq!(stopped.events.len(), stopped.source.sent as usize);
+    }
+
+    /// canned packets, read one after the other with the times they come with
+    struct FixtureSource {
+        packets: VecDeque<(DateTime<Local>, Vec<u8>)>,
+        current: Vec<u8>,
+        metrics: CaptureMetrics,
+    }
+
+    impl FixtureSource {
+        fn new(packets: impl IntoIterator<Item = (DateTime<Local>, Vec<u8>)>) -> Self {
+            Self {
+                packets: packets.into_iter().collect(),
+                current: Vec::new(),
+                metrics: CaptureMetrics::default(),
+            }
+        }
+    }
+
+    impl PacketSource for FixtureSource {
+        fn read_timeout(&mut self, _timeout: Duration) -> Result<Option<ReadPacket<'_>>> {
+            let (time, packet) = match self.packets.pop_front() {
+                Some(read) => read,
+                None => {
+                    self.metrics.read(0);
+                    return Ok(None);
+                }
+            };
+            self.metrics.read(packet.len());
+            self.current = packet;
+            Ok(Some((time, &mut self.current, None, false)))
+        }
+
+        fn connected(&self) -> bool {
+            !self.packets.is_empty()
+        }
+
+        fn buffer_size(&self) -> usize {
+            65535
+        }
+
+        fn metrics(&self) -> CaptureMetrics {
+            self.metrics
+        }
+    }
+
+    #[test]
+    fn test_fixture_pipeline() {
+        let start = Local.ymd(2021, 11, 8).and_hms(12, 0, 0);
+        let times = [0, 100, 250, 1100].map(|ms| start + TimeDelta::milliseconds(ms));
+        let packets = times
+            .iter()
+            .enumerate()
+            .map(|(i, &time)| (time, udp_packet(i as u16)));
+        // boxed as the cli holds its source
+        let source: Box<dyn PacketSource> = Box::new(FixtureSource::new(packets));
+        let thread = CaptureThread::spawn(source).unwrap();
+        let mut records = Vec::new();
+        while !thread.finished() {
+            thread::sleep(Duration::from_millis(1));
+            for event in drain(&thread, 5000) {
+                match event {
+                    CaptureEvent::Record(record) => records.push(record),
+                    event => panic!("{:?}", event),
+                }
+            }
+        }
+        let stopped = thread.stop();
+        assert!(!stopped.source.connected());
+        assert_eq!(records.iter().map(|r| r.time).collect::<Vec<_>>(), times);
+        assert_eq!(stopped.metrics.reads, 4);
+        assert_eq!(stopped.metrics.would_block, 0);
+        assert_eq!(stopped.metrics.bytes, 4 * 32);
+        assert_eq!(stopped.metrics.max_datagram, 32);
+        // however the packets were split among the drains, each drain took at least one
+        let drains = stopped.metrics.drain_sizes.iter().sum::<u64>();
+        assert!((1..=4).contains(&drains));
+
+        let mut stat = StatRecord::default();
+        stat.update_multiple(records.iter());
+        assert_eq!(stat.stat_net_table.packet_num, 4);
+        assert_eq!(stat.stat_net_table.byte_num, 4 * 32);
+        assert_eq!(stat.stat_trans_table["UDP"].packet_num, 4);
+
+        let plot = PlotRecord::from_records(records.iter(), Some(start), Some(times[3]), false);
+        let buckets = plot
+            .records
+            .iter()
+            .map(|r| r.packet_num)
+            .collect::<Vec<_>>();
+        assert_eq!(buckets, [2, 1, 0, 0, 0]);
+        assert_eq!(plot.uncommitted_record.packet_num, 1);
+    }
+
+    /// the steps taken, binding fails for the addresses in `refused`
+    #[derive(Default)]
+    struct RecordedSteps {
+        refused: Vec<Ipv4Addr>,
+        taken: Vec<&'static str>,
+    }
+
+    impl HandoverSteps for RecordedSteps {
+        fn join(&mut self) {
+            self.taken.push("join");
+        }
+        fn bind(&mut self, targets: &[(Ipv4Addr, String)]) -> Vec<(Ipv4Addr, CaptureError)> {
+            self.taken.push("bind");
+            targets
+                .iter()
+                .filter(|(address, _)| self.refused.contains(address))
+                .map(|(address, _)| (*address, CaptureError::AccessDenied))
+                .collect()
+        }
+        fn respawn(&mut self) {
+            self.taken.push("respawn");
+        }
+        fn stop(&mut self) {
+            self.taken.push("stop");
+        }
+    }
+
+    #[test]
+    fn test_handover_run() {
+        let (wifi, ethernet) = (Ipv4Addr::new(10, 0, 0, 2), Ipv4Addr::new(10, 0, 1, 2));
+        let targets = [
+            (wifi, "Wi-Fi".to_string()),
+            (ethernet, "Ethernet".to_string()),
+        ];
+        let name = |address| {
+            let (_, name) = targets.iter().find(|(a, _)| *a == address).unwrap();
+            format!("{} — {}", name, address)
+        };
+        let previous = [wifi];
+
+        let mut steps = RecordedSteps::default();
+        let outcome = Handover::run(&mut steps, false, &targets);
+        assert_eq!(steps.taken, ["join", "bind"]);
+        assert_eq!(outcome.handover, Handover::Idle);
+        assert!(outcome.status(name).is_none());
+        assert!(outcome.marker(&[], name).is_none());
+
+        let mut steps = RecordedSteps::default();
+        let outcome = Handover::run(&mut steps, true, &targets[1..]);
+        assert_eq!(steps.taken, ["join", "bind", "respawn"]);
+        assert_eq!(outcome.bound, [ethernet]);
+        assert_eq!(
+            outcome.marker(&previous, name).unwrap(),
+            "已从网卡 Wi-Fi — 10.0.0.2 切换到网卡 Ethernet — 10.0.1.2，捕获继续"
+        );
+        // binding the same adapters again is no switch
+        assert!(outcome.marker(&outcome.bound, name).is_none());
+
+        let mut steps = RecordedSteps {
+            refused: vec![ethernet],
+            ..Default::default()
+        };
+        let outcome = Handover::run(&mut steps, true, &targets);
+        assert_eq!(steps.taken, ["join", "bind", "respawn"]);
+        assert!(outcome.access_denied());
+        let (level, status) = outcome.status(name).unwrap();
+        assert_eq!(level, Level::Warn);
+        assert_eq!(
+            status,
+            format!(
+                "已连接 1 个网卡，以下网卡无法捕获：Ethernet — 10.0.1.2（{}）",
+                privilege_message()
+            )
+        );
+        assert!(outcome.marker(&previous, name).is_none());
+
+        let mut steps = RecordedSteps {
+            refused: vec![ethernet],
+            ..Default::default()
+        };
+        let outcome = Handover::run(&mut steps, true, &targets[1..]);
+        assert_eq!(steps.taken, ["join", "bind", "stop"]);
+        assert_eq!(outcome.handover, Handover::Stop);
+        let (level, status) = outcome.status(name).unwrap();
+        assert_eq!(level, Level::Error);
+        assert!(status.ends_with("，捕获已停止"));
+        assert!(outcome.marker(&previous, name).is_none());
+    }
+
+    #[test]
+    fn test_handover() {
+        assert_eq!(Handover::plan(false, 0), Handover::Idle);
+        assert_eq!(Handover::plan(false, 2), Handover::Idle);
+        assert_eq!(Handover::plan(true, 0), Handover::Stop);
+        assert_eq!(Handover::plan(true, 1), Handover::Continue);
+
+        // the gui takes the source back from the thread and goes on with another one
+        let start = Local.ymd(2021, 11, 8).and_hms(12, 0, 0);
+        let packet = |i: u16| (start + TimeDelta::seconds(i as i64), udp_packet(i));
+        let mut reads = ReadStats::default();
+        let mut records = Vec::new();
+        for source in [
+            FixtureSource::new((0..3).map(packet)),
+            FixtureSource::new((3..5).map(packet)),
+        ] {
+            let thread = CaptureThread::spawn(source).unwrap();
+            while !thread.finished() {
+                thread::sleep(Duration::from_millis(1));
+                records.extend(drain(&thread, 5000));
+            }
+            let stopped = thread.stop();
+            records.extend(stopped.events);
+            reads.merge(&stopped.reads);
+        }
+        let ports = records
+            .iter()
+            .map(|event| match event {
+                CaptureEvent::Record(record) => record.src_port.unwrap(),
+                event => panic!("{:?}", event),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(ports, [0, 1, 2, 3, 4]);
+        assert_eq!(reads.packet_num, 5);
+    }
+
+    /// never has anything to read, and waits until woken
+    struct IdleSource {
+        woken: Arc<(Mutex<bool>, std::sync::Condvar)>,
+        waits: u32,
+    }
+
+    impl PacketSource for IdleSource {
+        /// waits until woken, however long the timeout
+        fn read_timeout(&mut self, _timeout: Duration) -> Result<Option<ReadPacket<'_>>> {
+            self.waits += 1;
+            let (woken, condvar) = &*self.woken;
+            let _woken = condvar.wait_while(woken.lock().unwrap(), |woken| !*woken);
+            Ok(None)
+        }
+
+        fn connected(&self) -> bool {
+            true
+        }
+
+        fn buffer_size(&self) -> usize {
+            0
+        }
+
+        fn waker(&self) -> Option<Wake> {
+            let woken = self.woken.clone();
+            Some(Box::new(move || {
+                *woken.0.lock().unwrap() = true;
+                woken.1.notify_all();
+            }))
+        }
+    }
+
+    #[test]
+    fn test_stop_wakes_source() {
+        let thread = CaptureThread::spawn(IdleSource {
+            woken: Default::default(),
+            waits: 0,
+        })
+        .unwrap();
+        thread::sleep(Duration::from_millis(50));
+        let stopped = thread.stop();
+        // waited once for good rather than spinning, until stopping woke it
+        assert_eq!(stopped.source.waits, 1);
+        assert!(stopped.events.is_empty());
+    }
+
+    /// one packet, then nothing but timeouts
+    struct BurstSource {
+        packet: Option<Vec<u8>>,
+        current: Vec<u8>,
+    }
+
+    impl PacketSource for BurstSource {
+        fn read_timeout(&mut self, timeout: Duration) -> Result<Option<ReadPacket<'_>>> {
+            match self.packet.take() {
+                Some(packet) => {
+                    self.current = packet;
+                    Ok(Some((Local::now(), &mut self.current, None, false)))
+                }
+                None => {
+                    thread::sleep(timeout);
+                    Ok(None)
+                }
+            }
+        }
+
+        fn connected(&self) -> bool {
+            true
+        }
+
+        fn buffer_size(&self) -> usize {
+            65535
+        }
+    }
+
+    #[test]
+    fn test_publish_while_idle() {
+        let thread = CaptureThread::spawn(BurstSource {
+            packet: Some(udp_packet(0)),
+            current: Vec::new(),
+        })
+        .unwrap();
+        thread::sleep(PUBLISH_INTERVAL + READ_TIMEOUT * 2);
+        // read right after the thread started, and published while it waited since
+        assert_eq!(thread.reads().packet_num, 1);
+        let stopped = thread.stop();
+        assert_eq!(stopped.events.len(), 1);
+    }
+
+    #[test]
+    fn test_metrics() {
+        let mut metrics = CaptureMetrics::default();
+        for len in [60, 0, 1500, 0, 40] {
+            metrics.read(len);
+        }
+        for count in [0, 1, 1, 3, 4, 7, 8, 100_000] {
+            metrics.drained(count);
+        }
+        assert_eq!(metrics.reads, 5);
+        assert_eq!(metrics.would_block, 2);
+        assert_eq!(metrics.bytes, 1600);
+        assert_eq!(metrics.max_datagram, 1500);
+        assert_eq!(&metrics.drain_sizes[..5], &[2, 1, 2, 1, 0]);
+        assert_eq!(metrics.drain_sizes[DRAIN_BUCKETS - 1], 1);
+        assert_eq!(
+            metrics.to_text(),
+            "reads: 5 (2 found nothing), 1600 bytes, largest datagram 1500 bytes\n\
+             packets per drain: 1: 2, 2-3: 1, 4-7: 2, 8-15: 1, 32768+: 1"
+        );
+
+        let mut merged = CaptureMetrics::default();
+        merged.drained(2);
+        merged.merge_drains(&metrics);
+        assert_eq!(&merged.drain_sizes[..2], &[2, 2]);
+        assert_eq!(merged.reads, 0);
+        assert_eq!(
+            CaptureMetrics::default().to_text(),
+            "reads: 0 (0 found nothing), 0 bytes, largest datagram 0 bytes"
+        );
+        let truncated = CaptureMetrics {
+            truncated: 2,
+            filtered: 3,
+            protocol_fallbacks: 1,
+            ..Default::default()
+        };
+        assert_eq!(
+            truncated.to_text(),
+            "reads: 0 (0 found nothing), 0 bytes, largest datagram 0 bytes, 2 cut short by the read buffer, \
+             3 of other protocols left out, 1 sockets capture every protocol as the restricted one was refused"
+        );
+    }
+
+    #[test]
+    fn test_drop_text() {
+        let mut reads = ReadStats {
+            packet_num: 950,
+            byte_num: 950 * 1000,
+            full_reads: 2,
+            max_gap: Duration::from_micros(12_340),
+        };
+        reads.merge(&ReadStats {
+            packet_num: 50,
+            max_gap: Duration::from_millis(3),
+            ..Default::default()
+        });
+        assert_eq!(reads.packet_num, 1000);
+        assert_eq!(reads.max_gap, Duration::from_micros(12_340));
+        let before = IpCounts {
+            received: u32::MAX - 99,
+            sent: 400,
+            discarded: 7,
+        };
+        let after = IpCounts {
+            received: 700,
+            sent: 700,
+            discarded: 10,
+        };
+        assert_eq!(
+            reads.drop_text(Some((before, after)), None),
+            "suspected drops: 100 of 1100 ip stack packets (9.1%), 3 discarded by the stack, 2 full reads, longest gap between reads 12.3 ms"
+        );
+        assert_eq!(
+            reads.drop_text(None, None),
+            "suspected drops: unknown, no ip statistics, 2 full reads, longest gap between reads 12.3 ms"
+        );
+        assert_eq!(
+            reads.drop_text(Some((before, after)), Some(CaptureProtocol::Udp)),
+            "suspected drops: unknown, only UDP was captured while the ip stack counts every protocol, 2 full reads, longest gap between reads 12.3 ms"
+        );
+    }
+}
@@ -1,40 +1,255 @@
 use anyhow::{anyhow, bail, Result};
 
-use clap::Parser;
+use clap::{AppSettings, Args, Parser, Subcommand};
 
-use crate::{meta, socket::ipv4_capturer, utils::AppProtocol};
+use crate::{
+    bench,
+    capture::{CaptureMetrics, PacketSource, ReadMeter, ReadStats},
+    export::{OutputFormat, RotatingWriter, Rotation, WrittenFile},
+    filter::{create_filter, parse_filter, FilterError, FilterExpr, HostPattern, PortRange},
+    lang::{set_lang, system_lang, Lang},
+    meta,
+    pcap::{PcapReader, PcapSource, RawFormat, RawWriter},
+    record::{
+        endpoint_text, FollowedFlows, FollowedPacket, NetRecord, PlotProtocol, Record,
+        RecordColumn, StatRecord, TalkerTable,
+    },
+    report::{report_json, report_text, top_json, top_text, TopOrder},
+    resolve::Resolver,
+    socket::{AdapterLost, CaptureError, CaptureProtocol, Capturer, ReadMode, RecvMode},
+    synth::{demo_flows, SyntheticSource, DEMO_LOCAL_IP, SYNTHETIC_ADAPTER},
+    tr,
+    utils::{ip_packet_counts, AppProtocol, IpCounts},
+};
 use byteorder::{self, NetworkEndian, WriteBytesExt};
-use ipconfig;
+use chrono::Local;
+use ipconfig::{self, Adapter};
+use itertools::Itertools;
 use packet::{
     ip::{v4, Protocol},
     tcp, udp, Packet,
 };
 
 use std::{
+    collections::HashMap,
+    ffi::OsString,
     fmt::Display,
-    io::{self, Read, Write},
-    net::SocketAddr,
+    fs::File,
+    io::{self, BufReader, BufWriter, Write},
+    iter,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    path::{Path, PathBuf},
+    process, thread,
+    time::{Duration, Instant},
+};
+
+use crate::utils::{
+    alloc_console, ansi_bold, ansi_color, attach_console, catch_interrupt, enable_ansi, exit_code,
+    format_byte_rate, format_bytes, format_count, format_duration, format_seconds, interrupted,
+    is_elevated, match_interface, parse_conversation, parse_interval, parse_seconds, parse_size,
+    print_interfaces, read_key, text_table, trans_protocol_name, CliError, ColorChoice, ExitCode,
+    HexDump, InterfaceMatch, TimeFormat, Timestamps, TransProtocol,
 };
 
-use crate::utils::{alloc_console, attach_console, print_interfaces, Bytes, TransProtocol};
+const EXIT_CODES: &str = "EXIT CODES:
+    0    success
+    1    no packet matched under --quiet or --count-only, or an unexpected error
+    2    bad arguments or filter
+    3    not run as administrator
+    4    the capture socket could not be opened or read
+    5    the output file could not be opened or written";
 
-/// Capture ipv4 packet with winsock2
+/// Capture ipv4 packet with winsock2. Without a subcommand the gui opens, showing FILE if
+/// one is given
 #[derive(Parser, Debug)]
-#[clap(name = meta::NAME, version = meta::VERSION, author = meta::AUTHORS)]
+#[clap(name = meta::NAME, version = meta::VERSION, author = meta::AUTHORS, after_help = EXIT_CODES)]
+#[clap(setting = AppSettings::ArgsNegateSubcommands)]
 pub struct CliArgs {
-    /// Run as cli mode without gui. You can run in cli without this flag
-    /// as long as some other flags present
-    #[clap(short, long)]
-    pub cli: bool,
+    /// Mirror the log of the gui to a file
+    #[clap(long)]
+    pub debug: bool,
 
-    /// Print whole ip packet
-    #[clap(short, long)]
-    pub packet: bool,
+    /// A pcap file for the gui to show, as when one is dropped onto the executable
+    #[clap(parse(from_os_str))]
+    pub file: Option<PathBuf>,
+
+    /// Fill the gui with generated traffic, for screenshots
+    #[clap(long, hidden = true, conflicts_with = "file")]
+    pub selftest: bool,
+
+    /// Language to print in, zh or en. By default the one of LANG or of the windows user
+    #[clap(long, global = true, value_name = "zh|en")]
+    pub lang: Option<Lang>,
+
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// what the command line asks to start
+#[derive(Debug)]
+pub enum Launch {
+    Gui {
+        debug: bool,
+        file: Option<PathBuf>,
+        selftest: bool,
+    },
+    Cli {
+        command: Command,
+        lang: Option<Lang>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Capture packets and print them, the default when flags are given without a subcommand
+    Capture(CaptureArgs),
+    /// List the network interfaces and exit
+    Interfaces(InterfacesArgs),
+    /// Capture without printing packets, then print the network, transport and application
+    /// tables of the stat tab
+    Report(ReportArgs),
+    /// Read the packets of a pcap file and print them as capture does, which needs no
+    /// administrator rights. Only the ipv4 packets in it are read
+    Read(ReadArgs),
+    /// Check a filter expression, printing it back as it was understood. Exits with 2 when
+    /// it is invalid
+    CheckFilter(CheckFilterArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct InterfacesArgs {
+    /// Print the interfaces as a json array
+    #[clap(long)]
+    pub json: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct ReportArgs {
+    /// Print the tables as a json object, with byte counts as plain numbers
+    #[clap(long)]
+    pub json: bool,
+
+    /// Report on the packets of this pcap file instead of capturing them
+    #[clap(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = &["interface", "bind", "poll", "bench", "selftest", "proto"]
+    )]
+    pub read_pcap: Option<PathBuf>,
+
+    /// Also list the N busiest source addresses, destination addresses and conversations
+    #[clap(long, value_name = "N", validator = positive)]
+    pub top: Option<usize>,
+
+    /// What --top ranks by: bytes or packets, the other one breaking ties
+    #[clap(
+        long,
+        value_name = "bytes|packets",
+        default_value = "bytes",
+        requires = "top"
+    )]
+    pub sort_by: TopOrder,
+
+    #[clap(flatten)]
+    pub capture: CaptureArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct ReadArgs {
+    /// The pcap file to read; pcapng files have to be saved as pcap first
+    #[clap(value_name = "FILE")]
+    pub file: PathBuf,
+
+    #[clap(flatten)]
+    pub packets: PacketArgs,
+}
 
-    /// Use polling mode with non-blocking socket
+#[derive(Args, Debug)]
+pub struct CheckFilterArgs {
+    /// The filter, quoted as a single argument
+    #[clap(value_name = "EXPR")]
+    pub expr: String,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct CaptureArgs {
+    #[clap(flatten)]
+    pub live: LiveArgs,
+
+    #[clap(flatten)]
+    pub packets: PacketArgs,
+}
+
+/// Flags on where live packets are captured
+#[derive(Args, Debug, Clone)]
+pub struct LiveArgs {
+    /// Capture on this interface instead of asking for one: its number in the listing,
+    /// part of its description or one of its ipv4 addresses
+    #[clap(short, long, value_name = "INDEX|NAME|IP")]
+    pub interface: Option<String>,
+
+    /// Capture on this local ipv4 address directly, without looking through the interfaces,
+    /// which starts faster on machines with many virtual adapters
+    #[clap(long, value_name = "IP", conflicts_with = "interface")]
+    pub bind: Option<Ipv4Addr>,
+
+    /// Bind the capture socket to this port. It receives the packets of every port either
+    /// way, so this only matters when binding fails; a port already taken is swapped for one
+    /// the system picks, as is 0, the default
+    #[clap(long, value_name = "PORT", default_value = "0")]
+    pub bind_port: u16,
+
+    /// Which packets to capture: on, everything the interface sends and receives, which
+    /// includes the traffic of other hosts where the network hands it to the interface, such
+    /// as on a mirrored port; ip-level, only the packets to and from this host, leaving the
+    /// interface out of promiscuous mode; off, next to nothing, as tcp and udp never reach
+    /// a raw socket on their own
+    #[clap(long, value_name = "on|ip-level|off", default_value = "on")]
+    pub recv_mode: RecvMode,
+
+    /// Only capture this protocol. The socket is created for it where Windows allows, and the
+    /// packets of other protocols read all the same are left out before they are parsed; the
+    /// summary says so, as its tables then miss the other protocols
+    #[clap(long, value_name = "tcp|udp|icmp")]
+    pub proto: Option<CaptureProtocol>,
+
+    /// Read from a non-blocking socket, waiting for packets with WSAPoll rather than on socket
+    /// events. Either way a read returns as soon as a packet arrives; only --bench reads on a
+    /// 10 ms timer with it
     #[clap(short = 'P', long)]
     pub poll: bool,
 
+    /// Measure how many packets the capture keeps up with instead of printing them: read for
+    /// --duration, 10 s by default, without parsing anything, then print the reads per second,
+    /// the packets per read, the cpu time and an estimate of the packets dropped. Reads wait
+    /// on socket events, or with --poll happen once every 10 ms, as a reader driven by a
+    /// timer would
+    #[clap(
+        long,
+        conflicts_with_all = &[
+            "filter", "host", "port", "follow", "follow_host", "output", "quiet",
+            "count_only", "brief", "fields", "stats", "count", "heartbeat",
+        ]
+    )]
+    pub bench: bool,
+
+    /// Capture generated packets as they fall due instead of those of an interface, which
+    /// needs no administrator rights
+    #[clap(
+        long,
+        hidden = true,
+        conflicts_with_all = &["interface", "bind", "poll", "bench", "proto"]
+    )]
+    pub selftest: bool,
+}
+
+/// Flags on which packets are kept and how they are printed, shared by capture and read
+#[derive(Args, Debug, Clone)]
+pub struct PacketArgs {
+    /// Print whole ip packet
+    #[clap(short, long)]
+    pub packet: bool,
+
     /// Flush after printing info for each packet
     #[clap(short, long)]
     pub flush: bool,
@@ -42,165 +257,1969 @@ pub struct CliArgs {
     /// Print payload
     #[clap(short = 'l', long)]
     pub payload: bool,
+
+    /// Bytes per row of the --packet and --payload dumps
+    #[clap(long, value_name = "N", default_value = "16", validator = positive)]
+    pub hex_width: usize,
+
+    /// Follow each row of the --packet and --payload dumps with its printable characters
+    #[clap(long)]
+    pub ascii: bool,
+
+    /// Only print packets matching this filter, written as in the filter box of the gui
+    #[clap(long, value_name = "EXPR")]
+    pub filter: Option<String>,
+
+    /// Only print packets from or to this address, or an address of a network such as
+    /// 10.0.0.0/8. Repeat it to keep the packets of any of several
+    #[clap(
+        long,
+        value_name = "IP[/PREFIX]",
+        multiple_occurrences = true,
+        number_of_values = 1
+    )]
+    pub host: Vec<HostPattern>,
+
+    /// Only print packets from or to this port, or a port of a range such as 8000-8100.
+    /// Repeat it to keep the packets of any of several. Along with --host and --filter,
+    /// packets have to match all of them
+    #[clap(
+        long,
+        value_name = "N|N-M",
+        multiple_occurrences = true,
+        number_of_values = 1
+    )]
+    pub port: Vec<PortRange>,
+
+    /// Only print the packets between these two endpoints, either way, numbered within their
+    /// conversation, then what went each way. An endpoint without a port matches any port
+    #[clap(long, value_name = "IP[:PORT],IP[:PORT]", parse(try_from_str = parse_conversation))]
+    pub follow: Option<((Ipv4Addr, Option<u16>), (Ipv4Addr, Option<u16>))>,
+
+    /// Like --follow, for every conversation of this address
+    #[clap(long, value_name = "IP", conflicts_with = "follow")]
+    pub follow_host: Option<Ipv4Addr>,
+
+    /// Append each matched packet to this file, as csv or json lines depending on its extension
+    #[clap(short, long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+
+    /// Format of --output, for files without a csv or json extension
+    #[clap(long, value_name = "csv|json", requires = "output")]
+    pub output_format: Option<OutputFormat>,
+
+    /// Write --output to a new file each time the current one reaches this size, such as 50M
+    /// or 1G. The files are named after --output and their first packet, such as
+    /// capture.2024-06-01T12-00-00.csv, and each csv starts with the header
+    #[clap(
+        long,
+        value_name = "SIZE",
+        requires = "output",
+        parse(try_from_str = parse_size)
+    )]
+    pub rotate_size: Option<u64>,
+
+    /// Like --rotate-size, a new file this long after the first packet of the current one,
+    /// such as 30m, 1h or 1d; along with --rotate-size, whichever comes first
+    #[clap(
+        long,
+        value_name = "DURATION",
+        requires = "output",
+        parse(try_from_str = parse_interval)
+    )]
+    pub rotate_interval: Option<Duration>,
+
+    /// Write each matched packet to stdout as binary instead of printing it, to be piped into
+    /// another program; the summary goes to stderr. The capture ends when the reader does
+    #[clap(
+        long,
+        conflicts_with_all = &["packet", "payload", "quiet", "count_only", "brief", "fields", "stats"]
+    )]
+    pub raw_stdout: bool,
+
+    /// Framing of --raw-stdout: frames, each packet after its length as 4 bytes in little
+    /// endian, or pcap, a stream which tcpdump -r - and wireshark -k -i - read
+    #[clap(
+        long,
+        value_name = "frames|pcap",
+        default_value = "frames",
+        requires = "raw_stdout"
+    )]
+    pub raw_format: RawFormat,
+
+    /// Print no packets, only the summary. Exits with 1 when no packet matched
+    #[clap(short, long)]
+    pub quiet: bool,
+
+    /// Print nothing but the number of matched packets, on exit. Exits with 1 when it is 0
+    #[clap(long, conflicts_with_all = &["brief", "fields", "quiet"])]
+    pub count_only: bool,
+
+    /// Exit with 0 under --quiet or --count-only even when no packet matched
+    #[clap(long)]
+    pub no_match_exit_code: bool,
+
+    /// Print one line per packet instead of the detailed form
+    #[clap(short, long)]
+    pub brief: bool,
+
+    /// Print one line per packet with these fields, separated by commas
+    #[clap(
+        long,
+        value_name = "FIELD,...",
+        use_delimiter = true,
+        possible_values = RecordColumn::ALL.iter().map(RecordColumn::name)
+    )]
+    pub fields: Vec<RecordColumn>,
+
+    /// How packet times are printed: iso, epoch (seconds since 1970), delta (seconds since
+    /// the previous packet) or elapsed (seconds since the capture started)
+    #[clap(long, value_name = "FORMAT", default_value = "iso")]
+    pub time_format: TimeFormat,
+
+    /// Color the packets by protocol: auto (only on a console), always or never
+    #[clap(long, value_name = "WHEN", default_value = "auto")]
+    pub color: ColorChoice,
+
+    /// Show the host names of addresses as they are looked up in the background. Packets
+    /// printed before the lookup of their addresses finishes show the addresses only
+    #[clap(long)]
+    pub resolve: bool,
+
+    /// Separator between the fields of a line: "tab", "comma" or any text
+    #[clap(long, value_name = "SEP", default_value = "tab", parse(from_str = separator))]
+    pub separator: String,
+
+    /// Show live totals, rates and the busiest protocols and sources instead of the packets,
+    /// redrawn every second. Press q to stop and p to pause
+    #[clap(
+        short,
+        long,
+        conflicts_with_all = &["brief", "fields", "quiet", "count_only"]
+    )]
+    pub stats: bool,
+
+    /// Stop after capturing this many packets, counting matched ones only when filtering
+    #[clap(long, value_name = "N")]
+    pub count: Option<u64>,
+
+    /// Stop after capturing for this many seconds, fractions allowed
+    #[clap(long, value_name = "SECONDS", parse(try_from_str = parse_seconds))]
+    pub duration: Option<Duration>,
+
+    /// Every this many seconds, print to stderr how many packets matched meanwhile, so a quiet
+    /// capture can be told from a stuck one in the logs
+    #[clap(
+        long,
+        value_name = "SECONDS",
+        parse(try_from_str = parse_seconds),
+        conflicts_with = "stats"
+    )]
+    pub heartbeat: Option<Duration>,
 }
 
-pub fn main() -> Result<()> {
-    if attach_console().is_err() {
-        alloc_console()?;
+impl PacketArgs {
+    fn hex_dump<'a>(&self, bytes: &'a [u8]) -> HexDump<'a> {
+        HexDump {
+            bytes,
+            width: self.hex_width,
+            ascii: self.ascii,
+        }
+    }
+
+    /// the filter --follow or --follow-host stand for
+    fn follow_expr(&self) -> Option<FilterExpr> {
+        match (self.follow, self.follow_host) {
+            (Some((a, b)), _) => Some(FilterExpr::conversation(a, b)),
+            (None, Some(ip)) => Some(FilterExpr::host(ip)),
+            (None, None) => None,
+        }
+    }
+
+    /// the filter of the flags that stand for one, i.e. all but --filter
+    fn flags_expr(&self) -> Option<FilterExpr> {
+        iter::once(FilterExpr::hosts_and_ports(&self.host, &self.port))
+            .chain(iter::once(self.follow_expr()))
+            .flatten()
+            .reduce(FilterExpr::and)
+    }
+
+    /// whether some packets may be left out
+    fn filtered(&self) -> bool {
+        self.filter.is_some() || self.flags_expr().is_some()
+    }
+}
+
+fn positive(n: &str) -> Result<()> {
+    match n.parse::<usize>() {
+        Ok(n) if n > 0 => Ok(()),
+        _ => bail!("expected a positive number"),
+    }
+}
+
+/// parse `args`, the gui being started when no subcommand is given; parse errors and help
+/// are printed on a console, as the gui has none to print them on
+pub fn launch(args: Vec<OsString>) -> Launch {
+    match parse_launch(args) {
+        Ok(launch) => launch,
+        Err(err) => {
+            let own_console = open_console();
+            let _ = err.print();
+            if own_console {
+                wait_for_key();
+            }
+            process::exit(if err.use_stderr() {
+                ExitCode::Usage as i32
+            } else {
+                0
+            });
+        }
+    }
+}
+
+fn parse_launch(args: Vec<OsString>) -> Result<Launch, clap::Error> {
+    Ok(match CliArgs::try_parse_from(with_subcommand(args))? {
+        CliArgs {
+            command: Some(command),
+            lang,
+            ..
+        } => Launch::Cli { command, lang },
+        CliArgs {
+            debug,
+            file,
+            selftest,
+            command: None,
+            ..
+        } => Launch::Gui {
+            debug,
+            file,
+            selftest,
+        },
+    })
+}
+
+pub fn main(command: &Command, lang: Option<Lang>) -> Result<()> {
+    let own_console = open_console();
+    set_lang(lang.unwrap_or_else(system_lang));
+    let result = run(command);
+    // reported here rather than by returning it, which would print it for debugging and exit with 1
+    if let Err(err) = &result {
+        eprintln!("{}", tr!("error: {:#}", "错误：{:#}", err));
+    }
+    if own_console {
+        wait_for_key();
+    }
+    if let Err(err) = result {
+        process::exit(exit_code(&err));
     }
-    let cli_args = CliArgs::parse();
-    cli_main(&cli_args)?;
     Ok(())
 }
 
-pub fn cli_main(cli_args: &CliArgs) -> Result<()> {
-    /* Choose network interface */
-    let interfaces = {
-        let mut interfaces = ipconfig::get_adapters()?
-            .into_iter()
-            .filter(|adapter| adapter.ip_addresses().iter().any(|addr| addr.is_ipv4()))
-            .collect::<Vec<_>>();
-        interfaces.sort_by(|a1, a2| a1.description().cmp(a2.description()));
-        interfaces
+/// attach to the console of the shell that started us, or open a new one when started from
+/// explorer or a shortcut; `true` for a new one, which closes as soon as we exit
+fn open_console() -> bool {
+    attach_console().is_err() && alloc_console().is_ok()
+}
+
+/// keep a console of our own open until its output has been read
+fn wait_for_key() {
+    eprint!(
+        "{}",
+        tr!("press any key to close this window", "按任意键关闭此窗口")
+    );
+    while read_key().is_none() {
+        thread::sleep(Duration::from_millis(50));
+    }
+}
+
+/// `args` with the capture subcommand put in when flags come without one, as they did before
+/// there were subcommands; `--cli`, which used to ask for capturing, is dropped. A first
+/// argument that is no flag is a subcommand or the file for the gui, and is left alone
+fn with_subcommand(args: Vec<OsString>) -> Vec<OsString> {
+    const TOP_LEVEL_FLAGS: [&str; 6] = ["-h", "--help", "-V", "--version", "--debug", "--selftest"];
+    // --lang may come before a subcommand as well, what follows it decides
+    let skip = match args.get(1).and_then(|arg| arg.to_str()) {
+        Some("--lang") => 2,
+        Some(arg) if arg.starts_with("--lang=") => 1,
+        _ => 0,
     };
-    print_interfaces(interfaces.iter(), true);
-    println!("choose an interface with the number at the beginning of the row");
-    let interface = {
-        let mut choice = String::new();
-        loop {
-            io::stdout().flush()?;
-            choice.clear();
-            io::stdin().read_line(&mut choice)?;
-            let id: usize = match choice.trim().parse() {
-                Ok(num) => num,
-                Err(_) => {
-                    println!(
-                        "choice must be a number between 0 to {}",
-                        interfaces.len() - 1
-                    );
-                    continue;
-                }
+    let first = match args.get(1 + skip).and_then(|arg| arg.to_str()) {
+        Some(first) => first,
+        None => return args,
+    };
+    if !first.starts_with('-') || TOP_LEVEL_FLAGS.contains(&first) {
+        return args;
+    }
+    let mut args = args.into_iter();
+    iter::once(args.next().unwrap_or_default())
+        .chain(iter::once("capture".into()))
+        .chain(args.filter(|arg| arg != "--cli" && arg != "-c"))
+        .collect()
+}
+
+fn run(command: &Command) -> Result<()> {
+    // fail before the output file is touched or an interface is asked for
+    let elevate = || match is_elevated() {
+        Ok(false) => Err(CliError::privilege()),
+        _ => Ok(()),
+    };
+    match command {
+        Command::Capture(args) if args.live.selftest => {
+            cli_main(Input::Synthetic { paced: true }, &args.packets)
+        }
+        Command::Capture(args) => {
+            elevate()?;
+            if args.live.bench {
+                bench(args)
+            } else {
+                cli_main(Input::Live(&args.live), &args.packets)
+            }
+        }
+        Command::Interfaces(args) => {
+            print_interfaces(capture_interfaces()?.iter(), true, args.json)
+        }
+        Command::Report(args) => {
+            if args.read_pcap.is_none() && !args.capture.live.selftest {
+                elevate()?;
+            }
+            report(args)
+        }
+        Command::Read(args) => cli_main(Input::File(&args.file), &args.packets),
+        Command::CheckFilter(args) => check_filter(&args.expr),
+    }
+}
+
+/// print `expr` back the way it parses, with the operators it combines made explicit
+fn check_filter(expr: &str) -> Result<()> {
+    match parse_filter(expr) {
+        Ok(expr) => {
+            println!("{}", expr);
+            Ok(())
+        }
+        Err(err) => bail!(invalid_filter(expr, &err)),
+    }
+}
+
+/// `expr` with the error marked under it, which is printed after an "error: " line of its own
+fn invalid_filter(expr: &str, err: &FilterError<&str>) -> CliError {
+    CliError::new(
+        ExitCode::Usage,
+        tr!("invalid filter\n{}", "筛选器不合法\n{}", err.describe(expr)),
+    )
+}
+
+/// a flag `report` takes along with the other capture flags but has no use for, as it prints
+/// the tables only
+fn unused_report_flag(capture: &CaptureArgs) -> Option<&'static str> {
+    let packets = &capture.packets;
+    [
+        ("--bench", capture.live.bench),
+        ("--raw-stdout", packets.raw_stdout),
+        ("--output", packets.output.is_some()),
+        ("--packet", packets.packet),
+        ("--payload", packets.payload),
+        ("--brief", packets.brief),
+        ("--fields", !packets.fields.is_empty()),
+        ("--stats", packets.stats),
+        ("--count-only", packets.count_only),
+    ]
+    .iter()
+    .find(|(_, used)| *used)
+    .map(|(flag, _)| *flag)
+}
+
+fn report(args: &ReportArgs) -> Result<()> {
+    if let Some(flag) = unused_report_flag(&args.capture) {
+        bail!(CliError::new(
+            ExitCode::Usage,
+            tr!("report does not take {}", "report 不支持 {}", flag)
+        ));
+    }
+    let mut packets = args.capture.packets.clone();
+    // the tables are all there is to see
+    packets.quiet = true;
+    packets.stats = false;
+    let input = match &args.read_pcap {
+        Some(path) => Input::File(path),
+        // the whole of the generated traffic right away, the tables are the same
+        None if args.capture.live.selftest => Input::Synthetic { paced: false },
+        None => Input::Live(&args.capture.live),
+    };
+    let summary = capture(input, &packets, args.top.is_some())?;
+    let top = args.top.zip(summary.talkers.as_ref());
+    if args.json {
+        let mut json = report_json(&summary.stat);
+        if let Some(protocol) = summary.only {
+            json["only_protocol"] = protocol.name().into();
+        }
+        if let Some((n, talkers)) = top {
+            json["top"] = top_json(talkers, n, args.sort_by);
+        }
+        println!("{}", serde_json::to_string_pretty(&json)?);
+    } else {
+        if let Some(protocol) = summary.only {
+            println!("{}\n", only_text(protocol));
+        }
+        print!("{}", report_text(&summary.stat, &format_bytes));
+        if let Some((n, talkers)) = top {
+            print!("\n{}", top_text(talkers, n, args.sort_by, &format_bytes));
+        }
+    }
+    io::stdout().flush()?;
+    Ok(())
+}
+
+fn bench(args: &CaptureArgs) -> Result<()> {
+    let live = &args.live;
+    let (addr, name) = capture_address(live, true)?;
+    let mut capturer = Capturer::new();
+    capturer.set_recv_mode(live.recv_mode);
+    capturer.set_protocol(live.proto);
+    // bound as any other run, so both read from alike sockets
+    capturer
+        .capture(addr, live.bind_port, live.poll)
+        .map_err(|err| CliError::socket(&err, addr))?;
+    if !live.poll {
+        capturer
+            .set_read_mode(ReadMode::Events)
+            .map_err(|err| CliError::socket(&err, addr))?;
+    }
+    catch_interrupt()?;
+    let duration = args.packets.duration.unwrap_or(bench::DEFAULT_DURATION);
+    let report = bench::run(&mut capturer, &name, live.poll, duration)?;
+    print!("{}", report.to_text());
+    io::stdout().flush()?;
+    Ok(())
+}
+
+/// where the flags say to read packets from
+#[derive(Clone, Copy)]
+enum Input<'a> {
+    Live(&'a LiveArgs),
+    File(&'a Path),
+    /// the traffic of `synth::demo_flows`, in real time when `paced`
+    Synthetic {
+        paced: bool,
+    },
+}
+
+/// the address of the interface the flags choose, and its description; without `prompt`,
+/// not choosing one is an error rather than a question
+fn capture_address(live: &LiveArgs, prompt: bool) -> Result<(Ipv4Addr, String)> {
+    Ok(match live.bind {
+        // listing the adapters is what makes startup slow, so --bind goes without
+        Some(addr) => (addr, addr.to_string()),
+        None => {
+            let interfaces = capture_interfaces()?;
+            let interface = match live.interface.as_deref() {
+                Some(query) => find_interface(&interfaces, query),
+                None if prompt && atty::is(atty::Stream::Stdin) => choose_interface(&interfaces)?,
+                None => bail!(CliError::new(
+                    ExitCode::Usage,
+                    tr!(
+                        "no interface to capture on, pass one with --interface or --bind",
+                        "没有要捕获的网卡，请用 --interface 或 --bind 指定"
+                    )
+                )),
             };
-            break match interfaces.iter().nth(id) {
-                Some(ni) => {
-                    if ni.oper_status() != ipconfig::OperStatus::IfOperStatusUp {
-                        println!("Network Interface is not up, please choose another one");
-                        continue;
-                    }
-                    ni
-                }
-                None => {
-                    println!(
-                        "choice must be a number between 0 to {}",
-                        interfaces.len() - 1
-                    );
-                    continue;
-                }
+            let addr = interface
+                .ip_addresses()
+                .iter()
+                .find_map(|addr| match addr {
+                    IpAddr::V4(addr) => Some(*addr),
+                    IpAddr::V6(_) => None,
+                })
+                .ok_or(anyhow!(tr!("no address available", "网卡没有可用的地址")))?;
+            (addr, interface.description().to_string())
+        }
+    })
+}
+
+/// a capturer on the interface the flags choose, with its address and description; its reads
+/// wait on socket events, or with --poll on WSAPoll over a non-blocking socket
+fn open_capturer(live: &LiveArgs, prompt: bool) -> Result<(Capturer, Ipv4Addr, String)> {
+    let (interface_addr, interface_name) = capture_address(live, prompt)?;
+
+    let mut capturer = Capturer::new();
+    capturer.set_recv_mode(live.recv_mode);
+    capturer.set_protocol(live.proto);
+    let target = (interface_addr, interface_name.clone());
+    if let Some((_, err)) = capturer
+        .capture_all(&[target], live.bind_port, live.poll)
+        .pop()
+    {
+        bail!(CliError::socket(&err, interface_addr));
+    }
+    if !live.poll {
+        capturer
+            .set_read_mode(ReadMode::Events)
+            .map_err(|err| CliError::socket(&err, interface_addr))?;
+    }
+    Ok((capturer, interface_addr, interface_name))
+}
+
+/// a failed read of the capture socket on `addr` as a socket error, which picks the exit code;
+/// that of a saved capture as it is
+fn read_failed(err: anyhow::Error, addr: Option<Ipv4Addr>) -> anyhow::Error {
+    let addr = match addr {
+        Some(addr) => addr,
+        None => return err,
+    };
+    let err = match err.downcast::<AdapterLost>() {
+        Ok(lost) => return CliError::socket(&lost.source, addr).into(),
+        Err(err) => err,
+    };
+    match err.downcast::<CaptureError>() {
+        Ok(err) => CliError::socket(&err, addr).into(),
+        Err(err) => err,
+    }
+}
+
+/// adapters with an ipv4 address sorted by description, in the order they are listed
+fn capture_interfaces() -> Result<Vec<Adapter>> {
+    let mut interfaces = ipconfig::get_adapters()?
+        .into_iter()
+        .filter(|adapter| adapter.ip_addresses().iter().any(|addr| addr.is_ipv4()))
+        .collect::<Vec<_>>();
+    interfaces.sort_by(|a1, a2| a1.description().cmp(a2.description()));
+    Ok(interfaces)
+}
+
+fn cli_main(input: Input, cli_args: &PacketArgs) -> Result<()> {
+    let summary = capture(input, cli_args, false)?;
+    if cli_args.raw_stdout {
+        // stdout carries the packets alone
+        eprintln!(
+            "{}",
+            tr!(
+                "forwarded {} of {} packets in {:.1} s",
+                "{2:.1} 秒内转发了 {1} 个分组中的 {0} 个",
+                summary.packet_num,
+                summary.seen_num,
+                summary.elapsed.as_secs_f64()
+            )
+        );
+        if let Some(reads) = summary.reads_text() {
+            eprintln!("{}", reads);
+        }
+        for file in &summary.written {
+            eprintln!("{}", written_line(file));
+        }
+        return Ok(());
+    }
+    let scripted = cli_args.quiet || cli_args.count_only;
+    if cli_args.count_only {
+        println!("{}", summary.packet_num);
+    } else {
+        print_summary(
+            &summary.stat,
+            summary.seen_num,
+            summary.elapsed,
+            cli_args.filtered(),
+            summary.only,
+        );
+        if let Some(followed) = &summary.followed {
+            print_followed(followed);
+        }
+        if let Some(reads) = summary.reads_text() {
+            println!();
+            println!("{}", reads);
+        }
+        for file in &summary.written {
+            println!("{}", written_line(file));
+        }
+    }
+    io::stdout().flush()?;
+    // lets scripts ask whether anything matched with the exit code alone
+    if scripted && summary.packet_num == 0 && !cli_args.no_match_exit_code {
+        process::exit(ExitCode::NoMatch as i32);
+    }
+    Ok(())
+}
+
+/// what a run went through, for the output after it
+struct CaptureSummary {
+    stat: StatRecord,
+    /// packets read, and of them the ones passing the filter
+    seen_num: u64,
+    packet_num: u64,
+    /// how long the capture ran, or what a saved capture spans
+    elapsed: Duration,
+    /// the conversations of --follow and --follow-host
+    followed: Option<FollowedFlows>,
+    /// the traffic per address and conversation, for report --top
+    talkers: Option<TalkerTable>,
+    /// the files of --output, one unless rotating
+    written: Vec<WrittenFile>,
+    /// the protocol of --proto, which the tables are all of
+    only: Option<CaptureProtocol>,
+    /// the reads of a live capture, with the ip stack counters from before and after it and
+    /// the counters of the capturer
+    reads: Option<(ReadStats, Option<(IpCounts, IpCounts)>, CaptureMetrics)>,
+}
+
+impl CaptureSummary {
+    /// the line on the packets a live capture likely missed, followed by the ones on its reads
+    fn reads_text(&self) -> Option<String> {
+        self.reads.as_ref().map(|(reads, stack, metrics)| {
+            format!(
+                "{}\n{}",
+                reads.drop_text(*stack, self.only),
+                metrics.to_text()
+            )
+        })
+    }
+}
+
+/// read and print packets as the flags say until a limit, the end of a saved capture or Ctrl+C,
+/// also counting the top talkers for the summary when `talkers`
+fn capture(input: Input, cli_args: &PacketArgs, talkers: bool) -> Result<CaptureSummary> {
+    let filter = cli_args
+        .filter
+        .as_deref()
+        .map(|expr| create_filter(expr).map_err(|err| invalid_filter(expr, &err)))
+        .transpose()?;
+    let flags_filter = cli_args.flags_expr().map(FilterExpr::into_filter);
+    let mut followed = cli_args
+        .follow_expr()
+        .is_some()
+        .then(FollowedFlows::default);
+    let mut talkers = talkers.then(TalkerTable::default);
+
+    let mut output = match &cli_args.output {
+        Some(path) => {
+            let format = cli_args
+                .output_format
+                .or_else(|| OutputFormat::from_path(path))
+                .ok_or_else(|| {
+                    CliError::new(
+                        ExitCode::Usage,
+                        tr!(
+                            "can not tell the format of {} from its extension, pass --output-format",
+                            "无法从扩展名判断 {} 的格式，请用 --output-format 指定",
+                            path.display()
+                        ),
+                    )
+                })?;
+            let rotation = Rotation {
+                size: cli_args.rotate_size,
+                interval: cli_args.rotate_interval,
             };
+            // opened before the socket, or its directory checked when rotating, so a path that
+            // can not be written fails the run at once
+            let writer = RotatingWriter::new(path, format, rotation).map_err(|err| {
+                CliError::new(
+                    ExitCode::Output,
+                    tr!(
+                        "can not open {}: {}",
+                        "无法打开 {}：{}",
+                        path.display(),
+                        err
+                    ),
+                )
+            })?;
+            Some(writer)
         }
+        None => None,
     };
+    let write_failed = |writer: &RotatingWriter, err| CliError::output(writer.path(), err);
 
-    /* create ip packet sniffer */
-    let interface_addr = interface
-        .ip_addresses()
-        .iter()
-        .find(|&addr| addr.is_ipv4())
-        .ok_or(anyhow!("no address available"))?;
-    // It seems like you can bind any port to this?
-    let address = SocketAddr::from((interface_addr.clone(), 8000));
-    let mut socket = ipv4_capturer(address, cli_args.poll)?;
+    let mut raw = if cli_args.raw_stdout {
+        // a console would mangle binary output, which std refuses to write to one anyway;
+        // pipes and files get the bytes as they are, with no line ending translation
+        if atty::is(atty::Stream::Stdout) {
+            bail!(CliError::new(
+                ExitCode::Usage,
+                tr!(
+                    "--raw-stdout writes binary packets, pipe them into a program or redirect them to a file",
+                    "--raw-stdout 输出二进制分组，请用管道交给其他程序或重定向到文件"
+                )
+            ));
+        }
+        let writer = RawWriter::new(BufWriter::new(io::stdout()), cli_args.raw_format);
+        Some(writer.map_err(raw_write_failed)?)
+    } else {
+        None
+    };
+
+    let line_columns = if !cli_args.fields.is_empty() {
+        Some(cli_args.fields.clone())
+    } else if cli_args.brief {
+        Some(BRIEF_FIELDS.to_vec())
+    } else {
+        None
+    };
+
+    /* Choose where packets come from */
+    let only = match input {
+        Input::Live(live) => live.proto,
+        _ => None,
+    };
+    let (mut source, source_name, local_addr) = match input {
+        Input::File(path) => {
+            let not_readable = |err: anyhow::Error| {
+                CliError::new(
+                    ExitCode::Usage,
+                    tr!(
+                        "can not read {}: {}",
+                        "无法读取 {}：{}",
+                        path.display(),
+                        err
+                    ),
+                )
+            };
+            let file = File::open(path).map_err(|err| not_readable(err.into()))?;
+            let reader = PcapReader::new(BufReader::new(file)).map_err(not_readable)?;
+            let source: Box<dyn PacketSource> = Box::new(PcapSource::new(reader));
+            (source, path.display().to_string(), None)
+        }
+        Input::Live(live) => {
+            // the list and the prompt would end up among the packets of --raw-stdout
+            let (capturer, addr, name) = open_capturer(live, !cli_args.raw_stdout)?;
+            let source: Box<dyn PacketSource> = Box::new(capturer);
+            // the dashboard tells a capture of one protocol by its title
+            let name = match only {
+                Some(protocol) => tr!("{} ({} only)", "{}（仅 {}）", name, protocol.name()),
+                None => name,
+            };
+            (source, name, Some(addr))
+        }
+        Input::Synthetic { paced } => {
+            let source = SyntheticSource::new(demo_flows(), DEMO_LOCAL_IP, Local::now());
+            let source: Box<dyn PacketSource> = if paced {
+                Box::new(source.paced())
+            } else {
+                Box::new(source)
+            };
+            (source, SYNTHETIC_ADAPTER.to_string(), None)
+        }
+    };
+    catch_interrupt()?;
 
     /* start sniffing */
-    let mut buffer = vec![0; socket.recv_buffer_size()?];
+    let capacity = source.buffer_size();
+    // what the ip stack went through meanwhile tells how much of it the reads missed
+    let mut meter = ReadMeter::default();
+    let stack_before = local_addr.and_then(|_| ip_packet_counts().ok());
+    let started = Instant::now();
+    let capture_start = Local::now();
+    // of a saved capture, the times of its first and latest packets, which is what it spans
+    let mut file_span = None;
+    // packets read, and of them the ones passing the filter
+    let mut seen_num = 0u64;
+    let mut packet_num = 0u64;
+    let mut stat = StatRecord::default();
+    let mut timestamps = None;
+    let painter = Painter(cli_args.color.enabled());
+    let resolver = if cli_args.resolve {
+        Some(Resolver::system()?)
+    } else {
+        None
+    };
+    let mut dashboard = cli_args
+        .stats
+        .then(|| Dashboard::new(&source_name, resolver.clone()));
+    let mut heartbeat = cli_args.heartbeat.map(Heartbeat::new);
+    let mut flushed = Instant::now();
     loop {
-        match socket.read(buffer.as_mut_slice()) {
-            Ok(bytes) => {
-                /* parse and print packet info */
-                println!("read {} bytes: ", bytes);
-                if let Ok(mut ip_packet) = v4::Packet::new(&buffer[..bytes]) {
-                    if ip_packet.length() < 20 {
-                        println!(
-                            "corrupted ipv4 packet, Total Length = {} < 20",
-                            ip_packet.length()
-                        );
-                        if bytes > 4 {
-                            println!(
-                                "try to recover packet with whole byte array length {}...",
-                                bytes
-                            );
-                            (&mut buffer[2..]).write_u16::<NetworkEndian>(bytes as u16)?;
-                            ip_packet = v4::Packet::unchecked(&buffer[..bytes]);
-                        }
-                    }
-                    let have_payload = ip_packet.payload().len() != 0;
-
-                    println!(
-                        "transport layer protocol: {}",
-                        TransProtocol(ip_packet.protocol())
-                    );
-                    let src_ip = ip_packet.source();
-                    let dest_ip = ip_packet.destination();
-                    let (src_ipp, dest_ipp);
-                    let (src, dest): (&dyn Display, &dyn Display) = match ip_packet.protocol() {
-                        Protocol::Tcp if have_payload => {
-                            if let Ok(tcp_packet) = tcp::Packet::new(ip_packet.payload()) {
-                                let src_p = tcp_packet.source();
-                                let dest_p = tcp_packet.destination();
-                                src_ipp = SocketAddr::from((src_ip, src_p));
-                                dest_ipp = SocketAddr::from((dest_ip, dest_p));
-                                println!(
-                                    "application layer protocol: {}",
-                                    AppProtocol::from((src_p, dest_p))
-                                );
-                                (&src_ipp, &dest_ipp)
-                            } else {
-                                println!("corrupted TCP packet");
-                                (&src_ip, &dest_ip)
-                            }
-                        }
-                        Protocol::Udp if have_payload => {
-                            if let Ok(udp_packet) = udp::Packet::new(ip_packet.payload()) {
-                                let src_p = udp_packet.source();
-                                let dest_p = udp_packet.destination();
-                                src_ipp = SocketAddr::from((src_ip, src_p));
-                                dest_ipp = SocketAddr::from((dest_ip, dest_p));
-                                println!(
-                                    "application layer protocol: {}",
-                                    AppProtocol::from((src_p, dest_p))
-                                );
-                                (&src_ipp, &dest_ipp)
-                            } else {
-                                println!("corrupted UDP packet");
-                                (&src_ip, &dest_ip)
-                            }
+        if let Some(dashboard) = &mut dashboard {
+            if dashboard.handle_keys(&stat)? {
+                break;
+            }
+            dashboard.tick(&stat)?;
+        }
+        if flushed.elapsed() >= OUTPUT_FLUSH_INTERVAL {
+            if let Some(writer) = &mut output {
+                writer.flush().map_err(|err| write_failed(writer, err))?;
+            }
+            if let Some(raw) = &mut raw {
+                match raw.flush() {
+                    Err(err) if err.kind() == io::ErrorKind::BrokenPipe => break,
+                    result => result.map_err(raw_write_failed)?,
+                }
+            }
+            flushed = Instant::now();
+        }
+        if let Some(heartbeat) = &mut heartbeat {
+            heartbeat.tick(packet_num);
+        }
+        if interrupted()
+            || cli_args.count.map_or(false, |count| packet_num >= count)
+            || cli_args
+                .duration
+                .map_or(false, |duration| started.elapsed() >= duration)
+        {
+            break;
+        }
+        if !source.connected() {
+            break;
+        }
+        meter.start();
+        let (time, packet, local, truncated) = match source.read_timeout(READ_TIMEOUT) {
+            Ok(Some(read)) => read,
+            // back to the top, which flushes --output on a quiet interface as well
+            Ok(None) => continue,
+            Err(err) => bail!(read_failed(err, local_addr)),
+        };
+        meter.end(packet.len(), capacity);
+        if local_addr.is_none() {
+            let first = file_span.map_or(time, |(first, _)| first);
+            file_span = Some((first, time));
+        }
+        seen_num += 1;
+        let record = Record::from_packet(time, packet, local, truncated);
+        if let Some(filter) = &filter {
+            if !filter(&record) {
+                continue;
+            }
+        }
+        if let Some(flags_filter) = &flags_filter {
+            if !flags_filter(&record) {
+                continue;
+            }
+        }
+        packet_num += 1;
+        stat.update(&record);
+        if let Some(talkers) = &mut talkers {
+            talkers.update(&record);
+        }
+        let position = followed.as_mut().map(|flows| flows.add(&record));
+        if let Some(writer) = &mut output {
+            writer
+                .write(&record)
+                .map_err(|err| write_failed(writer, err))?;
+        }
+        if let Some(raw) = &mut raw {
+            let written = raw.write_packet(record.time, packet, record.len as usize);
+            let written = match written {
+                Ok(()) if cli_args.flush => raw.flush(),
+                written => written,
+            };
+            match written {
+                // the reader is gone, which ends the capture like Ctrl+C does
+                Err(err) if err.kind() == io::ErrorKind::BrokenPipe => break,
+                result => result.map_err(raw_write_failed)?,
+            }
+            continue;
+        }
+        if let Some(dashboard) = &mut dashboard {
+            dashboard.update(&record);
+            continue;
+        }
+        if cli_args.quiet || cli_args.count_only {
+            continue;
+        }
+        // relative times of a saved capture count from its first packet
+        let start = file_span.map_or(capture_start, |(first, _)| first);
+        let time = timestamps
+            .get_or_insert_with(|| Timestamps::new(cli_args.time_format, start))
+            .format(record.time);
+        match &line_columns {
+            Some(columns) => {
+                let mut cells = record.to_column_strings(columns);
+                for (cell, column) in cells.iter_mut().zip(columns) {
+                    let ip = match column {
+                        RecordColumn::Time => {
+                            *cell = time.clone();
+                            continue;
                         }
-                        _ => (&src_ip, &dest_ip),
+                        RecordColumn::SrcIp => record.src_ip,
+                        RecordColumn::DestIp => record.dest_ip,
+                        _ => continue,
                     };
-                    println!("source: {}", src);
-                    println!("destination: {}", dest);
-                    if cli_args.packet {
-                        println!("whole packet:");
-                        print!("{}", Bytes(ip_packet.as_ref()));
-                    }
-                    if cli_args.payload {
-                        println!("ip packet payload, {} bytes:", ip_packet.payload().len());
-                        print!("{}", Bytes(ip_packet.payload()));
-                    } else {
-                        println!("ip packet payload: {} bytes", ip_packet.payload().len());
+                    if let Some(name) = ip
+                        .zip(resolver.as_ref())
+                        .and_then(|(ip, resolver)| resolver.name(ip))
+                    {
+                        *cell = format!("{} ({})", name, cell);
                     }
-                    println!();
-                } else {
-                    println!("corrupted ipv4 packet");
-                    print!("{}", Bytes(&buffer[..bytes]));
                 }
+                let line = cells.join(&cli_args.separator);
+                println!("{}", painter.protocol(line, record.trans_proto));
             }
-            Err(err) => match err.raw_os_error() {
-                Some(10035) => continue,
-                _ => bail!(err),
+            None => match position {
+                Some(position) => {
+                    let line = followed_line(&record, position);
+                    println!("{}", painter.protocol(line, record.trans_proto));
+                }
+                None => print_packet(cli_args, painter, resolver.as_ref(), &time, packet)?,
             },
         }
         if cli_args.flush {
             io::stdout().flush()?;
         }
     }
+    if let Some(writer) = &mut output {
+        writer.flush().map_err(|err| write_failed(writer, err))?;
+    }
+    if let Some(raw) = &mut raw {
+        match raw.flush() {
+            Err(err) if err.kind() == io::ErrorKind::BrokenPipe => {}
+            result => result.map_err(raw_write_failed)?,
+        }
+    }
+    let elapsed = match file_span {
+        Some((first, last)) => (last - first).to_std().unwrap_or_default(),
+        None => started.elapsed(),
+    };
+    Ok(CaptureSummary {
+        stat,
+        seen_num,
+        packet_num,
+        elapsed,
+        followed,
+        talkers,
+        written: output.map_or_else(Vec::new, |writer| writer.files().to_vec()),
+        only,
+        reads: local_addr.map(|_| {
+            let stack = stack_before.zip(ip_packet_counts().ok());
+            (meter.stats(), stack, source.metrics())
+        }),
+    })
+}
+
+/// `#seq +secs src → dest len`, the line of a followed packet, with the seconds counted
+/// from the first packet of its conversation
+fn followed_line(record: &Record, position: FollowedPacket) -> String {
+    format!(
+        "#{} +{} {} → {} {} len {}",
+        position.seq,
+        format_seconds(position.since_start),
+        endpoint_text((record.src_ip, record.src_port)),
+        endpoint_text((record.dest_ip, record.dest_port)),
+        trans_protocol_name(record.trans_proto),
+        record.len
+    )
+}
+
+/// what went each way in each followed conversation
+fn print_followed(followed: &FollowedFlows) {
+    let rows = followed
+        .flows()
+        .iter()
+        .flat_map(|flow| {
+            let (a, b) = (endpoint_text(flow.key.a), endpoint_text(flow.key.b));
+            [(a.clone(), b.clone()), (b, a)]
+                .into_iter()
+                .zip(&flow.sent)
+                .map(move |((from, to), sent)| {
+                    vec![
+                        format!("{} {} → {}", flow.key.trans_proto, from, to),
+                        sent.packet_num.to_string(),
+                        format_bytes(sent.byte_num),
+                    ]
+                })
+        })
+        .collect::<Vec<_>>();
+    if !rows.is_empty() {
+        println!();
+        let headers = [
+            tr!("direction", "方向"),
+            tr!("packets", "分组数"),
+            tr!("bytes", "字节数"),
+        ];
+        print!("{}", text_table(&headers, &rows));
+    }
+}
+
+fn raw_write_failed(err: io::Error) -> CliError {
+    CliError::new(
+        ExitCode::Output,
+        tr!(
+            "can not write the packets to stdout: {}",
+            "无法将分组写到标准输出：{}",
+            err
+        ),
+    )
+}
+
+fn written_line(file: &WrittenFile) -> String {
+    tr!(
+        "wrote {} records to {}",
+        "已将 {} 条记录写入 {}",
+        file.record_num,
+        file.path.display()
+    )
+}
+
+/// the closing line with the packet counts, then the protocol tables
+fn print_summary(
+    stat: &StatRecord,
+    seen_num: u64,
+    elapsed: Duration,
+    filtered: bool,
+    only: Option<CaptureProtocol>,
+) {
+    let packet_num = stat.stat_net_table.packet_num;
+    let byte_num = stat.stat_net_table.byte_num;
+    let elapsed = elapsed.as_secs_f64();
+    if filtered {
+        println!(
+            "{}",
+            tr!(
+                "matched {} of {} packets in {:.1} s, {}",
+                "{2:.1} 秒内的 {1} 个分组中有 {0} 个匹配，共 {3}",
+                packet_num,
+                seen_num,
+                elapsed,
+                format_bytes(byte_num)
+            )
+        );
+    } else {
+        println!(
+            "{}",
+            tr!(
+                "captured {} packets in {:.1} s, {}",
+                "{1:.1} 秒内捕获了 {0} 个分组，共 {2}",
+                packet_num,
+                elapsed,
+                format_bytes(byte_num)
+            )
+        );
+    }
+    if let Some(protocol) = only {
+        println!("{}", only_text(protocol));
+    }
+    // the same tables as the report subcommand prints
+    if packet_num != 0 {
+        println!();
+        print!("{}", report_text(stat, &format_bytes));
+    }
+}
+
+/// the line that keeps a capture of one protocol from being taken for one of all traffic
+fn only_text(protocol: CaptureProtocol) -> String {
+    tr!(
+        "only {} was captured, the tables leave out every other protocol",
+        "仅捕获了 {}，各表不含其他协议",
+        protocol.name()
+    )
+}
+
+/// rows of each table on the `--stats` dashboard
+const DASHBOARD_ROWS: usize = 5;
+
+/// the view `--stats` redraws every second in place of printing packets
+struct Dashboard {
+    interface: String,
+    started: Instant,
+    resolver: Option<Resolver>,
+    /// packets and bytes sent from each address
+    sources: HashMap<Ipv4Addr, NetRecord>,
+    /// when the view was drawn last, rates are computed over the time since
+    drawn: Instant,
+    /// the totals and the bytes of each application protocol when the view was drawn last
+    drawn_total: NetRecord,
+    drawn_app_bytes: HashMap<&'static str, u64>,
+    paused: bool,
+    /// redraw in place with ansi sequences, otherwise each view is appended to the output
+    ansi: bool,
+    keys: bool,
+}
+
+impl Dashboard {
+    fn new(interface: &str, resolver: Option<Resolver>) -> Self {
+        let ansi = atty::is(atty::Stream::Stdout) && enable_ansi().is_ok();
+        if ansi {
+            // start from a blank screen, later views only overwrite it
+            print!("\x1b[2J");
+        }
+        Self {
+            interface: interface.to_string(),
+            started: Instant::now(),
+            resolver,
+            sources: HashMap::new(),
+            drawn: Instant::now(),
+            drawn_total: Default::default(),
+            drawn_app_bytes: HashMap::new(),
+            paused: false,
+            ansi,
+            keys: atty::is(atty::Stream::Stdin),
+        }
+    }
+
+    fn update(&mut self, record: &Record) {
+        if let Some(src_ip) = record.src_ip {
+            self.sources
+                .entry(src_ip)
+                .or_default()
+                .add_up(&record.into());
+        }
+    }
+
+    /// act on the keys pressed since the last call, returning whether to quit
+    fn handle_keys(&mut self, stat: &StatRecord) -> Result<bool> {
+        while let Some(key) = self.keys.then(read_key).flatten() {
+            match key.to_ascii_lowercase() {
+                'q' => return Ok(true),
+                'p' => {
+                    self.paused = !self.paused;
+                    // show the pause at once, rather than after a second of seeming to hang
+                    self.draw(stat)?;
+                }
+                _ => {}
+            }
+        }
+        Ok(false)
+    }
+
+    /// redraw once a second has passed since the last view
+    fn tick(&mut self, stat: &StatRecord) -> Result<()> {
+        if !self.paused && self.drawn.elapsed() >= Duration::from_secs(1) {
+            self.draw(stat)?;
+        }
+        Ok(())
+    }
+
+    fn draw(&mut self, stat: &StatRecord) -> Result<()> {
+        let text = self.render(stat);
+        if self.ansi {
+            let mut out = String::from("\x1b[H");
+            for line in text.lines() {
+                // clear what is left of the longer line drawn here last time
+                out += line;
+                out += "\x1b[K\n";
+            }
+            out += "\x1b[J";
+            print!("{}", out);
+        } else {
+            println!("{}", text);
+        }
+        io::stdout().flush()?;
+        self.drawn = Instant::now();
+        self.drawn_total = stat.stat_net_table.clone();
+        self.drawn_app_bytes = stat
+            .stat_app_table
+            .iter()
+            .map(|(&name, record)| (name, record.byte_num))
+            .collect();
+        Ok(())
+    }
+
+    fn render(&self, stat: &StatRecord) -> String {
+        let secs = self.drawn.elapsed().as_secs_f64().max(0.001);
+        let elapsed = chrono::Duration::milliseconds(self.started.elapsed().as_millis() as i64);
+        let total = &stat.stat_net_table;
+        let mut text = format!(
+            "{}  {}{}\n",
+            self.interface,
+            format_duration(elapsed),
+            if self.paused {
+                tr!("  [paused]", "  [已暂停]")
+            } else {
+                ""
+            }
+        );
+        text += &tr!(
+            "packets {} ({:.0}/s)  bytes {} ({})\n\n",
+            "分组 {} ({:.0}/s)  字节 {} ({})\n\n",
+            format_count(total.packet_num),
+            (total.packet_num - self.drawn_total.packet_num) as f64 / secs,
+            format_bytes(total.byte_num),
+            format_byte_rate((total.byte_num - self.drawn_total.byte_num) as f64 / secs)
+        );
+
+        let mut trans = stat.stat_trans_table.iter().collect::<Vec<_>>();
+        trans.sort_by(|(_, a), (_, b)| b.byte_num.cmp(&a.byte_num));
+        let rows = trans
+            .into_iter()
+            .take(DASHBOARD_ROWS)
+            .map(|(name, record)| {
+                vec![
+                    name.to_string(),
+                    format_count(record.packet_num),
+                    format_bytes(record.byte_num),
+                ]
+            })
+            .collect::<Vec<_>>();
+        let headers = [
+            tr!("transport", "传输层协议"),
+            tr!("packets", "分组数"),
+            tr!("bytes", "字节数"),
+        ];
+        text += &text_table(&headers, &rows);
+        text.push('\n');
+
+        let mut apps = stat.stat_app_table.iter().collect::<Vec<_>>();
+        apps.sort_by(|(_, a), (_, b)| b.byte_num.cmp(&a.byte_num));
+        let rows = apps
+            .into_iter()
+            .take(DASHBOARD_ROWS)
+            .map(|(name, record)| {
+                let drawn = self.drawn_app_bytes.get(name).copied().unwrap_or(0);
+                vec![
+                    name.to_string(),
+                    format_count(record.packet_num),
+                    format_bytes(record.byte_num),
+                    format_byte_rate((record.byte_num - drawn) as f64 / secs),
+                ]
+            })
+            .collect::<Vec<_>>();
+        let headers = [
+            tr!("application", "应用层协议"),
+            tr!("packets", "分组数"),
+            tr!("bytes", "字节数"),
+            tr!("rate", "速率"),
+        ];
+        text += &text_table(&headers, &rows);
+        text.push('\n');
+
+        let mut sources = self.sources.iter().collect::<Vec<_>>();
+        sources.sort_by(|(_, a), (_, b)| b.byte_num.cmp(&a.byte_num));
+        let rows = sources
+            .into_iter()
+            .take(DASHBOARD_ROWS)
+            .map(|(ip, record)| {
+                vec![
+                    match &self.resolver {
+                        Some(resolver) => resolver.label(*ip),
+                        None => ip.to_string(),
+                    },
+                    format_count(record.packet_num),
+                    format_bytes(record.byte_num),
+                ]
+            })
+            .collect::<Vec<_>>();
+        let headers = [
+            tr!("source", "源地址"),
+            tr!("packets", "分组数"),
+            tr!("bytes", "字节数"),
+        ];
+        text += &text_table(&headers, &rows);
+        if self.keys {
+            text += tr!("\nq quit  p pause\n", "\nq 退出  p 暂停\n");
+        }
+        text
+    }
+}
+
+/// print a packet read at `time` in the multi-line form, parsing it as it goes
+fn print_packet(
+    cli_args: &PacketArgs,
+    painter: Painter,
+    resolver: Option<&Resolver>,
+    time: &str,
+    packet: &mut [u8],
+) -> Result<()> {
+    let bytes = packet.len();
+    /* parse and print packet info */
+    println!(
+        "{}",
+        tr!("[{}] read {} bytes: ", "[{}] 读到 {} 字节：", time, bytes)
+    );
+    if let Ok(mut ip_packet) = v4::Packet::new(&packet[..]) {
+        if ip_packet.length() < 20 {
+            println!(
+                "{}",
+                painter.error(tr!(
+                    "corrupted ipv4 packet, Total Length = {} < 20",
+                    "损坏的 IPv4 分组，总长度 = {} < 20",
+                    ip_packet.length()
+                ))
+            );
+            if bytes > 4 {
+                println!(
+                    "{}",
+                    tr!(
+                        "try to recover packet with whole byte array length {}...",
+                        "尝试以读到的全部 {} 字节恢复分组…",
+                        bytes
+                    )
+                );
+                (&mut packet[2..]).write_u16::<NetworkEndian>(bytes as u16)?;
+                ip_packet = v4::Packet::unchecked(&packet[..]);
+            }
+        }
+        let have_payload = ip_packet.payload().len() != 0;
+        let protocol = ip_packet.protocol();
+
+        println!(
+            "{}{}",
+            tr!("transport layer protocol: ", "传输层协议："),
+            painter.protocol(TransProtocol(protocol), protocol)
+        );
+        let src_ip = ip_packet.source();
+        let dest_ip = ip_packet.destination();
+        let (src_ipp, dest_ipp);
+        let (src, dest): (&dyn Display, &dyn Display) = match ip_packet.protocol() {
+            Protocol::Tcp if have_payload => {
+                if let Ok(tcp_packet) = tcp::Packet::new(ip_packet.payload()) {
+                    let src_p = tcp_packet.source();
+                    let dest_p = tcp_packet.destination();
+                    src_ipp = SocketAddr::from((src_ip, src_p));
+                    dest_ipp = SocketAddr::from((dest_ip, dest_p));
+                    println!(
+                        "{}{}",
+                        tr!("application layer protocol: ", "应用层协议："),
+                        painter.protocol(AppProtocol::from((src_p, dest_p)), protocol)
+                    );
+                    (&src_ipp, &dest_ipp)
+                } else {
+                    println!(
+                        "{}",
+                        painter.error(tr!("corrupted TCP packet", "损坏的 TCP 报文段"))
+                    );
+                    (&src_ip, &dest_ip)
+                }
+            }
+            Protocol::Udp if have_payload => {
+                if let Ok(udp_packet) = udp::Packet::new(ip_packet.payload()) {
+                    let src_p = udp_packet.source();
+                    let dest_p = udp_packet.destination();
+                    src_ipp = SocketAddr::from((src_ip, src_p));
+                    dest_ipp = SocketAddr::from((dest_ip, dest_p));
+                    println!(
+                        "{}{}",
+                        tr!("application layer protocol: ", "应用层协议："),
+                        painter.protocol(AppProtocol::from((src_p, dest_p)), protocol)
+                    );
+                    (&src_ipp, &dest_ipp)
+                } else {
+                    println!(
+                        "{}",
+                        painter.error(tr!("corrupted UDP packet", "损坏的 UDP 报文段"))
+                    );
+                    (&src_ip, &dest_ip)
+                }
+            }
+            _ => (&src_ip, &dest_ip),
+        };
+        let label = |ip, addr: &dyn Display| match resolver.and_then(|resolver| resolver.name(ip)) {
+            Some(name) => format!("{} ({})", name, addr),
+            None => addr.to_string(),
+        };
+        println!(
+            "{}{}",
+            tr!("source: ", "源地址："),
+            painter.bold(label(src_ip, src))
+        );
+        println!(
+            "{}{}",
+            tr!("destination: ", "目的地址："),
+            painter.bold(label(dest_ip, dest))
+        );
+        let payload_len = ip_packet.payload().len();
+        if cli_args.packet {
+            println!("{}", tr!("whole packet:", "整个分组："));
+            print!("{}", cli_args.hex_dump(ip_packet.as_ref()));
+        }
+        if cli_args.payload {
+            println!(
+                "{}",
+                tr!(
+                    "ip packet payload, {} bytes:",
+                    "IP 分组数据，{} 字节：",
+                    payload_len
+                )
+            );
+            print!("{}", cli_args.hex_dump(ip_packet.payload()));
+        } else {
+            println!(
+                "{}",
+                tr!(
+                    "ip packet payload: {} bytes",
+                    "IP 分组数据：{} 字节",
+                    payload_len
+                )
+            );
+        }
+        println!();
+    } else {
+        println!(
+            "{}",
+            painter.error(tr!("corrupted ipv4 packet", "损坏的 IPv4 分组"))
+        );
+        print!("{}", cli_args.hex_dump(packet));
+    }
+    Ok(())
+}
+
+/// colors packet output, or leaves it plain when --color says not to
+#[derive(Clone, Copy)]
+struct Painter(bool);
+
+impl Painter {
+    /// for a dark console background, which is what windows gives a console by default; pink
+    /// rather than red, which icmp packets are shown in
+    const ERROR: (u8, u8, u8) = (240, 98, 146);
+
+    fn protocol(self, text: impl Display, protocol: Protocol) -> String {
+        self.color(text, PlotProtocol::from(protocol).color(true))
+    }
+
+    fn error(self, text: impl Display) -> String {
+        self.color(text, Self::ERROR)
+    }
+
+    fn color(self, text: impl Display, rgb: (u8, u8, u8)) -> String {
+        if self.0 {
+            ansi_color(text, rgb)
+        } else {
+            text.to_string()
+        }
+    }
+
+    fn bold(self, text: impl Display) -> String {
+        if self.0 {
+            ansi_bold(text)
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+/// the fields of a line printed with --brief
+const BRIEF_FIELDS: [RecordColumn; 6] = [
+    RecordColumn::Time,
+    RecordColumn::SrcIp,
+    RecordColumn::DestIp,
+    RecordColumn::TransProto,
+    RecordColumn::Len,
+    RecordColumn::AppProto,
+];
+
+fn separator(sep: &str) -> String {
+    match sep {
+        "tab" => "\t".to_string(),
+        "comma" => ",".to_string(),
+        _ => sep.to_string(),
+    }
+}
+
+/// how long written packets may sit in the buffer before they reach the --output file, with
+/// packets arriving or not
+const OUTPUT_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+/// how long a live capture waits for a packet before it checks for Ctrl+C and the duration
+/// limit again, so a quiet interface can not hold them off
+const READ_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// a line on stderr every --heartbeat, with the packets matched since the last one
+struct Heartbeat {
+    interval: Duration,
+    last: Instant,
+    last_num: u64,
+}
+
+impl Heartbeat {
+    fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            last: Instant::now(),
+            last_num: 0,
+        }
+    }
+
+    /// print the line if it is due, `packet_num` being the packets matched so far
+    fn tick(&mut self, packet_num: u64) {
+        // reads in between may well hold the line past its interval
+        let elapsed = self.last.elapsed();
+        if elapsed < self.interval {
+            return;
+        }
+        let recent = packet_num - self.last_num;
+        eprintln!("{}", heartbeat_line(recent, elapsed, packet_num));
+        self.last = Instant::now();
+        self.last_num = packet_num;
+    }
+}
+
+/// the line for `recent` packets matched over the `elapsed` time since the last one
+fn heartbeat_line(recent: u64, elapsed: Duration, total: u64) -> String {
+    tr!(
+        "… {} packets in the last {} s (total {})",
+        "… 最近 {1} 秒内 {0} 个分组（共 {2} 个）",
+        format_count(recent),
+        (elapsed.as_secs_f64() * 10.0).round() / 10.0,
+        format_count(total)
+    )
+}
+
+/// ask for an interface from the listing until an up one is chosen
+fn choose_interface(interfaces: &[Adapter]) -> Result<&Adapter> {
+    print_interfaces(interfaces.iter(), true, false)?;
+    println!(
+        "{}",
+        tr!(
+            "choose an interface with the number at the beginning of the row",
+            "请输入行首的序号选择网卡"
+        )
+    );
+    let mut choice = String::new();
+    let interface = loop {
+        io::stdout().flush()?;
+        choice.clear();
+        io::stdin().read_line(&mut choice)?;
+        let id: usize = match choice.trim().parse() {
+            Ok(num) => num,
+            Err(_) => {
+                println!(
+                    "{}",
+                    tr!(
+                        "choice must be a number between 0 to {}",
+                        "请输入 0 到 {} 之间的序号",
+                        interfaces.len() - 1
+                    )
+                );
+                continue;
+            }
+        };
+        break match interfaces.iter().nth(id) {
+            Some(ni) => {
+                if ni.oper_status() != ipconfig::OperStatus::IfOperStatusUp {
+                    println!(
+                        "{}",
+                        tr!(
+                            "Network Interface is not up, please choose another one",
+                            "网卡未启用，请选择其他网卡"
+                        )
+                    );
+                    continue;
+                }
+                ni
+            }
+            None => {
+                println!(
+                    "{}",
+                    tr!(
+                        "choice must be a number between 0 to {}",
+                        "请输入 0 到 {} 之间的序号",
+                        interfaces.len() - 1
+                    )
+                );
+                continue;
+            }
+        };
+    };
+    Ok(interface)
+}
+
+/// the interface `--interface` refers to, exiting with code 2 like other usage errors when it
+/// does not name exactly one interface that is up
+fn find_interface<'a>(interfaces: &'a [Adapter], query: &str) -> &'a Adapter {
+    let addresses = interfaces
+        .iter()
+        .map(|adapter| {
+            adapter
+                .ip_addresses()
+                .iter()
+                .filter_map(|addr| match addr {
+                    IpAddr::V4(addr) => Some(*addr),
+                    IpAddr::V6(_) => None,
+                })
+                .collect::<Vec<Ipv4Addr>>()
+        })
+        .collect::<Vec<_>>();
+    let adapters = interfaces
+        .iter()
+        .zip(addresses.iter())
+        .map(|(adapter, addresses)| (adapter.description(), addresses.as_slice()));
+    let candidates = match match_interface(query, adapters) {
+        InterfaceMatch::Found(idx) => {
+            let interface = &interfaces[idx];
+            if interface.oper_status() == ipconfig::OperStatus::IfOperStatusUp {
+                return interface;
+            }
+            eprintln!(
+                "{}",
+                tr!(
+                    "error: interface \"{}\" is not up",
+                    "错误：网卡 \"{}\" 未启用",
+                    interface.description()
+                )
+            );
+            vec![idx]
+        }
+        InterfaceMatch::Ambiguous(candidates) => {
+            eprintln!(
+                "{}",
+                tr!(
+                    "error: \"{}\" matches more than one interface",
+                    "错误：\"{}\" 匹配多个网卡",
+                    query
+                )
+            );
+            candidates
+        }
+        InterfaceMatch::NotFound => {
+            eprintln!(
+                "{}",
+                tr!(
+                    "error: no interface matches \"{}\"",
+                    "错误：没有网卡匹配 \"{}\"",
+                    query
+                )
+            );
+            (0..interfaces.len()).collect()
+        }
+    };
+    eprintln!("{}", tr!("candidates:", "候选网卡："));
+    for idx in candidates {
+        let interface = &interfaces[idx];
+        eprintln!(
+            "{:2} {} [{}]",
+            idx,
+            interface.description(),
+            interface.ip_addresses().iter().format(", ")
+        );
+    }
+    process::exit(ExitCode::Usage as i32)
+}
+
+#[cfg(test)]
+mod cli_test {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Command, clap::Error> {
+        let args = iter::once("ip_packet_stat")
+            .chain(args.iter().copied())
+            .map(OsString::from)
+            .collect();
+        match parse_launch(args)? {
+            Launch::Cli { command, .. } => Ok(command),
+            launch => panic!("{:?}", launch),
+        }
+    }
+
+    #[test]
+    fn test_with_subcommand() {
+        let rewritten = |args: &[&str]| {
+            with_subcommand(args.iter().map(OsString::from).collect())
+                .into_iter()
+                .map(|arg| arg.into_string().unwrap())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            rewritten(&["ips", "--cli", "-i", "0"]),
+            ["ips", "capture", "-i", "0"]
+        );
+        assert_eq!(rewritten(&["ips", "-b", "-c"]), ["ips", "capture", "-b"]);
+        assert_eq!(
+            rewritten(&["ips", "read", "a.pcap"]),
+            ["ips", "read", "a.pcap"]
+        );
+        assert_eq!(rewritten(&["ips", "--help"]), ["ips", "--help"]);
+        assert_eq!(rewritten(&["ips", "a.pcap"]), ["ips", "a.pcap"]);
+        assert_eq!(rewritten(&["ips"]), ["ips"]);
+    }
+
+    #[test]
+    fn test_launch() {
+        let launch = |args: &[&str]| {
+            parse_launch(
+                iter::once("ips")
+                    .chain(args.iter().copied())
+                    .map(OsString::from)
+                    .collect(),
+            )
+        };
+        assert!(matches!(
+            launch(&[]),
+            Ok(Launch::Gui {
+                debug: false,
+                file: None,
+                selftest: false
+            })
+        ));
+        match launch(&["--debug", r"C:\captures\a.pcap"]) {
+            Ok(Launch::Gui { debug, file, .. }) => {
+                assert!(debug);
+                assert_eq!(file, Some(PathBuf::from(r"C:\captures\a.pcap")));
+            }
+            launch => panic!("{:?}", launch),
+        }
+        assert!(matches!(
+            launch(&["--cli"]),
+            Ok(Launch::Cli {
+                command: Command::Capture(_),
+                lang: None
+            })
+        ));
+        assert!(matches!(
+            launch(&["interfaces"]),
+            Ok(Launch::Cli {
+                command: Command::Interfaces(_),
+                ..
+            })
+        ));
+        // a subcommand after the file would otherwise be dropped silently
+        assert!(launch(&["a.pcap", "interfaces"]).is_err());
+
+        assert!(matches!(
+            launch(&["--selftest"]),
+            Ok(Launch::Gui {
+                file: None,
+                selftest: true,
+                ..
+            })
+        ));
+        match launch(&["report", "--selftest", "--json"]) {
+            Ok(Launch::Cli {
+                command: Command::Report(args),
+                ..
+            }) => assert!(args.capture.live.selftest),
+            launch => panic!("{:?}", launch),
+        }
+        // generated traffic is not captured on an interface
+        assert!(launch(&["capture", "--selftest", "-i", "0"]).is_err());
+
+        for args in [
+            &["--lang", "zh", "interfaces"][..],
+            &["--lang=zh", "interfaces"],
+            &["interfaces", "--lang", "zh"],
+        ] {
+            assert!(
+                matches!(
+                    launch(args),
+                    Ok(Launch::Cli {
+                        command: Command::Interfaces(_),
+                        lang: Some(Lang::Zh)
+                    })
+                ),
+                "{:?}",
+                args
+            );
+        }
+        assert!(matches!(
+            launch(&["--lang", "en", "-i", "0"]),
+            Ok(Launch::Cli {
+                command: Command::Capture(_),
+                lang: Some(Lang::En)
+            })
+        ));
+        assert!(launch(&["interfaces", "--lang", "fr"]).is_err());
+    }
+
+    #[test]
+    fn test_capture_args() {
+        let args = match parse(&[
+            "--cli",
+            "-i",
+            "2",
+            "--host",
+            "10.0.0.0/8",
+            "--host",
+            "10.1.0.1",
+            "--count",
+            "5",
+        ]) {
+            Ok(Command::Capture(args)) => args,
+            command => panic!("{:?}", command),
+        };
+        assert_eq!(args.live.interface.as_deref(), Some("2"));
+        assert_eq!(args.packets.host.len(), 2);
+        assert_eq!(args.packets.count, Some(5));
+        match parse(&[
+            "capture",
+            "-o",
+            "a.csv",
+            "--rotate-size",
+            "50M",
+            "--rotate-interval",
+            "1h",
+        ]) {
+            Ok(Command::Capture(args)) => {
+                assert_eq!(args.packets.rotate_size, Some(50 << 20));
+                assert_eq!(
+                    args.packets.rotate_interval,
+                    Some(Duration::from_secs(3600))
+                );
+            }
+            command => panic!("{:?}", command),
+        }
+        assert!(matches!(
+            parse(&["capture", "--bench", "--poll"]),
+            Ok(Command::Capture(CaptureArgs {
+                live: LiveArgs {
+                    bench: true,
+                    poll: true,
+                    ..
+                },
+                ..
+            }))
+        ));
+        assert!(matches!(
+            parse(&["capture", "--bench"]),
+            Ok(Command::Capture(CaptureArgs {
+                live: LiveArgs { bind_port: 0, .. },
+                ..
+            }))
+        ));
+        assert!(matches!(
+            parse(&["capture", "--bind", "10.0.0.1", "--bind-port", "9000"]),
+            Ok(Command::Capture(CaptureArgs {
+                live: LiveArgs {
+                    bind_port: 9000,
+                    recv_mode: RecvMode::On,
+                    ..
+                },
+                ..
+            }))
+        ));
+
+        assert!(matches!(
+            parse(&["capture", "--recv-mode", "ip-level"]),
+            Ok(Command::Capture(CaptureArgs {
+                live: LiveArgs {
+                    recv_mode: RecvMode::IpLevel,
+                    ..
+                },
+                ..
+            }))
+        ));
+        assert!(matches!(
+            parse(&["capture", "--proto", "udp"]),
+            Ok(Command::Capture(CaptureArgs {
+                live: LiveArgs {
+                    proto: Some(CaptureProtocol::Udp),
+                    ..
+                },
+                ..
+            }))
+        ));
+
+        match parse(&["capture", "--fields", "time,truncated"]) {
+            Ok(Command::Capture(args)) => {
+                assert_eq!(
+                    args.packets.fields,
+                    [RecordColumn::Time, RecordColumn::Truncated]
+                )
+            }
+            other => panic!("{:?}", other),
+        }
+
+        for args in [
+            &["capture", "-i", "0", "--bind", "10.0.0.1"][..],
+            &["capture", "--fields", "time,size"],
+            &["capture", "--stats", "--brief"],
+            &["capture", "--stats", "--heartbeat", "30"],
+            &["capture", "--heartbeat", "0"],
+            &["capture", "--bench", "--heartbeat", "30"],
+            &["capture", "--count-only", "--quiet"],
+            &["capture", "--bench", "--filter", "len > 0"],
+            &["capture", "--raw-stdout", "--packet"],
+            // flags that only mean something along with another
+            &["capture", "--raw-format", "pcap"],
+            &["capture", "--output-format", "csv"],
+            &["capture", "--rotate-size", "50M"],
+            &["capture", "-o", "a.csv", "--rotate-interval", "1w"],
+            &[
+                "capture",
+                "--follow",
+                "10.0.0.5:80,10.0.0.6",
+                "--follow-host",
+                "10.0.0.5",
+            ],
+            &["capture", "--host", "10.0.0.0/33"],
+            &["capture", "--port", "443-80"],
+            // pcap files are read with the read subcommand
+            &["capture", "--read-pcap", "a.pcap"],
+            &["capture", "--proto", "igmp"],
+            // generated packets come from no socket to restrict
+            &["capture", "--selftest", "--proto", "tcp"],
+        ] {
+            assert!(parse(args).is_err(), "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn test_read_args() {
+        let args = match parse(&["read", "capture.pcap", "--brief", "--port", "53"]) {
+            Ok(Command::Read(args)) => args,
+            command => panic!("{:?}", command),
+        };
+        assert_eq!(args.file, Path::new("capture.pcap"));
+        assert!(args.packets.brief);
+        assert_eq!(args.packets.port.len(), 1);
+
+        assert!(parse(&["read"]).is_err());
+        // there is nothing to capture from
+        assert!(parse(&["read", "capture.pcap", "--interface", "0"]).is_err());
+        assert!(parse(&["read", "capture.pcap", "--bench"]).is_err());
+    }
+
+    #[test]
+    fn test_other_subcommands() {
+        assert!(matches!(
+            parse(&["report", "--json", "--read-pcap", "a.pcap"]),
+            Ok(Command::Report(ReportArgs { json: true, .. }))
+        ));
+        assert!(parse(&["report", "--read-pcap", "a.pcap", "--bind", "10.0.0.1"]).is_err());
+        assert!(parse(&["report", "--read-pcap", "a.pcap", "--proto", "tcp"]).is_err());
+        match parse(&["report", "--top", "20", "--sort-by", "packets"]) {
+            Ok(Command::Report(args)) => {
+                assert_eq!(args.top, Some(20));
+                assert_eq!(args.sort_by, TopOrder::Packets);
+            }
+            command => panic!("{:?}", command),
+        }
+        assert!(parse(&["report", "--top", "0"]).is_err());
+        assert!(parse(&["report", "--sort-by", "packets"]).is_err());
+        for (args, flag) in [
+            (&["report", "--bench"][..], Some("--bench")),
+            (&["report", "--output", "a.csv"], Some("--output")),
+            (&["report", "--raw-stdout"], Some("--raw-stdout")),
+            (&["report", "--filter", "len > 100", "--count", "5"], None),
+        ] {
+            match parse(args) {
+                Ok(Command::Report(args)) => assert_eq!(unused_report_flag(&args.capture), flag),
+                command => panic!("{:?}", command),
+            }
+        }
+        assert!(matches!(
+            parse(&["interfaces", "--json"]),
+            Ok(Command::Interfaces(InterfacesArgs { json: true }))
+        ));
+        assert!(parse(&["interfaces", "--brief"]).is_err());
+        match parse(&["check-filter", "len > 0 && src_port == 53"]) {
+            Ok(Command::CheckFilter(args)) => assert_eq!(args.expr, "len > 0 && src_port == 53"),
+            command => panic!("{:?}", command),
+        }
+        assert!(parse(&["check-filter"]).is_err());
+    }
+
+    #[test]
+    fn test_invalid_filter() {
+        let expr = "sorc_ip == 10.0.0.1 && len > 0";
+        let err = invalid_filter(expr, &parse_filter(expr).err().unwrap());
+        assert_eq!(err.code, ExitCode::Usage);
+        assert_eq!(
+            err.to_string(),
+            [
+                "invalid filter",
+                "  sorc_ip == 10.0.0.1 && len > 0",
+                "  ^^^^^^^",
+                "there is no field named \"sorc_ip\", did you mean \"src_ip\"?",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_painter_error() {
+        for protocol in PlotProtocol::ALL {
+            assert_ne!(protocol.color(true), Painter::ERROR);
+        }
+    }
+
+    #[test]
+    fn test_heartbeat_line() {
+        assert_eq!(
+            heartbeat_line(0, Duration::from_secs(30), 1204),
+            "… 0 packets in the last 30 s (total 1,204)"
+        );
+        assert_eq!(
+            heartbeat_line(12, Duration::from_millis(2500), 12),
+            "… 12 packets in the last 2.5 s (total 12)"
+        );
+        // a read holding the line up shows in the time it covers
+        assert_eq!(
+            heartbeat_line(40, Duration::from_millis(30_180), 1244),
+            "… 40 packets in the last 30.2 s (total 1,244)"
+        );
+        set_lang(Lang::Zh);
+        assert_eq!(
+            heartbeat_line(3, Duration::from_secs(30), 1204),
+            "… 最近 30 秒内 3 个分组（共 1,204 个）"
+        );
+    }
 }
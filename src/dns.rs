@@ -0,0 +1,296 @@
+use std::net::Ipv4Addr;
+
+/// the bits of a dns message the statistics care about
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DnsInfo {
+    Query {
+        name: String,
+    },
+    Response {
+        name: String,
+        rcode: u8,
+    },
+    /// the message is truncated or otherwise can not be parsed
+    Malformed,
+}
+
+pub const RCODE_SERVFAIL: u8 = 2;
+pub const RCODE_NXDOMAIN: u8 = 3;
+
+const HEADER_LEN: usize = 12;
+const MAX_NAME_LEN: usize = 255;
+
+const TYPE_PTR: u16 = 12;
+const CLASS_IN: u16 = 1;
+
+impl DnsInfo {
+    /// parse the payload of a udp segment carrying dns
+    pub fn from_udp_payload(payload: &[u8]) -> Self {
+        parse_message(payload).unwrap_or(Self::Malformed)
+    }
+
+    /// parse the payload of a tcp segment carrying dns, which is prefixed by a 2 bytes length;
+    /// `None` for a segment that does not start a message, e.g. the rest of a long response
+    pub fn from_tcp_payload(payload: &[u8]) -> Option<Self> {
+        if !starts_message(payload) {
+            return None;
+        }
+        Some(parse_message(&payload[2..]).unwrap_or(Self::Malformed))
+    }
+}
+
+/// whether a tcp segment starts a dns message rather than going on with one; there is no
+/// telling for sure without following the stream, so it is taken for a start when its length
+/// prefix and header are those of a standard message with a single question
+fn starts_message(payload: &[u8]) -> bool {
+    if payload.len() < 2 + HEADER_LEN {
+        return false;
+    }
+    let len = u16::from_be_bytes([payload[0], payload[1]]) as usize;
+    let msg = &payload[2..];
+    let opcode = (msg[2] >> 3) & 0x0f;
+    let qdcount = u16::from_be_bytes([msg[4], msg[5]]);
+    len >= HEADER_LEN && opcode <= 5 && qdcount == 1
+}
+
+/// a recursive query for the host name of `ip`, identified by `id`
+pub fn ptr_query(id: u16, ip: Ipv4Addr) -> Vec<u8> {
+    let mut msg = id.to_be_bytes().to_vec();
+    // recursion desired, one question
+    msg.extend_from_slice(&[0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0]);
+    let [a, b, c, d] = ip.octets();
+    for label in [d, c, b, a]
+        .iter()
+        .map(|octet| octet.to_string())
+        .chain(["in-addr".to_string(), "arpa".to_string()])
+    {
+        msg.push(label.len() as u8);
+        msg.extend_from_slice(label.as_bytes());
+    }
+    msg.push(0);
+    msg.extend_from_slice(&TYPE_PTR.to_be_bytes());
+    msg.extend_from_slice(&CLASS_IN.to_be_bytes());
+    msg
+}
+
+/// the host name in the answer to the `ptr_query` identified by `id`, `None` when there is
+/// no such answer or `msg` is not one
+pub fn parse_ptr_response(msg: &[u8], id: u16) -> Option<String> {
+    if msg.len() < HEADER_LEN || msg[..2] != id.to_be_bytes() {
+        return None;
+    }
+    let flags = u16::from_be_bytes([msg[2], msg[3]]);
+    if flags & 0x8000 == 0 || flags & 0x000f != 0 {
+        return None;
+    }
+    let qdcount = u16::from_be_bytes([msg[4], msg[5]]);
+    let ancount = u16::from_be_bytes([msg[6], msg[7]]);
+    let mut pos = HEADER_LEN;
+    for _ in 0..qdcount {
+        // type and class follow the name
+        pos = skip_name(msg, pos)? + 4;
+    }
+    for _ in 0..ancount {
+        pos = skip_name(msg, pos)?;
+        let fixed = msg.get(pos..pos + 10)?;
+        let rtype = u16::from_be_bytes([fixed[0], fixed[1]]);
+        let rdlen = u16::from_be_bytes([fixed[8], fixed[9]]) as usize;
+        pos += 10;
+        if rtype == TYPE_PTR {
+            return parse_name(msg, pos);
+        }
+        pos += rdlen;
+    }
+    None
+}
+
+/// the position right after the name at `pos`
+fn skip_name(msg: &[u8], mut pos: usize) -> Option<usize> {
+    loop {
+        let len = *msg.get(pos)? as usize;
+        match len & 0xc0 {
+            0x00 if len == 0 => return Some(pos + 1),
+            0x00 => pos += 1 + len,
+            // a compression pointer ends the name
+            0xc0 => return Some(pos + 2),
+            _ => return None,
+        }
+    }
+}
+
+fn parse_message(msg: &[u8]) -> Option<DnsInfo> {
+    if msg.len() < HEADER_LEN {
+        return None;
+    }
+    let flags = u16::from_be_bytes([msg[2], msg[3]]);
+    let qdcount = u16::from_be_bytes([msg[4], msg[5]]);
+    let name = if qdcount == 0 {
+        String::new()
+    } else {
+        parse_name(msg, HEADER_LEN)?
+    };
+    if flags & 0x8000 == 0 {
+        Some(DnsInfo::Query { name })
+    } else {
+        Some(DnsInfo::Response {
+            name,
+            rcode: (flags & 0x000f) as u8,
+        })
+    }
+}
+
+fn parse_name(msg: &[u8], mut pos: usize) -> Option<String> {
+    let mut name = String::new();
+    // every compression pointer must point backwards, so this also bounds the loop
+    let mut jumps = 0;
+    loop {
+        let len = *msg.get(pos)? as usize;
+        match len & 0xc0 {
+            0x00 => {
+                if len == 0 {
+                    break;
+                }
+                let label = msg.get(pos + 1..pos + 1 + len)?;
+                if !name.is_empty() {
+                    name.push('.');
+                }
+                name.push_str(&String::from_utf8_lossy(label));
+                if name.len() > MAX_NAME_LEN {
+                    return None;
+                }
+                pos += 1 + len;
+            }
+            0xc0 => {
+                let offset = ((len & 0x3f) << 8) | *msg.get(pos + 1)? as usize;
+                if offset >= pos || jumps > MAX_NAME_LEN {
+                    return None;
+                }
+                jumps += 1;
+                pos = offset;
+            }
+            _ => return None,
+        }
+    }
+    if name.is_empty() {
+        name.push('.');
+    }
+    Some(name)
+}
+
+#[cfg(test)]
+mod dns_test {
+    use super::*;
+
+    fn message(flags: u16, name: &[u8]) -> Vec<u8> {
+        let mut msg = vec![0x12, 0x34];
+        msg.extend_from_slice(&flags.to_be_bytes());
+        msg.extend_from_slice(&[0, 1, 0, 0, 0, 0, 0, 0]);
+        msg.extend_from_slice(name);
+        msg.extend_from_slice(&[0, 1, 0, 1]);
+        msg
+    }
+
+    const EXAMPLE: &[u8] = b"\x07example\x03com\x00";
+
+    #[test]
+    fn test_query() {
+        assert_eq!(
+            DnsInfo::from_udp_payload(&message(0x0100, EXAMPLE)),
+            DnsInfo::Query {
+                name: "example.com".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_response() {
+        assert_eq!(
+            DnsInfo::from_udp_payload(&message(0x8183, EXAMPLE)),
+            DnsInfo::Response {
+                name: "example.com".to_string(),
+                rcode: RCODE_NXDOMAIN
+            }
+        );
+    }
+
+    #[test]
+    fn test_tcp() {
+        let msg = message(0x0100, EXAMPLE);
+        let mut payload = (msg.len() as u16).to_be_bytes().to_vec();
+        payload.extend_from_slice(&msg);
+        assert_eq!(
+            DnsInfo::from_tcp_payload(&payload),
+            Some(DnsInfo::Query {
+                name: "example.com".to_string()
+            })
+        );
+
+        // the segments after the first one of a message are not counted at all
+        let response = message(0x8180, EXAMPLE);
+        let mut payload = (response.len() as u16 + 600).to_be_bytes().to_vec();
+        payload.extend_from_slice(&response);
+        assert!(matches!(
+            DnsInfo::from_tcp_payload(&payload),
+            Some(DnsInfo::Response { .. })
+        ));
+        let continuation = [0x2a; 600];
+        assert_eq!(DnsInfo::from_tcp_payload(&continuation), None);
+        assert_eq!(DnsInfo::from_tcp_payload(&[]), None);
+
+        // a start whose question can not be read is still malformed
+        let mut payload = payload[..2 + HEADER_LEN + 3].to_vec();
+        payload[2 + HEADER_LEN] = 60;
+        assert_eq!(
+            DnsInfo::from_tcp_payload(&payload),
+            Some(DnsInfo::Malformed)
+        );
+    }
+
+    #[test]
+    fn test_ptr() {
+        let query = ptr_query(0xbeef, Ipv4Addr::new(142, 250, 66, 78));
+        assert_eq!(&query[..4], &[0xbe, 0xef, 0x01, 0x00]);
+        assert_eq!(
+            DnsInfo::from_udp_payload(&query),
+            DnsInfo::Query {
+                name: "78.66.250.142.in-addr.arpa".to_string()
+            }
+        );
+
+        let mut response = query.clone();
+        response[2] = 0x81;
+        response[3] = 0x80;
+        // one answer, its name pointing back at the question
+        response[7] = 1;
+        response.extend_from_slice(&[0xc0, 0x0c, 0, 12, 0, 1, 0, 0, 0x0e, 0x10]);
+        let host = b"\x0flhr25s33-in-f14\x051e100\x03net\x00";
+        response.extend_from_slice(&(host.len() as u16).to_be_bytes());
+        response.extend_from_slice(host);
+        assert_eq!(
+            parse_ptr_response(&response, 0xbeef),
+            Some("lhr25s33-in-f14.1e100.net".to_string())
+        );
+        // the answer to some other query
+        assert_eq!(parse_ptr_response(&response, 0xbeee), None);
+        // no answer, or a name error
+        assert_eq!(parse_ptr_response(&response[..query.len()], 0xbeef), None);
+        response[3] = 0x83;
+        assert_eq!(parse_ptr_response(&response, 0xbeef), None);
+    }
+
+    #[test]
+    fn test_malformed() {
+        assert_eq!(DnsInfo::from_udp_payload(&[0x12, 0x34]), DnsInfo::Malformed);
+        // label runs past the end of the message
+        let msg = message(0x0100, b"\x07exam");
+        assert_eq!(
+            DnsInfo::from_udp_payload(&msg[..HEADER_LEN + 5]),
+            DnsInfo::Malformed
+        );
+        // compression pointer pointing to itself
+        assert_eq!(
+            DnsInfo::from_udp_payload(&message(0x0100, b"\xc0\x0c")),
+            DnsInfo::Malformed
+        );
+    }
+}
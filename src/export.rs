@@ -0,0 +1,565 @@
+use anyhow::{anyhow, Error, Result};
+
+use chrono::{DateTime, Local};
+
+use crate::{
+    record::{Direction, Record, RecordColumn},
+    utils::TransProtocol,
+};
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, BufWriter, Write},
+    iter,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+
+/// lets excel detect utf-8, otherwise the chinese headers come out garbled
+const UTF8_BOM: &[u8] = b"\xef\xbb\xbf";
+
+/// quote a csv field when it contains a separator, a quote or a line break
+fn csv_field(text: &str) -> String {
+    if text.contains(&[',', '"', '\n', '\r'][..]) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+fn write_csv_row<W: Write>(writer: &mut W, cells: impl Iterator<Item = String>) -> Result<()> {
+    let row = cells.map(|cell| csv_field(&cell)).collect::<Vec<_>>();
+    write!(writer, "{}\r\n", row.join(","))?;
+    Ok(())
+}
+
+/// write `records` as csv with a header row, one column per entry of `columns`, and the
+/// `summary` of the session in a row of its own after a blank one
+pub fn write_csv<'a, W: Write>(
+    mut writer: W,
+    records: impl IntoIterator<Item = &'a Record>,
+    columns: &[RecordColumn],
+    summary: Option<&str>,
+) -> Result<()> {
+    writer.write_all(UTF8_BOM)?;
+    write_csv_row(
+        &mut writer,
+        columns.iter().map(|column| column.title().to_string()),
+    )?;
+    for record in records {
+        write_csv_row(&mut writer, record.to_column_strings(columns).into_iter())?;
+    }
+    if let Some(summary) = summary {
+        write!(writer, "\r\n")?;
+        write_csv_row(&mut writer, iter::once(summary.to_string()))?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+pub fn export_csv<'a>(
+    path: &Path,
+    records: impl IntoIterator<Item = &'a Record>,
+    columns: &[RecordColumn],
+    summary: Option<&str>,
+) -> Result<()> {
+    write_csv(
+        BufWriter::new(File::create(path)?),
+        records,
+        columns,
+        summary,
+    )
+}
+
+/// format of the records written while capturing from the command line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Csv,
+    /// one json object per line
+    Ndjson,
+}
+
+impl OutputFormat {
+    /// the format the extension of `path` implies
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "csv" => Some(Self::Csv),
+            "json" | "ndjson" | "jsonl" => Some(Self::Ndjson),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "csv" => Ok(Self::Csv),
+            "json" | "ndjson" => Ok(Self::Ndjson),
+            _ => Err(anyhow!(
+                "unknown output format \"{}\", expected csv or json",
+                s
+            )),
+        }
+    }
+}
+
+/// `column` of `record` as json, numbers staying numbers and missing values null
+fn json_value(record: &Record, column: RecordColumn) -> serde_json::Value {
+    use serde_json::json;
+    match column {
+        RecordColumn::Time => json!(record.time.to_rfc3339()),
+        RecordColumn::SrcIp => json!(record.src_ip),
+        RecordColumn::SrcPort => json!(record.src_port),
+        RecordColumn::DestIp => json!(record.dest_ip),
+        RecordColumn::DestPort => json!(record.dest_port),
+        RecordColumn::Len => json!(record.len),
+        RecordColumn::IpPayloadLen => json!(record.ip_payload_len),
+        RecordColumn::TransProto => json!(TransProtocol(record.trans_proto).to_string()),
+        RecordColumn::TransPayloadLen => json!(record.trans_payload_len),
+        RecordColumn::AppProto => json!(record.app_proto.to_string()),
+        RecordColumn::Direction => json!(match record.direction {
+            Direction::Inbound => "inbound",
+            Direction::Outbound => "outbound",
+            Direction::Unknown => "unknown",
+        }),
+        RecordColumn::Interface => json!(record.interface),
+        RecordColumn::Truncated => json!(record.truncated),
+    }
+}
+
+/// `record` as a json object keyed by the column names
+fn record_json(record: &Record) -> serde_json::Value {
+    serde_json::Value::Object(
+        RecordColumn::ALL
+            .iter()
+            .map(|&column| (column.name().to_string(), json_value(record, column)))
+            .collect(),
+    )
+}
+
+/// writes records one at a time as they are captured, as csv rows or json lines
+pub struct RecordWriter<W: Write> {
+    writer: W,
+    format: OutputFormat,
+    /// bytes written so far, the header included
+    written: u64,
+}
+
+impl<W: Write> RecordWriter<W> {
+    /// `header` starts a csv with the header row, leave it out when appending to existing rows
+    pub fn new(writer: W, format: OutputFormat, header: bool) -> Result<Self> {
+        let mut writer = Self {
+            writer,
+            format,
+            written: 0,
+        };
+        if header && format == OutputFormat::Csv {
+            let mut bytes = UTF8_BOM.to_vec();
+            write_csv_row(
+                &mut bytes,
+                RecordColumn::ALL
+                    .iter()
+                    .map(|column| column.title().to_string()),
+            )?;
+            writer.write_bytes(&bytes)?;
+        }
+        Ok(writer)
+    }
+
+    pub fn write(&mut self, record: &Record) -> Result<()> {
+        let mut bytes = Vec::new();
+        match self.format {
+            OutputFormat::Csv => write_csv_row(
+                &mut bytes,
+                record.to_column_strings(&RecordColumn::ALL).into_iter(),
+            )?,
+            OutputFormat::Ndjson => {
+                serde_json::to_writer(&mut bytes, &record_json(record))?;
+                bytes.push(b'\n');
+            }
+        }
+        self.write_bytes(&bytes)
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<()> {
+        self.writer.write_all(bytes)?;
+        self.written += bytes.len() as u64;
+        Ok(())
+    }
+
+    pub fn written(&self) -> u64 {
+        self.written
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// open `path` to append records to, with a header when the file is new or empty
+pub fn append_records(path: &Path, format: OutputFormat) -> Result<RecordWriter<BufWriter<File>>> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    let header = file.metadata()?.len() == 0;
+    RecordWriter::new(BufWriter::new(file), format, header)
+}
+
+/// when a `RotatingWriter` moves on to a new file; with neither limit, it keeps to one
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Rotation {
+    /// bytes a file is written up to; the record reaching it is the last of the file, so
+    /// records are never split across files
+    pub size: Option<u64>,
+    /// how long after its first record, by the record times, a file is written to
+    pub interval: Option<Duration>,
+}
+
+impl Rotation {
+    pub fn is_none(&self) -> bool {
+        self.size.is_none() && self.interval.is_none()
+    }
+}
+
+/// a file written by a `RotatingWriter`, and how many records it got
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WrittenFile {
+    pub path: PathBuf,
+    pub record_num: u64,
+}
+
+/// writes records to `path`, appending to it, or when rotating, to new files named after it
+/// and their first record, each starting with the csv header
+pub struct RotatingWriter {
+    path: PathBuf,
+    format: OutputFormat,
+    rotation: Rotation,
+    /// the open file and the time of its first record
+    current: Option<(RecordWriter<BufWriter<File>>, DateTime<Local>)>,
+    files: Vec<WrittenFile>,
+}
+
+impl RotatingWriter {
+    /// without rotation, `path` is opened at once so that a path that can not be written fails
+    /// early, otherwise the first file is created with the first record
+    pub fn new(path: &Path, format: OutputFormat, rotation: Rotation) -> Result<Self> {
+        let mut writer = Self {
+            path: path.to_path_buf(),
+            format,
+            rotation,
+            current: None,
+            files: Vec::new(),
+        };
+        if rotation.is_none() {
+            writer.current = Some((append_records(path, format)?, Local::now()));
+            writer.files.push(WrittenFile {
+                path: path.to_path_buf(),
+                record_num: 0,
+            });
+        } else if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            if !dir.is_dir() {
+                return Err(anyhow!("{} is not a directory", dir.display()));
+            }
+        }
+        Ok(writer)
+    }
+
+    pub fn write(&mut self, record: &Record) -> Result<()> {
+        if self.due(record) {
+            self.flush()?;
+            self.current = None;
+        }
+        if self.current.is_none() {
+            let (file, path) = create_rotated(&self.path, record.time)?;
+            let writer = RecordWriter::new(BufWriter::new(file), self.format, true)?;
+            self.current = Some((writer, record.time));
+            self.files.push(WrittenFile {
+                path,
+                record_num: 0,
+            });
+        }
+        if let Some((writer, _)) = &mut self.current {
+            writer.write(record)?;
+        }
+        if let Some(file) = self.files.last_mut() {
+            file.record_num += 1;
+        }
+        Ok(())
+    }
+
+    /// whether the open file is done with before `record`
+    fn due(&self, record: &Record) -> bool {
+        let (writer, opened) = match &self.current {
+            Some(current) if !self.rotation.is_none() => current,
+            _ => return false,
+        };
+        let full = self
+            .rotation
+            .size
+            .map_or(false, |size| writer.written() >= size);
+        // times may go back a little between packets, which never ends a file
+        let expired = self.rotation.interval.map_or(false, |interval| {
+            (record.time - *opened)
+                .to_std()
+                .map_or(false, |elapsed| elapsed >= interval)
+        });
+        full || expired
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        match &mut self.current {
+            Some((writer, _)) => writer.flush(),
+            None => Ok(()),
+        }
+    }
+
+    /// the file written to last, or `path` before any is
+    pub fn path(&self) -> &Path {
+        self.files.last().map_or(&self.path, |file| &file.path)
+    }
+
+    /// the files written so far in the order they were, with the open one last
+    pub fn files(&self) -> &[WrittenFile] {
+        &self.files
+    }
+}
+
+/// `capture.2024-06-01T12-00-00.csv` next to `path` such as `capture.csv`, with a -2, -3 and so
+/// on after the time for files that already exist
+fn rotated_path(path: &Path, time: DateTime<Local>, n: usize) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .map_or("capture".into(), |stem| stem.to_string_lossy());
+    let mut name = format!("{}.{}", stem, time.format("%Y-%m-%dT%H-%M-%S"));
+    if n > 1 {
+        name += &format!("-{}", n);
+    }
+    if let Some(extension) = path.extension() {
+        name += &format!(".{}", extension.to_string_lossy());
+    }
+    path.with_file_name(name)
+}
+
+fn create_rotated(path: &Path, time: DateTime<Local>) -> Result<(File, PathBuf)> {
+    let mut n = 1;
+    loop {
+        let path = rotated_path(path, time, n);
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(file) => return Ok((file, path)),
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => n += 1,
+            Err(err) => return Err(err.into()),
+        }
+    }
+}
+
+/// `capture-20211107-153000.csv` under `dir`, named after the capture start
+pub fn timestamped_path(dir: &Path, time: DateTime<Local>) -> PathBuf {
+    dir.join(format!("capture-{}.csv", time.format("%Y%m%d-%H%M%S")))
+}
+
+#[cfg(test)]
+mod export_test {
+    use super::*;
+
+    use crate::{record::Direction, utils::AppProtocol};
+
+    use chrono::{NaiveDate, TimeZone};
+    use packet::ip::Protocol;
+
+    #[test]
+    fn test_csv_field() {
+        assert_eq!(csv_field("udp"), "udp");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+    }
+
+    #[test]
+    fn test_write_csv() {
+        let time = Local
+            .from_local_datetime(&NaiveDate::from_ymd(2021, 11, 7).and_hms(15, 30, 0))
+            .unwrap();
+        let record = Record::builder()
+            .time(time)
+            .src([192, 168, 1, 2], Some(53))
+            .dest([192, 168, 1, 3], Some(5353))
+            .proto(Protocol::Udp, AppProtocol::Dns)
+            .interface("Ethernet, 2")
+            .build();
+        let mut buffer = Vec::new();
+        let columns = [
+            RecordColumn::SrcIp,
+            RecordColumn::Len,
+            RecordColumn::Interface,
+        ];
+        write_csv(&mut buffer, &[record.clone()], &columns, None).unwrap();
+        let text = String::from_utf8(buffer).unwrap();
+        assert_eq!(
+            text,
+            "\u{feff}源IP,IP分组长度,网卡\r\n192.168.1.2,60,\"Ethernet, 2\"\r\n"
+        );
+
+        let mut buffer = Vec::new();
+        write_csv(&mut buffer, &[record], &columns, Some("捕获 1 秒，共 60 B")).unwrap();
+        let text = String::from_utf8(buffer).unwrap();
+        assert!(text.ends_with("\"Ethernet, 2\"\r\n\r\n捕获 1 秒，共 60 B\r\n"));
+    }
+
+    #[test]
+    fn test_output_format() {
+        assert_eq!(
+            OutputFormat::from_path(Path::new("capture.CSV")),
+            Some(OutputFormat::Csv)
+        );
+        assert_eq!(
+            OutputFormat::from_path(Path::new("out/capture.jsonl")),
+            Some(OutputFormat::Ndjson)
+        );
+        assert_eq!(OutputFormat::from_path(Path::new("capture.txt")), None);
+        assert_eq!(OutputFormat::from_path(Path::new("capture")), None);
+        assert_eq!(
+            "json".parse::<OutputFormat>().unwrap(),
+            OutputFormat::Ndjson
+        );
+        assert!("xml".parse::<OutputFormat>().is_err());
+    }
+
+    #[test]
+    fn test_record_writer() {
+        let time = Local
+            .from_local_datetime(&NaiveDate::from_ymd(2021, 11, 7).and_hms(15, 30, 0))
+            .unwrap();
+        let record = Record::builder()
+            .time(time)
+            .src([192, 168, 1, 2], None)
+            .dest([192, 168, 1, 3], None)
+            .proto(Protocol::Icmp, AppProtocol::Unknown)
+            .len(28)
+            .direction(Direction::Outbound)
+            .build();
+
+        let mut buffer = Vec::new();
+        let mut writer = RecordWriter::new(&mut buffer, OutputFormat::Ndjson, true).unwrap();
+        writer.write(&record).unwrap();
+        writer.write(&record).unwrap();
+        writer.flush().unwrap();
+        let text = String::from_utf8(buffer).unwrap();
+        let lines = text.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        let json: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(json["src_ip"], "192.168.1.2");
+        assert_eq!(json["src_port"], serde_json::Value::Null);
+        assert_eq!(json["len"], 28);
+        assert_eq!(json["trans_proto"], "ICMP");
+        assert_eq!(json["direction"], "outbound");
+
+        let mut buffer = Vec::new();
+        let mut writer = RecordWriter::new(&mut buffer, OutputFormat::Csv, false).unwrap();
+        writer.write(&record).unwrap();
+        let text = String::from_utf8(buffer).unwrap();
+        assert!(text.starts_with("2021-11-07 15:30:00.000000,192.168.1.2,,"));
+        assert_eq!(text.matches("\r\n").count(), 1);
+    }
+
+    #[test]
+    fn test_timestamped_path() {
+        let time = Local
+            .from_local_datetime(&NaiveDate::from_ymd(2021, 11, 7).and_hms(15, 30, 0))
+            .unwrap();
+        assert_eq!(
+            timestamped_path(Path::new("out"), time),
+            Path::new("out").join("capture-20211107-153000.csv")
+        );
+    }
+
+    #[test]
+    fn test_rotating_writer() {
+        let dir = std::env::temp_dir().join(format!("ip_packet_stat-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let start = Local
+            .from_local_datetime(&NaiveDate::from_ymd(2024, 6, 1).and_hms(12, 0, 0))
+            .unwrap();
+        let record = |minutes| {
+            Record::builder()
+                .time(start + chrono::Duration::minutes(minutes))
+                .src([10, 0, 0, 1], Some(50000))
+                .build()
+        };
+        let names = |writer: &RotatingWriter| {
+            writer
+                .files()
+                .iter()
+                .map(|file| {
+                    let name = file.path.file_name().unwrap().to_string_lossy();
+                    (name.into_owned(), file.record_num)
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let rotation = Rotation {
+            size: None,
+            interval: Some(Duration::from_secs(3600)),
+        };
+        let path = dir.join("hourly.csv");
+        let mut writer = RotatingWriter::new(&path, OutputFormat::Csv, rotation).unwrap();
+        assert!(writer.files().is_empty());
+        for minutes in [0, 30, 59, 61, 60, 125] {
+            writer.write(&record(minutes)).unwrap();
+        }
+        writer.flush().unwrap();
+        assert_eq!(
+            names(&writer),
+            [
+                ("hourly.2024-06-01T12-00-00.csv".to_string(), 3),
+                ("hourly.2024-06-01T13-01-00.csv".to_string(), 2),
+                ("hourly.2024-06-01T14-05-00.csv".to_string(), 1),
+            ]
+        );
+        // every file is a csv of its own
+        for file in writer.files() {
+            let text = std::fs::read_to_string(&file.path).unwrap();
+            assert!(text.starts_with("\u{feff}时间,"));
+            assert_eq!(text.lines().count() as u64, file.record_num + 1);
+        }
+
+        // a file ends with the record that fills it, and a file of the same second gets a number
+        let rotation = Rotation {
+            size: Some(1),
+            interval: None,
+        };
+        let path = dir.join("small.json");
+        let mut writer = RotatingWriter::new(&path, OutputFormat::Ndjson, rotation).unwrap();
+        for minutes in [0, 0, 1] {
+            writer.write(&record(minutes)).unwrap();
+        }
+        assert_eq!(
+            names(&writer),
+            [
+                ("small.2024-06-01T12-00-00.json".to_string(), 1),
+                ("small.2024-06-01T12-00-00-2.json".to_string(), 1),
+                ("small.2024-06-01T12-01-00.json".to_string(), 1),
+            ]
+        );
+        assert_eq!(writer.path(), dir.join("small.2024-06-01T12-01-00.json"));
+
+        // without a rotation everything goes to the file itself
+        let path = dir.join("all.csv");
+        let mut writer =
+            RotatingWriter::new(&path, OutputFormat::Csv, Rotation::default()).unwrap();
+        for minutes in [0, 600] {
+            writer.write(&record(minutes)).unwrap();
+        }
+        assert_eq!(names(&writer), [("all.csv".to_string(), 2)]);
+
+        assert!(RotatingWriter::new(
+            &dir.join("missing").join("a.csv"),
+            OutputFormat::Csv,
+            rotation
+        )
+        .is_err());
+        drop(writer);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,12 +1,17 @@
-use crate::record::Record;
-use crate::utils::{str_to_trans_protocol, AppProtocol};
-use anyhow::Result;
+use crate::record::{Record, RecordColumn};
+use crate::tr;
+use crate::utils::{
+    char_width, display_width, str_to_trans_protocol, trans_protocol_name, AppProtocol,
+};
+use anyhow::{anyhow, bail, Result};
 use chrono::prelude::*;
 use packet::ip::Protocol;
-use std::{net::Ipv4Addr, str::FromStr};
+use std::{fmt::Display, net::Ipv4Addr, ops::Range, str::FromStr};
+
+const TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.f";
 
 #[derive(Debug, PartialEq, Clone)]
-enum Literal {
+pub enum Literal {
     Time(DateTime<Local>),
     Ipv4(Ipv4Addr),
     Port(u16),
@@ -16,7 +21,7 @@ enum Literal {
 }
 
 #[derive(Debug, PartialEq, Clone)]
-enum Field {
+pub enum Field {
     Time,
     SrcIp,
     SrcPort,
@@ -97,7 +102,9 @@ fn record_filter(pred: &Pred, record: &Record) -> bool {
             },
             Operation::Gt(f, l) => match (f, l) {
                 (Field::Time, Literal::Time(l)) => &record.time > l,
+                (Field::SrcIp, Literal::Ipv4(l)) => record.src_ip.as_ref() > Some(l),
                 (Field::SrcPort, Literal::Port(l)) => record.src_port.as_ref() > Some(l),
+                (Field::DestIp, Literal::Ipv4(l)) => record.dest_ip.as_ref() > Some(l),
                 (Field::DestPort, Literal::Port(l)) => record.dest_port.as_ref() > Some(l),
                 (Field::Len, Literal::Len(l)) => &record.len > l,
                 (Field::IpPayloadLen, Literal::Len(l)) => record.ip_payload_len.as_ref() > Some(l),
@@ -108,7 +115,9 @@ fn record_filter(pred: &Pred, record: &Record) -> bool {
             },
             Operation::Ge(f, l) => match (f, l) {
                 (Field::Time, Literal::Time(l)) => &record.time >= l,
+                (Field::SrcIp, Literal::Ipv4(l)) => record.src_ip.as_ref() >= Some(l),
                 (Field::SrcPort, Literal::Port(l)) => record.src_port.as_ref() >= Some(l),
+                (Field::DestIp, Literal::Ipv4(l)) => record.dest_ip.as_ref() >= Some(l),
                 (Field::DestPort, Literal::Port(l)) => record.dest_port.as_ref() >= Some(l),
                 (Field::Len, Literal::Len(l)) => &record.len >= l,
                 (Field::IpPayloadLen, Literal::Len(l)) => record.ip_payload_len.as_ref() >= Some(l),
@@ -119,7 +128,9 @@ fn record_filter(pred: &Pred, record: &Record) -> bool {
             },
             Operation::Lt(f, l) => match (f, l) {
                 (Field::Time, Literal::Time(l)) => &record.time < l,
+                (Field::SrcIp, Literal::Ipv4(l)) => record.src_ip.as_ref() < Some(l),
                 (Field::SrcPort, Literal::Port(l)) => record.src_port.as_ref() < Some(l),
+                (Field::DestIp, Literal::Ipv4(l)) => record.dest_ip.as_ref() < Some(l),
                 (Field::DestPort, Literal::Port(l)) => record.dest_port.as_ref() < Some(l),
                 (Field::Len, Literal::Len(l)) => &record.len < l,
                 (Field::IpPayloadLen, Literal::Len(l)) => record.ip_payload_len.as_ref() < Some(l),
@@ -130,7 +141,9 @@ fn record_filter(pred: &Pred, record: &Record) -> bool {
             },
             Operation::Le(f, l) => match (f, l) {
                 (Field::Time, Literal::Time(l)) => &record.time <= l,
+                (Field::SrcIp, Literal::Ipv4(l)) => record.src_ip.as_ref() <= Some(l),
                 (Field::SrcPort, Literal::Port(l)) => record.src_port.as_ref() <= Some(l),
+                (Field::DestIp, Literal::Ipv4(l)) => record.dest_ip.as_ref() <= Some(l),
                 (Field::DestPort, Literal::Port(l)) => record.dest_port.as_ref() <= Some(l),
                 (Field::Len, Literal::Len(l)) => &record.len <= l,
                 (Field::IpPayloadLen, Literal::Len(l)) => record.ip_payload_len.as_ref() <= Some(l),
@@ -150,6 +163,287 @@ fn pred_to_filter(pred: Pred) -> impl Fn(&Record) -> bool {
     Box::new(move |r: &Record| -> bool { record_filter(&pred, r) })
 }
 
+/// a filter built in code instead of parsed from text,
+/// it displays as an expression `create_filter` accepts
+#[derive(Debug, PartialEq, Clone)]
+pub struct FilterExpr(Pred);
+
+impl FilterExpr {
+    pub fn equal(field: Field, literal: Literal) -> Self {
+        Self(Pred::FieldPred(Operation::Eq(field, literal)))
+    }
+    pub fn ge(field: Field, literal: Literal) -> Self {
+        Self(Pred::FieldPred(Operation::Ge(field, literal)))
+    }
+    pub fn lt(field: Field, literal: Literal) -> Self {
+        Self(Pred::FieldPred(Operation::Lt(field, literal)))
+    }
+    pub fn le(field: Field, literal: Literal) -> Self {
+        Self(Pred::FieldPred(Operation::Le(field, literal)))
+    }
+    pub fn and(self, other: Self) -> Self {
+        Self(Pred::And(Box::new(self.0), Box::new(other.0)))
+    }
+    pub fn or(self, other: Self) -> Self {
+        Self(Pred::Or(Box::new(self.0), Box::new(other.0)))
+    }
+    pub fn negate(self) -> Self {
+        Self(Pred::Not(Box::new(self.0)))
+    }
+
+    /// records with `start <= time < end`
+    pub fn time_range(start: DateTime<Local>, end: DateTime<Local>) -> Self {
+        Self::ge(Field::Time, Literal::Time(start)).and(Self::lt(Field::Time, Literal::Time(end)))
+    }
+
+    /// packets between `a` and `b` in either direction; an endpoint without a port matches
+    /// any port of its address
+    pub fn conversation(a: (Ipv4Addr, Option<u16>), b: (Ipv4Addr, Option<u16>)) -> Self {
+        let one_way = |src, dest| {
+            Self::endpoint(Field::SrcIp, Field::SrcPort, src).and(Self::endpoint(
+                Field::DestIp,
+                Field::DestPort,
+                dest,
+            ))
+        };
+        one_way(a, b).or(one_way(b, a))
+    }
+
+    /// packets from or to `ip`
+    pub fn host(ip: Ipv4Addr) -> Self {
+        Self::equal(Field::SrcIp, Literal::Ipv4(ip))
+            .or(Self::equal(Field::DestIp, Literal::Ipv4(ip)))
+    }
+
+    /// packets from or to an address of `network`
+    pub fn network(network: HostPattern) -> Self {
+        let (first, last) = network.range();
+        if first == last {
+            return Self::host(first);
+        }
+        let within = |field: Field| {
+            Self::ge(field.clone(), Literal::Ipv4(first)).and(Self::le(field, Literal::Ipv4(last)))
+        };
+        within(Field::SrcIp).or(within(Field::DestIp))
+    }
+
+    /// packets from or to a port of `ports`
+    pub fn port_range(ports: PortRange) -> Self {
+        let within = |field: Field| {
+            if ports.first == ports.last {
+                Self::equal(field, Literal::Port(ports.first))
+            } else {
+                Self::ge(field.clone(), Literal::Port(ports.first))
+                    .and(Self::le(field, Literal::Port(ports.last)))
+            }
+        };
+        within(Field::SrcPort).or(within(Field::DestPort))
+    }
+
+    /// packets of any of `hosts` and any of `ports`, as the --host and --port flags take them;
+    /// `None` when both are empty
+    pub fn hosts_and_ports(hosts: &[HostPattern], ports: &[PortRange]) -> Option<Self> {
+        let hosts = hosts.iter().copied().map(Self::network).reduce(Self::or);
+        let ports = ports.iter().copied().map(Self::port_range).reduce(Self::or);
+        match (hosts, ports) {
+            (Some(hosts), Some(ports)) => Some(hosts.and(ports)),
+            (hosts, ports) => hosts.or(ports),
+        }
+    }
+
+    fn endpoint(ip_field: Field, port_field: Field, (ip, port): (Ipv4Addr, Option<u16>)) -> Self {
+        let expr = Self::equal(ip_field, Literal::Ipv4(ip));
+        match port {
+            Some(port) => expr.and(Self::equal(port_field, Literal::Port(port))),
+            None => expr,
+        }
+    }
+
+    /// records sharing the value `record` has in `column`, `None` when the cell is empty or
+    /// the column can not be filtered on; times are shown to the millisecond, so they
+    /// match the whole millisecond
+    pub fn same_value(record: &Record, column: RecordColumn) -> Option<Self> {
+        let (field, literal) = match column {
+            RecordColumn::Time => {
+                let start = record
+                    .time
+                    .with_nanosecond(record.time.nanosecond() / 1_000_000 * 1_000_000)?;
+                return Some(Self::time_range(
+                    start,
+                    start + chrono::Duration::milliseconds(1),
+                ));
+            }
+            RecordColumn::SrcIp => (Field::SrcIp, Literal::Ipv4(record.src_ip?)),
+            RecordColumn::SrcPort => (Field::SrcPort, Literal::Port(record.src_port?)),
+            RecordColumn::DestIp => (Field::DestIp, Literal::Ipv4(record.dest_ip?)),
+            RecordColumn::DestPort => (Field::DestPort, Literal::Port(record.dest_port?)),
+            RecordColumn::Len => (Field::Len, Literal::Len(record.len)),
+            RecordColumn::IpPayloadLen => {
+                (Field::IpPayloadLen, Literal::Len(record.ip_payload_len?))
+            }
+            RecordColumn::TransProto => (
+                Field::TransProto,
+                Literal::TransProtocol(record.trans_proto),
+            ),
+            RecordColumn::TransPayloadLen => (
+                Field::TransPayloadLen,
+                Literal::Len(record.trans_payload_len?),
+            ),
+            RecordColumn::AppProto
+                if matches!(record.trans_proto, Protocol::Tcp | Protocol::Udp) =>
+            {
+                (
+                    Field::AppProto,
+                    Literal::AppProtocol(record.app_proto.clone()),
+                )
+            }
+            RecordColumn::AppProto
+            | RecordColumn::Direction
+            | RecordColumn::Interface
+            | RecordColumn::Truncated => return None,
+        };
+        Some(Self::equal(field, literal))
+    }
+
+    pub fn into_filter(self) -> impl Fn(&Record) -> bool {
+        pred_to_filter(self.0)
+    }
+}
+
+impl Display for FilterExpr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// an address, or a network such as "10.0.0.0/8"
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct HostPattern {
+    pub ip: Ipv4Addr,
+    pub prefix: u8,
+}
+
+impl HostPattern {
+    /// the first and last addresses of the network
+    pub fn range(self) -> (Ipv4Addr, Ipv4Addr) {
+        let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+        let first = u32::from(self.ip) & mask;
+        (Ipv4Addr::from(first), Ipv4Addr::from(first | !mask))
+    }
+}
+
+impl FromStr for HostPattern {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (ip, prefix) = match s.split_once('/') {
+            Some((ip, prefix)) => (ip, Some(prefix)),
+            None => (s, None),
+        };
+        let ip = Ipv4Addr::from_str(ip).map_err(|_| anyhow!("{} is not an ipv4 address", ip))?;
+        let prefix = match prefix {
+            Some(prefix) => match prefix.parse() {
+                Ok(prefix) if prefix <= 32 => prefix,
+                _ => bail!("{} is not a prefix length between 0 and 32", prefix),
+            },
+            None => 32,
+        };
+        Ok(Self { ip, prefix })
+    }
+}
+
+/// a port, or an inclusive range of ports such as "8000-8100"
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct PortRange {
+    pub first: u16,
+    pub last: u16,
+}
+
+impl FromStr for PortRange {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let port = |s: &str| u16::from_str(s).map_err(|_| anyhow!("{} is not a port", s));
+        let range = match s.split_once('-') {
+            Some((first, last)) => Self {
+                first: port(first)?,
+                last: port(last)?,
+            },
+            None => Self {
+                first: port(s)?,
+                last: port(s)?,
+            },
+        };
+        if range.first > range.last {
+            bail!("{} is an empty range of ports", s);
+        }
+        Ok(range)
+    }
+}
+
+impl Display for Pred {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Pred::FieldPred(op) => write!(f, "{}", op),
+            Pred::Not(p) => write!(f, "!({})", p),
+            Pred::And(l, r) => {
+                // `&&` binds tighter than `||`, so only `||` operands need parens
+                let operand = |p: &Pred| match p {
+                    Pred::Or(_, _) => format!("({})", p),
+                    _ => p.to_string(),
+                };
+                write!(f, "{} && {}", operand(l), operand(r))
+            }
+            Pred::Or(l, r) => write!(f, "{} || {}", l, r),
+        }
+    }
+}
+
+impl Display for Operation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (field, operator, literal) = match self {
+            Operation::Eq(field, literal) => (field, "==", literal),
+            Operation::Ne(field, literal) => (field, "!=", literal),
+            Operation::Gt(field, literal) => (field, ">", literal),
+            Operation::Ge(field, literal) => (field, ">=", literal),
+            Operation::Lt(field, literal) => (field, "<", literal),
+            Operation::Le(field, literal) => (field, "<=", literal),
+        };
+        write!(f, "{} {} {}", field, operator, literal)
+    }
+}
+
+impl Display for Field {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Field::Time => "time",
+            Field::SrcIp => "src_ip",
+            Field::SrcPort => "src_port",
+            Field::DestIp => "dest_ip",
+            Field::DestPort => "dest_port",
+            Field::Len => "len",
+            Field::IpPayloadLen => "ip_payload_len",
+            Field::TransProto => "trans_proto",
+            Field::TransPayloadLen => "trans_payload_len",
+            Field::AppProto => "app_proto",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl Display for Literal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Literal::Time(t) => write!(f, "{}", t.format("%Y-%m-%d %H:%M:%S%.3f")),
+            Literal::Ipv4(ip) => write!(f, "{}", ip),
+            Literal::Port(port) => write!(f, "{}", port),
+            Literal::Len(len) => write!(f, "{}", len),
+            Literal::TransProtocol(p) => write!(f, "{}", trans_protocol_name(*p)),
+            Literal::AppProtocol(p) => write!(f, "{}", p),
+        }
+    }
+}
+
 use nom::{
     self,
     branch::alt,
@@ -185,13 +479,173 @@ impl<'a, I> ParseError<I> for FilterError<'a, I> {
     }
 }
 
+impl<'a> FilterError<'a, &'a str> {
+    /// the byte range of `input` this error points at, `None` when it points at nothing in
+    /// particular or into some other string
+    pub fn span(&self, input: &str) -> Option<Range<usize>> {
+        let (part, whole) = match self {
+            FilterError::InvalidLiteral(part)
+            | FilterError::InvalidField(part)
+            | FilterError::UnsupportedOperator(_, part) => (*part, true),
+            // these carry the rest of the input, only where it starts is known
+            FilterError::InvalidOperator(rest) | FilterError::Nom(rest, _) => (*rest, false),
+            FilterError::Failed => return None,
+        };
+        let start = (part.as_ptr() as usize).checked_sub(input.as_ptr() as usize)?;
+        if start + part.len() > input.len() {
+            return None;
+        }
+        let len = if whole {
+            part.len()
+        } else {
+            part.chars().next().map_or(0, char::len_utf8)
+        };
+        Some(start..start + len)
+    }
+
+    /// `input` with the part the error points at marked by carets underneath, followed by the
+    /// error, for a terminal; a long `input` is cut down to the part around the error
+    pub fn describe(&self, input: &str) -> String {
+        let span = match self.span(input) {
+            Some(span) => span,
+            None => return self.to_string(),
+        };
+        let (shown, span) = error_window(input, span);
+        format!(
+            "  {}\n  {}{}\n{}",
+            shown,
+            " ".repeat(display_width(&shown[..span.start])),
+            "^".repeat(display_width(&shown[span]).max(1)),
+            self
+        )
+    }
+}
+
+impl<'a> Display for FilterError<'a, &'a str> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let text = match self {
+            FilterError::InvalidLiteral(literal) => {
+                tr!(
+                    "\"{}\" is not a valid value here",
+                    "这里不能用值 \"{}\" 来筛选",
+                    literal
+                )
+            }
+            FilterError::InvalidField(field) => match suggest_field(field) {
+                Some(name) => tr!(
+                    "there is no field named \"{}\", did you mean \"{}\"?",
+                    "名为 \"{}\" 的项目不存在，是指 \"{}\" 吗？",
+                    field,
+                    name
+                ),
+                None => tr!(
+                    "there is no field named \"{}\"",
+                    "名为 \"{}\" 的项目不存在",
+                    field
+                ),
+            },
+            FilterError::InvalidOperator(_) => tr!(
+                "expected one of the operators ==, !=, >, >=, <, <=",
+                "应为 ==、!=、>、>=、<、<= 之一"
+            )
+            .to_string(),
+            FilterError::UnsupportedOperator(field, op) => tr!(
+                "\"{}\" can not be used on the field \"{}\"",
+                "不能在 \"{1}\" 项目上使用 \"{0}\" 操作筛选",
+                op,
+                field
+            ),
+            FilterError::Failed | FilterError::Nom(_, _) => {
+                tr!("invalid filter", "筛选器不合法").to_string()
+            }
+        };
+        f.write_str(&text)
+    }
+}
+
+/// terminal columns a long filter is cut down to around the part an error points at
+const ERROR_WIDTH: usize = 60;
+/// columns kept before the part, more when the part is close to the end
+const ERROR_CONTEXT: usize = 20;
+
+/// the part of `input` around `span` that fits in `ERROR_WIDTH` columns, with an ellipsis
+/// where it is cut, and `span` moved along into it
+fn error_window(input: &str, span: Range<usize>) -> (String, Range<usize>) {
+    if display_width(input) <= ERROR_WIDTH {
+        return (input.to_string(), span);
+    }
+    let context =
+        ERROR_CONTEXT.max(ERROR_WIDTH.saturating_sub(display_width(&input[span.start..])));
+    let mut start = span.start;
+    let mut width = 0;
+    for (idx, c) in input[..span.start].char_indices().rev() {
+        width += char_width(c);
+        if width > context {
+            break;
+        }
+        start = idx;
+    }
+    let mut end = start;
+    let mut width = 0;
+    for (idx, c) in input[start..].char_indices() {
+        width += char_width(c);
+        if width > ERROR_WIDTH {
+            break;
+        }
+        end = start + idx + c.len_utf8();
+    }
+    let prefix = if start > 0 { "…" } else { "" };
+    let suffix = if end < input.len() { "…" } else { "" };
+    let shift = |pos: usize| pos.min(end).saturating_sub(start) + prefix.len();
+    (
+        format!("{}{}{}", prefix, &input[start..end], suffix),
+        shift(span.start)..shift(span.end),
+    )
+}
+
+/// the field name closest to the unknown `name`, written in the same script, if it is only a
+/// typo or two away
+pub fn suggest_field(name: &str) -> Option<&'static str> {
+    let ascii = name.is_ascii();
+    let typos = (name.chars().count() / 3).max(1);
+    FIELD_NAMES
+        .iter()
+        .map(|(known, _)| *known)
+        .filter(|known| known.is_ascii() == ascii)
+        .map(|known| (edit_distance(name, known), known))
+        .filter(|&(distance, _)| distance <= typos)
+        .min_by_key(|&(distance, _)| distance)
+        .map(|(_, known)| known)
+}
+
+/// characters to insert, delete or replace to turn `a` into `b`
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let replaced = diagonal + (ca != cb) as usize;
+            diagonal = row[j + 1];
+            row[j + 1] = replaced.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
 type IRes<'a, I, O> = IResult<I, O, FilterError<'a, I>>;
 
 pub fn create_filter<'a>(
     input: &'a str,
 ) -> Result<impl Fn(&Record) -> bool, FilterError<'a, &'a str>> {
+    parse_filter(input).map(FilterExpr::into_filter)
+}
+
+/// parse `input` into an expression, which displays as the filter was understood
+pub fn parse_filter<'a>(input: &'a str) -> Result<FilterExpr, FilterError<'a, &'a str>> {
     match parse_pred(input) {
-        Ok((_, pred)) => Ok(pred_to_filter(pred)),
+        Ok((_, pred)) => Ok(FilterExpr(pred)),
         Err(NomErr(err)) => Err(err),
         _ => Err(FilterError::Failed),
     }
@@ -202,7 +656,8 @@ fn parse_pred(input: &str) -> IRes<&str, Pred> {
     if input.is_empty() {
         Ok((input, pred))
     } else {
-        Err(NomErr(FilterError::Failed))
+        // keep where parsing stopped, so that the error can point at it
+        Err(NomErr(FilterError::Nom(input, ErrorKind::Eof)))
     }
 }
 
@@ -215,9 +670,9 @@ fn parse_or(input: &str) -> IRes<&str, Pred> {
     let (input, ands) = many0(preceded(tag("||"), parse_and))(input)?;
     Ok((
         input,
+        // left to right, so the operands keep the order they were written in
         ands.into_iter()
-            .rev()
-            .fold(and, |pred, and| Pred::Or(Box::new(and), Box::new(pred))),
+            .fold(and, |pred, and| Pred::Or(Box::new(pred), Box::new(and))),
     ))
 }
 
@@ -227,8 +682,7 @@ fn parse_and(input: &str) -> IRes<&str, Pred> {
     Ok((
         input,
         ands.into_iter()
-            .rev()
-            .fold(and, |pred, and| Pred::And(Box::new(and), Box::new(pred))),
+            .fold(and, |pred, and| Pred::And(Box::new(pred), Box::new(and))),
     ))
 }
 
@@ -272,24 +726,37 @@ fn parse_field_str(input: &str) -> IRes<&str, &str> {
     )))(input)
 }
 
+/// the names each field can be written as, in english or as the column titles of the gui
+const FIELD_NAMES: [(&str, Field); 22] = [
+    ("time", Field::Time),
+    ("时间", Field::Time),
+    ("src_ip", Field::SrcIp),
+    ("源IP", Field::SrcIp),
+    ("src_port", Field::SrcPort),
+    ("源端口", Field::SrcPort),
+    ("dest_ip", Field::DestIp),
+    ("目的IP", Field::DestIp),
+    ("dest_port", Field::DestPort),
+    ("目的端口", Field::DestPort),
+    ("len", Field::Len),
+    ("IP分组长度", Field::Len),
+    ("ip_payload_len", Field::IpPayloadLen),
+    ("IP数据长度", Field::IpPayloadLen),
+    ("trans_proto", Field::TransProto),
+    ("trans_protocol", Field::TransProto),
+    ("传输层协议", Field::TransProto),
+    ("trans_payload_len", Field::TransPayloadLen),
+    ("报文段数据长度", Field::TransPayloadLen),
+    ("app_proto", Field::AppProto),
+    ("app_protocol", Field::AppProto),
+    ("应用层协议", Field::AppProto),
+];
+
 fn parse_field(input: &str) -> IRes<&str, (&str, Field)> {
     let (input, field) = parse_field_str(input)?;
-    match field {
-        "time" | "时间" => Ok((input, (field, Field::Time))),
-        "src_ip" | "源IP" => Ok((input, (field, Field::SrcIp))),
-        "src_port" | "源端口" => Ok((input, (field, Field::SrcPort))),
-        "dest_ip" | "目的IP" => Ok((input, (field, Field::DestIp))),
-        "dest_port" | "目的端口" => Ok((input, (field, Field::DestPort))),
-        "len" | "IP分组长度" => Ok((input, (field, Field::Len))),
-        "ip_payload_len" | "IP数据长度" => Ok((input, (field, Field::IpPayloadLen))),
-        "trans_proto" | "trans_protocol" | "传输层协议" => {
-            Ok((input, (field, Field::TransProto)))
-        }
-        "trans_payload_len" | "报文段数据长度" => {
-            Ok((input, (field, Field::TransPayloadLen)))
-        }
-        "app_proto" | "app_protocol" | "应用层协议" => Ok((input, (field, Field::AppProto))),
-        _ => Err(NomErr(FilterError::InvalidField(field))),
+    match FIELD_NAMES.iter().find(|(name, _)| *name == field) {
+        Some((_, known)) => Ok((input, (field, known.clone()))),
+        None => Err(NomErr(FilterError::InvalidField(field))),
     }
 }
 
@@ -325,7 +792,7 @@ fn parse_operation(input: &str) -> IRes<&str, Pred> {
         tuple((multispace0, parse_operator, multispace0, parse_literal))(input)?;
     match f {
         Field::Time => {
-            if let Ok(l) = NaiveDateTime::parse_from_str(literal, "%Y-%m-%d %H:%M:%S") {
+            if let Ok(l) = NaiveDateTime::parse_from_str(literal, TIME_FORMAT) {
                 let l = Literal::Time(Local.from_local_datetime(&l).unwrap());
                 match operator {
                     "==" => Ok((input, Pred::FieldPred(Operation::Eq(f, l)))),
@@ -346,6 +813,10 @@ fn parse_operation(input: &str) -> IRes<&str, Pred> {
                 match operator {
                     "==" => Ok((input, Pred::FieldPred(Operation::Eq(f, l)))),
                     "!=" => Ok((input, Pred::FieldPred(Operation::Ne(f, l)))),
+                    ">" => Ok((input, Pred::FieldPred(Operation::Gt(f, l)))),
+                    ">=" => Ok((input, Pred::FieldPred(Operation::Ge(f, l)))),
+                    "<" => Ok((input, Pred::FieldPred(Operation::Lt(f, l)))),
+                    "<=" => Ok((input, Pred::FieldPred(Operation::Le(f, l)))),
                     _ => Err(NomErr(FilterError::UnsupportedOperator(field, operator))),
                 }
             } else {
@@ -374,6 +845,10 @@ fn parse_operation(input: &str) -> IRes<&str, Pred> {
                 match operator {
                     "==" => Ok((input, Pred::FieldPred(Operation::Eq(f, l)))),
                     "!=" => Ok((input, Pred::FieldPred(Operation::Ne(f, l)))),
+                    ">" => Ok((input, Pred::FieldPred(Operation::Gt(f, l)))),
+                    ">=" => Ok((input, Pred::FieldPred(Operation::Ge(f, l)))),
+                    "<" => Ok((input, Pred::FieldPred(Operation::Lt(f, l)))),
+                    "<=" => Ok((input, Pred::FieldPred(Operation::Le(f, l)))),
                     _ => Err(NomErr(FilterError::UnsupportedOperator(field, operator))),
                 }
             } else {
@@ -486,6 +961,89 @@ fn parse_operation(input: &str) -> IRes<&str, Pred> {
 #[cfg(test)]
 mod filter_test {
     use super::*;
+    use crate::record::Direction;
+
+    #[test]
+    fn test_error_describe() {
+        let input = "src_port == http";
+        let err = create_filter(input).err().unwrap();
+        assert_eq!(err.span(input), Some(12..16));
+        assert_eq!(
+            err.describe(input),
+            "  src_port == http\n              ^^^^\n\"http\" is not a valid value here"
+        );
+
+        let input = "源端口 > 80 && 端口 == 80";
+        let err = create_filter(input).err().unwrap();
+        assert_eq!(err, FilterError::Nom("&& 端口 == 80", ErrorKind::Eof));
+        assert_eq!(
+            err.describe(input),
+            format!("  {}\n  {}^\ninvalid filter", input, " ".repeat(12))
+        );
+
+        let input = "sorc_ip == 10.0.0.1";
+        assert_eq!(
+            create_filter(input).err().unwrap().describe(input),
+            "  sorc_ip == 10.0.0.1\n  ^^^^^^^\nthere is no field named \"sorc_ip\", did you mean \"src_ip\"?"
+        );
+
+        assert_eq!(FilterError::Failed.span("len > 0"), None);
+        assert_eq!(FilterError::InvalidField("port").span("len > 0"), None);
+        assert_eq!(FilterError::Failed.describe("len > 0"), "invalid filter");
+    }
+
+    #[test]
+    fn test_error_window() {
+        let clauses = (1..=8)
+            .map(|port| format!("src_port == {}", port))
+            .collect::<Vec<_>>();
+        let describe = |input: &str| create_filter(input).err().unwrap().describe(input);
+        assert_eq!(
+            describe(&format!("dest_port == http || {}", clauses.join(" || "))),
+            [
+                "  dest_port == http || src_port == 1 || src_port == 2 || src_p…",
+                "               ^^^^",
+                "\"http\" is not a valid value here",
+            ]
+            .join("\n")
+        );
+        assert_eq!(
+            describe(&format!(
+                "{} ||| {}",
+                clauses[..4].join(" || "),
+                clauses[4..].join(" || ")
+            )),
+            [
+                "  … 3 || src_port == 4 ||| src_port == 5 || src_port == 6 || sr…",
+                "                       ^",
+                "invalid filter",
+            ]
+            .join("\n")
+        );
+        // close to the end, the window takes in more of what comes before, wide characters
+        // taking two columns
+        assert_eq!(
+            describe(&format!("{} || 目的端口 == 8o", clauses.join(" || "))),
+            [
+                "  …ort == 6 || src_port == 7 || src_port == 8 || 目的端口 == 8o",
+                "                                              ^",
+                "invalid filter",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_suggest_field() {
+        assert_eq!(suggest_field("sorc_ip"), Some("src_ip"));
+        assert_eq!(suggest_field("dst_port"), Some("dest_port"));
+        assert_eq!(suggest_field("app_protocl"), Some("app_protocol"));
+        assert_eq!(suggest_field("目的端"), Some("目的端口"));
+        assert_eq!(suggest_field("port"), None);
+        assert_eq!(suggest_field("address"), None);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(display_width("源IP == 10.0.0.1"), 16);
+    }
 
     #[test]
     fn test_operation() {
@@ -518,4 +1076,219 @@ mod filter_test {
             ))
         );
     }
+
+    #[test]
+    fn test_fractional_time() {
+        let input = "time >= 2021-10-01 14:02:11.200";
+        let time = Local.ymd(2021, 10, 1).and_hms_milli(14, 2, 11, 200);
+        assert_eq!(
+            parse_pred(input),
+            Ok((
+                "",
+                Pred::FieldPred(Operation::Ge(Field::Time, Literal::Time(time)))
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_filter() {
+        let expr = parse_filter("源端口>=80&&(len<100||!(app_proto == DNS))").unwrap();
+        assert_eq!(
+            expr.to_string(),
+            "src_port >= 80 && (len < 100 || !(app_proto == DNS))"
+        );
+        assert_eq!(
+            parse_filter("len > 0 &&").err(),
+            Some(FilterError::Nom("&&", ErrorKind::Eof))
+        );
+    }
+
+    #[test]
+    fn test_builder_round_trip() {
+        let start = Local.ymd(2021, 10, 1).and_hms_milli(14, 2, 11, 200);
+        let end = Local.ymd(2021, 10, 1).and_hms_milli(14, 2, 12, 0);
+        let exprs = [
+            FilterExpr::time_range(start, end),
+            FilterExpr::equal(Field::SrcIp, Literal::Ipv4(Ipv4Addr::new(10, 0, 0, 5)))
+                .or(FilterExpr::le(Field::DestPort, Literal::Port(443)))
+                .and(FilterExpr::equal(
+                    Field::TransProto,
+                    Literal::TransProtocol(Protocol::Tcp),
+                )),
+            FilterExpr::equal(Field::AppProto, Literal::AppProtocol(AppProtocol::Dns)).negate(),
+        ];
+        let record = |ms: i64, dest_port: u16, app_proto: AppProtocol| {
+            Record::builder()
+                .time(start + chrono::Duration::milliseconds(ms))
+                .src([10, 0, 0, 5], Some(52311))
+                .dest([93, 184, 216, 34], Some(dest_port))
+                .proto(Protocol::Tcp, app_proto)
+                .direction(Direction::Outbound)
+                .build()
+        };
+        let records = [
+            record(-1, 443, AppProtocol::Dns),
+            record(0, 443, AppProtocol::Https),
+            record(799, 8080, AppProtocol::Unknown),
+            record(800, 53, AppProtocol::Dns),
+        ];
+        // the parser may nest operands differently, so compare by what they match
+        for expr in exprs {
+            let text = expr.to_string();
+            let (rest, parsed) = parse_pred(&text).unwrap();
+            assert_eq!(rest, "", "{}", text);
+            for r in records.iter() {
+                assert_eq!(
+                    record_filter(&parsed, r),
+                    record_filter(&expr.0, r),
+                    "{}",
+                    text
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_conversation() {
+        let client = Ipv4Addr::new(10, 0, 0, 5);
+        let server = Ipv4Addr::new(93, 184, 216, 34);
+        let expr = FilterExpr::conversation((client, Some(52311)), (server, Some(443)));
+        assert_eq!(
+            expr.to_string(),
+            "src_ip == 10.0.0.5 && src_port == 52311 && dest_ip == 93.184.216.34 && dest_port == 443 \
+             || src_ip == 93.184.216.34 && src_port == 443 && dest_ip == 10.0.0.5 && dest_port == 52311"
+        );
+        let record = |src: (Ipv4Addr, u16), dest: (Ipv4Addr, u16)| {
+            Record::builder()
+                .src(src.0, Some(src.1))
+                .dest(dest.0, Some(dest.1))
+                .proto(Protocol::Tcp, AppProtocol::Https)
+                .direction(Direction::Outbound)
+                .build()
+        };
+        let outbound = record((client, 52311), (server, 443));
+        let inbound = record((server, 443), (client, 52311));
+        let other_port = record((client, 52312), (server, 443));
+        let other_host = record((client, 52311), (Ipv4Addr::new(10, 0, 0, 1), 443));
+
+        let filter = expr.into_filter();
+        assert!(filter(&outbound) && filter(&inbound));
+        assert!(!filter(&other_port) && !filter(&other_host));
+        let filter = FilterExpr::conversation((client, None), (server, Some(443))).into_filter();
+        assert!(filter(&inbound) && filter(&other_port) && !filter(&other_host));
+        let filter = FilterExpr::host(server).into_filter();
+        assert!(filter(&outbound) && filter(&inbound) && !filter(&other_host));
+    }
+
+    #[test]
+    fn test_hosts_and_ports() {
+        let record = |src_ip: [u8; 4], src_port: Option<u16>, dest_port: Option<u16>| {
+            Record::builder()
+                .src(src_ip, src_port)
+                .dest([192, 168, 1, 2], dest_port)
+                .proto(Protocol::Udp, AppProtocol::Unknown)
+                .direction(Direction::Inbound)
+                .build()
+        };
+        let records = [
+            record([10, 0, 0, 5], Some(52311), Some(53)),
+            record([10, 255, 255, 255], Some(8080), Some(52311)),
+            record([11, 0, 0, 1], Some(53), Some(52311)),
+            record([192, 168, 1, 9], Some(8001), Some(52311)),
+            record([192, 168, 1, 9], Some(52311), Some(443)),
+            record([10, 0, 0, 5], None, None),
+        ];
+        let hosts = ["10.0.0.0/8", "192.168.1.9"].map(|host| host.parse().unwrap());
+        let ports = ["53", "8000-8100"].map(|port| port.parse().unwrap());
+        let cases = [
+            (
+                FilterExpr::hosts_and_ports(&hosts, &ports),
+                "(src_ip >= 10.0.0.0 && src_ip <= 10.255.255.255 \
+                 || dest_ip >= 10.0.0.0 && dest_ip <= 10.255.255.255 \
+                 || src_ip == 192.168.1.9 || dest_ip == 192.168.1.9) \
+                 && (src_port == 53 || dest_port == 53 \
+                 || src_port >= 8000 && src_port <= 8100 || dest_port >= 8000 && dest_port <= 8100)",
+                [true, true, false, true, false, false],
+            ),
+            (
+                FilterExpr::hosts_and_ports(&hosts[..1], &[]),
+                "src_ip >= 10.0.0.0 && src_ip <= 10.255.255.255 \
+                 || dest_ip >= 10.0.0.0 && dest_ip <= 10.255.255.255",
+                [true, true, false, false, false, true],
+            ),
+            (
+                FilterExpr::hosts_and_ports(&[], &ports[..1]),
+                "src_port == 53 || dest_port == 53",
+                [true, false, true, false, false, false],
+            ),
+        ];
+        for (expr, text, matches) in cases {
+            let filter = expr.unwrap().into_filter();
+            let written = create_filter(text).unwrap();
+            for (record, expected) in records.iter().zip(matches) {
+                assert_eq!(filter(record), expected, "{:?}", record);
+                assert_eq!(written(record), expected, "{:?}", record);
+            }
+        }
+        assert_eq!(FilterExpr::hosts_and_ports(&[], &[]), None);
+    }
+
+    #[test]
+    fn test_host_and_port_patterns() {
+        let network = |s: &str| s.parse::<HostPattern>().map(HostPattern::range).ok();
+        let ip = Ipv4Addr::new;
+        assert_eq!(
+            network("192.168.7.9/20"),
+            Some((ip(192, 168, 0, 0), ip(192, 168, 15, 255)))
+        );
+        assert_eq!(
+            network("10.0.0.5"),
+            Some((ip(10, 0, 0, 5), ip(10, 0, 0, 5)))
+        );
+        assert_eq!(
+            network("10.0.0.5/0"),
+            Some((ip(0, 0, 0, 0), ip(255, 255, 255, 255)))
+        );
+        assert_eq!(network("10.0.0.5/33"), None);
+        assert_eq!(network("10.0.0/8"), None);
+
+        let ports = |s: &str| s.parse::<PortRange>().map(|r| (r.first, r.last)).ok();
+        assert_eq!(ports("443"), Some((443, 443)));
+        assert_eq!(ports("8000-8100"), Some((8000, 8100)));
+        assert_eq!(ports("8100-8000"), None);
+        assert_eq!(ports("http"), None);
+    }
+
+    #[test]
+    fn test_same_value() {
+        let record = Record::builder()
+            .time(Local.ymd(2021, 10, 1).and_hms_micro(14, 2, 11, 200_123))
+            .src([10, 0, 0, 5], None)
+            .dest([10, 0, 0, 1], None)
+            .proto(Protocol::Icmp, AppProtocol::Unknown)
+            .len(84)
+            .direction(Direction::Inbound)
+            .build();
+        for column in RecordColumn::ALL {
+            let expr = match FilterExpr::same_value(&record, column) {
+                Some(expr) => expr,
+                None => continue,
+            };
+            // the value always matches the record it came from, also after a round trip
+            let (_, parsed) = parse_pred(&expr.to_string()).unwrap();
+            assert!(record_filter(&parsed, &record), "{}", expr);
+            assert!(!record_filter(&expr.negate().0, &record), "{:?}", column);
+        }
+        assert_eq!(
+            FilterExpr::same_value(&record, RecordColumn::SrcIp),
+            Some(FilterExpr::equal(
+                Field::SrcIp,
+                Literal::Ipv4(Ipv4Addr::new(10, 0, 0, 5))
+            ))
+        );
+        assert!(FilterExpr::same_value(&record, RecordColumn::SrcPort).is_none());
+        assert!(FilterExpr::same_value(&record, RecordColumn::AppProto).is_none());
+        assert!(FilterExpr::same_value(&record, RecordColumn::Direction).is_none());
+        assert!(FilterExpr::same_value(&record, RecordColumn::Interface).is_none());
+    }
 }
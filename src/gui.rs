@@ -13,29 +13,86 @@ use nwg::{
 
 use plotters::prelude::*;
 
-use packet::{Packet, ip::{v4, Protocol}, udp, tcp};
-use byteorder::{self, NetworkEndian, WriteBytesExt};
+use packet::ip::Protocol;
 
 use crate::{
-    filter::{FilterError, create_filter},
+    capture::{CaptureEvent, CaptureMetrics, CaptureThread, Handover, HandoverOutcome, HandoverSteps, PacketSource, ReadStats},
+    export::{export_csv, timestamped_path},
+    filter::{Field, FilterExpr, Literal, create_filter},
+    lang::{Lang, set_lang},
+    log::{Level, Log},
     meta, 
-    record::{NetRecord, Record, StatRecord}, 
-    rect, size, 
-    socket::Capturer, 
-    utils::{AppProtocol, attach_console}
+    pcap::{PcapReader, PcapSource},
+    record::{Flow, FlowTable, PlotProtocol, PlotRecord, Record, RecordColumn, RecordStore, StatRecord, PLOT_SAMPLING_INTERVAL}, 
+    rect, resolve::Resolver, size, 
+    schedule::{Schedule, ScheduleAction},
+    settings::{clamp_stat_split_ratio, ColumnSetting, FontSize, PlotSeries, Profile, SavedAdapter, Settings, StatRefresh},
+    socket::{AdapterLost, CaptureError, CaptureProtocol, Capturer, ReadMode, RecvMode}, 
+    synth::{demo_flows, SyntheticSource, DEMO_LOCAL_IP},
+    utils::{AppProtocol, IpCounts, attach_console, ip_packet_counts, is_elevated, open_url, os_version, privilege_message, relaunch_elevated, str_to_trans_protocol, format_byte_rate, format_bytes, format_count, format_duration, moving_average, downsample_chunk, downsample_max, max_axis_labels, time_label_format, AxisUnit}
 };
 
-use ipconfig::{Adapter, OperStatus};
+use ipconfig::OperStatus;
+
+use winapi::{
+    shared::{minwindef::{BOOL, FALSE, HIWORD, LOWORD, LPARAM, TRUE, WPARAM}, windef::{HWND, RECT}},
+    um::{
+        commctrl::{
+            LVM_ENABLEGROUPVIEW, LVM_ENSUREVISIBLE, LVM_GETCOUNTPERPAGE, LVM_GETTOPINDEX, LVM_INSERTGROUP,
+            LVM_ISITEMVISIBLE, LVM_REMOVEALLGROUPS, LVM_REMOVEGROUP, LVM_SETGROUPINFO, LVM_SETITEMTEXTW, LVM_SETITEMW, LVGF_GROUPID, LVGF_HEADER, LVGF_STATE,
+            LVGROUP, LVGS_COLLAPSED, LVGS_COLLAPSIBLE, LVIF_GROUPID, LVITEMW, SB_SETPARTS,
+        },
+        iphlpapi::NotifyAddrChange,
+        winuser::{
+            DispatchMessageW, EnumChildWindows, GetAncestor, GetClassNameW, GetKeyState, GetMessageW, IsDialogMessageW, 
+            SendMessageW, SetMenuItemInfoW, TranslateMessage, GA_ROOT, MENUITEMINFOW, MIIM_STRING, MSG, 
+            VK_CONTROL, VK_ESCAPE, VK_MENU, VK_SHIFT, VK_SPACE, WM_KEYDOWN, WM_SETFONT,
+            LoadCursorW, ReleaseCapture, SetCapture, SetCursor, IDC_SIZENS,
+            SetWindowPos, SWP_NOACTIVATE, SWP_NOZORDER, USER_DEFAULT_SCREEN_DPI, WM_DPICHANGED,
+            EN_KILLFOCUS, WM_COMMAND,
+        },
+    },
+};
 
 use std::{
-    cell::RefCell, 
-    iter, mem,
-    net::SocketAddr, 
-    time::Duration as StdDuration
+    cell::{Cell, RefCell}, 
+    collections::VecDeque,
+    env,
+    ffi::OsStr,
+    iter,
+    mem,
+    ops::Range,
+    ptr,
+    rc::Rc,
+    os::windows::ffi::OsStrExt,
+    fmt::Display,
+    fs::File,
+    io::BufReader,
+    net::{IpAddr, Ipv4Addr, SocketAddr}, 
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration as StdDuration, Instant}
 };
 
-// TODO: make this configurable
-const PLOT_SAMPLING_INTERVAL: u64 = 200;
+// upper bound of points per series, however wide the plot is
+const MAX_PLOT_POINTS: usize = 2000;
+// protocols carrying less than this share of bytes are drawn as "其他"
+const MIN_PROTOCOL_SHARE: f64 = 0.01;
+// logical pixels of the stat table titles, their collapse buttons and the splitter between them
+const STAT_TITLE_HEIGHT: u32 = 30;
+const STAT_COLLAPSE_WIDTH: u32 = 30;
+const STAT_SPLITTER_HEIGHT: u32 = 6;
+// conversations with more packets than this start out collapsed in the grouped record table
+const FLOW_COLLAPSE_ROWS: u64 = 50;
+// records a view rebuild goes through per `rebuild_timer` tick
+const REBUILD_CHUNK: usize = 20_000;
+// records taken from the capture thread per `polling_timer` tick, and how long taking them may go on,
+// so that a flood can not starve the window; whatever is left waits for the next ticks
+const TICK_RECORDS: usize = 5_000;
+const TICK_BUDGET: StdDuration = StdDuration::from_millis(5);
+// how long binding an adapter whose address changed waits before trying again, doubling up to the max
+const REBIND_BACKOFF: StdDuration = StdDuration::from_secs(1);
+const REBIND_BACKOFF_MAX: StdDuration = StdDuration::from_secs(30);
 
 // The numbers here are the index of each tab,  
 // and they purposely match the UI declared below.
@@ -44,7 +101,8 @@ enum Mode {
     Record = 0,
     Plot = 1,
     Stat = 2,
-    About = 3,
+    Settings = 3,
+    About = 4,
 }
 
 impl Default for Mode {
@@ -53,13 +111,29 @@ impl Default for Mode {
     }
 }
 
+impl Mode {
+    /// record, plot and stat tabs can each hold their own filter
+    const FILTERED: [Mode; 3] = [Mode::Record, Mode::Plot, Mode::Stat];
+
+    fn title(&self) -> &'static str {
+        match self {
+            Self::Record => "捕获记录",
+            Self::Plot => "流量图表",
+            Self::Stat => "统计结果",
+            Self::Settings => "设置",
+            Self::About => "关于",
+        }
+    }
+}
+
 impl From<usize> for Mode {
     fn from(idx: usize) -> Self {
         match idx {
             0 => Self::Record,
             1 => Self::Plot,
             2 => Self::Stat,
-            3 => Self::About,
+            3 => Self::Settings,
+            4 => Self::About,
             _ => unreachable!(),
         }
     }
@@ -67,157 +141,287 @@ impl From<usize> for Mode {
 
 #[derive(Default)]
 pub struct State {
-    interfaces: Vec<Adapter>,
+    interfaces: Vec<Interface>,
+    // the interfaces the capturer is bound to
+    connected_interfaces: Vec<Interface>,
     capturing: bool,
 
-    records: Vec<Record>,
+    records: RecordStore,
     start_time: Option<DateTime<Local>>,
     end_time: Option<DateTime<Local>>,
+    // (packets/s, bytes/s) of the latest sample, kept after capture stops
+    throughput: Option<(f64, f64)>,
+    // same as `throughput`, averaged over the configured moving window
+    window_throughput: Option<(f64, f64)>,
     
     mode: Mode,
     filter: Option<Box<dyn Fn(&Record) -> bool>>,
+    // text `filter` was created from
+    filter_text: String,
+    // filters of the tabs that stopped following `filter`, indexed by `Mode`
+    pinned_filters: [Option<PinnedFilter>; 3],
+    // the status bar tells why the text in the filter box does not parse
+    filter_error: bool,
+    // stat tables are behind `stat_records` and wait for the next refresh tick
+    stat_dirty: bool,
+    // chart geometry of the last plot redraw, used to map the cursor back to buckets
+    plot_geometry: Option<PlotGeometry>,
+    // cursor position over the plot along with the bucket under it
+    plot_hover: Option<((i32, i32), usize)>,
+    // series shown on the plot, mirrored into the settings
+    plot_series: PlotSeries,
+    // time range selected on the plot, in milliseconds since the plot start
+    plot_selection: Option<Range<i64>>,
+    // start and current time of a selection still being dragged
+    plot_drag: Option<(i64, i64)>,
+    // filter text from before the selection was applied, restored when clearing it
+    selection_base_filter: Option<String>,
+    plot_theme: PlotTheme,
+    // the plot needs a redraw, which happens at most once per `plot_redraw_interval_ms`
+    plot_dirty: bool,
+    last_plot_draw: Option<Instant>,
+    // things that happened during the session, marked on the plot
+    plot_events: Vec<PlotEvent>,
+    // record table columns as edited in the column chooser, applied on confirm
+    column_draft: Vec<ColumnSetting>,
+    // when the views were frozen; records keep being collected in the background
+    display_paused: Option<DateTime<Local>>,
+    // record table row of the current quick search hit
+    search_row: Option<usize>,
+    // connected interfaces whose socket failed for good, e.g. because the adapter went down
+    lost_interfaces: Vec<Interface>,
+    // the record table is grouped by conversation
+    group_by_flow: bool,
+    // conversations of the record table rows, kept along with the table for its groups
+    record_flows: FlowTable,
+    // position in the store, evicted records included, of the record in each record table row;
+    // rows stay while the table is paused, whatever is evicted from the store meanwhile
+    record_rows: VecDeque<u64>,
+    // the record table, and maybe the stats, being refilled over several `rebuild_timer` ticks
+    rebuild: Option<Rebuild>,
+    // value of the cell the record table menu was opened on
+    menu_value: Option<FilterExpr>,
+    // text of the cell a table menu was opened on, and of its whole row separated by tabs
+    menu_copy: Option<(String, String)>,
+    // repeated captures driven by `schedule_timer`
+    schedule: Option<Schedule>,
+    // the splitter between the stat tables is being dragged
+    stat_split_drag: bool,
+    // reads of the capture threads done with since the capture started, none before a live capture
+    read_stats: Option<ReadStats>,
+    // ip stack counters when the capture started and stopped, what it read is held against them
+    stack_counts: (Option<IpCounts>, Option<IpCounts>),
+    // drains of the capture threads done with since the capture started, the capturer keeps the other counters
+    capture_drains: CaptureMetrics,
+    // failed attempts at binding adapters whose address changed during the capture, none while nothing waits
+    rebind_attempts: Option<u32>,
+    // the only protocol the adapters are bound for, if any
+    bound_protocol: Option<CaptureProtocol>,
+    // the only protocol the records were captured for, if any, which the stats have to tell
+    only_protocol: Option<CaptureProtocol>,
+    // administrator rights were offered and turned down, binding failing for lack of them does not ask again
+    elevation_declined: bool,
 }
 
-const MARGIN_TSE: Rect<Dimension> = rect!{10.0, 10.0, 0.0};
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PlotEventKind {
+    CaptureStarted,
+    CaptureStopped,
+    FilterChanged,
+    AddressChanged,
+    AdapterChanged,
+}
 
-pub struct PlotRecord {
-    sample_interval: Duration,
-    start_time: Option<DateTime<Local>>,
-    end_time: Option<DateTime<Local>>,
-    uncommitted_record: NetRecord,
-    records: Vec<NetRecord>,
+impl PlotEventKind {
+    /// the short label drawn next to the marker
+    fn label(&self) -> &'static str {
+        match self {
+            Self::CaptureStarted => "开始",
+            Self::CaptureStopped => "停止",
+            Self::FilterChanged => "筛选",
+            Self::AddressChanged => "重绑",
+            Self::AdapterChanged => "切换",
+        }
+    }
+}
+
+pub struct PlotEvent {
+    time: DateTime<Local>,
+    kind: PlotEventKind,
+    text: String,
+}
+
+/// colors of the traffic plot
+#[derive(Clone, Copy)]
+pub struct PlotTheme {
+    background: RGBColor,
+    text: RGBColor,
+    packets: RGBColor,
+    bytes: RGBColor,
+    /// layers of the stacked byte series, indexed by `PlotProtocol`
+    protocols: [RGBColor; 4],
+    /// crosshair, selection band and borders
+    overlay: RGBColor,
+    legend_background: RGBColor,
+}
+
+impl PlotTheme {
+    const LIGHT: Self = Self {
+        background: WHITE,
+        text: BLACK,
+        packets: RGBColor(167, 79, 1),
+        bytes: RGBColor(17, 125, 187),
+        protocols: protocol_colors(false),
+        overlay: BLACK,
+        legend_background: WHITE,
+    };
+
+    // the light series colors get lost on a dark background, so these are brighter
+    const DARK: Self = Self {
+        background: RGBColor(32, 32, 32),
+        text: RGBColor(220, 220, 220),
+        packets: RGBColor(255, 167, 38),
+        bytes: RGBColor(79, 195, 247),
+        protocols: protocol_colors(true),
+        overlay: RGBColor(220, 220, 220),
+        legend_background: RGBColor(48, 48, 48),
+    };
+
+    fn new(dark: bool) -> Self {
+        if dark { Self::DARK } else { Self::LIGHT }
+    }
+}
+
+/// `PlotProtocol::color` of every protocol, in the order of `PlotProtocol::ALL`
+const fn protocol_colors(dark: bool) -> [RGBColor; 4] {
+    const fn rgb((r, g, b): (u8, u8, u8)) -> RGBColor {
+        RGBColor(r, g, b)
+    }
+    [
+        rgb(PlotProtocol::Tcp.color(dark)),
+        rgb(PlotProtocol::Udp.color(dark)),
+        rgb(PlotProtocol::Icmp.color(dark)),
+        rgb(PlotProtocol::Other.color(dark)),
+    ]
 }
 
-impl Default for PlotRecord {
+impl Default for PlotTheme {
     fn default() -> Self {
-        Self {
-            sample_interval: Duration::milliseconds(PLOT_SAMPLING_INTERVAL as i64),
-            start_time: Default::default(),
-            end_time: Default::default(),
-            uncommitted_record: Default::default(),
-            records: Default::default(),
-        }
+        Self::LIGHT
     }
 }
 
-impl PlotRecord {
-    fn clear(&mut self) {
-        self.start_time = None;
-        self.end_time = None;
-        self.uncommitted_record = Default::default();
-        self.records.clear();
+pub struct PlotGeometry {
+    x_pixels: Range<i32>,
+    y_pixels: Range<i32>,
+    time_range: Range<i64>,
+}
+
+impl PlotGeometry {
+    fn contains(&self, (x, y): (i32, i32)) -> bool {
+        self.x_pixels.contains(&x) && self.y_pixels.contains(&y)
     }
 
-    fn clear_with_time(&mut self, time: DateTime<Local>) {
-        self.clear();
-        self.start_time = Some(time);
-        self.end_time = Some(time);
+    /// time on the x axis at the pixel column `x`, clamped to the plotted range
+    fn time_at(&self, x: i32) -> i64 {
+        let x = x.clamp(self.x_pixels.start, self.x_pixels.end);
+        let width = (self.x_pixels.end - self.x_pixels.start).max(1) as i64;
+        let span = self.time_range.end - self.time_range.start;
+        self.time_range.start + (x - self.x_pixels.start) as i64 * span / width
     }
 
-    fn commit_rest(&mut self) {
-        if self.uncommitted_record.packet_num != 0 || self.uncommitted_record.byte_num != 0 {
-            self.end_time.map(|t| t + self.sample_interval);
-            self.records.push(mem::take(&mut self.uncommitted_record));
+    /// index of the bucket under the pixel `(x, y)`, if it lies inside the plotting area
+    fn bucket_at(&self, (x, y): (i32, i32), sample_interval: Duration) -> Option<usize> {
+        if !self.contains((x, y)) {
+            return None;
         }
+        let time = self.time_at(x);
+        if time < 0 {
+            return None;
+        }
+        Some((time / sample_interval.num_milliseconds().max(1)) as usize)
     }
+}
 
-    fn from_records<'a>(
-        iter: impl Iterator<Item = &'a Record>,
-        start_time: Option<DateTime<Local>>, 
-        end_time: Option<DateTime<Local>>) -> Self {
+/// a filter held by a single tab, edits to the shared one leave it alone
+struct PinnedFilter {
+    text: String,
+    filter: Option<Box<dyn Fn(&Record) -> bool>>,
+}
 
-        let mut records = Self {
-            start_time,
-            end_time: start_time,
-            ..Default::default()
-        };
-        records.update_records(iter, end_time);
+/// a filter set aside for going back to, with the tab it belongs to
+struct PreviousFilter {
+    mode: Mode,
+    text: String,
+    filter: Option<Box<dyn Fn(&Record) -> bool>>,
+}
 
-        if let (Some(end_time), Some(record_end_time)) = (end_time, records.end_time) {
-            if end_time > record_end_time {
-                records.end_time = Some(end_time);
-            }
-        }
+/// a view rebuild in progress, going through the records a chunk at a time
+struct Rebuild {
+    /// position of the next record, counting the evicted ones so that it survives evictions
+    next: u64,
+    /// whether the stats are refilled along with the record table
+    stats: bool,
+    /// what cancelling goes back to, `None` when the rebuild can not be cancelled
+    previous: Option<PreviousFilter>,
+}
 
-        records
+impl State {
+    fn pinned_filter(&self, mode: Mode) -> Option<&PinnedFilter> {
+        self.pinned_filters.get(mode as usize)?.as_ref()
     }
 
-    fn update_records<'a>(
-        &mut self,
-        iter: impl Iterator<Item = &'a Record>,
-        end_time: Option<DateTime<Local>>) {
-
-        let mut iter = iter.peekable();
-        if let Some(&record) = iter.peek() {
-            if let Some(start_time) = self.start_time {
-                if record.time < start_time {
-                    self.start_time = Some(record.time);
-                }
-            } else {
-                self.start_time = Some(record.time);
-            }
-            if self.end_time.is_none() {
-                self.end_time = Some(record.time);
-            }
-        } else if self.end_time.is_none() {
-            if end_time.is_some() {
-                self.end_time = end_time
-            } else {
-                return;
-            }
+    /// the filter `mode` applies, its own one when pinned
+    fn filter_of(&self, mode: Mode) -> Option<&dyn Fn(&Record) -> bool> {
+        match self.pinned_filter(mode) {
+            Some(pinned) => pinned.filter.as_deref(),
+            None => self.filter.as_deref(),
         }
+    }
 
-        let mut iter_without_dummy = iter.map(|r| {
-            let nr: NetRecord = r.into();
-            (&r.time, nr)
-        });
-        let mut iter_with_dummy;
-        let dummy_end_time;
-        let iter: &mut dyn Iterator<Item = (&DateTime<Local>, NetRecord)>;
-        if let Some(end_time) = end_time {
-            dummy_end_time = end_time;
-            iter_with_dummy = iter_without_dummy.chain(iter::once((
-                &dummy_end_time,
-                NetRecord {
-                    packet_num: 0,
-                    byte_num: 0,
-                }
-            )));
-            iter = &mut iter_with_dummy;
-        } else {
-            iter = &mut iter_without_dummy;
+    fn filter_text_of(&self, mode: Mode) -> &str {
+        match self.pinned_filter(mode) {
+            Some(pinned) => &pinned.text,
+            None => &self.filter_text,
         }
+    }
 
-        let mut time = self.end_time.unwrap();
-        let mut next_time = time + self.sample_interval;
+    fn passes(&self, mode: Mode, record: &Record) -> bool {
+        self.filter_of(mode).map_or(true, |f| f(record))
+    }
 
-        for (record_time, record) in iter {
-            if record_time < &next_time {
-                self.uncommitted_record.add_up(&record.into());
-            } else {
-                self.records.push(self.uncommitted_record.clone());
-                self.uncommitted_record = Default::default();
-                self.uncommitted_record.add_up(&record.into());
-                time = next_time;
-                next_time = time + self.sample_interval;
-                while record_time >= &next_time {
-                    self.records.push(Default::default());
-                    time = next_time;
-                    next_time = time + self.sample_interval;
-                }
-            }
-        }
+    /// the record shown in `row` of the record table, none once it was evicted from the store
+    fn row_record(&self, row: usize) -> Option<&Record> {
+        let position = self.record_rows.get(row)?.checked_sub(self.records.evicted())?;
+        self.records.get(position as usize)
+    }
 
-        self.end_time = Some(time);
+    /// replace the filter of `mode`, returning the one it had
+    fn set_filter_of(&mut self, mode: Mode, text: String, filter: Option<Box<dyn Fn(&Record) -> bool>>) -> PreviousFilter {
+        let (text, filter) = match self.pinned_filters.get_mut(mode as usize) {
+            Some(Some(pinned)) => (mem::replace(&mut pinned.text, text), mem::replace(&mut pinned.filter, filter)),
+            _ => (mem::replace(&mut self.filter_text, text), mem::replace(&mut self.filter, filter)),
+        };
+        PreviousFilter { mode, text, filter }
     }
 }
 
+const MARGIN_TSE: Rect<Dimension> = rect!{10.0, 10.0, 0.0};
+
 #[derive(Default, NwgUi)]
 pub struct App {
     state: RefCell<State>,
+    settings: RefCell<Settings>,
     capturer: RefCell<Capturer>,
+    // reads on its own while capturing, holding the capturer until it is stopped
+    capture_thread: RefCell<Option<CaptureThread<Capturer>>>,
     stat_records: RefCell<StatRecord>,
     plot_records: RefCell<PlotRecord>,
+    log: RefCell<Log>,
+    // looks up the host names in the record table while `Settings::resolve_hosts` is on
+    resolver: RefCell<Option<Resolver>>,
+    // underlined ui font of the link labels on the about tab, follows the font size setting
+    link_font: RefCell<nwg::Font>,
 
     #[nwg_resource(module: None)]
     embed_resource: nwg::EmbedResource,
@@ -236,7 +440,7 @@ pub struct App {
         OnInit: [Self::init],
         OnWindowMaximize: [Self::window_maximize],
         OnResize: [Self::window_resize],
-        OnWindowClose: [Self::window_close],
+        OnWindowClose: [Self::window_close(SELF, EVT_DATA)],
     )]
     window: nwg::Window,
 
@@ -256,6 +460,66 @@ pub struct App {
     #[nwg_events( OnTimerStop: [Self::stop_capture] )]
     capturing_timer: nwg::AnimationTimer,
 
+    // the interval follows `Settings::stat_refresh`, set on init
+    #[nwg_control(parent: window, interval: StdDuration::from_millis(250))]
+    #[nwg_events( OnTimerTick: [Self::refresh_stat_table] )]
+    stat_refresh_timer: nwg::AnimationTimer,
+
+    #[nwg_control(parent: window, interval: StdDuration::from_millis(1000))]
+    #[nwg_events( OnTimerTick: [Self::schedule_tick] )]
+    schedule_timer: nwg::AnimationTimer,
+
+    #[nwg_control(parent: window, interval: StdDuration::from_millis(10))]
+    #[nwg_events( OnTimerTick: [Self::rebuild_step] )]
+    rebuild_timer: nwg::AnimationTimer,
+
+    // host names finish looking up after their rows are inserted, so the rows in view catch up
+    #[nwg_control(parent: window, interval: StdDuration::from_millis(1000))]
+    #[nwg_events( OnTimerTick: [Self::backfill_host_names] )]
+    resolve_timer: nwg::AnimationTimer,
+
+    // the stat tables are placed once the flexbox layout has sized their frame
+    #[nwg_control(parent: window, lifetime: Some(StdDuration::from_millis(1)))]
+    #[nwg_events( OnTimerStop: [Self::layout_stat_tables] )]
+    stat_layout_timer: nwg::AnimationTimer,
+
+    // raised from `watch_addresses` whenever the ip address table changes
+    #[nwg_control(parent: window)]
+    #[nwg_events( OnNotice: [Self::addresses_changed] )]
+    address_notice: nwg::Notice,
+
+    // raised from `watch_dpi` once the window was moved onto a display of another scaling
+    #[nwg_control(parent: window)]
+    #[nwg_events( OnNotice: [Self::dpi_changed] )]
+    dpi_notice: nwg::Notice,
+    // the scaling `watch_dpi` saw the window move to, for `dpi_changed` to take in
+    pending_scale: Rc<Cell<Option<f64>>>,
+
+    // raised from `watch_focus_out` as a settings field is left, which applies it like enter
+    #[nwg_control(parent: window)]
+    #[nwg_events( OnNotice: [Self::set_rate_window] )]
+    rate_window_notice: nwg::Notice,
+    #[nwg_control(parent: window)]
+    #[nwg_events( OnNotice: [Self::set_smoothing_window] )]
+    smoothing_window_notice: nwg::Notice,
+    #[nwg_control(parent: window)]
+    #[nwg_events( OnNotice: [Self::set_plot_window] )]
+    plot_window_notice: nwg::Notice,
+    #[nwg_control(parent: window)]
+    #[nwg_events( OnNotice: [Self::set_plot_redraw_interval] )]
+    plot_redraw_interval_notice: nwg::Notice,
+    #[nwg_control(parent: window)]
+    #[nwg_events( OnNotice: [Self::set_auto_export_dir] )]
+    auto_export_dir_notice: nwg::Notice,
+
+    // tries binding adapters whose address changed again, its lifetime set by the backoff
+    #[nwg_control(parent: window, lifetime: Some(REBIND_BACKOFF))]
+    #[nwg_events( OnTimerStop: [Self::rebind_addresses] )]
+    rebind_timer: nwg::AnimationTimer,
+
+    #[nwg_control(parent: window)]
+    tooltip: nwg::Tooltip,
+
     // ----- main column -----
     #[nwg_control()]
     #[nwg_layout(parent: window, flex_direction: FlexDirection::Column)]
@@ -281,11 +545,53 @@ pub struct App {
     #[nwg_events(OnComboxBoxSelection: [Self::connect_interface])]
     interfaces: nwg::ComboBox<String>,
 
+    #[nwg_control(parent: interface_row_frame, text: "刷新")]
+    #[nwg_layout_item(layout: interface_row, size: size!{60.0, auto}, margin: rect!{end: 10.0})]
+    #[nwg_events(OnButtonClick: [Self::refresh_interfaces])]
+    refresh_interfaces: nwg::Button,
+
+    #[nwg_control(parent: interface_row_frame, text: "多选…")]
+    #[nwg_layout_item(layout: interface_row, size: size!{60.0, auto}, margin: rect!{end: 10.0})]
+    #[nwg_events(OnButtonClick: [Self::show_interface_chooser])]
+    choose_interfaces: nwg::Button,
+
+    // enabled while some adapter is bound
+    #[nwg_control(parent: interface_row_frame, text: "断开网卡", enabled: false)]
+    #[nwg_layout_item(layout: interface_row, size: size!{80.0, auto}, margin: rect!{end: 10.0})]
+    #[nwg_events(OnButtonClick: [Self::disconnect_interfaces])]
+    disconnect_interfaces: nwg::Button,
+
     #[nwg_control(parent: interface_row_frame, text: "开始捕获")]
     #[nwg_layout_item(layout: interface_row, size: size!{100.0, auto})]
     #[nwg_events(MousePressLeftUp: [Self::toggle_capture])]
     capture: nwg::Button,
 
+    #[nwg_control(parent: interface_row_frame, text: "暂停显示")]
+    #[nwg_layout_item(layout: interface_row, size: size!{100.0, auto}, margin: rect!{start: 10.0})]
+    #[nwg_events(OnButtonClick: [Self::toggle_display_pause])]
+    pause_display: nwg::Button,
+
+    #[nwg_control(parent: interface_row_frame, text: "清空")]
+    #[nwg_layout_item(layout: interface_row, size: size!{80.0, auto}, margin: rect!{start: 10.0})]
+    #[nwg_events(OnButtonClick: [Self::clear_records])]
+    clear_records: nwg::Button,
+
+    #[nwg_control(parent: interface_row_frame, text: "追加到现有记录")]
+    #[nwg_layout_item(layout: interface_row, size: size!{130.0, auto}, margin: rect!{start: 10.0})]
+    #[nwg_events(OnButtonClick: [Self::toggle_append_records])]
+    append_records: nwg::CheckBox,
+
+    #[nwg_control(parent: interface_row_frame, text: "定时…")]
+    #[nwg_layout_item(layout: interface_row, size: size!{80.0, auto}, margin: rect!{start: 10.0})]
+    #[nwg_events(OnButtonClick: [Self::toggle_schedule])]
+    schedule_button: nwg::Button,
+
+    // only shown while a lost adapter is back
+    #[nwg_control(parent: interface_row_frame, text: "重新连接")]
+    #[nwg_layout_item(layout: interface_row, size: size!{80.0, auto}, margin: rect!{start: 10.0})]
+    #[nwg_events(OnButtonClick: [Self::reconnect_lost_interfaces])]
+    reconnect_button: nwg::Button,
+
     // ----- capturing setting row -----
     #[nwg_control(parent: window, flags: "VISIBLE")]
     #[nwg_layout_item(layout: main_column,
@@ -307,11 +613,43 @@ pub struct App {
     #[nwg_events(OnTextInput: [Self::create_filter])]
     filter: nwg::TextInput,
 
+    #[nwg_control(parent: capturing_setting_row_frame, text: "独立筛选")]
+    #[nwg_layout_item(layout: capturing_setting_row,
+        min_size: size!{90.0, 30.0}, margin: rect!{end: 10.0}
+    )]
+    #[nwg_events(OnButtonClick: [Self::toggle_filter_pin])]
+    filter_pin: nwg::CheckBox,
+
+    // only shown while the views are rebuilt for a new filter
+    #[nwg_control(parent: capturing_setting_row_frame, text: "取消筛选")]
+    #[nwg_layout_item(layout: capturing_setting_row,
+        min_size: size!{80.0, 30.0}, margin: rect!{end: 10.0}
+    )]
+    #[nwg_events(OnButtonClick: [Self::cancel_rebuild])]
+    cancel_rebuild_button: nwg::Button,
+
     #[nwg_control(parent: capturing_setting_row_frame, placeholder_text: Some("请输入捕获时间（毫秒）"))]
-    #[nwg_layout_item(layout: capturing_setting_row, min_size: size!{180.0, 30.0})]
+    #[nwg_layout_item(layout: capturing_setting_row,
+        min_size: size!{180.0, 30.0}, margin: rect!{end: 10.0}
+    )]
     #[nwg_events(OnTextInput: [Self::set_timeout])]
     timeout: nwg::TextInput,
 
+    #[nwg_control(parent: capturing_setting_row_frame)]
+    #[nwg_layout_item(layout: capturing_setting_row, min_size: size!{160.0, 30.0})]
+    #[nwg_events(OnComboxBoxSelection: [Self::apply_profile])]
+    profiles: nwg::ComboBox<String>,
+
+    #[nwg_control(parent: capturing_setting_row_frame, text: "另存为配置…")]
+    #[nwg_layout_item(layout: capturing_setting_row, size: size!{100.0, auto}, margin: rect!{start: 10.0})]
+    #[nwg_events(OnButtonClick: [Self::open_profile_dialog])]
+    save_profile_button: nwg::Button,
+
+    #[nwg_control(parent: capturing_setting_row_frame, text: "删除配置")]
+    #[nwg_layout_item(layout: capturing_setting_row, size: size!{80.0, auto}, margin: rect!{start: 10.0})]
+    #[nwg_events(OnButtonClick: [Self::delete_profile])]
+    delete_profile_button: nwg::Button,
+
     // ----- tab container -----
     #[nwg_control(parent: window, flags: "VISIBLE")]
     #[nwg_layout_item(layout: main_column,
@@ -332,24 +670,197 @@ pub struct App {
     )]
     record_tab_layout: nwg::FlexboxLayout,
 
+    #[nwg_control(parent: record_tab, flags: "VISIBLE")]
+    #[nwg_layout_item(layout: record_tab_layout,
+        min_size: size!{height: 30.0}, max_size: size!{height: 30.0},
+    )]
+    record_toolbar_frame: nwg::Frame,
+
+    #[nwg_control(parent: record_toolbar_frame)]
+    #[nwg_layout(parent: record_toolbar_frame,
+        align_items: AlignItems::Stretch,
+        flex_direction: FlexDirection::Row, padding: Default::default()
+    )]
+    record_toolbar: nwg::FlexboxLayout,
+
+    #[nwg_control(parent: record_toolbar_frame, placeholder_text: Some("搜索记录"))]
+    #[nwg_layout_item(layout: record_toolbar, flex_grow: 1.0, margin: rect!{end: 10.0})]
+    #[nwg_events(
+        OnTextInput: [Self::search_records],
+        OnKeyEnter: [Self::search_by_enter],
+    )]
+    record_search: nwg::TextInput,
+
+    #[nwg_control(parent: record_toolbar_frame, text: "上一个")]
+    #[nwg_layout_item(layout: record_toolbar, size: size!{60.0, auto})]
+    #[nwg_events(OnButtonClick: [Self::search_previous])]
+    search_previous: nwg::Button,
+
+    #[nwg_control(parent: record_toolbar_frame, text: "下一个")]
+    #[nwg_layout_item(layout: record_toolbar, size: size!{60.0, auto})]
+    #[nwg_events(OnButtonClick: [Self::search_next])]
+    search_next: nwg::Button,
+
+    #[nwg_control(parent: record_toolbar_frame, text: "", background_color: Some([0xff, 0xff, 0xff]))]
+    #[nwg_layout_item(layout: record_toolbar, size: size!{140.0, auto}, margin: rect!{start: 10.0})]
+    search_status: nwg::Label,
+
+    #[nwg_control(parent: record_toolbar_frame, text: "自动滚动",
+        background_color: Some([0xff, 0xff, 0xff]),
+    )]
+    #[nwg_layout_item(layout: record_toolbar, size: size!{90.0, auto})]
+    #[nwg_events(OnButtonClick: [Self::toggle_auto_scroll])]
+    auto_scroll: nwg::CheckBox,
+
+    #[nwg_control(parent: record_toolbar_frame, text: "按会话分组",
+        background_color: Some([0xff, 0xff, 0xff]),
+    )]
+    #[nwg_layout_item(layout: record_toolbar, size: size!{100.0, auto})]
+    #[nwg_events(OnButtonClick: [Self::toggle_group_by_flow])]
+    group_by_flow: nwg::CheckBox,
+
     #[nwg_control(parent: record_tab, list_style: nwg::ListViewStyle::Detailed, focus: true,
         ex_flags: nwg::ListViewExFlags::GRID | nwg::ListViewExFlags::FULL_ROW_SELECT, 
     )]
-    #[nwg_layout_item(layout: record_tab_layout)]
+    #[nwg_layout_item(layout: record_tab_layout, flex_grow: 1.0)]
+    #[nwg_events(OnListViewRightClick: [Self::show_record_menu(SELF, EVT_DATA)])]
     record_table: nwg::ListView,
 
+    #[nwg_control(parent: window, popup: true)]
+    record_menu: nwg::Menu,
+
+    #[nwg_control(parent: record_menu, text: "筛选此值")]
+    #[nwg_events(OnMenuItemSelected: [Self::filter_by_value])]
+    menu_filter_value: nwg::MenuItem,
+
+    #[nwg_control(parent: record_menu, text: "排除此值")]
+    #[nwg_events(OnMenuItemSelected: [Self::filter_out_value])]
+    menu_exclude_value: nwg::MenuItem,
+
+    // "&" marks a menu mnemonic, so each one shown is written twice
+    #[nwg_control(parent: record_menu, text: "追加到现有筛选器 (&&&&)")]
+    #[nwg_events(OnMenuItemSelected: [Self::append_value_filter])]
+    menu_append_value: nwg::MenuItem,
+
+    #[nwg_control(parent: record_menu)]
+    record_menu_copy_separator: nwg::MenuSeparator,
+
+    #[nwg_control(parent: record_menu, text: "复制该单元格")]
+    #[nwg_events(OnMenuItemSelected: [Self::copy_menu_cell])]
+    menu_copy_cell: nwg::MenuItem,
+
+    #[nwg_control(parent: record_menu, text: "复制整行")]
+    #[nwg_events(OnMenuItemSelected: [Self::copy_menu_row])]
+    menu_copy_row: nwg::MenuItem,
+
+    #[nwg_control(parent: record_menu)]
+    record_menu_separator: nwg::MenuSeparator,
+
+    #[nwg_control(parent: record_menu, text: "选择显示的列...")]
+    #[nwg_events(OnMenuItemSelected: [Self::show_column_chooser])]
+    menu_columns: nwg::MenuItem,
+
     // ----- plot tab -----
     #[nwg_control(parent: tabs_container, text: "流量图表")]
     plot_tab: nwg::Tab,
 
     #[nwg_control(parent: plot_tab)]
     #[nwg_layout(parent: plot_tab,
-        flex_direction: FlexDirection::Row, 
+        flex_direction: FlexDirection::Column, 
     )]
     plot_tab_layout: nwg::FlexboxLayout,
 
+    #[nwg_control(parent: plot_tab, flags: "VISIBLE")]
+    #[nwg_layout_item(layout: plot_tab_layout,
+        min_size: size!{height: 30.0}, max_size: size!{height: 30.0},
+    )]
+    plot_toolbar_frame: nwg::Frame,
+
+    #[nwg_control(parent: plot_toolbar_frame)]
+    #[nwg_layout(parent: plot_toolbar_frame,
+        align_items: AlignItems::Stretch,
+        flex_direction: FlexDirection::Row, padding: Default::default()
+    )]
+    plot_toolbar: nwg::FlexboxLayout,
+
+    #[nwg_control(parent: plot_toolbar_frame, text: "移动平均")]
+    #[nwg_layout_item(layout: plot_toolbar, size: size!{100.0, auto})]
+    #[nwg_events(OnButtonClick: [Self::toggle_plot_smoothing])]
+    plot_smoothing: nwg::CheckBox,
+
+    #[nwg_control(parent: plot_toolbar_frame, text: "按协议堆叠")]
+    #[nwg_layout_item(layout: plot_toolbar, size: size!{100.0, auto})]
+    #[nwg_events(OnButtonClick: [Self::toggle_plot_stacked])]
+    plot_stacked: nwg::CheckBox,
+
+    #[nwg_control(parent: plot_toolbar_frame, text: "仅显示最近 30 秒")]
+    #[nwg_layout_item(layout: plot_toolbar, size: size!{160.0, auto})]
+    #[nwg_events(OnButtonClick: [Self::toggle_plot_rolling])]
+    plot_rolling: nwg::CheckBox,
+
+    #[nwg_control(parent: plot_toolbar_frame, text: "分组与字节分开显示")]
+    #[nwg_layout_item(layout: plot_toolbar, size: size!{160.0, auto})]
+    #[nwg_events(OnButtonClick: [Self::toggle_plot_split])]
+    plot_split: nwg::CheckBox,
+
+    #[nwg_control(parent: plot_toolbar_frame, text: "选择区间")]
+    #[nwg_layout_item(layout: plot_toolbar, size: size!{100.0, auto})]
+    plot_select: nwg::CheckBox,
+
+    #[nwg_control(parent: plot_toolbar_frame, text: "清除区间")]
+    #[nwg_layout_item(layout: plot_toolbar, size: size!{100.0, auto})]
+    #[nwg_events(OnButtonClick: [Self::clear_plot_selection])]
+    plot_clear_selection: nwg::Button,
+
+    #[nwg_control(parent: plot_tab, flags: "VISIBLE")]
+    #[nwg_layout_item(layout: plot_tab_layout,
+        min_size: size!{height: 30.0}, max_size: size!{height: 30.0},
+    )]
+    plot_series_frame: nwg::Frame,
+
+    #[nwg_control(parent: plot_series_frame)]
+    #[nwg_layout(parent: plot_series_frame,
+        align_items: AlignItems::Stretch,
+        flex_direction: FlexDirection::Row, padding: Default::default()
+    )]
+    plot_series_bar: nwg::FlexboxLayout,
+
+    #[nwg_control(parent: plot_series_frame, text: "分组/秒")]
+    #[nwg_layout_item(layout: plot_series_bar, size: size!{80.0, auto})]
+    #[nwg_events(OnButtonClick: [Self::toggle_plot_series])]
+    series_packets: nwg::CheckBox,
+
+    #[nwg_control(parent: plot_series_frame, text: "字节/秒")]
+    #[nwg_layout_item(layout: plot_series_bar, size: size!{80.0, auto})]
+    #[nwg_events(OnButtonClick: [Self::toggle_plot_series])]
+    series_bytes: nwg::CheckBox,
+
+    #[nwg_control(parent: plot_series_frame, text: "TCP")]
+    #[nwg_layout_item(layout: plot_series_bar, size: size!{60.0, auto})]
+    #[nwg_events(OnButtonClick: [Self::toggle_plot_series])]
+    series_tcp: nwg::CheckBox,
+
+    #[nwg_control(parent: plot_series_frame, text: "UDP")]
+    #[nwg_layout_item(layout: plot_series_bar, size: size!{60.0, auto})]
+    #[nwg_events(OnButtonClick: [Self::toggle_plot_series])]
+    series_udp: nwg::CheckBox,
+
+    #[nwg_control(parent: plot_series_frame, text: "ICMP")]
+    #[nwg_layout_item(layout: plot_series_bar, size: size!{60.0, auto})]
+    #[nwg_events(OnButtonClick: [Self::toggle_plot_series])]
+    series_icmp: nwg::CheckBox,
+
+    #[nwg_control(parent: plot_series_frame, text: "其他")]
+    #[nwg_layout_item(layout: plot_series_bar, size: size!{60.0, auto})]
+    #[nwg_events(OnButtonClick: [Self::toggle_plot_series])]
+    series_other: nwg::CheckBox,
+
     #[nwg_control(parent: plot_tab)]
     #[nwg_layout_item(layout: plot_tab_layout, flex_grow: 1.0)]
+    #[nwg_events(
+        OnMouseMove: [Self::plot_mouse_move],
+        OnMousePress: [Self::plot_mouse_press(SELF, EVT)],
+    )]
     plot_graph: nwg::Plotters,
 
     // ----- stat tab -----
@@ -362,647 +873,4543 @@ pub struct App {
     )]
     stat_tab_layout: nwg::FlexboxLayout,
 
-    #[nwg_control(parent: stat_tab, text: "统计结果", background_color: Some([0xff, 0xff, 0xff]))]
+    #[nwg_control(parent: stat_tab, flags: "VISIBLE")]
     #[nwg_layout_item(layout: stat_tab_layout,
-        min_size: size!{height: 30.0},
+        min_size: size!{height: 30.0}, max_size: size!{height: 30.0},
+    )]
+    stat_net_row_frame: nwg::Frame,
+
+    #[nwg_control(parent: stat_net_row_frame)]
+    #[nwg_layout(parent: stat_net_row_frame,
+        align_items: AlignItems::Stretch,
+        flex_direction: FlexDirection::Row, padding: Default::default()
     )]
+    stat_net_row: nwg::FlexboxLayout,
+
+    #[nwg_control(parent: stat_net_row_frame, text: "统计结果", background_color: Some([0xff, 0xff, 0xff]))]
+    #[nwg_layout_item(layout: stat_net_row, flex_grow: 1.0)]
     stat_net_info: nwg::Label,
 
-    #[nwg_control(parent: stat_tab, text: "传输层统计结果", background_color: Some([0xff, 0xff, 0xff]))]
+    // only shown when the stat tab is refreshed by hand
+    #[nwg_control(parent: stat_net_row_frame, text: "刷新")]
+    #[nwg_layout_item(layout: stat_net_row, size: size!{80.0, auto})]
+    #[nwg_events(OnButtonClick: [Self::display_stat_table])]
+    stat_refresh_button: nwg::Button,
+
+    #[nwg_control(parent: stat_tab, text: "", background_color: Some([0xff, 0xff, 0xff]))]
+    #[nwg_layout_item(layout: stat_tab_layout,
+        min_size: size!{height: 30.0},
+    )]
+    stat_session_info: nwg::Label,
+
+    #[nwg_control(parent: stat_tab, text: "", background_color: Some([0xff, 0xff, 0xff]))]
+    #[nwg_layout_item(layout: stat_tab_layout,
+        min_size: size!{height: 30.0},
+    )]
+    stat_rate_info: nwg::Label,
+
+    // the packets the capture likely missed, empty until a live capture
+    #[nwg_control(parent: stat_tab, text: "", background_color: Some([0xff, 0xff, 0xff]))]
     #[nwg_layout_item(layout: stat_tab_layout,
         min_size: size!{height: 30.0},
     )]
+    stat_drop_info: nwg::Label,
+
+    // byte share per transport protocol, hidden until there is something to show
+    #[nwg_control(parent: stat_tab)]
+    #[nwg_layout_item(layout: stat_tab_layout,
+        min_size: size!{height: 100.0},
+    )]
+    stat_share_chart: nwg::Plotters,
+
+    // the transport and app tables with a draggable splitter between them, placed by
+    // `layout_stat_tables` since a flexbox can not change its proportions at runtime
+    #[nwg_control(parent: stat_tab, flags: "VISIBLE")]
+    #[nwg_layout_item(layout: stat_tab_layout, flex_grow: 2.0)]
+    stat_tables_frame: nwg::Frame,
+
+    #[nwg_control(parent: stat_tables_frame, text: "传输层统计结果", background_color: Some([0xff, 0xff, 0xff]))]
     stat_trans_label: nwg::Label,
 
-    #[nwg_control(parent: stat_tab, list_style: nwg::ListViewStyle::Detailed, focus: true,
+    #[nwg_control(parent: stat_tables_frame, text: "▲")]
+    #[nwg_events(OnButtonClick: [Self::toggle_stat_trans_collapsed])]
+    stat_trans_collapse: nwg::Button,
+
+    #[nwg_control(parent: stat_tables_frame, list_style: nwg::ListViewStyle::Detailed, focus: true,
         ex_flags: nwg::ListViewExFlags::GRID | nwg::ListViewExFlags::FULL_ROW_SELECT, 
     )]
-    #[nwg_layout_item(layout: stat_tab_layout, flex_grow: 1.0)]
+    #[nwg_events(
+        OnListViewDoubleClick: [Self::filter_by_trans_row(SELF, EVT_DATA)],
+        OnListViewRightClick: [Self::show_stat_trans_menu(SELF, EVT_DATA)],
+    )]
     stat_trans_table: nwg::ListView,
 
-    #[nwg_control(parent: stat_tab, text: "应用层统计结果", background_color: Some([0xff, 0xff, 0xff]))]
-    #[nwg_layout_item(layout: stat_tab_layout,
-        min_size: size!{height: 30.0},
+    #[nwg_control(parent: stat_tables_frame, flags: "VISIBLE")]
+    #[nwg_events(
+        OnMouseMove: [Self::stat_splitter_move],
+        OnMousePress: [Self::stat_splitter_press(SELF, EVT)],
     )]
+    stat_splitter: nwg::Frame,
+
+    #[nwg_control(parent: stat_tables_frame, text: "应用层统计结果", background_color: Some([0xff, 0xff, 0xff]))]
     stat_app_label: nwg::Label,
 
-    #[nwg_control(parent: stat_tab, list_style: nwg::ListViewStyle::Detailed, focus: true,
+    #[nwg_control(parent: stat_tables_frame, text: "▲")]
+    #[nwg_events(OnButtonClick: [Self::toggle_stat_app_collapsed])]
+    stat_app_collapse: nwg::Button,
+
+    #[nwg_control(parent: stat_tables_frame, list_style: nwg::ListViewStyle::Detailed, focus: true,
         ex_flags: nwg::ListViewExFlags::GRID | nwg::ListViewExFlags::FULL_ROW_SELECT, 
     )]
-    #[nwg_layout_item(layout: stat_tab_layout, flex_grow: 1.0)]
+    #[nwg_events(
+        OnListViewDoubleClick: [Self::filter_by_app_row(SELF, EVT_DATA)],
+        OnListViewRightClick: [Self::show_stat_app_menu(SELF, EVT_DATA)],
+    )]
     stat_app_table: nwg::ListView,
 
-    // ----- about tab -----
-    #[nwg_control(parent: tabs_container, text: "关于")]
-    about_tab: nwg::Tab,
+    #[nwg_control(parent: window, popup: true)]
+    stat_menu: nwg::Menu,
 
-    #[nwg_resource(family: "Segoe UI", size: 30)]
-    about_font: nwg::Font,
+    #[nwg_control(parent: stat_menu, text: "复制该单元格")]
+    #[nwg_events(OnMenuItemSelected: [Self::copy_menu_cell])]
+    stat_copy_cell: nwg::MenuItem,
 
-    #[nwg_control(parent: about_tab)]
-    #[nwg_layout(parent: about_tab,
-        align_items: AlignItems::Center,
-        justify_content: JustifyContent::Center,
-        flex_direction: FlexDirection::Row, 
+    #[nwg_control(parent: stat_menu, text: "复制整行")]
+    #[nwg_events(OnMenuItemSelected: [Self::copy_menu_row])]
+    stat_copy_row: nwg::MenuItem,
+
+    #[nwg_control(parent: stat_tab, text: "DNS 统计结果", background_color: Some([0xff, 0xff, 0xff]))]
+    #[nwg_layout_item(layout: stat_tab_layout,
+        min_size: size!{height: 30.0},
     )]
-    about_tab_layout: nwg::FlexboxLayout,
+    stat_dns_info: nwg::Label,
 
-    #[nwg_resource(
-        source_embed: Some(&data.embed_resource),
-        source_embed_str: Some("LOGO"),
-        size: Some((128, 128))
+    #[nwg_control(parent: stat_tab, list_style: nwg::ListViewStyle::Detailed,
+        ex_flags: nwg::ListViewExFlags::GRID | nwg::ListViewExFlags::FULL_ROW_SELECT, 
     )]
-    app_logo: nwg::Icon,
+    #[nwg_layout_item(layout: stat_tab_layout, flex_grow: 0.5)]
+    stat_dns_table: nwg::ListView,
 
-    #[nwg_control(parent: about_tab, size: (128, 128),
+    // ----- settings tab -----
+    #[nwg_control(parent: tabs_container, text: "设置")]
+    settings_tab: nwg::Tab,
+
+    #[nwg_control(parent: settings_tab)]
+    #[nwg_layout(parent: settings_tab,
+        flex_direction: FlexDirection::Column, 
+    )]
+    settings_tab_layout: nwg::FlexboxLayout,
+
+    #[nwg_control(parent: settings_tab, text: "以 KB、MB 等单位显示字节数",
         background_color: Some([0xff, 0xff, 0xff]),
-        icon: Some(&data.app_logo),
     )]
-    #[nwg_layout_item(layout: about_tab_layout, size: size!{128.0, 128.0})]
-    about_logo: nwg::ImageFrame,
+    #[nwg_layout_item(layout: settings_tab_layout,
+        min_size: size!{height: 30.0}, max_size: size!{height: 30.0},
+    )]
+    #[nwg_events(OnButtonClick: [Self::toggle_humanize_bytes])]
+    humanize_bytes: nwg::CheckBox,
 
-    #[nwg_control(parent: about_tab,
+    #[nwg_control(parent: settings_tab, text: "图表使用深色主题",
         background_color: Some([0xff, 0xff, 0xff]),
-        text: format!(
-r"{} {}
-by {}
+    )]
+    #[nwg_layout_item(layout: settings_tab_layout,
+        min_size: size!{height: 30.0}, max_size: size!{height: 30.0},
+    )]
+    #[nwg_events(OnButtonClick: [Self::toggle_plot_dark])]
+    plot_dark: nwg::CheckBox,
 
-",
-        meta::NAME, meta::VERSION, meta::AUTHORS).as_str(),
+    #[nwg_control(parent: settings_tab, text: "启动时自动连接上次使用的网卡",
+        background_color: Some([0xff, 0xff, 0xff]),
     )]
-    #[nwg_layout_item(layout: about_tab_layout, size: size!{200.0, 180.0})]
-    about_info: nwg::Label,
+    #[nwg_layout_item(layout: settings_tab_layout,
+        min_size: size!{height: 30.0}, max_size: size!{height: 30.0},
+    )]
+    #[nwg_events(OnButtonClick: [Self::toggle_auto_connect])]
+    auto_connect: nwg::CheckBox,
 
-    // ----- status bar -----
-    #[nwg_control(parent: window, text: "准备就绪")]
-    #[nwg_layout_item(layout: main_column, 
-        margin: rect!{top: 10.0},
-        min_size: size!{height: 30.0}
+    #[nwg_control(parent: settings_tab, text: "在记录表中显示 IP 地址的主机名",
+        background_color: Some([0xff, 0xff, 0xff]),
     )]
-    status_bar: nwg::StatusBar,
-}
+    #[nwg_layout_item(layout: settings_tab_layout,
+        min_size: size!{height: 30.0}, max_size: size!{height: 30.0},
+    )]
+    #[nwg_events(OnButtonClick: [Self::toggle_resolve_hosts])]
+    resolve_hosts: nwg::CheckBox,
 
-impl App {
-    fn new() -> Result<Self> {
-        let mut state = State::default();
-        state.capturing = false;
-        state.interfaces = {
-            let mut interfaces = ipconfig::get_adapters()?
-                .into_iter()
-                .filter(|adapter| {
-                    adapter.oper_status() == OperStatus::IfOperStatusUp
-                        && adapter.ip_addresses().iter().any(|addr| addr.is_ipv4())
-                })
-                .collect::<Vec<_>>();
-            interfaces.sort_by(|a1, a2| a1.description().cmp(a2.description()));
-            interfaces
-        };
+    #[nwg_control(parent: settings_tab, text: "轮询读取网卡（兼容模式，空闲时也占用 CPU，下次开始捕获时生效）",
+        background_color: Some([0xff, 0xff, 0xff]),
+    )]
+    #[nwg_layout_item(layout: settings_tab_layout,
+        min_size: size!{height: 30.0}, max_size: size!{height: 30.0},
+    )]
+    #[nwg_events(OnButtonClick: [Self::toggle_poll_capture])]
+    poll_capture: nwg::CheckBox,
 
-        Ok(Self {
-            state: RefCell::new(state),
-            ..Default::default()
-        })
-    }
+    #[nwg_control(parent: settings_tab, flags: "VISIBLE")]
+    #[nwg_layout_item(layout: settings_tab_layout,
+        min_size: size!{height: 30.0}, max_size: size!{height: 30.0},
+    )]
+    recv_mode_row_frame: nwg::Frame,
 
-    fn reset_status_bar(&self) {
-        let capturing = self.state.borrow().capturing;
-        if capturing {
-            self.status_bar.set_text(0, "正在捕获...");
-        } else {
-            self.status_bar.set_text(0, "准备就绪");
-        }
-    }
+    #[nwg_control(parent: recv_mode_row_frame)]
+    #[nwg_layout(parent: recv_mode_row_frame,
+        align_items: AlignItems::Stretch,
+        flex_direction: FlexDirection::Row, padding: Default::default()
+    )]
+    recv_mode_row: nwg::FlexboxLayout,
 
-    fn init(&self) {
-        let state = self.state.borrow();
-        for (i, adapter) in state.interfaces.iter().enumerate() {
-            self.interfaces.insert(i, adapter.description().to_string());
-        }
+    #[nwg_control(parent: recv_mode_row_frame, text: "捕获范围（下次连接时生效）")]
+    #[nwg_layout_item(layout: recv_mode_row, size: size!{200.0, auto})]
+    recv_mode_label: nwg::Label,
 
-        self.tabs_container.set_selected_tab(state.mode as usize);
+    #[nwg_control(parent: recv_mode_row_frame)]
+    #[nwg_layout_item(layout: recv_mode_row, size: size!{300.0, auto})]
+    #[nwg_events(OnComboxBoxSelection: [Self::set_recv_mode])]
+    recv_mode: nwg::ComboBox<String>,
 
-        // ----- record tab -----
-        self.record_table.insert_column("时间");
-        self.record_table.set_column_width(0, 220);
-        self.record_table.insert_column("源IP");
-        self.record_table.set_column_width(1, 135);
-        self.record_table.insert_column("源端口");
-        self.record_table.set_column_width(2, 60);
-        self.record_table.insert_column("目的IP");
-        self.record_table.set_column_width(3, 135);
-        self.record_table.insert_column("目的端口");
-        self.record_table.set_column_width(4, 80);
-        self.record_table.insert_column("IP分组长度");
-        self.record_table.insert_column("IP数据长度");
-        self.record_table.insert_column("传输层协议");
-        self.record_table.insert_column("报文段数据长度");
-        self.record_table.set_column_width(8, 120);
-        self.record_table.insert_column("应用层协议");
-        self.record_table.set_headers_enabled(true);
+    #[nwg_control(parent: settings_tab, flags: "VISIBLE")]
+    #[nwg_layout_item(layout: settings_tab_layout,
+        min_size: size!{height: 30.0}, max_size: size!{height: 30.0},
+    )]
+    capture_protocol_row_frame: nwg::Frame,
 
-        // ----- stat tab -----
-        self.stat_trans_table.insert_column("协议");
-        self.stat_trans_table.insert_column("分组数量");
-        self.stat_trans_table.insert_column("字节数");
-        self.stat_trans_table.insert_column("网络层上传输的字节数");
-        self.stat_trans_table.set_column_width(3, 180);
-        self.stat_trans_table.set_headers_enabled(true);
+    #[nwg_control(parent: capture_protocol_row_frame)]
+    #[nwg_layout(parent: capture_protocol_row_frame,
+        align_items: AlignItems::Stretch,
+        flex_direction: FlexDirection::Row, padding: Default::default()
+    )]
+    capture_protocol_row: nwg::FlexboxLayout,
 
-        self.stat_app_table.insert_column("协议");
-        self.stat_app_table.insert_column("分组数量");
-        self.stat_app_table.insert_column("字节数");
-        self.stat_app_table.insert_column("网络层上传输的字节数");
-        self.stat_app_table.set_column_width(3, 180);
-        self.stat_app_table.insert_column("传输层上传输的字节数");
-        self.stat_app_table.set_column_width(4, 180);
-        self.stat_app_table.set_headers_enabled(true);
+    #[nwg_control(parent: capture_protocol_row_frame, text: "仅捕获协议（下次连接时生效）")]
+    #[nwg_layout_item(layout: capture_protocol_row, size: size!{200.0, auto})]
+    capture_protocol_label: nwg::Label,
 
-        // ----- about tab -----
-        self.about_info.set_font(Some(&self.about_font));
-    }
+    #[nwg_control(parent: capture_protocol_row_frame)]
+    #[nwg_layout_item(layout: capture_protocol_row, size: size!{300.0, auto})]
+    #[nwg_events(OnComboxBoxSelection: [Self::set_capture_protocol])]
+    capture_protocol: nwg::ComboBox<String>,
 
-    fn connect_interface(&self) {
-        if let Some(idx) = self.interfaces.selection() {
-            let addr = self.state.borrow()
-                .interfaces[idx].ip_addresses().iter()
-                .find(|&addr| addr.is_ipv4())
-                .map(|addr| addr.clone());
-            if let Some(interface_addr) = addr {
-                let address = SocketAddr::from((interface_addr.clone(), 8000));
-                let mut capturer = self.capturer.borrow_mut();
-                if let Err(err) = capturer.capture(address, true) {
-                    match err.raw_os_error() {
-                        Some(10013) => self.status_bar.set_text(0, "没有管理员权限，请以管理员权限重新运行程序"),
-                        _ => self.status_bar.set_text(0, format!("未知错误：{}", err).as_str())
-                    }
-                } else {
-                    self.reset_status_bar();
-                }
-            } else {
-                self.status_bar.set_text(0, "没有可用 ipv4 地址，请选择其他网卡");
-            }
-        }
-    }
+    #[nwg_control(parent: settings_tab, flags: "VISIBLE")]
+    #[nwg_layout_item(layout: settings_tab_layout,
+        min_size: size!{height: 30.0}, max_size: size!{height: 30.0},
+    )]
+    record_limit_row_frame: nwg::Frame,
 
-    fn tab_changed(&self) {
-        let mode: Mode = self.tabs_container.selected_tab().into();
-        let capturing = self.state.borrow().capturing;
-        
-        if capturing {
-            if mode == Mode::Plot {
-                self.plotting_sample_timer.start();
+    #[nwg_control(parent: record_limit_row_frame)]
+    #[nwg_layout(parent: record_limit_row_frame,
+        align_items: AlignItems::Stretch,
+        flex_direction: FlexDirection::Row, padding: Default::default()
+    )]
+    record_limit_row: nwg::FlexboxLayout,
+
+    #[nwg_control(parent: record_limit_row_frame, text: "最多保留记录（条 / MB，0 为不限）")]
+    #[nwg_layout_item(layout: record_limit_row, size: size!{200.0, auto})]
+    record_limit_label: nwg::Label,
+
+    // evicting is for good, so the limits apply on enter or the button rather than on every keystroke
+    #[nwg_control(parent: record_limit_row_frame)]
+    #[nwg_layout_item(layout: record_limit_row, size: size!{100.0, auto}, margin: rect!{end: 10.0})]
+    #[nwg_events(OnKeyEnter: [Self::set_record_limit])]
+    max_records: nwg::TextInput,
+
+    #[nwg_control(parent: record_limit_row_frame)]
+    #[nwg_layout_item(layout: record_limit_row, size: size!{100.0, auto}, margin: rect!{end: 10.0})]
+    #[nwg_events(OnKeyEnter: [Self::set_record_limit])]
+    max_records_mb: nwg::TextInput,
+
+    #[nwg_control(parent: record_limit_row_frame, text: "应用")]
+    #[nwg_layout_item(layout: record_limit_row, size: size!{60.0, auto})]
+    #[nwg_events(OnButtonClick: [Self::set_record_limit])]
+    record_limit_apply: nwg::Button,
+
+    #[nwg_control(parent: settings_tab, flags: "VISIBLE")]
+    #[nwg_layout_item(layout: settings_tab_layout,
+        min_size: size!{height: 30.0}, max_size: size!{height: 30.0},
+    )]
+    auto_export_row_frame: nwg::Frame,
+
+    #[nwg_control(parent: auto_export_row_frame)]
+    #[nwg_layout(parent: auto_export_row_frame,
+        align_items: AlignItems::Stretch,
+        flex_direction: FlexDirection::Row, padding: Default::default()
+    )]
+    auto_export_row: nwg::FlexboxLayout,
+
+    #[nwg_control(parent: auto_export_row_frame, text: "停止捕获后自动导出到")]
+    #[nwg_layout_item(layout: auto_export_row, size: size!{200.0, auto})]
+    auto_export_label: nwg::Label,
+
+    #[nwg_control(parent: auto_export_row_frame, placeholder_text: Some("留空则不自动导出"))]
+    #[nwg_layout_item(layout: auto_export_row, size: size!{300.0, auto}, margin: rect!{end: 10.0})]
+    #[nwg_events(OnKeyEnter: [Self::set_auto_export_dir])]
+    auto_export_dir: nwg::TextInput,
+
+    #[nwg_control(parent: auto_export_row_frame, text: "浏览…")]
+    #[nwg_layout_item(layout: auto_export_row, size: size!{60.0, auto})]
+    #[nwg_events(OnButtonClick: [Self::browse_auto_export_dir])]
+    auto_export_browse: nwg::Button,
+
+    #[nwg_control(parent: settings_tab, flags: "VISIBLE")]
+    #[nwg_layout_item(layout: settings_tab_layout,
+        min_size: size!{height: 30.0}, max_size: size!{height: 30.0},
+    )]
+    font_size_row_frame: nwg::Frame,
+
+    #[nwg_control(parent: font_size_row_frame)]
+    #[nwg_layout(parent: font_size_row_frame,
+        align_items: AlignItems::Stretch,
+        flex_direction: FlexDirection::Row, padding: Default::default()
+    )]
+    font_size_row: nwg::FlexboxLayout,
+
+    #[nwg_control(parent: font_size_row_frame, text: "界面字号")]
+    #[nwg_layout_item(layout: font_size_row, size: size!{200.0, auto})]
+    font_size_label: nwg::Label,
+
+    #[nwg_control(parent: font_size_row_frame)]
+    #[nwg_layout_item(layout: font_size_row, size: size!{100.0, auto})]
+    #[nwg_events(OnComboxBoxSelection: [Self::set_font_size])]
+    font_size: nwg::ComboBox<String>,
+
+    #[nwg_control(parent: settings_tab, flags: "VISIBLE")]
+    #[nwg_layout_item(layout: settings_tab_layout,
+        min_size: size!{height: 30.0}, max_size: size!{height: 30.0},
+    )]
+    rate_window_row_frame: nwg::Frame,
+
+    #[nwg_control(parent: rate_window_row_frame)]
+    #[nwg_layout(parent: rate_window_row_frame,
+        align_items: AlignItems::Stretch,
+        flex_direction: FlexDirection::Row, padding: Default::default()
+    )]
+    rate_window_row: nwg::FlexboxLayout,
+
+    #[nwg_control(parent: rate_window_row_frame, text: "平均速率窗口（秒）")]
+    #[nwg_layout_item(layout: rate_window_row, size: size!{200.0, auto})]
+    rate_window_label: nwg::Label,
+
+    #[nwg_control(parent: rate_window_row_frame)]
+    #[nwg_layout_item(layout: rate_window_row, size: size!{100.0, auto})]
+    #[nwg_events(OnKeyEnter: [Self::set_rate_window])]
+    rate_window: nwg::TextInput,
+
+    #[nwg_control(parent: settings_tab, flags: "VISIBLE")]
+    #[nwg_layout_item(layout: settings_tab_layout,
+        min_size: size!{height: 30.0}, max_size: size!{height: 30.0},
+    )]
+    smoothing_window_row_frame: nwg::Frame,
+
+    #[nwg_control(parent: smoothing_window_row_frame)]
+    #[nwg_layout(parent: smoothing_window_row_frame,
+        align_items: AlignItems::Stretch,
+        flex_direction: FlexDirection::Row, padding: Default::default()
+    )]
+    smoothing_window_row: nwg::FlexboxLayout,
+
+    #[nwg_control(parent: smoothing_window_row_frame, text: "移动平均窗口（采样点数）")]
+    #[nwg_layout_item(layout: smoothing_window_row, size: size!{200.0, auto})]
+    smoothing_window_label: nwg::Label,
+
+    #[nwg_control(parent: smoothing_window_row_frame)]
+    #[nwg_layout_item(layout: smoothing_window_row, size: size!{100.0, auto})]
+    #[nwg_events(OnKeyEnter: [Self::set_smoothing_window])]
+    smoothing_window: nwg::TextInput,
+
+    #[nwg_control(parent: settings_tab, flags: "VISIBLE")]
+    #[nwg_layout_item(layout: settings_tab_layout,
+        min_size: size!{height: 30.0}, max_size: size!{height: 30.0},
+    )]
+    plot_window_row_frame: nwg::Frame,
+
+    #[nwg_control(parent: plot_window_row_frame)]
+    #[nwg_layout(parent: plot_window_row_frame,
+        align_items: AlignItems::Stretch,
+        flex_direction: FlexDirection::Row, padding: Default::default()
+    )]
+    plot_window_row: nwg::FlexboxLayout,
+
+    #[nwg_control(parent: plot_window_row_frame, text: "捕获时图表显示最近（秒）")]
+    #[nwg_layout_item(layout: plot_window_row, size: size!{200.0, auto})]
+    plot_window_label: nwg::Label,
+
+    #[nwg_control(parent: plot_window_row_frame)]
+    #[nwg_layout_item(layout: plot_window_row, size: size!{100.0, auto})]
+    #[nwg_events(OnKeyEnter: [Self::set_plot_window])]
+    plot_window: nwg::TextInput,
+
+    #[nwg_control(parent: settings_tab, flags: "VISIBLE")]
+    #[nwg_layout_item(layout: settings_tab_layout,
+        min_size: size!{height: 30.0}, max_size: size!{height: 30.0},
+    )]
+    plot_redraw_row_frame: nwg::Frame,
+
+    #[nwg_control(parent: plot_redraw_row_frame)]
+    #[nwg_layout(parent: plot_redraw_row_frame,
+        align_items: AlignItems::Stretch,
+        flex_direction: FlexDirection::Row, padding: Default::default()
+    )]
+    plot_redraw_row: nwg::FlexboxLayout,
+
+    #[nwg_control(parent: plot_redraw_row_frame, text: "图表最短重绘间隔（毫秒）")]
+    #[nwg_layout_item(layout: plot_redraw_row, size: size!{200.0, auto})]
+    plot_redraw_label: nwg::Label,
+
+    #[nwg_control(parent: plot_redraw_row_frame)]
+    #[nwg_layout_item(layout: plot_redraw_row, size: size!{100.0, auto})]
+    #[nwg_events(OnKeyEnter: [Self::set_plot_redraw_interval])]
+    plot_redraw_interval: nwg::TextInput,
+
+    #[nwg_control(parent: settings_tab, flags: "VISIBLE")]
+    #[nwg_layout_item(layout: settings_tab_layout,
+        min_size: size!{height: 30.0}, max_size: size!{height: 30.0},
+    )]
+    stat_refresh_row_frame: nwg::Frame,
+
+    #[nwg_control(parent: stat_refresh_row_frame)]
+    #[nwg_layout(parent: stat_refresh_row_frame,
+        align_items: AlignItems::Stretch,
+        flex_direction: FlexDirection::Row, padding: Default::default()
+    )]
+    stat_refresh_row: nwg::FlexboxLayout,
+
+    #[nwg_control(parent: stat_refresh_row_frame, text: "统计结果刷新间隔")]
+    #[nwg_layout_item(layout: stat_refresh_row, size: size!{200.0, auto})]
+    stat_refresh_label: nwg::Label,
+
+    #[nwg_control(parent: stat_refresh_row_frame)]
+    #[nwg_layout_item(layout: stat_refresh_row, size: size!{100.0, auto})]
+    #[nwg_events(OnComboxBoxSelection: [Self::set_stat_refresh])]
+    stat_refresh: nwg::ComboBox<String>,
+
+    // ----- about tab -----
+    #[nwg_control(parent: tabs_container, text: "关于")]
+    about_tab: nwg::Tab,
+
+    #[nwg_resource(family: "Segoe UI", size: 30)]
+    about_font: nwg::Font,
+
+    #[nwg_control(parent: about_tab)]
+    #[nwg_layout(parent: about_tab,
+        align_items: AlignItems::Center,
+        justify_content: JustifyContent::Center,
+        flex_direction: FlexDirection::Row, 
+    )]
+    about_tab_layout: nwg::FlexboxLayout,
+
+    #[nwg_resource(
+        source_embed: Some(&data.embed_resource),
+        source_embed_str: Some("LOGO"),
+        size: Some((128, 128))
+    )]
+    app_logo: nwg::Icon,
+
+    #[nwg_control(parent: about_tab, size: (128, 128),
+        background_color: Some([0xff, 0xff, 0xff]),
+        icon: Some(&data.app_logo),
+    )]
+    #[nwg_layout_item(layout: about_tab_layout, size: size!{128.0, 128.0})]
+    about_logo: nwg::ImageFrame,
+
+    #[nwg_control(parent: about_tab, flags: "VISIBLE")]
+    #[nwg_layout_item(layout: about_tab_layout, size: size!{460.0, 390.0})]
+    about_column_frame: nwg::Frame,
+
+    #[nwg_control(parent: about_column_frame)]
+    #[nwg_layout(parent: about_column_frame,
+        flex_direction: FlexDirection::Column, padding: Default::default()
+    )]
+    about_column: nwg::FlexboxLayout,
+
+    #[nwg_control(parent: about_column_frame,
+        background_color: Some([0xff, 0xff, 0xff]),
+        text: format!(
+r"{} {}
+by {}",
+        meta::NAME, meta::VERSION, meta::AUTHORS).as_str(),
+    )]
+    #[nwg_layout_item(layout: about_column, size: size!{auto, 90.0})]
+    about_info: nwg::Label,
+
+    #[nwg_control(parent: about_column_frame,
+        background_color: Some([0xff, 0xff, 0xff]),
+        text: format!(
+r"许可证：{}
+构建：{}（{}）
+依赖：{}",
+        meta::LICENSE, meta::BUILD_DATE, meta::GIT_HASH, meta::DEPENDENCIES).as_str(),
+    )]
+    #[nwg_layout_item(layout: about_column, size: size!{auto, 120.0})]
+    about_details: nwg::Label,
+
+    #[nwg_control(parent: about_column_frame,
+        background_color: Some([0xff, 0xff, 0xff]),
+        text: format!("项目主页：{}", meta::REPOSITORY).as_str(),
+    )]
+    #[nwg_layout_item(layout: about_column, size: size!{auto, 30.0})]
+    #[nwg_events(OnLabelClick: [Self::open_repository])]
+    about_repository: nwg::Label,
+
+    #[nwg_control(parent: about_column_frame,
+        background_color: Some([0xff, 0xff, 0xff]),
+        text: format!("问题反馈：{}", meta::ISSUES).as_str(),
+    )]
+    #[nwg_layout_item(layout: about_column, size: size!{auto, 30.0})]
+    #[nwg_events(OnLabelClick: [Self::open_issues])]
+    about_issues: nwg::Label,
+
+    // counters of the capture path, filled in whenever the tab is shown
+    #[nwg_control(parent: about_column_frame,
+        background_color: Some([0xff, 0xff, 0xff]),
+    )]
+    #[nwg_layout_item(layout: about_column, size: size!{auto, 60.0})]
+    about_capture_metrics: nwg::Label,
+
+    #[nwg_control(parent: about_column_frame, flags: "VISIBLE")]
+    #[nwg_layout_item(layout: about_column, size: size!{auto, 30.0}, margin: rect!{top: 10.0})]
+    about_buttons_frame: nwg::Frame,
+
+    #[nwg_control(parent: about_buttons_frame)]
+    #[nwg_layout(parent: about_buttons_frame,
+        align_items: AlignItems::Stretch,
+        flex_direction: FlexDirection::Row, padding: Default::default()
+    )]
+    about_buttons: nwg::FlexboxLayout,
+
+    #[nwg_control(parent: about_buttons_frame, text: "复制诊断信息")]
+    #[nwg_layout_item(layout: about_buttons, size: size!{120.0, auto}, margin: rect!{end: 10.0})]
+    #[nwg_events(OnButtonClick: [Self::copy_diagnostics])]
+    about_diagnostics: nwg::Button,
+
+    #[nwg_control(parent: about_buttons_frame, text: "日志…")]
+    #[nwg_layout_item(layout: about_buttons, size: size!{80.0, auto})]
+    #[nwg_events(OnButtonClick: [Self::show_log_window])]
+    about_log: nwg::Button,
+
+    // ----- status bar -----
+    #[nwg_control(parent: window, text: "准备就绪")]
+    #[nwg_layout_item(layout: main_column, 
+        margin: rect!{top: 10.0},
+        min_size: size!{height: 30.0}
+    )]
+    status_bar: nwg::StatusBar,
+
+    // ----- column chooser -----
+    #[nwg_control(title: "选择显示的列", size: (320, 380), flags: "WINDOW", center: true,
+        icon: Some(&data.window_icon)
+    )]
+    #[nwg_events(OnWindowClose: [Self::close_column_chooser(SELF, EVT_DATA)])]
+    column_window: nwg::Window,
+
+    #[nwg_control(parent: column_window)]
+    #[nwg_layout(parent: column_window,
+        flex_direction: FlexDirection::Column, 
+    )]
+    column_window_layout: nwg::FlexboxLayout,
+
+    #[nwg_control(parent: column_window)]
+    #[nwg_layout_item(layout: column_window_layout, flex_grow: 1.0)]
+    #[nwg_events(OnListBoxDoubleClick: [Self::toggle_column])]
+    column_list: nwg::ListBox<String>,
+
+    #[nwg_control(parent: column_window, flags: "VISIBLE")]
+    #[nwg_layout_item(layout: column_window_layout,
+        min_size: size!{height: 30.0}, max_size: size!{height: 30.0},
+    )]
+    column_buttons_frame: nwg::Frame,
+
+    #[nwg_control(parent: column_buttons_frame)]
+    #[nwg_layout(parent: column_buttons_frame,
+        align_items: AlignItems::Stretch,
+        flex_direction: FlexDirection::Row, padding: Default::default()
+    )]
+    column_buttons: nwg::FlexboxLayout,
+
+    #[nwg_control(parent: column_buttons_frame, text: "显示/隐藏")]
+    #[nwg_layout_item(layout: column_buttons, size: size!{80.0, auto})]
+    #[nwg_events(OnButtonClick: [Self::toggle_column])]
+    column_toggle: nwg::Button,
+
+    #[nwg_control(parent: column_buttons_frame, text: "上移")]
+    #[nwg_layout_item(layout: column_buttons, size: size!{60.0, auto})]
+    #[nwg_events(OnButtonClick: [Self::move_column_up])]
+    column_up: nwg::Button,
+
+    #[nwg_control(parent: column_buttons_frame, text: "下移")]
+    #[nwg_layout_item(layout: column_buttons, size: size!{60.0, auto})]
+    #[nwg_events(OnButtonClick: [Self::move_column_down])]
+    column_down: nwg::Button,
+
+    #[nwg_control(parent: column_buttons_frame, text: "确定")]
+    #[nwg_layout_item(layout: column_buttons, size: size!{60.0, auto})]
+    #[nwg_events(OnButtonClick: [Self::apply_columns])]
+    column_apply: nwg::Button,
+
+    // ----- log viewer -----
+    #[nwg_control(title: "日志", size: (640, 400), flags: "WINDOW|RESIZABLE", center: true,
+        icon: Some(&data.window_icon)
+    )]
+    #[nwg_events(OnWindowClose: [Self::close_log_window(SELF, EVT_DATA)])]
+    log_window: nwg::Window,
+
+    #[nwg_control(parent: log_window)]
+    #[nwg_layout(parent: log_window,
+        flex_direction: FlexDirection::Column, 
+    )]
+    log_window_layout: nwg::FlexboxLayout,
+
+    #[nwg_control(parent: log_window, readonly: true, flags: "VISIBLE|VSCROLL|AUTOVSCROLL")]
+    #[nwg_layout_item(layout: log_window_layout, flex_grow: 1.0)]
+    log_text: nwg::TextBox,
+
+    #[nwg_control(parent: log_window, flags: "VISIBLE")]
+    #[nwg_layout_item(layout: log_window_layout,
+        min_size: size!{height: 30.0}, max_size: size!{height: 30.0},
+    )]
+    log_buttons_frame: nwg::Frame,
+
+    #[nwg_control(parent: log_buttons_frame)]
+    #[nwg_layout(parent: log_buttons_frame,
+        align_items: AlignItems::Stretch,
+        flex_direction: FlexDirection::Row, padding: Default::default()
+    )]
+    log_buttons: nwg::FlexboxLayout,
+
+    #[nwg_control(parent: log_buttons_frame, text: "复制")]
+    #[nwg_layout_item(layout: log_buttons, size: size!{60.0, auto})]
+    #[nwg_events(OnButtonClick: [Self::copy_log])]
+    log_copy: nwg::Button,
+
+    #[nwg_control(parent: log_buttons_frame, text: "清空")]
+    #[nwg_layout_item(layout: log_buttons, size: size!{60.0, auto})]
+    #[nwg_events(OnButtonClick: [Self::clear_log])]
+    log_clear: nwg::Button,
+
+    // ----- interface chooser -----
+    #[nwg_control(title: "选择要捕获的网卡", size: (420, 300), flags: "WINDOW", center: true,
+        icon: Some(&data.window_icon)
+    )]
+    #[nwg_events(OnWindowClose: [Self::close_interface_chooser(SELF, EVT_DATA)])]
+    interface_window: nwg::Window,
+
+    #[nwg_control(parent: interface_window)]
+    #[nwg_layout(parent: interface_window,
+        flex_direction: FlexDirection::Column, 
+    )]
+    interface_window_layout: nwg::FlexboxLayout,
+
+    #[nwg_control(parent: interface_window, flags: "VISIBLE|MULTI_SELECT")]
+    #[nwg_layout_item(layout: interface_window_layout, flex_grow: 1.0)]
+    interface_list: nwg::ListBox<String>,
+
+    #[nwg_control(parent: interface_window, text: "连接")]
+    #[nwg_layout_item(layout: interface_window_layout,
+        min_size: size!{height: 30.0}, max_size: size!{height: 30.0},
+    )]
+    #[nwg_events(OnButtonClick: [Self::connect_chosen_interfaces])]
+    interface_connect: nwg::Button,
+
+    // ----- schedule dialog -----
+    #[nwg_control(title: "定时捕获", size: (360, 260), flags: "WINDOW", center: true,
+        icon: Some(&data.window_icon)
+    )]
+    #[nwg_events(OnWindowClose: [Self::close_schedule_dialog(SELF, EVT_DATA)])]
+    schedule_window: nwg::Window,
+
+    #[nwg_control(parent: schedule_window)]
+    #[nwg_layout(parent: schedule_window,
+        flex_direction: FlexDirection::Column, 
+    )]
+    schedule_window_layout: nwg::FlexboxLayout,
+
+    #[nwg_control(parent: schedule_window, placeholder_text: Some("每次捕获时长（秒）"))]
+    #[nwg_layout_item(layout: schedule_window_layout,
+        min_size: size!{height: 30.0}, max_size: size!{height: 30.0},
+    )]
+    schedule_duration: nwg::TextInput,
+
+    #[nwg_control(parent: schedule_window, placeholder_text: Some("间隔（分钟）"))]
+    #[nwg_layout_item(layout: schedule_window_layout,
+        min_size: size!{height: 30.0}, max_size: size!{height: 30.0},
+    )]
+    schedule_interval: nwg::TextInput,
+
+    #[nwg_control(parent: schedule_window, placeholder_text: Some("重复次数"))]
+    #[nwg_layout_item(layout: schedule_window_layout,
+        min_size: size!{height: 30.0}, max_size: size!{height: 30.0},
+    )]
+    schedule_runs: nwg::TextInput,
+
+    #[nwg_control(parent: schedule_window, flags: "VISIBLE")]
+    #[nwg_layout_item(layout: schedule_window_layout,
+        min_size: size!{height: 30.0}, max_size: size!{height: 30.0},
+    )]
+    schedule_dir_frame: nwg::Frame,
+
+    #[nwg_control(parent: schedule_dir_frame)]
+    #[nwg_layout(parent: schedule_dir_frame,
+        align_items: AlignItems::Stretch,
+        flex_direction: FlexDirection::Row, padding: Default::default()
+    )]
+    schedule_dir_row: nwg::FlexboxLayout,
+
+    #[nwg_control(parent: schedule_dir_frame, placeholder_text: Some("导出目录"))]
+    #[nwg_layout_item(layout: schedule_dir_row, flex_grow: 1.0, margin: rect!{end: 10.0})]
+    schedule_dir: nwg::TextInput,
+
+    #[nwg_control(parent: schedule_dir_frame, text: "浏览…")]
+    #[nwg_layout_item(layout: schedule_dir_row, size: size!{60.0, auto})]
+    #[nwg_events(OnButtonClick: [Self::browse_schedule_dir])]
+    schedule_browse: nwg::Button,
+
+    #[nwg_resource(title: "选择导出目录", action: nwg::FileDialogAction::OpenDirectory)]
+    schedule_dir_dialog: nwg::FileDialog,
+
+    #[nwg_control(parent: schedule_window, text: "开始")]
+    #[nwg_layout_item(layout: schedule_window_layout,
+        min_size: size!{height: 30.0}, max_size: size!{height: 30.0},
+    )]
+    #[nwg_events(OnButtonClick: [Self::start_schedule])]
+    schedule_start: nwg::Button,
+
+    // ----- profile dialog -----
+    #[nwg_control(title: "另存为配置", size: (320, 120), flags: "WINDOW", center: true,
+        icon: Some(&data.window_icon)
+    )]
+    #[nwg_events(OnWindowClose: [Self::close_profile_dialog(SELF, EVT_DATA)])]
+    profile_window: nwg::Window,
+
+    #[nwg_control(parent: profile_window)]
+    #[nwg_layout(parent: profile_window,
+        flex_direction: FlexDirection::Column, 
+    )]
+    profile_window_layout: nwg::FlexboxLayout,
+
+    #[nwg_control(parent: profile_window, placeholder_text: Some("配置名称"))]
+    #[nwg_layout_item(layout: profile_window_layout,
+        min_size: size!{height: 30.0}, max_size: size!{height: 30.0},
+    )]
+    profile_name: nwg::TextInput,
+
+    #[nwg_control(parent: profile_window, text: "保存")]
+    #[nwg_layout_item(layout: profile_window_layout,
+        min_size: size!{height: 30.0}, max_size: size!{height: 30.0},
+    )]
+    #[nwg_events(OnButtonClick: [Self::save_profile])]
+    profile_save: nwg::Button,
+
+    #[nwg_resource(title: "导出记录", action: nwg::FileDialogAction::Save, filters: "CSV(*.csv)")]
+    export_dialog: nwg::FileDialog,
+}
+
+impl App {
+    fn new(debug: bool) -> Result<Self> {
+        let mut state = State::default();
+        state.capturing = false;
+        state.interfaces = usable_interfaces()?;
+
+        let settings = Settings::load();
+        state.plot_series = settings.plot_series;
+        state.plot_theme = PlotTheme::new(settings.plot_dark);
+        state.records = RecordStore::new(settings.max_records, settings.max_records_mb.saturating_mul(1024 * 1024));
+
+        let mut log = Log::default();
+        if debug {
+            if let Err(err) = log.mirror_to_file() {
+                log.push(Level::Error, format!("无法打开日志文件：{}", err));
+            }
+        }
+
+        Ok(Self {
+            state: RefCell::new(state),
+            settings: RefCell::new(settings),
+            log: RefCell::new(log),
+            ..Default::default()
+        })
+    }
+
+    fn reset_status_bar(&self) {
+        let state = self.state.borrow();
+        let window_secs = self.settings.borrow().rate_window_secs;
+        let rate = state.throughput.map(|(packets, bytes)| {
+            let window_rate = state.window_throughput.map_or(String::new(), |(_, bytes)| {
+                format!("（近{}秒平均 {}）", window_secs, self.display_byte_rate(bytes))
+            });
+            format!("{:.0} 包/秒，{}{}", packets, self.display_byte_rate(bytes), window_rate)
+        });
+        if state.capturing && state.display_paused.is_some() {
+            self.status_bar.set_text(0, "已暂停显示（仍在捕获）");
+            return;
+        }
+        match (state.capturing, rate) {
+            (true, Some(rate)) => self.status_bar.set_text(0, format!("正在捕获... {}", rate).as_str()),
+            (true, None) => self.status_bar.set_text(0, "正在捕获..."),
+            (false, Some(rate)) => self.status_bar.set_text(0, format!("准备就绪（最终速率：{}）", rate).as_str()),
+            (false, None) => self.status_bar.set_text(0, "准备就绪"),
+        }
+    }
+
+    fn init(&self) {
+        let state = self.state.borrow();
+        self.interfaces.set_collection(state.interfaces.iter().map(|interface| interface.to_string()).collect());
+
+        self.tabs_container.set_selected_tab(state.mode as usize);
+        self.layout_status_bar();
+
+        self.tooltip.register(&self.capture, "开始/停止捕获（空格 或 Ctrl+R）");
+        self.tooltip.register(&self.filter, "筛选器（Ctrl+L，Esc 清空）");
+        self.tooltip.register(&self.filter_pin, "当前页只使用自己的筛选器，不再跟随其他页");
+        self.tooltip.register(&self.record_search, "搜索记录（Ctrl+F）");
+        self.tooltip.register(&self.tabs_container, "切换标签页（Ctrl+1 到 Ctrl+4），导出记录（Ctrl+E）");
+
+        // ----- record tab -----
+        self.build_record_columns();
+
+        // ----- stat tab -----
+        self.stat_layout_timer.start();
+        for title in ["协议", "分组数量", "字节数", "网络层上传输的字节数", "接收字节数", "发送字节数"] {
+            self.stat_trans_table.insert_column(title);
+        }
+        self.stat_trans_table.set_headers_enabled(true);
+        for title in ["协议", "分组数量", "字节数", "网络层上传输的字节数", "传输层上传输的字节数", "接收字节数", "发送字节数"] {
+            self.stat_app_table.insert_column(title);
+        }
+        self.stat_app_table.set_headers_enabled(true);
+        self.stat_dns_table.insert_column("查询最多的域名");
+        self.stat_dns_table.insert_column("查询次数");
+        self.stat_dns_table.set_headers_enabled(true);
+        self.size_stat_columns();
+
+        // ----- settings tab -----
+        let settings = self.settings.borrow();
+        self.humanize_bytes.set_check_state(check_state(settings.humanize_bytes));
+        self.auto_scroll.set_check_state(check_state(settings.auto_scroll));
+        self.append_records.set_check_state(check_state(!settings.clear_on_start));
+        self.auto_connect.set_check_state(check_state(settings.auto_connect));
+        self.resolve_hosts.set_check_state(check_state(settings.resolve_hosts));
+        self.poll_capture.set_check_state(check_state(settings.poll_capture));
+        self.max_records.set_text(settings.max_records.to_string().as_str());
+        self.recv_mode.set_collection(RecvMode::ALL.iter().map(|&mode| recv_mode_label(mode).to_string()).collect());
+        self.recv_mode.set_selection(RecvMode::ALL.iter().position(|&mode| mode == settings.recv_mode));
+        // every protocol comes first, then the ones a capture can be restricted to
+        self.capture_protocol.set_collection(iter::once("全部协议".to_string())
+            .chain(CaptureProtocol::ALL.iter().map(|protocol| protocol.name().to_string()))
+            .collect());
+        self.capture_protocol.set_selection(Some(settings.capture_protocol
+            .and_then(|protocol| CaptureProtocol::ALL.iter().position(|&p| p == protocol))
+            .map_or(0, |idx| idx + 1)));
+        self.font_size.set_collection(FontSize::ALL.iter().map(|size| size.label().to_string()).collect());
+        self.font_size.set_selection(FontSize::ALL.iter().position(|&size| size == settings.font_size));
+        self.max_records_mb.set_text(settings.max_records_mb.to_string().as_str());
+        self.rate_window.set_text(settings.rate_window_secs.to_string().as_str());
+        self.smoothing_window.set_text(settings.smoothing_window.to_string().as_str());
+        self.plot_smoothing.set_check_state(check_state(settings.plot_smoothing));
+        self.plot_stacked.set_check_state(check_state(settings.plot_stacked));
+        self.plot_rolling.set_check_state(check_state(settings.plot_rolling));
+        self.plot_split.set_check_state(check_state(settings.plot_split));
+        self.plot_dark.set_check_state(check_state(settings.plot_dark));
+        self.series_packets.set_check_state(check_state(settings.plot_series.packets));
+        self.series_bytes.set_check_state(check_state(settings.plot_series.bytes));
+        for (checkbox, &shown) in self.protocol_series_checkboxes().iter().zip(settings.plot_series.protocols.iter()) {
+            checkbox.set_check_state(check_state(shown));
+        }
+        self.plot_rolling.set_text(rolling_text(settings.plot_window_secs).as_str());
+        self.plot_window.set_text(settings.plot_window_secs.to_string().as_str());
+        self.plot_redraw_interval.set_text(settings.plot_redraw_interval_ms.to_string().as_str());
+        self.stat_refresh.set_collection(StatRefresh::ALL.iter().map(|refresh| refresh.label().to_string()).collect());
+        self.stat_refresh.set_selection(StatRefresh::ALL.iter().position(|&refresh| refresh == settings.stat_refresh));
+        self.apply_stat_refresh(settings.stat_refresh);
+        if let Some(dir) = &settings.auto_export_dir {
+            self.auto_export_dir.set_text(&dir.to_string_lossy());
+        }
+        self.plot_records.borrow_mut().by_protocol = settings.plot_stacked;
+
+        // ----- about tab -----
+        self.about_info.set_font(Some(&self.about_font));
+        self.set_link_font(settings.font_size);
+
+        let auto_connect = settings.auto_connect;
+        let resolve_hosts = settings.resolve_hosts;
+        drop(settings);
+        drop(state);
+        self.display_profiles(None);
+        self.reconnect_button.set_visible(false);
+        self.cancel_rebuild_button.set_visible(false);
+        watch_addresses(self.address_notice.sender());
+        if let Err(err) = watch_dpi(&self.window, self.dpi_notice.sender(), self.pending_scale.clone()) {
+            self.log(Level::Warn, format!("无法跟随显示器缩放：{}", err));
+        }
+        // the typed settings apply on enter or leaving them, not halfway through typing
+        let fields = [
+            (&self.rate_window_row_frame, &self.rate_window_notice),
+            (&self.smoothing_window_row_frame, &self.smoothing_window_notice),
+            (&self.plot_window_row_frame, &self.plot_window_notice),
+            (&self.plot_redraw_row_frame, &self.plot_redraw_interval_notice),
+            (&self.auto_export_row_frame, &self.auto_export_dir_notice),
+        ];
+        for (frame, notice) in fields {
+            if let Err(err) = watch_focus_out(&frame.handle, notice.sender()) {
+                self.log(Level::Warn, format!("无法在离开输入框时应用设置：{}", err));
+            }
+        }
+        if resolve_hosts {
+            self.apply_resolve_hosts(true);
+        }
+        if auto_connect {
+            self.connect_last_interfaces();
+        }
+    }
+
+    /// connect to the interfaces used last time, or the first ipv4 address of an adapter whose
+    /// address went away
+    fn connect_last_interfaces(&self) {
+        let last_adapters = self.settings.borrow().last_adapters.clone();
+        if last_adapters.is_empty() {
+            return;
+        }
+        if self.connect_adapters(&last_adapters) == Some(last_adapters.len()) {
+            self.set_status(Level::Warn, "上次使用的网卡不存在或未启用，请重新选择网卡");
+        }
+    }
+
+    /// connect to the saved adapters that are up, returning how many of them are not; `None`
+    /// when the app ends for an elevated instance
+    fn connect_adapters(&self, adapters: &[SavedAdapter]) -> Option<usize> {
+        let mut interfaces = Vec::<Interface>::new();
+        let mut missing = 0;
+        {
+            let state = self.state.borrow();
+            let candidates = state.interfaces.iter()
+                .map(|interface| (interface.adapter_name.as_str(), interface.address))
+                .collect::<Vec<_>>();
+            for adapter in adapters {
+                match adapter.find(&candidates) {
+                    // two addresses whose adapter lost both fall back to the same one
+                    Some(idx) => if !interfaces.contains(&state.interfaces[idx]) {
+                        interfaces.push(state.interfaces[idx].clone());
+                    },
+                    None => missing += 1,
+                }
+            }
+        }
+        if interfaces.is_empty() {
+            return Some(missing);
+        }
+        if let [interface] = interfaces.as_slice() {
+            let idx = self.state.borrow().interfaces.iter().position(|i| i == interface);
+            self.interfaces.set_selection(idx);
+        }
+        self.connect_interfaces(interfaces)?;
+        Some(missing)
+    }
+
+    /// byte count for display, according to the unit setting
+    fn display_bytes(&self, bytes: u64) -> String {
+        if self.settings.borrow().humanize_bytes {
+            format_bytes(bytes)
+        } else {
+            bytes.to_string()
+        }
+    }
+
+    /// like `display_bytes`, but always carries a unit
+    fn display_bytes_text(&self, bytes: u64) -> String {
+        if self.settings.borrow().humanize_bytes {
+            format_bytes(bytes)
+        } else {
+            format!("{} 字节", bytes)
+        }
+    }
+
+    fn display_byte_rate(&self, bytes_per_sec: f64) -> String {
+        if self.settings.borrow().humanize_bytes {
+            format_byte_rate(bytes_per_sec)
+        } else {
+            format!("{:.0} 字节/秒", bytes_per_sec)
+        }
+    }
+
+    /// show `text` in the status bar and keep it in the log, the next message would otherwise
+    /// overwrite it for good
+    fn set_status(&self, level: Level, text: &str) {
+        self.status_bar.set_text(0, text);
+        self.log(level, text);
+    }
+
+    fn log(&self, level: Level, message: impl Into<String>) {
+        self.log.borrow_mut().push(level, message);
+        if self.log_window.visible() {
+            self.refresh_log_text();
+        }
+    }
+
+    fn save_settings(&self) {
+        if let Err(err) = self.settings.borrow().save() {
+            self.set_status(Level::Error, format!("无法保存设置：{}", err).as_str());
+        }
+    }
+
+    fn set_font_size(&self) {
+        let size = match self.font_size.selection() {
+            Some(idx) => FontSize::ALL[idx],
+            None => return,
+        };
+        self.settings.borrow_mut().font_size = size;
+        self.save_settings();
+        self.apply_font(size);
+    }
+
+    /// build the ui font at `size` for the current scaling and hand it to every control
+    fn apply_font(&self, size: FontSize) {
+        match ui_font(size) {
+            Ok(font) => {
+                // controls keep the font they were created with, so hand the new one to each of them
+                for window in [&self.window, &self.column_window, &self.interface_window, &self.schedule_window, &self.log_window, &self.profile_window] {
+                    if let Some(hwnd) = window.handle.hwnd() {
+                        unsafe {
+                            EnumChildWindows(hwnd, Some(set_child_font), font.handle as LPARAM);
+                        }
+                    }
+                }
+                self.about_info.set_font(Some(&self.about_font));
+                self.set_link_font(size);
+                // nothing uses the previous default anymore, so it can go
+                nwg::Font::set_global_default(Some(font));
+            }
+            Err(err) => self.set_status(Level::Error, format!("无法创建字体：{}", err).as_str()),
+        }
+    }
+
+    fn set_link_font(&self, size: FontSize) {
+        match link_font(size) {
+            Ok(font) => {
+                for label in [&self.about_repository, &self.about_issues] {
+                    label.set_font(Some(&font));
+                }
+                // the labels hold the new one now, the old one is dropped here
+                *self.link_font.borrow_mut() = font;
+            }
+            Err(err) => self.log(Level::Error, format!("无法创建字体：{}", err)),
+        }
+    }
+
+    fn set_stat_refresh(&self) {
+        let refresh = match self.stat_refresh.selection() {
+            Some(idx) => StatRefresh::ALL[idx],
+            None => return,
+        };
+        self.settings.borrow_mut().stat_refresh = refresh;
+        self.save_settings();
+        self.apply_stat_refresh(refresh);
+    }
+
+    /// reconfigure the refresh timer, also in the middle of a capture
+    fn apply_stat_refresh(&self, refresh: StatRefresh) {
+        self.stat_refresh_button.set_visible(refresh == StatRefresh::Manual);
+        match refresh.millis() {
+            Some(ms) => {
+                self.stat_refresh_timer.set_interval(StdDuration::from_millis(ms));
+                if self.state.borrow().capturing {
+                    self.stat_refresh_timer.start();
+                }
+            }
+            None => self.stat_refresh_timer.stop(),
+        }
+    }
+
+    fn set_record_limit(&self) {
+        let parse = |input: &nwg::TextInput| input.text().trim().parse::<usize>();
+        let (max_records, max_records_mb) = match (parse(&self.max_records), parse(&self.max_records_mb)) {
+            (Ok(max_records), Ok(max_records_mb)) => (max_records, max_records_mb),
+            _ => {
+                self.status_bar.set_text(0, "记录上限需为非负整数");
+                return;
+            }
+        };
+        {
+            let mut settings = self.settings.borrow_mut();
+            settings.max_records = max_records;
+            settings.max_records_mb = max_records_mb;
+        }
+        self.save_settings();
+        let evicted = self.state.borrow_mut().records.set_limits(max_records, max_records_mb.saturating_mul(1024 * 1024));
+        if !evicted.is_empty() {
+            self.rebuild_record_table();
+            self.display_capture_info();
+        }
+        self.set_status(Level::Info, format!("记录上限已应用，移除了 {} 条记录", evicted.len()).as_str());
+    }
+
+    fn set_rate_window(&self) {
+        match self.rate_window.text().trim().parse::<u32>() {
+            Ok(secs) if (1..=3600).contains(&secs) => {
+                self.settings.borrow_mut().rate_window_secs = secs;
+                self.save_settings();
+            }
+            _ => self.status_bar.set_text(0, "平均速率窗口需为 1 到 3600 之间的整数"),
+        }
+    }
+
+    fn set_smoothing_window(&self) {
+        match self.smoothing_window.text().trim().parse::<usize>() {
+            Ok(n) if (1..=1000).contains(&n) => {
+                self.settings.borrow_mut().smoothing_window = n;
+                self.save_settings();
+                self.request_plot_redraw();
+            }
+            _ => self.status_bar.set_text(0, "移动平均窗口需为 1 到 1000 之间的整数"),
+        }
+    }
+
+    fn toggle_plot_smoothing(&self) {
+        self.settings.borrow_mut().plot_smoothing =
+            self.plot_smoothing.check_state() == nwg::CheckBoxState::Checked;
+        self.save_settings();
+        self.request_plot_redraw();
+    }
+
+    fn set_plot_window(&self) {
+        match self.plot_window.text().trim().parse::<u32>() {
+            Ok(secs) if (1..=3600).contains(&secs) => {
+                self.settings.borrow_mut().plot_window_secs = secs;
+                self.plot_rolling.set_text(rolling_text(secs).as_str());
+                self.save_settings();
+                self.request_plot_redraw();
+            }
+            _ => self.status_bar.set_text(0, "显示时长需为 1 到 3600 之间的整数"),
+        }
+    }
+
+    fn set_plot_redraw_interval(&self) {
+        match self.plot_redraw_interval.text().trim().parse::<u64>() {
+            Ok(ms) if (1..=5000).contains(&ms) => {
+                self.settings.borrow_mut().plot_redraw_interval_ms = ms;
+                self.save_settings();
+            }
+            _ => self.status_bar.set_text(0, "重绘间隔需为 1 到 5000 之间的整数"),
+        }
+    }
+
+    fn toggle_plot_rolling(&self) {
+        self.settings.borrow_mut().plot_rolling =
+            self.plot_rolling.check_state() == nwg::CheckBoxState::Checked;
+        self.save_settings();
+        self.request_plot_redraw();
+    }
+
+    /// checkboxes of the stacked layers, indexed by `PlotProtocol`
+    fn protocol_series_checkboxes(&self) -> [&nwg::CheckBox; 4] {
+        [&self.series_tcp, &self.series_udp, &self.series_icmp, &self.series_other]
+    }
+
+    fn toggle_plot_series(&self) {
+        let checked = |checkbox: &nwg::CheckBox| checkbox.check_state() == nwg::CheckBoxState::Checked;
+        let mut series = PlotSeries {
+            packets: checked(&self.series_packets),
+            bytes: checked(&self.series_bytes),
+            ..Default::default()
+        };
+        for (shown, checkbox) in series.protocols.iter_mut().zip(self.protocol_series_checkboxes().iter()) {
+            *shown = checked(checkbox);
+        }
+        self.state.borrow_mut().plot_series = series;
+        self.settings.borrow_mut().plot_series = series;
+        self.save_settings();
+        self.request_plot_redraw();
+    }
+
+    fn toggle_auto_connect(&self) {
+        self.settings.borrow_mut().auto_connect =
+            self.auto_connect.check_state() == nwg::CheckBoxState::Checked;
+        self.save_settings();
+    }
+
+    fn toggle_poll_capture(&self) {
+        self.settings.borrow_mut().poll_capture =
+            self.poll_capture.check_state() == nwg::CheckBoxState::Checked;
+        self.save_settings();
+    }
+
+    fn set_recv_mode(&self) {
+        if let Some(idx) = self.recv_mode.selection() {
+            self.settings.borrow_mut().recv_mode = RecvMode::ALL[idx];
+            self.save_settings();
+        }
+    }
+
+    fn set_capture_protocol(&self) {
+        if let Some(idx) = self.capture_protocol.selection() {
+            self.settings.borrow_mut().capture_protocol = idx.checked_sub(1).map(|idx| CaptureProtocol::ALL[idx]);
+            self.save_settings();
+        }
+    }
+
+    fn toggle_resolve_hosts(&self) {
+        let on = self.resolve_hosts.check_state() == nwg::CheckBoxState::Checked;
+        self.settings.borrow_mut().resolve_hosts = on;
+        self.save_settings();
+        self.apply_resolve_hosts(on);
+    }
+
+    /// start or stop looking up host names, the rows in view follow right away
+    fn apply_resolve_hosts(&self, on: bool) {
+        let resolver = if on {
+            match Resolver::system() {
+                Ok(resolver) => Some(resolver),
+                Err(err) => {
+                    self.log(Level::Error, format!("无法解析主机名：{:#}", err));
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        if resolver.is_some() {
+            self.resolve_timer.start();
+        } else {
+            self.resolve_timer.stop();
+        }
+        *self.resolver.borrow_mut() = resolver;
+        self.backfill_host_names();
+    }
+
+    /// rewrite the address cells of the rows in view, with the host names known by now
+    fn backfill_host_names(&self) {
+        let columns = self.settings.borrow().visible_columns();
+        let address_columns = columns.iter()
+            .enumerate()
+            .filter(|(_, column)| matches!(column, RecordColumn::SrcIp | RecordColumn::DestIp))
+            .map(|(idx, _)| idx)
+            .collect::<Vec<_>>();
+        if address_columns.is_empty() {
+            return;
+        }
+        let state = self.state.borrow();
+        for row in rows_in_view(&self.record_table, state.group_by_flow) {
+            let record = match state.row_record(row) {
+                Some(record) => record,
+                None => continue,
+            };
+            let cells = self.row_strings(record, &columns);
+            for &idx in address_columns.iter() {
+                set_item_text(&self.record_table, row, idx, &cells[idx]);
+            }
+        }
+    }
+
+    /// the cells of `record` in the record table, with host names for the addresses once known
+    fn row_strings(&self, record: &Record, columns: &[RecordColumn]) -> Vec<String> {
+        let mut cells = record.to_column_strings(columns);
+        if let Some(resolver) = self.resolver.borrow().as_ref() {
+            for (cell, column) in cells.iter_mut().zip(columns) {
+                let ip = match column {
+                    RecordColumn::SrcIp => record.src_ip,
+                    RecordColumn::DestIp => record.dest_ip,
+                    _ => None,
+                };
+                if let Some(ip) = ip {
+                    *cell = resolver.label(ip);
+                }
+            }
+        }
+        cells
+    }
+
+    fn set_auto_export_dir(&self) {
+        let text = self.auto_export_dir.text();
+        let text = text.trim();
+        self.settings.borrow_mut().auto_export_dir = if text.is_empty() { None } else { Some(PathBuf::from(text)) };
+        self.save_settings();
+    }
+
+    fn browse_auto_export_dir(&self) {
+        if self.schedule_dir_dialog.run(Some(&self.window)) {
+            if let Ok(dir) = self.schedule_dir_dialog.get_selected_item() {
+                self.auto_export_dir.set_text(&dir.to_string_lossy());
+                self.set_auto_export_dir();
+            }
+        }
+    }
+
+    fn toggle_append_records(&self) {
+        self.settings.borrow_mut().clear_on_start =
+            self.append_records.check_state() != nwg::CheckBoxState::Checked;
+        self.save_settings();
+    }
+
+    fn toggle_auto_scroll(&self) {
+        let auto_scroll = self.auto_scroll.check_state() == nwg::CheckBoxState::Checked;
+        self.settings.borrow_mut().auto_scroll = auto_scroll;
+        self.save_settings();
+        if auto_scroll {
+            scroll_to_end(&self.record_table);
+        }
+    }
+
+    fn toggle_plot_dark(&self) {
+        let dark = self.plot_dark.check_state() == nwg::CheckBoxState::Checked;
+        self.settings.borrow_mut().plot_dark = dark;
+        self.state.borrow_mut().plot_theme = PlotTheme::new(dark);
+        self.save_settings();
+        self.request_plot_redraw();
+    }
+
+    fn toggle_plot_split(&self) {
+        self.settings.borrow_mut().plot_split =
+            self.plot_split.check_state() == nwg::CheckBoxState::Checked;
+        self.save_settings();
+        self.request_plot_redraw();
+    }
+
+    fn toggle_plot_stacked(&self) {
+        self.settings.borrow_mut().plot_stacked =
+            self.plot_stacked.check_state() == nwg::CheckBoxState::Checked;
+        self.save_settings();
+        // per protocol buckets are only kept while stacking, so rebuild them from the records
+        self.sync_plot_data();
+        self.request_plot_redraw();
+    }
+
+    fn toggle_humanize_bytes(&self) {
+        self.settings.borrow_mut().humanize_bytes =
+            self.humanize_bytes.check_state() == nwg::CheckBoxState::Checked;
+        self.save_settings();
+        self.display_stat_table();
+        self.request_plot_redraw();
+    }
+
+    /// picking an adapter during a capture hands the capture over to it, see `Handover`
+    fn connect_interface(&self) {
+        let interface = match self.interfaces.selection() {
+            Some(idx) => self.state.borrow().interfaces[idx].clone(),
+            None => return,
+        };
+        let previous = self.state.borrow().connected_interfaces.clone();
+        if previous == [interface.clone()] {
+            return;
+        }
+        let outcome = match self.connect_interfaces(vec![interface.clone()]) {
+            Some(outcome) => outcome,
+            None => return,
+        };
+        let addresses = previous.iter().map(|interface| interface.address).collect::<Vec<_>>();
+        let known = previous.into_iter().chain(iter::once(interface)).collect::<Vec<_>>();
+        if let Some(message) = outcome.marker(&addresses, |address| interface_name(&known, address)) {
+            self.log(Level::Info, message.clone());
+            self.push_plot_event(PlotEventKind::AdapterChanged, message.clone());
+            self.set_status(Level::Info, message.as_str());
+        }
+    }
+
+    /// bind the capturer to every interface that allows it, reporting the others one by one;
+    /// `None` when that was refused and the app ends for an elevated instance to take over
+    fn connect_interfaces(&self, interfaces: Vec<Interface>) -> Option<HandoverOutcome> {
+        let targets = interfaces.iter()
+            .map(|interface| (interface.address, interface.description.clone()))
+            .collect::<Vec<_>>();
+        // a running capture lets go of its sockets while they are bound anew, then goes on with the new ones
+        let running = self.state.borrow().capturing;
+        let outcome = Handover::run(&mut AppHandover(self), running, &targets);
+        let connected = interfaces.iter()
+            .filter(|interface| outcome.bound.contains(&interface.address))
+            .cloned()
+            .collect::<Vec<_>>();
+        if !connected.is_empty() {
+            self.settings.borrow_mut().last_adapters = connected.iter().map(Interface::saved).collect();
+            self.save_settings();
+        }
+        {
+            let mut state = self.state.borrow_mut();
+            state.connected_interfaces = connected;
+            // whatever was lost before is not waited for anymore
+            state.lost_interfaces.clear();
+        }
+        self.reconnect_button.set_visible(false);
+        match outcome.status(|address| interface_name(&interfaces, address)) {
+            Some((level, status)) => self.set_status(level, status.as_str()),
+            None => self.reset_status_bar(),
+        }
+        self.display_connection();
+        if outcome.access_denied() && self.access_denied() {
+            return None;
+        }
+        Some(outcome)
+    }
+
+    /// binding was refused for lack of administrator rights after all, offer them the way startup does;
+    /// an elevated process refused by some policy has nothing to gain from it; returns whether
+    /// this one is ending, leaving its callers nothing more to do
+    fn access_denied(&self) -> bool {
+        if self.state.borrow().elevation_declined || !matches!(is_elevated(), Ok(false)) {
+            return false;
+        }
+        if offer_elevation() {
+            // the elevated instance takes over, this one ends without asking about the capture
+            self.shut_down();
+            true
+        } else {
+            self.state.borrow_mut().elevation_declined = true;
+            false
+        }
+    }
+
+    /// stop capturing and let go of the adapters, so that none is left receiving everything
+    fn disconnect_interfaces(&self) {
+        // a scheduled run would only find nothing to capture from
+        self.cancel_schedule();
+        self.stop_capture();
+        self.capturer.borrow_mut().disconnect();
+        {
+            let mut state = self.state.borrow_mut();
+            state.connected_interfaces.clear();
+            state.lost_interfaces.clear();
+        }
+        self.reconnect_button.set_visible(false);
+        self.interfaces.set_selection(None);
+        self.display_connection();
+        self.set_status(Level::Info, "已断开网卡");
+    }
+
+    /// the disconnect button follows whether some adapter is bound
+    fn display_connection(&self) {
+        self.disconnect_interfaces.set_enabled(self.connected());
+    }
+
+    /// enumerate the adapters again, e.g. after plugging in a nic or connecting a vpn
+    fn refresh_interfaces(&self) {
+        let interfaces = match usable_interfaces() {
+            Ok(interfaces) => interfaces,
+            Err(err) => {
+                self.set_status(Level::Error, format!("无法获取网卡列表：{}", err).as_str());
+                return;
+            }
+        };
+        let selected = self.interfaces.selection()
+            .and_then(|idx| self.state.borrow().interfaces.get(idx).cloned());
+        let connected = self.state.borrow().connected_interfaces.clone();
+
+        self.interfaces.set_collection(interfaces.iter().map(|interface| interface.to_string()).collect());
+        let position = |interface: &Option<Interface>| interface.as_ref()
+            .and_then(|interface| interfaces.iter().position(|i| i == interface));
+        self.interfaces.set_selection(position(&selected));
+        let connected_gone = connected.iter().any(|interface| !interfaces.contains(interface));
+        self.state.borrow_mut().interfaces = interfaces;
+
+        if connected_gone {
+            if self.state.borrow().capturing {
+                self.stop_capture();
+            }
+            self.capturer.borrow_mut().disconnect();
+            self.state.borrow_mut().connected_interfaces.clear();
+            self.display_connection();
+            self.set_status(Level::Warn, "正在使用的网卡已不存在，捕获已停止，请重新选择网卡");
+        } else {
+            self.set_status(Level::Info, "网卡列表已刷新");
+        }
+    }
+
+    /// a socket stopped capturing for good; the capture goes on while any adapter is left
+    fn adapter_lost(&self, lost: &AdapterLost) {
+        self.log(Level::Error, format!("{:#}", lost));
+        let interfaces = {
+            let mut state = self.state.borrow_mut();
+            let (interfaces, kept) = state.connected_interfaces.drain(..)
+                .partition::<Vec<_>, _>(|i| i.address == lost.ip && i.description == lost.name);
+            state.connected_interfaces = kept;
+            state.lost_interfaces.extend(interfaces.iter().cloned());
+            interfaces
+        };
+        let reason = match lost.source {
+            CaptureError::AdapterGone => "地址已改变",
+            CaptureError::NetworkDown => "网络已断开",
+            CaptureError::NetworkReset => "连接已重置",
+            _ => "无法继续捕获",
+        };
+        let name = interfaces.first().map_or(lost.name.clone(), |interface| interface.to_string());
+        if !self.state.borrow().connected_interfaces.is_empty() {
+            self.set_status(Level::Warn, format!("网卡 {} {}，其余网卡继续捕获", name, reason).as_str());
+        } else if matches!(lost.source, CaptureError::AdapterGone) && self.state.borrow().capturing {
+            // the capture waits for the adapter to be bound to its new address
+            self.set_status(Level::Warn, format!("网卡 {} {}，等待重新绑定", name, reason).as_str());
+            self.rebind_addresses();
+        } else {
+            self.stop_capture();
+            self.display_connection();
+            self.set_status(Level::Error, format!("网卡 {} {}，捕获已停止，网卡恢复后可重新连接", name, reason).as_str());
+        }
+    }
+
+    /// check whether a lost adapter is back whenever addresses change
+    fn addresses_changed(&self) {
+        if self.state.borrow().capturing {
+            self.rebind_addresses();
+        }
+        let lost = self.state.borrow().lost_interfaces.clone();
+        if lost.is_empty() {
+            return;
+        }
+        // the adapter may come back with another address, e.g. from dhcp
+        let back = match usable_interfaces() {
+            Ok(interfaces) => lost.iter().any(|l| interfaces.iter().any(|i| i.adapter_name == l.adapter_name)),
+            Err(_) => false,
+        };
+        if back != self.reconnect_button.visible() {
+            self.reconnect_button.set_visible(back);
+            if back {
+                self.set_status(Level::Info, "断开的网卡已恢复，点击“重新连接”继续使用");
+            }
+        }
+    }
+
+    /// bind the adapters whose address changed during the capture, e.g. on a dhcp renewal or a vpn connect,
+    /// to their new one; one that has none yet is tried again with a growing backoff
+    fn rebind_addresses(&self) {
+        self.rebind_timer.stop();
+        if !self.state.borrow().capturing {
+            self.state.borrow_mut().rebind_attempts = None;
+            return;
+        }
+        // failing to list the adapters is as good as finding no new address
+        let interfaces = usable_interfaces().unwrap_or_default();
+        let bound = {
+            let state = self.state.borrow();
+            state.connected_interfaces.iter().chain(state.lost_interfaces.iter()).cloned().collect::<Vec<_>>()
+        };
+        let mut targets = Vec::new();
+        let mut moved = Vec::new();
+        let mut waiting = Vec::new();
+        for interface in bound.iter() {
+            if interfaces.contains(interface) {
+                targets.push(interface.clone());
+                continue;
+            }
+            let new = interfaces.iter()
+                .find(|i| i.adapter_name == interface.adapter_name && !targets.contains(*i) && !bound.contains(*i));
+            match new {
+                Some(new) => {
+                    targets.push(new.clone());
+                    moved.push((interface.clone(), new.clone()));
+                }
+                None => waiting.push(interface.clone()),
+            }
+        }
+        if moved.is_empty() && waiting.is_empty() {
+            self.state.borrow_mut().rebind_attempts = None;
+            return;
+        }
+        if !moved.is_empty() {
+            // the capture thread lets go of the sockets and goes on with the new ones, records included
+            match self.connect_interfaces(targets) {
+                // not even the adapters that kept their address could be bound again
+                Some(outcome) if outcome.handover == Handover::Stop => return,
+                Some(_) => {}
+                None => return,
+            }
+            self.refresh_interfaces();
+            let connected = self.state.borrow().connected_interfaces.clone();
+            let (rebound, failed) = moved.into_iter().partition::<Vec<_>, _>(|(_, new)| connected.contains(new));
+            let message = format!("地址已变更，自动重新绑定：{}", rebound.iter()
+                .map(|(old, new)| format!("{} {} → {}", new.description, old.address, new.address))
+                .collect::<Vec<_>>()
+                .join("，"));
+            if !rebound.is_empty() {
+                self.log(Level::Info, message.clone());
+                self.push_plot_event(PlotEventKind::AddressChanged, message.clone());
+                if let [interface] = connected.as_slice() {
+                    let idx = self.state.borrow().interfaces.iter().position(|i| i == interface);
+                    self.interfaces.set_selection(idx);
+                }
+            }
+            // kept with their old address, so the next attempt finds them moved again
+            waiting.extend(failed.into_iter().map(|(old, _)| old));
+            self.state.borrow_mut().lost_interfaces.extend(waiting.iter().cloned());
+            if waiting.is_empty() {
+                self.state.borrow_mut().rebind_attempts = None;
+                self.set_status(Level::Info, message.as_str());
+                return;
+            }
+        }
+        let attempts = {
+            let mut state = self.state.borrow_mut();
+            let attempts = state.rebind_attempts.map_or(1, |attempts| attempts + 1);
+            state.rebind_attempts = Some(attempts);
+            attempts
+        };
+        let backoff = (REBIND_BACKOFF * 2u32.saturating_pow(attempts.min(16) - 1)).min(REBIND_BACKOFF_MAX);
+        self.rebind_timer.set_lifetime(Some(backoff));
+        self.rebind_timer.start();
+        self.set_status(Level::Warn, format!(
+            "网卡 {} 地址已变更，尚无可用的新地址，{} 秒后重试（第 {} 次）",
+            waiting.iter().map(|interface| interface.to_string()).collect::<Vec<_>>().join("，"),
+            backoff.as_secs(),
+            attempts,
+        ).as_str());
+    }
+
+    /// connect the lost adapters again, along with the ones still connected
+    fn reconnect_lost_interfaces(&self) {
+        self.refresh_interfaces();
+        let adapters = {
+            let state = self.state.borrow();
+            state.connected_interfaces.iter()
+                .chain(state.lost_interfaces.iter())
+                .map(Interface::saved)
+                .collect::<Vec<_>>()
+        };
+        if self.connect_adapters(&adapters) == Some(adapters.len()) {
+            self.set_status(Level::Warn, "断开的网卡尚未恢复");
+        }
+    }
+
+    fn tab_changed(&self) {
+        let mode: Mode = self.tabs_container.selected_tab().into();
+        let paused = self.state.borrow().display_paused.is_some();
+
+        // paused tables keep what they showed, the plot stays frozen at the pause time on its own
+        match mode {
+            Mode::Record | Mode::Stat if paused => {},
+            Mode::Record => self.rebuild_record_table(),
+            Mode::Plot => self.request_plot_redraw(),
+            Mode::Stat => self.display_stat_table(),
+            Mode::Settings => {},
+            Mode::About => self.display_capture_metrics(),
+        };
+        if mode == Mode::Stat {
+            // the frame may have been resized while the tab was hidden
+            self.stat_layout_timer.start();
+        }
+
+        {
+            let mut state = self.state.borrow_mut();
+            state.mode = mode;
+            // the cursor left the plot along with the tab, so drop its tooltip and any unfinished drag
+            state.plot_hover = None;
+            state.plot_drag = None;
+        }
+        self.display_filter_pin();
+    }
+
+    fn set_timeout(&self) {
+        let text = self.timeout.text();
+        let text = text.trim();
+        if text.is_empty() {
+            self.capturing_timer.set_lifetime(None);
+        } else {
+            if let Ok(timeout) = text.parse::<u64>() {
+                self.capturing_timer.set_lifetime(Some(StdDuration::from_millis(timeout)));
+            } else {
+                self.capturing_timer.set_lifetime(None);
+                self.set_status(Level::Warn, "捕获时间不正确");
+                return;
+            }
+        }
+        self.reset_status_bar();
+    }
+
+    /// start capturing from the bound adapters, returns whether the capture thread could be started
+    fn start_capture(&self) -> bool {
+        // a selection refers to the previous session's timeline
+        self.clear_plot_selection();
+        let clear = self.settings.borrow().clear_on_start;
+        {
+            let mut state = self.state.borrow_mut();
+            state.capturing = true;
+            state.end_time = None;
+            state.throughput = None;
+            state.window_throughput = None;
+            state.display_paused = None;
+            state.read_stats = Some(ReadStats::default());
+            state.only_protocol = state.bound_protocol;
+            state.stack_counts = (ip_packet_counts().ok(), None);
+            state.capture_drains = CaptureMetrics::default();
+            let now = Local::now();
+            if clear || state.start_time.is_none() {
+                state.records.clear();
+                self.stat_records.borrow_mut().clear();
+                state.start_time = Some(now);
+                self.plot_records.borrow_mut().clear_with_time(now);
+                state.plot_events.clear();
+            } else {
+                // appending keeps the first session's start, the idle time since the last one is a gap
+                self.plot_records.borrow_mut().resume(now);
+            }
+        }
+        self.pause_display.set_text("暂停显示");
+        self.push_plot_event(PlotEventKind::CaptureStarted, "开始捕获".to_string());
+        self.capture.set_text("停止捕获");
+        self.reset_status_bar();
+        if clear {
+            self.clear_record_table();
+        }
+        self.capturing_timer.start();
+        self.plotting_sample_timer.start();
+        if self.settings.borrow().stat_refresh != StatRefresh::Manual {
+            self.stat_refresh_timer.start();
+        }
+        self.capturer.borrow_mut().reset_metrics();
+        if let Err(err) = self.spawn_capture_thread() {
+            // nothing reads the sockets, so the capture ends as soon as it started
+            self.stop_capture();
+            self.set_status(Level::Error, format!("无法启动捕获线程：{}", err).as_str());
+            return false;
+        }
+        self.polling_timer.start();
+        true
+    }
+
+    fn spawn_capture_thread(&self) -> Result<()> {
+        let mut capturer = mem::take(&mut *self.capturer.borrow_mut());
+        // the thread sleeps until a packet arrives or stopping wakes it, unless polling is asked for
+        let mode = if self.settings.borrow().poll_capture { ReadMode::Polling } else { ReadMode::Events };
+        if let (Err(err), true) = (capturer.set_read_mode(mode), capturer.connected()) {
+            self.log(Level::Warn, format!("无法设置套接字的读取方式，改为轮询：{:#}", err));
+            if let Err(err) = capturer.set_read_mode(ReadMode::Polling) {
+                self.log(Level::Warn, format!("无法设置套接字的读取方式：{:#}", err));
+            }
+        }
+        *self.capture_thread.borrow_mut() = Some(CaptureThread::spawn(capturer)?);
+        Ok(())
+    }
+
+    /// end the capture thread and take the capturer back, along with what it read meanwhile
+    fn join_capture_thread(&self) -> Vec<CaptureEvent> {
+        let thread = self.capture_thread.borrow_mut().take();
+        match thread {
+            Some(thread) => {
+                let stopped = thread.stop();
+                *self.capturer.borrow_mut() = stopped.source;
+                // reconnecting mid capture joins the thread too, the reads before it still count
+                let mut state = self.state.borrow_mut();
+                if let Some(reads) = state.read_stats.as_mut() {
+                    reads.merge(&stopped.reads);
+                }
+                state.capture_drains.merge_drains(&stopped.metrics);
+                stopped.events
+            }
+            None => Vec::new(),
+        }
+    }
+
+    fn stop_capture(&self) {
+        // the timeout may fire while closing has already stopped the capture, or the other way round
+        if !self.state.borrow().capturing {
+            return;
+        }
+        // the capture may end on its timeout while paused, the final results should show regardless
+        self.resume_display();
+        self.polling_timer.stop();
+        self.rebind_timer.stop();
+        self.state.borrow_mut().rebind_attempts = None;
+        // what was read before stopping still counts; adapters lost meanwhile are told once stopped
+        let lost = self.join_capture_thread()
+            .into_iter()
+            .filter_map(|event| self.capture_event(event))
+            .collect::<Vec<_>>();
+        self.plotting_sample_timer.stop();
+        self.capturing_timer.stop();
+        self.stat_refresh_timer.stop();
+        {
+            let mut state = self.state.borrow_mut();
+            state.capturing = false;
+            state.end_time = Some(Local::now());
+            state.stack_counts.1 = ip_packet_counts().ok();
+            state.stat_dirty = true;
+        }
+        self.plot_records.borrow_mut().commit_rest();
+        self.push_plot_event(PlotEventKind::CaptureStopped, "停止捕获".to_string());
+        // flush whatever arrived since the last refresh tick, along with the session summary
+        self.refresh_stat_table();
+        self.request_plot_redraw();
+        self.capture.set_text("开始捕获");
+        self.reset_status_bar();
+        // the final numbers stay, the sampler that updated them is stopped now
+        self.display_capture_info();
+        // scheduled runs export on their own
+        if self.state.borrow().schedule.is_none() {
+            self.auto_export();
+        }
+        for adapter in &lost {
+            self.adapter_lost(adapter);
+        }
+    }
+
+    /// write the session to the auto export directory, if one is set
+    fn auto_export(&self) {
+        let dir = match self.settings.borrow().auto_export_dir.clone() {
+            Some(dir) => dir,
+            None => return,
+        };
+        let path = {
+            let state = self.state.borrow();
+            if state.records.is_empty() {
+                return;
+            }
+            timestamped_path(&dir, state.start_time.unwrap_or_else(Local::now))
+        };
+        let columns = self.settings.borrow().visible_columns();
+        match export_csv(&path, &self.state.borrow().records, &columns, self.session_summary().as_deref()) {
+            Ok(()) => self.set_status(Level::Info, format!("已自动导出到 {}", path.display()).as_str()),
+            Err(err) => self.set_status(Level::Error, format!("无法自动导出到 {}：{}", path.display(), err).as_str()),
+        }
+    }
+
+    fn clear_records(&self) {
+        if self.state.borrow().capturing {
+            self.set_status(Level::Warn, "请先停止捕获再清空记录");
+            return;
+        }
+        self.clear_plot_selection();
+        {
+            let mut state = self.state.borrow_mut();
+            state.records.clear();
+            state.start_time = None;
+            state.end_time = None;
+            state.throughput = None;
+            state.window_throughput = None;
+            state.plot_events.clear();
+            state.plot_hover = None;
+            state.search_row = None;
+        }
+        self.stat_records.borrow_mut().clear();
+        self.plot_records.borrow_mut().clear();
+        self.clear_record_table();
+        self.search_status.set_text("");
+        // the stat tables are refilled from the now empty `stat_records`
+        self.display_stat_table();
+        self.request_plot_redraw();
+        self.reset_status_bar();
+        self.display_capture_info();
+    }
+
+    /// show the ipv4 packets of a pcap file as a finished capture, spanning its first to its
+    /// last packet; a file dropped onto the executable comes here
+    fn load_pcap(&self, path: &Path) {
+        let reader = File::open(path)
+            .map_err(anyhow::Error::from)
+            .and_then(|file| PcapReader::new(BufReader::new(file)));
+        let mut source = match reader {
+            Ok(reader) => PcapSource::new(reader),
+            Err(err) => {
+                self.set_status(Level::Error, format!("无法读取 {}：{}", path.display(), err).as_str());
+                return;
+            }
+        };
+        self.load_source(&mut source, &path.display().to_string());
+    }
+
+    /// show some generated traffic that ended just now, as if it was read from a file
+    fn load_selftest(&self) {
+        let start = Local::now() - Duration::seconds(10);
+        let mut source = SyntheticSource::new(demo_flows(), DEMO_LOCAL_IP, start);
+        self.load_source(&mut source, "生成的流量");
+    }
+
+    /// replace the records with all of `source` at once, `name` telling where they came from
+    fn load_source(&self, source: &mut dyn PacketSource, name: &str) {
+        self.clear_records();
+        // whatever the adapters are bound for, a file holds what it was saved with
+        self.state.borrow_mut().only_protocol = None;
+        let mut span = None;
+        let mut count = 0u64;
+        let result = loop {
+            match source.read_timeout(StdDuration::ZERO) {
+                // the end of the file
+                Ok(None) => break Ok(()),
+                Ok(Some((time, packet, local, truncated))) => {
+                    span = Some(span.map_or((time, time), |(first, _)| (first, time)));
+                    count += 1;
+                    self.update_record(Record::from_packet(time, packet, local, truncated));
+                }
+                Err(err) => break Err(err),
+            }
+        };
+        if let Some((first, last)) = span {
+            let mut state = self.state.borrow_mut();
+            state.start_time = Some(first);
+            state.end_time = Some(last);
+        }
+        self.plot_records.borrow_mut().commit_rest();
+        self.display_stat_table();
+        self.request_plot_redraw();
+        self.display_capture_info();
+        match result {
+            Ok(()) => self.set_status(Level::Info, format!("已载入 {} 中的 {} 个分组", name, count).as_str()),
+            // the packets before the damaged one stay
+            Err(err) => self.set_status(
+                Level::Error,
+                format!("读取 {} 时出错：{}，已载入 {} 个分组", name, err, count).as_str(),
+            ),
+        }
+    }
+
+    fn toggle_display_pause(&self) {
+        let (capturing, paused) = {
+            let state = self.state.borrow();
+            (state.capturing, state.display_paused.is_some())
+        };
+        if paused {
+            self.resume_display();
+        } else if capturing {
+            self.state.borrow_mut().display_paused = Some(Local::now());
+            self.pause_display.set_text("继续显示");
+        } else {
+            self.set_status(Level::Warn, "只能在捕获时暂停显示");
+            return;
+        }
+        self.reset_status_bar();
+    }
+
+    fn resume_display(&self) {
+        if self.state.borrow_mut().display_paused.take().is_none() {
+            return;
+        }
+        self.pause_display.set_text("暂停显示");
+        // catch up with everything collected meanwhile, once
+        let mode = self.state.borrow().mode;
+        match mode {
+            Mode::Record => self.rebuild_record_table(),
+            Mode::Stat => self.display_stat_table(),
+            _ => {},
+        }
+        self.request_plot_redraw();
+    }
+
+    /// record table rows matching the quick search text, in order
+    fn search_hits(&self) -> Vec<usize> {
+        let text = self.record_search.text();
+        if text.is_empty() {
+            return Vec::new();
+        }
+        let columns = self.settings.borrow().visible_columns();
+        let state = self.state.borrow();
+        // a paused table lags behind the records, only its rows can be jumped to
+        (0..state.record_rows.len())
+            .filter(|&row| state.row_record(row).map_or(false, |r| r.contains_text(&columns, &text)))
+            .collect()
+    }
+
+    fn search_records(&self) {
+        self.state.borrow_mut().search_row = None;
+        self.jump_to_hit(true);
+    }
+
+    fn search_by_enter(&self) {
+        // shift + enter searches backwards, like in most editors
+        let shift = unsafe { GetKeyState(VK_SHIFT) } < 0;
+        self.jump_to_hit(!shift);
+    }
+
+    fn search_next(&self) {
+        self.jump_to_hit(true);
+    }
+
+    fn search_previous(&self) {
+        self.jump_to_hit(false);
+    }
+
+    /// select the next (or previous) hit after the current one, wrapping around
+    fn jump_to_hit(&self, forward: bool) {
+        let hits = self.search_hits();
+        let current = self.state.borrow().search_row;
+        let hit = match (current, forward) {
+            (None, true) => hits.first(),
+            (None, false) => hits.last(),
+            (Some(row), true) => hits.iter().find(|&&hit| hit > row).or_else(|| hits.first()),
+            (Some(row), false) => hits.iter().rev().find(|&&hit| hit < row).or_else(|| hits.last()),
+        }
+        .copied();
+
+        if let Some(row) = current {
+            self.record_table.select_item(row, false);
+        }
+        self.state.borrow_mut().search_row = hit;
+        match hit {
+            Some(row) => {
+                self.record_table.select_item(row, true);
+                ensure_visible(&self.record_table, row);
+                let idx = hits.iter().position(|&h| h == row).unwrap_or(0);
+                self.search_status.set_text(format!("第 {} / {} 个匹配", idx + 1, hits.len()).as_str());
+            }
+            None if self.record_search.text().is_empty() => self.search_status.set_text(""),
+            None => self.search_status.set_text("没有匹配"),
+        }
+    }
+
+    fn toggle_capture(&self) {
+        let capturing = self.state.borrow().capturing;
+        let connected = self.connected();
+        if connected {
+            if capturing {
+                self.stop_capture();
+            } else if self.confirm_clear_on_start() {
+                self.start_capture();
+            }
+        } else {
+            self.set_status(Level::Warn, "请首先选择网卡");
+        }
+    }
+
+    /// ask before a new capture drops the records of the previous ones, returns whether to go on
+    fn confirm_clear_on_start(&self) -> bool {
+        let count = self.state.borrow().records.len();
+        if !self.settings.borrow().clear_on_start || count == 0 {
+            return true;
+        }
+        let choice = nwg::modal_message(&self.window, &nwg::MessageParams {
+            title: "开始捕获",
+            content: &format!("将清除 {} 条现有记录，继续？", format_count(count as u64)),
+            buttons: nwg::MessageButtons::YesNo,
+            icons: nwg::MessageIcons::Warning,
+        });
+        matches!(choice, nwg::MessageChoice::Yes)
+    }
+
+    /// handle a key pressed anywhere in the main window before the focused control sees it,
+    /// returns whether it was a shortcut
+    fn handle_shortcut(&self, key: i32, target: HWND) -> bool {
+        let in_main_window = match self.window.handle.hwnd() {
+            Some(hwnd) => unsafe { GetAncestor(target, GA_ROOT) == hwnd },
+            None => false,
+        };
+        if !in_main_window {
+            return false;
+        }
+        let pressed = |key| unsafe { GetKeyState(key) < 0 };
+        if pressed(VK_MENU) || pressed(VK_SHIFT) {
+            return false;
+        }
+        if !pressed(VK_CONTROL) {
+            match key {
+                // a space typed into a text input is just a space
+                VK_SPACE if !is_text_input(target) => self.toggle_capture(),
+                VK_ESCAPE => self.filter.set_text(""),
+                _ => return false,
+            }
+            return true;
+        }
+        match key {
+            0x52 /* R */ => self.toggle_capture(),
+            0x4C /* L */ => self.filter.set_focus(),
+            0x45 /* E */ => {
+                self.export_records();
+            }
+            0x46 /* F */ => {
+                self.select_tab(Mode::Record);
+                self.record_search.set_focus();
+            }
+            0x31 /* 1 */ => self.select_tab(Mode::Record),
+            0x32 /* 2 */ => self.select_tab(Mode::Plot),
+            0x33 /* 3 */ => self.select_tab(Mode::Stat),
+            0x34 /* 4 */ => self.select_tab(Mode::Settings),
+            _ => return false,
+        }
+        true
+    }
+
+    fn select_tab(&self, mode: Mode) {
+        if self.tabs_container.selected_tab() != mode as usize {
+            // selecting programmatically does not raise `TabsContainerChanged`
+            self.tabs_container.set_selected_tab(mode as usize);
+            self.tab_changed();
+        }
+    }
+
+    /// the filter box edits the filter of the tab on display, shared unless the tab is pinned
+    fn create_filter(&self) {
+        let filter_str = self.filter.text();
+        // the error about what was typed before goes, whatever comes of this text
+        if mem::take(&mut self.state.borrow_mut().filter_error) {
+            self.reset_status_bar();
+        }
+        let (mode, pinned) = {
+            let state = self.state.borrow();
+            // switching tabs puts the text of their filter into the box, which changes nothing
+            if state.filter_text_of(state.mode) == filter_str {
+                return;
+            }
+            (state.mode, state.pinned_filter(state.mode).is_some())
+        };
+        let filter: Option<Box<dyn Fn(&Record) -> bool>> = if filter_str.is_empty() {
+            None
+        } else {
+            match create_filter(filter_str.as_str()) {
+                Ok(filter) => Some(Box::new(filter)),
+                Err(err) => {
+                    self.state.borrow_mut().filter_error = true;
+                    self.status_bar.set_text(0, err.to_string().as_str());
+                    return;
+                },
+            }
+        };
+        let previous = self.state.borrow_mut().set_filter_of(mode, filter_str.clone(), filter);
+        let scope = if pinned { format!("{}的", mode.title()) } else { String::new() };
+        let event = if filter_str.is_empty() {
+            format!("清除{}筛选器", scope)
+        } else {
+            format!("{}筛选器改为 \"{}\"", scope, filter_str)
+        };
+        self.push_plot_event(PlotEventKind::FilterChanged, event);
+        self.start_rebuild(true, Some(previous));
+    }
+
+    /// rebuild everything derived from the filtered records
+    fn refresh_filtered_views(&self) {
+        self.start_rebuild(true, None);
+    }
+
+    /// refill the record table, and the stats when `stats`, from the records; big stores are
+    /// gone through over several timer ticks with the progress in the status bar
+    fn start_rebuild(&self, stats: bool, previous: Option<PreviousFilter>) {
+        {
+            let mut state = self.state.borrow_mut();
+            // one in progress starts over, going back to the filter from before it all along
+            let (stats, previous) = match state.rebuild.take() {
+                Some(running) => (stats || running.stats, running.previous.or(previous)),
+                None => (stats, previous),
+            };
+            let next = state.records.evicted();
+            state.rebuild = Some(Rebuild { next, stats, previous });
+            // rows are renumbered, the next search starts over
+            state.search_row = None;
+        }
+        self.clear_record_table();
+        if self.state.borrow().rebuild.as_ref().map_or(false, |rebuild| rebuild.stats) {
+            self.stat_records.borrow_mut().clear();
+        }
+        self.record_table.set_redraw(false);
+        // a store that fits in one chunk is done right away, without any flicker of the controls
+        self.rebuild_step();
+        let cancellable = match self.state.borrow().rebuild.as_ref() {
+            Some(rebuild) => rebuild.previous.is_some(),
+            None => return,
+        };
+        self.filter.set_enabled(false);
+        self.cancel_rebuild_button.set_visible(cancellable);
+        self.rebuild_timer.start();
+    }
+
+    /// go through the next chunk of records, finishing the rebuild at the end of the store
+    fn rebuild_step(&self) {
+        let columns = self.settings.borrow().visible_columns();
+        let mut flows = mem::take(&mut self.state.borrow_mut().record_flows);
+        let mut rows = Vec::new();
+        let (next, done, len) = {
+            let state = self.state.borrow();
+            let rebuild = match state.rebuild.as_ref() {
+                Some(rebuild) => rebuild,
+                None => return,
+            };
+            // records evicted before their turn are skipped, like they were never there
+            let len = state.records.len();
+            let start = (rebuild.next.saturating_sub(state.records.evicted()) as usize).min(len);
+            let end = (start + REBUILD_CHUNK).min(len);
+            if rebuild.stats {
+                self.stat_records.borrow_mut()
+                    .update_multiple(state.records.range(start..end).filter(|r| state.passes(Mode::Stat, r)));
+            }
+            let records = (start..end).zip(state.records.range(start..end));
+            for (idx, record) in records.filter(|(_, r)| state.passes(Mode::Record, r)) {
+                flows.add(record);
+                self.record_table.insert_items_row(None, &self.row_strings(record, &columns));
+                rows.push(state.records.evicted() + idx as u64);
+            }
+            (state.records.evicted() + end as u64, end, len)
+        };
+        {
+            let mut state = self.state.borrow_mut();
+            state.record_flows = flows;
+            state.record_rows.extend(rows);
+            if let Some(rebuild) = state.rebuild.as_mut() {
+                rebuild.next = next;
+            }
+        }
+        if done == len {
+            self.finish_rebuild();
+        } else {
+            self.status_bar.set_text(0, format!("正在按筛选器重建视图… {}%", done * 100 / len).as_str());
+        }
+    }
+
+    fn finish_rebuild(&self) {
+        self.rebuild_timer.stop();
+        let stats = match self.state.borrow_mut().rebuild.take() {
+            Some(rebuild) => rebuild.stats,
+            None => return,
+        };
+        self.filter.set_enabled(true);
+        self.cancel_rebuild_button.set_visible(false);
+        self.apply_record_groups();
+        self.record_table.set_redraw(true);
+        if self.settings.borrow().auto_scroll && !self.state.borrow().group_by_flow {
+            scroll_to_end(&self.record_table);
+        }
+        if stats {
+            self.sync_plot_data();
+            self.display_stat_table();
+            self.request_plot_redraw();
+            self.display_capture_info();
+        }
+        self.reset_status_bar();
+    }
+
+    /// abandon the rebuild for a new filter and rebuild for the one from before instead
+    fn cancel_rebuild(&self) {
+        let previous = match self.state.borrow_mut().rebuild.as_mut().and_then(|rebuild| rebuild.previous.take()) {
+            Some(previous) => previous,
+            None => return,
+        };
+        let PreviousFilter { mode, text, filter } = previous;
+        self.state.borrow_mut().set_filter_of(mode, text, filter);
+        // the box then holds the current filter again, so it does not start another rebuild
+        self.display_filter_pin();
+        self.start_rebuild(true, None);
+        self.log(Level::Info, "已取消筛选，恢复之前的筛选器");
+    }
+
+    fn toggle_filter_pin(&self) {
+        let pin = self.filter_pin.check_state() == nwg::CheckBoxState::Checked;
+        {
+            let mut state = self.state.borrow_mut();
+            let mode = state.mode;
+            let filter_text = state.filter_text.clone();
+            let slot = match state.pinned_filters.get_mut(mode as usize) {
+                Some(slot) => slot,
+                None => return,
+            };
+            *slot = if pin {
+                // the tab keeps the filter it is showing, later edits only go to its own copy;
+                // the shared text was accepted before, so it compiles again
+                let filter = create_filter(&filter_text).ok()
+                    .filter(|_| !filter_text.is_empty())
+                    .map(|f| Box::new(f) as Box<dyn Fn(&Record) -> bool>);
+                Some(PinnedFilter { text: filter_text, filter })
+            } else {
+                None
+            };
+        }
+        self.display_filter_pin();
+        if !pin {
+            // back on the shared filter, which may differ from what the tab held
+            self.refresh_filtered_views();
+        }
+    }
+
+    /// make clear which filter the current tab uses: the pin box, the tab titles and the text
+    fn display_filter_pin(&self) {
+        let (mode, pinned, text) = {
+            let state = self.state.borrow();
+            for mode in Mode::FILTERED {
+                let title = if state.pinned_filter(mode).is_some() {
+                    format!("{}（独立筛选）", mode.title())
+                } else {
+                    mode.title().to_string()
+                };
+                self.tab_of(mode).set_text(&title);
+            }
+            (state.mode, state.pinned_filter(state.mode).is_some(), state.filter_text_of(state.mode).to_string())
+        };
+        self.filter_pin.set_enabled(Mode::FILTERED.contains(&mode));
+        self.filter_pin.set_check_state(if pinned { nwg::CheckBoxState::Checked } else { nwg::CheckBoxState::Unchecked });
+        self.filter.set_placeholder_text(Some(if pinned { "请输入本页的筛选器" } else { "请输入筛选器" }));
+        // setting the text goes through `create_filter`, the state must not be borrowed here
+        if self.filter.text() != text {
+            self.filter.set_text(&text);
+        }
+    }
+
+    fn tab_of(&self, mode: Mode) -> &nwg::Tab {
+        match mode {
+            Mode::Record => &self.record_tab,
+            Mode::Plot => &self.plot_tab,
+            Mode::Stat => &self.stat_tab,
+            Mode::Settings => &self.settings_tab,
+            Mode::About => &self.about_tab,
+        }
+    }
+
+    fn push_plot_event(&self, kind: PlotEventKind, text: String) {
+        let time = Local::now();
+        let mut state = self.state.borrow_mut();
+        let events = &mut state.plot_events;
+        // the filter applies on every keystroke, so one edit only keeps its final text
+        if let Some(last) = events.last_mut() {
+            if kind == PlotEventKind::FilterChanged && last.kind == kind && time - last.time < Duration::seconds(1) {
+                *last = PlotEvent { time, kind, text };
+                return;
+            }
+        }
+        events.push(PlotEvent { time, kind, text });
+    }
+
+    fn sync_plot_data(&self) {
+        let state = self.state.borrow();
+        let mut plot_records = self.plot_records.borrow_mut();
+
+        // gaps come from the sampler rather than the records, so they outlive the rebuild
+        let gaps = std::mem::take(&mut plot_records.gaps);
+        *plot_records = PlotRecord::from_records(
+            state.records.iter().filter(|&r| state.passes(Mode::Plot, r)), 
+            if state.capturing { None } else { state.start_time }, 
+            if state.capturing { Some(Local::now()) } else { state.end_time },
+            self.settings.borrow().plot_stacked,
+        );
+        plot_records.gaps = gaps;
+    }
+
+    fn update_plot_data(&self, record: &Record) {
+        let mut plot_records = self.plot_records.borrow_mut();
+
+        plot_records.update_records(
+            iter::once(record), 
+            None
+        );
+    }
+
+    fn rebuild_record_table(&self) {
+        self.start_rebuild(false, None);
+    }
+
+    fn clear_record_table(&self) {
+        self.record_table.clear();
+        {
+            let mut state = self.state.borrow_mut();
+            state.record_flows.clear();
+            state.record_rows.clear();
+        }
+        if let Some(hwnd) = self.record_table.handle.hwnd() {
+            unsafe {
+                SendMessageW(hwnd, LVM_REMOVEALLGROUPS, 0, 0);
+            }
+        }
+    }
+
+    /// put every record table row into the group of its conversation, or take the groups away;
+    /// the groups come from `record_flows`, which is kept along with the rows
+    fn apply_record_groups(&self) {
+        let hwnd = match self.record_table.handle.hwnd() {
+            Some(hwnd) => hwnd,
+            None => return,
+        };
+        let state = self.state.borrow();
+        unsafe {
+            SendMessageW(hwnd, LVM_REMOVEALLGROUPS, 0, 0);
+            SendMessageW(hwnd, LVM_ENABLEGROUPVIEW, state.group_by_flow as WPARAM, 0);
+        }
+        if !state.group_by_flow {
+            return;
+        }
+        for flow in state.record_flows.flows() {
+            insert_group(&self.record_table, flow.id, &self.flow_header(flow), flow.packet_num > FLOW_COLLAPSE_ROWS);
+        }
+        for row in 0..state.record_rows.len() {
+            if let Some(flow) = state.row_record(row).and_then(|record| state.record_flows.get(record)) {
+                set_item_group(&self.record_table, row, flow.id);
+            }
+        }
+    }
+
+    fn toggle_group_by_flow(&self) {
+        let grouped = self.group_by_flow.check_state() == nwg::CheckBoxState::Checked;
+        let paused = {
+            let mut state = self.state.borrow_mut();
+            state.group_by_flow = grouped;
+            state.display_paused.is_some()
+        };
+        // a paused table lags behind the records the groups are taken from
+        if paused {
+            self.rebuild_record_table();
+            return;
+        }
+        self.record_table.set_redraw(false);
+        self.apply_record_groups();
+        self.record_table.set_redraw(true);
+        if !grouped && self.settings.borrow().auto_scroll {
+            scroll_to_end(&self.record_table);
+        }
+    }
+
+    /// e.g. "10.0.0.5:52311 ⇄ 93.184.216.34:443 · TCP · 182 包 / 214 KB"
+    fn flow_header(&self, flow: &Flow) -> String {
+        format!(
+            "{} · {} · {} 包 / {}",
+            flow.key, flow.key.trans_proto, format_count(flow.packet_num), self.display_bytes_text(flow.byte_num),
+        )
+    }
+
+
+    fn refresh_plot_graph(&self) {
+        let window = Duration::seconds(self.settings.borrow().rate_window_secs as i64);
+        let (throughput, window_throughput) = {
+            let mut plot_records = self.plot_records.borrow_mut();
+
+            plot_records.sample(Local::now());
+
+            // the sampler keeps appending empty buckets, so both rates decay to zero when idle
+            (plot_records.recent_rate(Duration::seconds(1)), plot_records.recent_rate(window))
+        };
+
+        let (mode, paused) = {
+            let mut state = self.state.borrow_mut();
+            state.throughput = Some(throughput);
+            state.window_throughput = Some(window_throughput);
+            (state.mode, state.display_paused.is_some())
+        };
+        self.reset_status_bar();
+        self.display_capture_info();
+        if paused {
+            return;
+        }
+
+        // new samples leave the plot dirty even while hidden, it renders once its tab shows
+        self.request_plot_redraw();
+        if mode == Mode::Stat {
+            self.display_rate_info();
+        }
+    }
+
+    fn display_rate_info(&self) {
+        let (throughput, window_throughput) = {
+            let state = self.state.borrow();
+            (state.throughput, state.window_throughput)
+        };
+        let window_secs = self.settings.borrow().rate_window_secs;
+        let rate = |r: Option<(f64, f64)>| r.map_or("-".to_string(), |(_, bytes)| self.display_byte_rate(bytes));
+        // a capture of one protocol is not to be taken for all of the traffic
+        let only = self.state.borrow().only_protocol
+            .map_or(String::new(), |protocol| format!("（仅捕获 {}）", protocol.name()));
+        self.stat_rate_info.set_text(format!(
+            "当前速率 {}，近{}秒平均速率 {}{}",
+            rate(throughput),
+            window_secs,
+            rate(window_throughput),
+            only,
+        ).as_str());
+        self.stat_drop_info.set_text(self.drop_summary().as_deref().unwrap_or(""));
+    }
+
+    /// the packets the capture likely missed so far, going by the ip stack counters
+    fn drop_summary(&self) -> Option<String> {
+        let state = self.state.borrow();
+        let mut reads = state.read_stats?;
+        if let Some(thread) = self.capture_thread.borrow().as_ref() {
+            reads.merge(&thread.reads());
+        }
+        let (before, after) = state.stack_counts;
+        let after = if state.capturing { ip_packet_counts().ok() } else { after };
+        Some(reads.drop_text(before.zip(after), state.only_protocol))
+    }
+
+    fn plot_mouse_move(&self) {
+        let position = nwg::GlobalCursor::local_position(&self.plot_graph, None);
+        let sample_interval = self.plot_records.borrow().sample_interval;
+        let mut state = self.state.borrow_mut();
+        let hover = state.plot_geometry.as_ref()
+            .and_then(|geometry| geometry.bucket_at(position, sample_interval))
+            .map(|idx| (position, idx));
+        // moving within the same bucket changes nothing on the chart, so skip the redraw
+        let mut changed = hover.map(|(_, idx)| idx) != state.plot_hover.map(|(_, idx)| idx);
+        state.plot_hover = hover;
+        if let (Some((start, end)), Some(geometry)) = (state.plot_drag, state.plot_geometry.as_ref()) {
+            let time = geometry.time_at(position.0);
+            changed |= time != end;
+            state.plot_drag = Some((start, time));
+        }
+        if changed {
+            self.request_plot_redraw();
+        }
+    }
+
+    fn plot_mouse_press(&self, event: nwg::Event) {
+        if self.plot_select.check_state() != nwg::CheckBoxState::Checked {
+            return;
+        }
+        let position = nwg::GlobalCursor::local_position(&self.plot_graph, None);
+        match event {
+            nwg::Event::OnMousePress(nwg::MousePressEvent::MousePressLeftDown) => {
+                let mut state = self.state.borrow_mut();
+                state.plot_drag = state.plot_geometry.as_ref()
+                    .filter(|geometry| geometry.contains(position))
+                    .map(|geometry| {
+                        let time = geometry.time_at(position.0);
+                        (time, time)
+                    });
+            }
+            nwg::Event::OnMousePress(nwg::MousePressEvent::MousePressLeftUp) => {
+                let drag = self.state.borrow_mut().plot_drag.take();
+                if let Some((start, end)) = drag {
+                    self.select_plot_range(start.min(end), start.max(end));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// filter the records down to `start..end`, in milliseconds since the plot start,
+    /// on top of whatever filter was there before the first selection
+    fn select_plot_range(&self, start: i64, end: i64) {
+        let start_time = match self.plot_records.borrow().start_time {
+            Some(start_time) => start_time,
+            None => return,
+        };
+        // a plain click selects the bucket under the cursor
+        let interval = PLOT_SAMPLING_INTERVAL as i64;
+        let (start, end) = if end - start < interval {
+            let start = start.div_euclid(interval) * interval;
+            (start, start + interval)
+        } else {
+            (start, end)
+        };
+        let range = FilterExpr::time_range(
+            start_time + Duration::milliseconds(start),
+            start_time + Duration::milliseconds(end),
+        );
+
+        let base = {
+            let mut state = self.state.borrow_mut();
+            state.plot_selection = Some(start..end);
+            state.selection_base_filter.get_or_insert_with(|| self.filter.text()).clone()
+        };
+        let text = if base.trim().is_empty() {
+            range.to_string()
+        } else {
+            format!("({}) && {}", base.trim(), range)
+        };
+        // setting the text applies it through `create_filter`, just like typing it
+        self.filter.set_text(&text);
+        self.request_plot_redraw();
+    }
+
+    fn clear_plot_selection(&self) {
+        let base = {
+            let mut state = self.state.borrow_mut();
+            state.plot_selection = None;
+            state.plot_drag = None;
+            state.selection_base_filter.take()
+        };
+        if let Some(base) = base {
+            self.filter.set_text(&base);
+        }
+        self.request_plot_redraw();
+    }
+
+    /// mark the plot dirty and schedule a redraw, multiple requests coalesce into one render
+    fn request_plot_redraw(&self) {
+        self.state.borrow_mut().plot_dirty = true;
+        self.plotting_timer.start();
+    }
+
+    fn display_plot_graph(&self) {
+        {
+            let mut state = self.state.borrow_mut();
+            // switching to the plot tab requests a redraw, so a hidden plot can wait until then
+            if !state.plot_dirty || state.mode != Mode::Plot {
+                return;
+            }
+            let min_interval = StdDuration::from_millis(self.settings.borrow().plot_redraw_interval_ms);
+            if state.last_plot_draw.map_or(false, |last| last.elapsed() < min_interval) {
+                self.plotting_timer.start();
+                return;
+            }
+            state.plot_dirty = false;
+            state.last_plot_draw = Some(Instant::now());
+        }
+        if let Err(err) = self.display_plot_graph_with_result() {
+            self.log(Level::Error, format!("无法绘制图表：{:#}", err));
+        }
+    }
+
+    fn display_plot_graph_with_result(&self) -> Result<()> {
+        let records = self.plot_records.borrow();
+
+        let graph = self.plot_graph.draw()?;
+        graph.fill(&self.state.borrow().plot_theme.background)?;
+
+        // a paused display ends the axis at the pause, later buckets wait for the resume
+        let paused = self.state.borrow().display_paused;
+        let end_time = records.end_time.map(|t| paused.map_or(t, |paused| t.min(paused)));
+        let max_time = if let (Some(start_time), Some(end_time)) = (records.start_time, end_time) {
+            end_time - start_time
+        } else {
+            Duration::seconds(10)
+        };
+
+        // while capturing the axis ends at the latest sample and spans at least 10 seconds,
+        // or exactly the rolling window when enabled; a stopped capture always shows in full
+        let (rolling, rolling_secs) = {
+            let settings = self.settings.borrow();
+            (settings.plot_rolling, settings.plot_window_secs)
+        };
+        let time_range = if self.state.borrow().capturing {
+            let span = if rolling {
+                Duration::seconds(rolling_secs as i64)
+            } else {
+                max_time.max(Duration::seconds(10))
+            };
+            (max_time - span).num_milliseconds()..max_time.num_milliseconds()
+        } else {
+            0..max_time.num_milliseconds()
+        };
+
+        // only the buckets within the time range are fed into the series
+        let first_bucket = (time_range.start.max(0) / PLOT_SAMPLING_INTERVAL as i64) as usize;
+        let end_bucket = ((max_time.num_milliseconds() / PLOT_SAMPLING_INTERVAL as i64) as usize).min(records.records.len());
+        let buckets = &records.records[first_bucket.min(end_bucket)..end_bucket];
+
+        // buckets are plotted as per second rates, independent of the sampling interval
+        let per_sec = records.per_second();
+        let series = self.state.borrow().plot_series;
+        let nums = buckets.iter().map(|r| r.packet_num as f64 * per_sec).collect::<Vec<_>>();
+        let lens = buckets.iter().map(|r| r.byte_num as f64 * per_sec).collect::<Vec<_>>();
+
+        // running sums over the shown protocols, so each layer sits on top of the previous ones
+        let shown_protocols = PlotProtocol::ALL.iter()
+            .copied()
+            .filter(|&proto| series.protocols[proto as usize])
+            .collect::<Vec<_>>();
+        let mut stacked = vec![Vec::new(); shown_protocols.len()];
+        if records.by_protocol {
+            let end_bucket = end_bucket.min(records.protocol_records.len());
+            let protocol_buckets = &records.protocol_records[first_bucket.min(end_bucket)..end_bucket];
+            for bucket in protocol_buckets.iter() {
+                let mut sum = 0.0;
+                for (layer, &proto) in stacked.iter_mut().zip(shown_protocols.iter()) {
+                    sum += bucket.get(proto) as f64 * per_sec;
+                    layer.push(sum);
+                }
+            }
+        }
+
+        // hidden series do not count, so the shown ones scale up to fill the chart
+        let max_of = |values: &[f64]| values.iter().copied().fold(10f64, f64::max);
+        let max_num = if series.packets { max_of(&nums) } else { 10f64 };
+        let max_len = match (series.bytes, records.by_protocol) {
+            (false, _) => 10f64,
+            (true, true) => stacked.last().map_or(10f64, |top| max_of(top)),
+            (true, false) => max_of(&lens),
+        };
+
+        // the split layout puts packets in an upper chart and bytes in a lower one; both charts
+        // get the same time range and label areas, so their x axes line up
+        let split = self.settings.borrow().plot_split;
+        let areas = graph.split_evenly((if split { 2 } else { 1 }, 1));
+        let mut charts = Vec::with_capacity(areas.len());
+        for area in areas.iter() {
+            charts.push(ChartBuilder::on(area)
+                .margin_left(scaled(10) as u32)
+                .margin_right(scaled(10) as u32)
+                .x_label_area_size(scaled(30) as u32)
+                .y_label_area_size(scaled(60) as u32)
+                .right_y_label_area_size(scaled(60) as u32)
+                .build_cartesian_2d(time_range.clone(), 0f64..max_num)?
+                .set_secondary_coord(time_range.clone(), 0f64..max_len));
+        }
+        let (num_chart, len_chart) = (0, charts.len() - 1);
+
+        let (x_pixels, y_pixels) = charts[num_chart].plotting_area().get_pixel_range();
+        let y_pixels = y_pixels.start..charts[len_chart].plotting_area().get_pixel_range().1.end;
+        // long captures have far more buckets than pixels, so merge adjacent ones keeping their peaks;
+        // a narrower time range has fewer buckets and gets full resolution back
+        let max_points = (x_pixels.end - x_pixels.start).max(1) as usize;
+        let chunk = downsample_chunk(buckets.len(), max_points.min(MAX_PLOT_POINTS));
+        self.state.borrow_mut().plot_geometry = Some(PlotGeometry {
+            x_pixels: x_pixels.clone(),
+            y_pixels,
+            time_range: time_range.clone(),
+        });
+
+        let theme = self.state.borrow().plot_theme;
+        let num_color = theme.packets;
+        let len_color = theme.bytes;
+        let label_style = TextStyle::from(("Segoe UI", scaled(12))).color(&theme.text);
+
+        // as many time labels as fit the plotting area, only as precise as the visible span needs
+        let label_format = time_label_format(Duration::milliseconds(time_range.end - time_range.start));
+        let start_time = records.start_time;
+        let x_formatter = |x: &i64| match start_time {
+            Some(start_time) => (start_time + Duration::milliseconds(*x)).format(label_format).to_string(),
+            None => String::new(),
+        };
+        let (label_width, _) = graph.estimate_text_size(&x_formatter(&time_range.end), &label_style)?;
+        let x_labels = max_axis_labels((x_pixels.end - x_pixels.start).max(0) as u32, label_width);
+
+        // every tick of an axis uses the unit picked from its maximum
+        let num_unit = AxisUnit::packets(max_num);
+        let len_unit = if self.settings.borrow().humanize_bytes {
+            AxisUnit::bytes(max_len)
+        } else {
+            AxisUnit::bytes(0.0)
+        };
+        let num_formatter = |y: &f64| format!("{}{} 包", num_unit.scale(*y), num_unit.symbol);
+        for (idx, chart) in charts.iter_mut().enumerate() {
+            let mut mesh = chart.configure_mesh();
+            mesh.light_line_style(ShapeStyle { color: TRANSPARENT, filled: false, stroke_width: 0 })
+                .x_labels(x_labels)
+                .x_label_formatter(&x_formatter)
+                .y_label_formatter(&num_formatter)
+                .axis_style(ShapeStyle::from(num_color))
+                .label_style(label_style.clone());
+            if idx != num_chart {
+                mesh.disable_y_axis();
+            }
+            mesh.draw()?;
+        }
+
+        let selection = {
+            let state = self.state.borrow();
+            state.plot_drag
+                .map(|(start, end)| start.min(end)..start.max(end))
+                .or_else(|| state.plot_selection.clone())
+        };
+        if let Some(selection) = selection {
+            for chart in charts.iter_mut() {
+                chart.draw_series(iter::once(Rectangle::new(
+                    [(selection.start, 0.0), (selection.end, max_num)],
+                    theme.overlay.mix(0.15).filled()
+                )))?;
+            }
+        }
+
+        // event markers, as offsets from the plot start like the rest of the x axis
+        let events = match records.start_time {
+            Some(start_time) => self.state.borrow().plot_events.iter()
+                .map(|event| ((event.time - start_time).num_milliseconds(), event.kind.label()))
+                .filter(|(x, _)| time_range.contains(x))
+                .collect::<Vec<_>>(),
+            None => Vec::new(),
+        };
+        let marker_style = theme.overlay.mix(0.6);
+        for &(x, label) in events.iter() {
+            let points = (0..=20).map(|i| (x, max_num * i as f64 / 20.0)).collect::<Vec<_>>();
+            for chart in charts.iter_mut() {
+                chart.draw_series(dashed_path(&points, ShapeStyle::from(&marker_style)))?;
+            }
+            charts[num_chart].draw_series(iter::once(Text::new(
+                label,
+                (x, max_num),
+                TextStyle::from(("Segoe UI", scaled(10))).color(&theme.text)
+            )))?;
+        }
+
+        let y_formatter = |y: &f64| format!("{} {}", len_unit.scale(*y), len_unit.symbol);
+        charts[len_chart].configure_secondary_axes()
+            .y_label_formatter(&y_formatter)
+            .axis_style(ShapeStyle::from(len_color))
+            .label_style(label_style.clone())
+            .draw()?;
+
+        // let time_samples = (0..records.records.len() as u64).map(|idx| (idx * PLOT_SAMPLING_INTERVAL) as i64);
+        let time_samples = (first_bucket as i64 * PLOT_SAMPLING_INTERVAL as i64..max_time.num_milliseconds())
+            .step_by(PLOT_SAMPLING_INTERVAL as usize * chunk);
+        if series.packets {
+            let data = time_samples.clone().zip(downsample_max(&nums, chunk));
+            charts[num_chart]
+                .draw_series(LineSeries::new(data.clone(),&num_color))?
+                .label(format!("分组/秒（{}包）", num_unit.symbol))
+                .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], &num_color));
+            charts[num_chart]
+                .draw_series(AreaSeries::new(
+                    data.clone(),
+                    0.0,
+                    num_color.mix(0.2)
+                ))?;
+        }
+
+        if series.bytes && records.by_protocol {
+            // draw the tallest layer first so the lower ones are painted over it
+            for (layer, &proto) in stacked.iter().zip(shown_protocols.iter()).rev() {
+                let color = theme.protocols[proto as usize];
+                charts[len_chart]
+                    .draw_secondary_series(AreaSeries::new(
+                        time_samples.clone().zip(downsample_max(layer, chunk)),
+                        0.0,
+                        &color.mix(0.6)
+                    ).border_style(&color))?
+                    .label(format!("{} 字节/秒（{}）", proto, len_unit.symbol))
+                    .legend(move |(x, y)| Rectangle::new([(x, y - 5), (x + 20, y + 5)], color.filled()));
+            }
+        } else if series.bytes {
+            let data = time_samples.clone().zip(downsample_max(&lens, chunk));
+            charts[len_chart]
+                .draw_secondary_series(LineSeries::new(data.clone(),&len_color))?
+                .label(format!("字节/秒（{}）", len_unit.symbol))
+                .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], &len_color));
+            charts[len_chart]
+                .draw_secondary_series(AreaSeries::new(
+                    data.clone(),
+                    0.0,
+                    len_color.mix(0.2)
+                ))?;
+        }
+
+        // gaps are covered by a hatched band, hiding the zeros the series drew there
+        if let Some(start_time) = records.start_time {
+            for &(gap_start, gap_end) in records.gaps.iter() {
+                let start = (gap_start - start_time).num_milliseconds().max(time_range.start);
+                let end = (gap_end - start_time).num_milliseconds().min(time_range.end);
+                if start >= end {
+                    continue;
+                }
+                for chart in charts.iter() {
+                    let (left, top) = chart.backend_coord(&(start, max_num));
+                    let (right, bottom) = chart.backend_coord(&(end, 0.0));
+                    graph.draw(&Rectangle::new([(left, top), (right, bottom)], theme.background.filled()))?;
+                    graph.draw(&Rectangle::new([(left, top), (right, bottom)], theme.overlay.mix(0.1).filled()))?;
+                    for line in hatch_lines(left..right, top..bottom, 8) {
+                        graph.draw(&PathElement::new(line, &theme.overlay.mix(0.3)))?;
+                    }
+                }
+            }
+        }
+
+        let (smoothing, smoothing_window) = {
+            let settings = self.settings.borrow();
+            (settings.plot_smoothing, settings.smoothing_window)
+        };
+        // averaged over the whole session, so the line does not restart at the left edge
+        if smoothing && series.packets {
+            let session_nums = records.records.iter().map(|r| r.packet_num as f64 * per_sec).collect::<Vec<_>>();
+            let averages = moving_average(&session_nums, smoothing_window).split_off(first_bucket.min(session_nums.len()));
+            let data = time_samples.clone()
+                .zip(downsample_max(&averages, chunk))
+                .collect::<Vec<_>>();
+            charts[num_chart]
+                .draw_series(dashed_path(&data, num_color.stroke_width(2)))?
+                .label(format!("分组/秒（{}包，移动平均）", num_unit.symbol))
+                .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 8, y)], num_color.stroke_width(2)));
+        }
+        if smoothing && series.bytes {
+            let session_lens = records.records.iter().map(|r| r.byte_num as f64 * per_sec).collect::<Vec<_>>();
+            let averages = moving_average(&session_lens, smoothing_window).split_off(first_bucket.min(session_lens.len()));
+            let data = time_samples.clone()
+                .zip(downsample_max(&averages, chunk))
+                .collect::<Vec<_>>();
+            charts[len_chart]
+                .draw_secondary_series(dashed_path(&data, len_color.stroke_width(2)))?
+                .label(format!("字节/秒（{}，移动平均）", len_unit.symbol))
+                .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 8, y)], len_color.stroke_width(2)));
+        }
+
+        for chart in charts.iter_mut() {
+            chart
+                .configure_series_labels()
+                .label_font(label_style.clone())
+                .background_style(&theme.legend_background.mix(0.8))
+                .border_style(&theme.overlay)
+                .draw()?;
+        }
+
+        let hover = self.state.borrow().plot_hover
+            .map(|(_, idx)| idx)
+            .filter(|&idx| idx < records.records.len());
+        if let (Some(idx), Some(start_time)) = (hover, records.start_time) {
+            let bucket = &records.records[idx];
+            let time = records.sample_interval * idx as i32;
+            let x = time.num_milliseconds();
+            let packets = bucket.packet_num as f64 * per_sec;
+            let bytes = bucket.byte_num as f64 * per_sec;
+
+            for chart in charts.iter_mut() {
+                chart.draw_series(iter::once(PathElement::new(vec![(x, 0.0), (x, max_num)], &theme.overlay.mix(0.5))))?;
+            }
+            if series.packets {
+                charts[num_chart].draw_series(iter::once(Circle::new((x, packets), 3, num_color.filled())))?;
+            }
+            if series.bytes {
+                charts[len_chart].draw_secondary_series(iter::once(Circle::new((x, bytes), 3, len_color.filled())))?;
+            }
+
+            let mut text = if records.is_gap(idx) {
+                format!("{} — 无数据", (start_time + time).format("%H:%M:%S%.3f"))
+            } else {
+                format!(
+                    "{} — {:.0} 包/秒，{}",
+                    (start_time + time).format("%H:%M:%S%.3f"),
+                    packets,
+                    self.display_byte_rate(bytes)
+                )
+            };
+            // events within the hovered bucket are spelled out in full
+            let bucket_end = start_time + time + records.sample_interval;
+            for event in self.state.borrow().plot_events.iter() {
+                if event.time >= start_time + time && event.time < bucket_end {
+                    text.push_str("；");
+                    text.push_str(&event.text);
+                }
+            }
+            let font = label_style.clone();
+            let (text_width, text_height) = graph.estimate_text_size(&text, &font)?;
+            let (width, height) = (text_width as i32 + 10, text_height as i32 + 8);
+            let (x_pixels, y_pixels) = charts[num_chart].plotting_area().get_pixel_range();
+            let (cursor_x, _) = charts[num_chart].backend_coord(&(x, 0.0));
+            // keep the box inside the plotting area, flipping it to the left of the line near the edge
+            let left = if cursor_x + 10 + width > x_pixels.end {
+                cursor_x - 10 - width
+            } else {
+                cursor_x + 10
+            };
+            let top = y_pixels.start + 10;
+            graph.draw(&Rectangle::new([(left, top), (left + width, top + height)], theme.legend_background.mix(0.9).filled()))?;
+            graph.draw(&Rectangle::new([(left, top), (left + width, top + height)], &theme.overlay))?;
+            graph.draw(&Text::new(text, (left + 5, top + 4), font))?;
+        }
+
+        Ok(())
+    }
+
+    /// one line summary of a finished capture, `None` while capturing or before any capture
+    fn session_summary(&self) -> Option<String> {
+        let (start_time, end_time) = {
+            let state = self.state.borrow();
+            if state.capturing {
+                return None;
+            }
+            (state.start_time?, state.end_time?)
+        };
+        let duration = end_time - start_time;
+        let byte_num = self.stat_records.borrow().stat_net_table.byte_num;
+        // time the sampler was not running says nothing about the traffic, leave it out of the average
+        let sampled = duration - self.plot_records.borrow().gap_duration();
+        let secs = sampled.num_milliseconds() as f64 / 1000.0;
+        let average = if secs > 0.0 { byte_num as f64 / secs } else { 0.0 };
+        let peak = match self.plot_records.borrow().peak_rate() {
+            Some((time, rate)) if rate > 0.0 => format!(
+                "，峰值 {}（于 {}）",
+                self.display_byte_rate(rate),
+                time.format("%H:%M:%S"),
+            ),
+            _ => String::new(),
+        };
+        Some(format!(
+            "捕获 {}，共 {}，平均 {}{}",
+            format_duration(duration),
+            self.display_bytes_text(byte_num),
+            self.display_byte_rate(average),
+            peak,
+        ))
+    }
+
+    fn refresh_stat_table(&self) {
+        let (dirty, mode) = {
+            let state = self.state.borrow();
+            (state.stat_dirty, state.mode)
+        };
+        if dirty && mode == Mode::Stat && self.state.borrow().display_paused.is_none() {
+            self.display_stat_table();
+        }
+    }
+
+    /// the rows of the transport layer stat table, sorted by protocol, with the totals last
+    fn stat_trans_rows(&self, stat_records: &StatRecord) -> Vec<Vec<String>> {
+        let bytes = |n| self.display_bytes(n);
+        let mut trans_records = stat_records.stat_trans_table.iter().collect::<Vec<_>>();
+        trans_records.sort_by(|a, b| a.0.cmp(b.0));
+        let mut rows = trans_records.into_iter()
+            .map(|(proto, record)| iter::once(proto.to_string()).chain(record.to_display_array(&bytes).into_iter()).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        // the totals row always goes last, after the sorted protocol rows
+        if !rows.is_empty() {
+            let trans_total = stat_records.trans_total();
+            rows.push(iter::once("合计".to_string()).chain(trans_total.to_display_array(&bytes).into_iter()).collect());
+        }
+        rows
+    }
+
+    /// the rows of the application layer stat table, sorted by protocol, with the totals last
+    fn stat_app_rows(&self, stat_records: &StatRecord) -> Vec<Vec<String>> {
+        let bytes = |n| self.display_bytes(n);
+        let mut app_records = stat_records.stat_app_table.iter().collect::<Vec<_>>();
+        app_records.sort_by(|a, b| a.0.cmp(b.0));
+        let mut rows = app_records.into_iter()
+            .map(|(proto, record)| iter::once(proto.to_string()).chain(record.to_display_array(&bytes).into_iter()).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        if !rows.is_empty() {
+            let (trans_total, app_total) = (stat_records.trans_total(), stat_records.app_total());
+            // share of transport layer bytes carried by tcp/udp, the rest is other protocols
+            let coverage = if trans_total.byte_num != 0 {
+                format!("合计（占传输层 {:.1}%）", app_total.byte_num_in_trans as f64 * 100.0 / trans_total.byte_num as f64)
+            } else {
+                "合计".to_string()
+            };
+            rows.push(iter::once(coverage).chain(app_total.to_display_array(&bytes).into_iter()).collect());
+        }
+        rows
+    }
+
+    fn display_stat_table(&self) {
+        self.state.borrow_mut().stat_dirty = false;
+        let stat_records = self.stat_records.borrow();
+        self.stat_net_info.set_text(format!(
+            "统计结果：{} 个 IPv4 分组，共 {}；接收 {} 包 / {}，发送 {} 包 / {}", 
+            stat_records.stat_net_table.packet_num, 
+            self.display_bytes_text(stat_records.stat_net_table.byte_num),
+            stat_records.stat_net_inbound.packet_num,
+            self.display_bytes_text(stat_records.stat_net_inbound.byte_num),
+            stat_records.stat_net_outbound.packet_num,
+            self.display_bytes_text(stat_records.stat_net_outbound.byte_num),
+        ).as_str());
+        self.stat_session_info.set_text(self.session_summary().as_deref().unwrap_or(""));
+        self.display_rate_info();
+        self.display_protocol_share(&stat_records.protocol_share(MIN_PROTOCOL_SHARE));
+
+        self.stat_trans_table.clear();
+        for (idx, row) in self.stat_trans_rows(&stat_records).iter().enumerate() {
+            self.stat_trans_table.insert_items_row(Some(idx as i32), row.as_slice());
+        }
+        self.stat_app_table.clear();
+        for (idx, row) in self.stat_app_rows(&stat_records).iter().enumerate() {
+            self.stat_app_table.insert_items_row(Some(idx as i32), row.as_slice());
+        }
+
+        let dns = &stat_records.stat_dns;
+        self.stat_dns_info.set_text(format!(
+            "DNS 统计结果：查询 {} 次，响应 {} 次（应答率 {}），NXDOMAIN {} 次，ServFail {} 次，无法解析 {} 个",
+            dns.queries,
+            dns.responses,
+            dns.answered_ratio().map_or("-".to_string(), |r| format!("{:.1}%", r * 100.0)),
+            dns.nxdomain,
+            dns.servfail,
+            dns.malformed,
+        ).as_str());
+
+        self.stat_dns_table.clear();
+        for (idx, (name, count)) in dns.top_names(10).into_iter().enumerate() {
+            self.stat_dns_table.insert_items_row(Some(idx as i32), &[name.to_string(), count.to_string()]);
+        }
+    }
+
+    fn display_protocol_share(&self, shares: &[(String, f64)]) {
+        self.stat_share_chart.set_visible(!shares.is_empty());
+        if shares.is_empty() {
+            return;
+        }
+        if let Err(err) = self.display_protocol_share_with_result(shares) {
+            self.log(Level::Error, format!("无法绘制协议占比图：{:#}", err));
+        }
+    }
+
+    fn display_protocol_share_with_result(&self, shares: &[(String, f64)]) -> Result<()> {
+        let theme = self.state.borrow().plot_theme;
+        let graph = self.stat_share_chart.draw()?;
+        graph.fill(&theme.background)?;
+
+        let (width, height) = graph.dim_in_pixel();
+        let font = TextStyle::from(("Segoe UI", scaled(12))).color(&theme.text);
+        // leave room for the protocol name on the left and the percentage on the right
+        let (label_width, value_width) = (scaled(60), scaled(60));
+        let bar_width = (width as i32 - label_width - value_width).max(0) as f64;
+        let row_height = (height as i32 / shares.len() as i32).min(scaled(24));
+        for (idx, (proto, share)) in shares.iter().enumerate() {
+            let top = idx as i32 * row_height;
+            let color = PlotProtocol::ALL.iter()
+                .find(|p| p.to_string() == *proto)
+                .map_or(theme.protocols[PlotProtocol::Other as usize], |&p| theme.protocols[p as usize]);
+            let bar_end = label_width + (bar_width * share) as i32;
+            graph.draw(&Text::new(proto.as_str(), (5, top + 4), font.clone()))?;
+            graph.draw(&Rectangle::new([(label_width, top + 3), (bar_end, top + row_height - 3)], color.filled()))?;
+            graph.draw(&Text::new(format!("{:.1}%", share * 100.0), (bar_end + 5, top + 4), font.clone()))?;
+        }
+        Ok(())
+    }
+
+    fn update_record(&self, record: Record) {
+        let (to_record, to_plot, to_stat) = {
+            let state = self.state.borrow();
+            // a rebuild in progress gets to the record on its own
+            let (table_rebuilt, stats_rebuilt) = match state.rebuild.as_ref() {
+                Some(rebuild) => (true, rebuild.stats),
+                None => (false, false),
+            };
+            (
+                !table_rebuilt && state.passes(Mode::Record, &record),
+                state.passes(Mode::Plot, &record),
+                !stats_rebuilt && state.passes(Mode::Stat, &record),
+            )
+        };
+        if to_stat {
+            self.stat_records.borrow_mut().update(&record);
+        }
+        if to_plot {
+            self.update_plot_data(&record);
+        }
+
+        let (mode, paused) = {
+            let state = self.state.borrow();
+            (state.mode, state.display_paused.is_some())
+        };
+        if !paused {
+            match mode {
+                Mode::Record if to_record => self.update_record_table(&record),
+                Mode::Stat if to_stat => self.state.borrow_mut().stat_dirty = true,
+                _ => {},
+            }
+        }
+
+        // the store takes the record last so it moves in instead of being cloned,
+        // rows of the records it evicts go after the new row is in
+        let evicted = self.state.borrow_mut().records.push(record);
+        if !evicted.is_empty() {
+            self.remove_evicted_rows(&evicted);
+        }
+    }
+
+    /// drop the rows of records evicted from the store; stats and plot keep counting them
+    fn remove_evicted_rows(&self, evicted: &[Record]) {
+        let removed = {
+            let state = self.state.borrow();
+            // a paused or hidden table is rebuilt from the store when it shows again
+            if state.mode != Mode::Record || state.display_paused.is_some() {
+                return;
+            }
+            // a rebuild in progress has only added rows for the records before `next`
+            let first = state.records.evicted() - evicted.len() as u64;
+            let added = |idx: usize| state.rebuild.as_ref().map_or(true, |rebuild| first + (idx as u64) < rebuild.next);
+            evicted.iter()
+                .enumerate()
+                .filter(|&(idx, r)| added(idx) && state.passes(Mode::Record, r))
+                .map(|(_, r)| r)
+                .collect::<Vec<_>>()
+        };
+        let rows = removed.len();
+        for _ in 0..rows.min(self.record_table.len()) {
+            self.record_table.remove_item(0);
+        }
+        let mut state = self.state.borrow_mut();
+        let rows = rows.min(state.record_rows.len());
+        state.record_rows.drain(..rows);
+        state.search_row = state.search_row.and_then(|row| row.checked_sub(rows));
+        let grouped = state.group_by_flow;
+        for record in removed {
+            match state.record_flows.remove(record) {
+                // no "0 包" header is left behind by a conversation evicted whole
+                Some(flow) if grouped && flow.packet_num == 0 => remove_group(&self.record_table, flow.id),
+                Some(flow) if grouped => set_group_header(&self.record_table, flow.id, &self.flow_header(&flow), false),
+                _ => {}
+            }
+        }
+    }
+
+    fn update_record_table(&self, record: &Record) {
+        let (columns, auto_scroll) = {
+            let settings = self.settings.borrow();
+            (settings.visible_columns(), settings.auto_scroll)
+        };
+        let grouped = self.state.borrow().group_by_flow;
+        // scrolling up to an older row pauses following until the view is back at the bottom;
+        // grouped rows are not in arrival order, so there is no bottom to follow
+        let follow = auto_scroll && !grouped && is_scrolled_to_end(&self.record_table);
+        self.record_table.insert_items_row(None, &self.row_strings(record, &columns));
+        let mut state = self.state.borrow_mut();
+        // the store takes the record right after its row is in
+        let position = state.records.evicted() + state.records.len() as u64;
+        state.record_rows.push_back(position);
+        let flow = state.record_flows.add(record);
+        if grouped {
+            let header = self.flow_header(flow);
+            if flow.packet_num == 1 {
+                insert_group(&self.record_table, flow.id, &header, false);
             } else {
-                self.plotting_sample_timer.stop();
+                // a conversation growing past the limit folds away once, the user may open it again
+                set_group_header(&self.record_table, flow.id, &header, flow.packet_num == FLOW_COLLAPSE_ROWS + 1);
             }
+            set_item_group(&self.record_table, self.record_table.len() - 1, flow.id);
         }
+        drop(state);
+        if follow {
+            scroll_to_end(&self.record_table);
+        }
+    }
 
-        match mode {
-            Mode::Record => self.rebuild_record_table(),
-            Mode::Plot => self.plotting_timer.start(),
-            Mode::Stat => self.display_stat_table(),
-            Mode::About => {},
+    fn tick(&self) {
+        let started = Instant::now();
+        let mut drained = 0;
+        for _ in 0..TICK_RECORDS {
+            if started.elapsed() >= TICK_BUDGET {
+                break;
+            }
+            let event = match self.capture_thread.borrow().as_ref().and_then(CaptureThread::next) {
+                Some(event) => event,
+                None => break,
+            };
+            drained += 1;
+            if let Some(lost) = self.capture_event(event) {
+                self.adapter_lost(&lost);
+            }
+            // the last adapter lost stops the capture, whatever else was drained is gone with it;
+            // the thread went with it too, so this drain is counted with those it already made
+            if !self.state.borrow().capturing {
+                self.state.borrow_mut().capture_drains.drained(drained);
+                return;
+            }
+        }
+        if let Some(thread) = self.capture_thread.borrow().as_ref() {
+            thread.drained(drained);
+        }
+        // nothing was bound to begin with, or reconnecting bound nothing
+        let finished = self.capture_thread.borrow().as_ref().map_or(false, |thread| thread.finished());
+        // an adapter waiting for its new address is bound again by `rebind_addresses`
+        if finished && self.state.borrow().rebind_attempts.is_none() {
+            self.stop_capture();
+        }
+    }
+
+    /// count in a record from the capture thread, handing back a lost adapter for the caller to deal with
+    fn capture_event(&self, event: CaptureEvent) -> Option<AdapterLost> {
+        match event {
+            CaptureEvent::Record(record) => self.update_record(record),
+            CaptureEvent::Lost(lost) => return Some(lost),
+            // passing, the next read may well succeed
+            CaptureEvent::Failed(err) => self.log(Level::Warn, format!("读取分组失败：{:#}", err)),
+        }
+        None
+    }
+
+    /// whether some adapter is bound, by the capture thread while capturing
+    fn connected(&self) -> bool {
+        self.capture_thread.borrow().is_some() || self.capturer.borrow().connected()
+    }
+
+    fn window_maximize(&self) {
+        self.request_plot_redraw();
+    }
+
+    fn window_resize(&self) {
+        self.layout_status_bar();
+        self.stat_layout_timer.start();
+        self.request_plot_redraw();
+    }
+
+    /// stack the stat tables in their frame, each under its title, split by `stat_split_ratio`;
+    /// a collapsed table leaves only its title and the other one takes the rest
+    fn layout_stat_tables(&self) {
+        let (width, height) = self.stat_tables_frame.size();
+        let settings = self.settings.borrow();
+        let (trans_shown, app_shown) = (!settings.stat_trans_collapsed, !settings.stat_app_collapsed);
+        let splitter = if trans_shown && app_shown { STAT_SPLITTER_HEIGHT } else { 0 };
+        let available = height.saturating_sub(2 * STAT_TITLE_HEIGHT + splitter);
+        let trans_height = match (trans_shown, app_shown) {
+            (true, true) => (available as f64 * settings.stat_split_ratio).round() as u32,
+            (true, false) => available,
+            (false, _) => 0,
+        };
+        let app_height = if app_shown { available.saturating_sub(trans_height) } else { 0 };
+
+        let place = |label: &nwg::Label, button: &nwg::Button, table: &nwg::ListView, top: u32, table_height: u32| {
+            let title_width = width.saturating_sub(STAT_COLLAPSE_WIDTH);
+            label.set_position(0, top as i32);
+            label.set_size(title_width, STAT_TITLE_HEIGHT);
+            button.set_position(title_width as i32, top as i32);
+            button.set_size(STAT_COLLAPSE_WIDTH, STAT_TITLE_HEIGHT);
+            button.set_text(if table_height > 0 { "▲" } else { "▼" });
+            table.set_visible(table_height > 0);
+            table.set_position(0, (top + STAT_TITLE_HEIGHT) as i32);
+            table.set_size(width, table_height);
         };
+        place(&self.stat_trans_label, &self.stat_trans_collapse, &self.stat_trans_table, 0, trans_height);
+        let splitter_top = STAT_TITLE_HEIGHT + trans_height;
+        self.stat_splitter.set_visible(splitter > 0);
+        self.stat_splitter.set_position(0, splitter_top as i32);
+        self.stat_splitter.set_size(width, splitter);
+        place(&self.stat_app_label, &self.stat_app_collapse, &self.stat_app_table, splitter_top + splitter, app_height);
+    }
 
-        self.state.borrow_mut().mode = mode;
+    fn toggle_stat_trans_collapsed(&self) {
+        {
+            let mut settings = self.settings.borrow_mut();
+            settings.stat_trans_collapsed = !settings.stat_trans_collapsed;
+        }
+        self.save_settings();
+        self.layout_stat_tables();
     }
 
-    fn set_timeout(&self) {
-        let text = self.timeout.text();
-        let text = text.trim();
-        if text.is_empty() {
-            self.capturing_timer.set_lifetime(None);
+    fn toggle_stat_app_collapsed(&self) {
+        {
+            let mut settings = self.settings.borrow_mut();
+            settings.stat_app_collapsed = !settings.stat_app_collapsed;
+        }
+        self.save_settings();
+        self.layout_stat_tables();
+    }
+
+    fn stat_splitter_press(&self, event: nwg::Event) {
+        let hwnd = match self.stat_splitter.handle.hwnd() {
+            Some(hwnd) => hwnd,
+            None => return,
+        };
+        match event {
+            nwg::Event::OnMousePress(nwg::MousePressEvent::MousePressLeftDown) => {
+                // keep getting the moves when the cursor outruns the thin splitter
+                unsafe { SetCapture(hwnd) };
+                self.state.borrow_mut().stat_split_drag = true;
+            }
+            nwg::Event::OnMousePress(nwg::MousePressEvent::MousePressLeftUp) => {
+                unsafe { ReleaseCapture() };
+                self.state.borrow_mut().stat_split_drag = false;
+                self.save_settings();
+            }
+            _ => {}
+        }
+    }
+
+    fn stat_splitter_move(&self) {
+        unsafe { SetCursor(LoadCursorW(ptr::null_mut(), IDC_SIZENS)) };
+        if !self.state.borrow().stat_split_drag {
+            return;
+        }
+        // the cursor is in physical pixels, the frame size in logical ones
+        let (_, y) = nwg::GlobalCursor::local_position(&self.stat_tables_frame, None);
+        let y = y as f64 / nwg::scale_factor();
+        let (_, height) = self.stat_tables_frame.size();
+        let available = height as f64 - (2 * STAT_TITLE_HEIGHT + STAT_SPLITTER_HEIGHT) as f64;
+        if available <= 0.0 {
+            return;
+        }
+        let ratio = (y - STAT_TITLE_HEIGHT as f64) / available;
+        self.settings.borrow_mut().stat_split_ratio = clamp_stat_split_ratio(ratio);
+        self.layout_stat_tables();
+    }
+
+    /// status, record counts and elapsed time, the last two sections sized for their usual text
+    fn layout_status_bar(&self) {
+        let width = self.window.size().0 as i32;
+        let parts = [(width - 520).max(100), (width - 100).max(200), -1];
+        if let Some(hwnd) = self.status_bar.handle.hwnd() {
+            unsafe {
+                SendMessageW(hwnd, SB_SETPARTS, parts.len(), parts.as_ptr() as _);
+            }
+        }
+    }
+
+    fn display_capture_info(&self) {
+        let state = self.state.borrow();
+        // every record passing the stat filter is counted into the stats, so that total is the filtered count
+        let filtered = self.stat_records.borrow().stat_net_table.packet_num;
+        let captured = state.records.len() as u64 + state.records.evicted();
+        let mut count = if state.filter_of(Mode::Stat).is_some() {
+            format!("已捕获 {} 条记录（筛选后 {} 条）", format_count(captured), format_count(filtered))
         } else {
-            if let Ok(timeout) = text.parse::<u64>() {
-                self.capturing_timer.set_lifetime(Some(StdDuration::from_millis(timeout)));
-            } else {
-                self.capturing_timer.set_lifetime(None);
-                self.status_bar.set_text(0, "捕获时间不正确");
-                return;
+            format!("已捕获 {} 条记录", format_count(captured))
+        };
+        if state.records.evicted() > 0 {
+            count += &format!("，已丢弃最早的 {} 条", format_count(state.records.evicted()));
+        }
+        self.status_bar.set_text(1, &count);
+        let elapsed = match (state.start_time, state.end_time) {
+            (Some(start_time), Some(end_time)) => format_duration(end_time - start_time),
+            (Some(start_time), None) if state.capturing => format_duration(Local::now() - start_time),
+            _ => String::new(),
+        };
+        self.status_bar.set_text(2, &elapsed);
+    }
+
+    fn window_close(&self, data: &nwg::EventData) {
+        if self.state.borrow().capturing && !self.confirm_close() {
+            if let nwg::EventData::OnWindowClose(close) = data {
+                close.close(false);
             }
+            return;
         }
-        self.reset_status_bar();
+        self.shut_down();
+    }
+
+    /// end the capture and let go of the adapters, keeping the settings, then leave the event loop
+    fn shut_down(&self) {
+        self.cancel_schedule();
+        self.stop_capture();
+        self.capturer.borrow_mut().disconnect();
+        // column widths are only read back here, there is no event for the user dragging them
+        self.store_column_widths();
+        self.save_settings();
+        nwg::stop_thread_dispatch();
+    }
+
+    /// ask what to do with the running capture, returns whether to go on closing
+    fn confirm_close(&self) -> bool {
+        // the schedule must not start another run while the question is open
+        self.schedule_timer.stop();
+        let choice = nwg::modal_message(&self.window, &nwg::MessageParams {
+            title: "正在捕获",
+            content: "捕获仍在进行，退出前是否导出记录？\n\n是：停止并导出…\n否：直接退出\n取消：继续捕获",
+            buttons: nwg::MessageButtons::YesNoCancel,
+            icons: nwg::MessageIcons::Warning,
+        });
+        let confirmed = match choice {
+            // cancelling the file dialog cancels closing too, before the capture is stopped
+            nwg::MessageChoice::Yes => match self.choose_export_path() {
+                Some(path) => {
+                    self.stop_capture();
+                    self.export_records_to(&path)
+                }
+                None => false,
+            },
+            nwg::MessageChoice::No => true,
+            _ => false,
+        };
+        if !confirmed && self.state.borrow().schedule.is_some() {
+            self.schedule_timer.start();
+        }
+        confirmed
+    }
+
+    /// ask for a file and write the records as csv, returns whether they were written
+    fn export_records(&self) -> bool {
+        match self.choose_export_path() {
+            Some(path) => self.export_records_to(&path),
+            None => false,
+        }
+    }
+
+    /// ask where to export to, `None` when the dialog was cancelled
+    fn choose_export_path(&self) -> Option<PathBuf> {
+        if !self.export_dialog.run(Some(&self.window)) {
+            return None;
+        }
+        let path = PathBuf::from(self.export_dialog.get_selected_item().ok()?);
+        Some(if path.extension().is_none() { path.with_extension("csv") } else { path })
+    }
+
+    fn export_records_to(&self, path: &Path) -> bool {
+        let columns = self.settings.borrow().visible_columns();
+        match export_csv(path, &self.state.borrow().records, &columns, self.session_summary().as_deref()) {
+            Ok(()) => {
+                self.set_status(Level::Info, format!("已导出到 {}", path.display()).as_str());
+                true
+            }
+            Err(err) => {
+                self.set_status(Level::Error, format!("无法导出：{}", err).as_str());
+                false
+            }
+        }
+    }
+
+    fn build_record_columns(&self) {
+        while self.record_table.column_len() > 0 {
+            self.record_table.remove_column(0);
+        }
+        let settings = self.settings.borrow();
+        for (idx, column) in settings.record_columns.iter().filter(|c| c.visible).enumerate() {
+            self.record_table.insert_column(column.column.title());
+            // widths are kept at 100% scaling, so they carry over between displays
+            self.record_table.set_column_width(idx, scaled_width(column.width));
+        }
+        self.record_table.set_headers_enabled(true);
+    }
+
+    /// the widths of the columns that are not sized by the user, at the current scaling
+    fn size_stat_columns(&self) {
+        self.stat_trans_table.set_column_width(0, scaled_width(100));
+        self.stat_trans_table.set_column_width(3, scaled_width(180));
+        self.stat_app_table.set_column_width(0, scaled_width(160));
+        self.stat_app_table.set_column_width(3, scaled_width(180));
+        self.stat_app_table.set_column_width(4, scaled_width(180));
+        self.stat_dns_table.set_column_width(0, scaled_width(360));
+    }
+
+    /// the window is on a display of another scaling now and windows already resized it; nwg
+    /// sizes the layouts by the scaling it started with, within the new window size, while the
+    /// font, the column widths and the plots are scaled here
+    fn dpi_changed(&self) {
+        let scale = match self.pending_scale.take() {
+            Some(scale) => scale,
+            None => return,
+        };
+        // the widths the user set are taken back to 100% at the scaling they were set at
+        self.store_column_widths();
+        SCALE.with(|current| current.set(scale));
+        {
+            let settings = self.settings.borrow();
+            for (idx, column) in settings.record_columns.iter().filter(|c| c.visible).enumerate() {
+                self.record_table.set_column_width(idx, scaled_width(column.width));
+            }
+        }
+        self.size_stat_columns();
+        let size = self.settings.borrow().font_size;
+        self.apply_font(size);
+        self.window_resize();
+    }
+
+    fn store_column_widths(&self) {
+        let mut settings = self.settings.borrow_mut();
+        for (idx, column) in settings.record_columns.iter_mut().filter(|c| c.visible).enumerate() {
+            column.width = unscaled_width(self.record_table.column_width(idx));
+        }
+    }
+
+    /// the record shown in `row` of the record table
+    fn table_record(&self, row: usize) -> Option<Record> {
+        self.state.borrow().row_record(row).cloned()
+    }
+
+    fn show_record_menu(&self, data: &nwg::EventData) {
+        let columns = self.settings.borrow().visible_columns();
+        let (value, copy) = match data {
+            &nwg::EventData::OnListViewItemIndex { row_index, column_index } if row_index < self.record_table.len() => {
+                // the clicked column is counted among the visible ones, in their display order
+                match (columns.get(column_index).copied(), self.table_record(row_index)) {
+                    (Some(column), Some(record)) => {
+                        // copied from the record rather than the control, which only holds what is on screen
+                        let row = record.to_column_strings(&columns);
+                        let copy = (row[column_index].clone(), row.join("\t"));
+                        (FilterExpr::same_value(&record, column).map(|expr| (column, expr)), Some(copy))
+                    }
+                    _ => (None, None),
+                }
+            }
+            _ => (None, None),
+        };
+        for item in [&self.menu_copy_cell, &self.menu_copy_row] {
+            item.set_enabled(copy.is_some());
+        }
+        self.state.borrow_mut().menu_copy = copy;
+
+        let enabled = value.is_some();
+        for item in [&self.menu_filter_value, &self.menu_exclude_value, &self.menu_append_value] {
+            item.set_enabled(enabled);
+        }
+        match &value {
+            Some((column, expr)) => {
+                // "&&" of a time range would otherwise show as a single mnemonic prefix
+                let expr = expr.to_string().replace('&', "&&");
+                set_menu_item_text(&self.menu_filter_value, &format!("筛选 {}：{}", column.title(), expr));
+                set_menu_item_text(&self.menu_exclude_value, &format!("排除 {}：{}", column.title(), expr));
+            }
+            None => {
+                set_menu_item_text(&self.menu_filter_value, "筛选此值");
+                set_menu_item_text(&self.menu_exclude_value, "排除此值");
+            }
+        }
+        self.state.borrow_mut().menu_value = value.map(|(_, expr)| expr);
+
+        let (x, y) = nwg::GlobalCursor::position();
+        self.record_menu.popup(x, y);
+    }
+
+    fn show_stat_trans_menu(&self, data: &nwg::EventData) {
+        let rows = self.stat_trans_rows(&self.stat_records.borrow());
+        self.show_stat_menu(&self.stat_trans_table, rows, data);
+    }
+
+    fn show_stat_app_menu(&self, data: &nwg::EventData) {
+        let rows = self.stat_app_rows(&self.stat_records.borrow());
+        self.show_stat_menu(&self.stat_app_table, rows, data);
+    }
+
+    /// the stat tables are copied from the stat records rather than the control, which only
+    /// holds what fits; the row is found by the protocol it shows, as a paused view keeps rows
+    /// the records have moved on from
+    fn show_stat_menu(&self, table: &nwg::ListView, rows: Vec<Vec<String>>, data: &nwg::EventData) {
+        let row = Self::stat_row_name(table, data).and_then(|name| {
+            let is_total = |cell: &str| cell.starts_with("合计");
+            rows.into_iter().find(|row| row[0] == name || (is_total(&row[0]) && is_total(&name)))
+        });
+        let copy = match (data, row) {
+            (&nwg::EventData::OnListViewItemIndex { column_index, .. }, Some(row)) => {
+                row.get(column_index).cloned().map(|cell| (cell, row.join("\t")))
+            }
+            _ => None,
+        };
+        if copy.is_none() {
+            return;
+        }
+        self.state.borrow_mut().menu_copy = copy;
+        let (x, y) = nwg::GlobalCursor::position();
+        self.stat_menu.popup(x, y);
+    }
+
+    fn copy_menu_cell(&self) {
+        if let Some((cell, _)) = self.state.borrow_mut().menu_copy.take() {
+            nwg::Clipboard::set_data_text(&self.window, &cell);
+        }
+    }
+
+    fn copy_menu_row(&self) {
+        if let Some((_, row)) = self.state.borrow_mut().menu_copy.take() {
+            nwg::Clipboard::set_data_text(&self.window, &row);
+        }
+    }
+
+    /// protocol name in the first column of a double clicked stat row, `None` for the totals row
+    fn stat_row_name(table: &nwg::ListView, data: &nwg::EventData) -> Option<String> {
+        match data {
+            &nwg::EventData::OnListViewItemIndex { row_index, .. } if row_index < table.len() => {
+                table.item(row_index, 0, 64).map(|item| item.text)
+            }
+            _ => None,
+        }
+    }
+
+    fn filter_by_trans_row(&self, data: &nwg::EventData) {
+        let proto = match Self::stat_row_name(&self.stat_trans_table, data) {
+            Some(name) => match str_to_trans_protocol(&name) {
+                Ok(proto) => proto,
+                // the totals row
+                Err(_) => return,
+            },
+            None => return,
+        };
+        // the filter box follows the tab, switch first so the record tab gets the filter
+        self.select_tab(Mode::Record);
+        self.set_filter_text(FilterExpr::equal(Field::TransProto, Literal::TransProtocol(proto)), false);
+    }
+
+    fn filter_by_app_row(&self, data: &nwg::EventData) {
+        let proto = match Self::stat_row_name(&self.stat_app_table, data) {
+            Some(name) => match name.parse::<AppProtocol>() {
+                Ok(proto) => proto,
+                Err(_) => return,
+            },
+            None => return,
+        };
+        // the app table only counts tcp/udp, while non-tcp/udp records are unknown here too
+        let expr = if proto == AppProtocol::Unknown {
+            FilterExpr::equal(Field::TransProto, Literal::TransProtocol(Protocol::Tcp))
+                .or(FilterExpr::equal(Field::TransProto, Literal::TransProtocol(Protocol::Udp)))
+                .and(FilterExpr::equal(Field::AppProto, Literal::AppProtocol(proto)))
+        } else {
+            FilterExpr::equal(Field::AppProto, Literal::AppProtocol(proto))
+        };
+        // the filter box follows the tab, switch first so the record tab gets the filter
+        self.select_tab(Mode::Record);
+        self.set_filter_text(expr, false);
+    }
+
+    /// put `expr` into the filter box, combined with what is there when `append`
+    fn set_filter_text(&self, expr: FilterExpr, append: bool) {
+        let base = self.filter.text();
+        let text = if append && !base.trim().is_empty() {
+            format!("({}) && {}", base.trim(), expr)
+        } else {
+            expr.to_string()
+        };
+        // setting the text applies it through `create_filter`, just like typing it
+        self.filter.set_text(&text);
+    }
+
+    fn filter_by_value(&self) {
+        if let Some(expr) = self.state.borrow_mut().menu_value.take() {
+            self.set_filter_text(expr, false);
+        }
+    }
+
+    fn filter_out_value(&self) {
+        if let Some(expr) = self.state.borrow_mut().menu_value.take() {
+            self.set_filter_text(expr.negate(), false);
+        }
+    }
+
+    fn append_value_filter(&self) {
+        if let Some(expr) = self.state.borrow_mut().menu_value.take() {
+            self.set_filter_text(expr, true);
+        }
+    }
+
+    fn show_column_chooser(&self) {
+        self.store_column_widths();
+        self.state.borrow_mut().column_draft = self.settings.borrow().record_columns.clone();
+        self.refresh_column_list(Some(0));
+        self.column_window.set_visible(true);
     }
 
-    fn start_capture(&self) {
+    fn refresh_column_list(&self, selection: Option<usize>) {
+        let items = self.state.borrow().column_draft.iter()
+            .map(|c| format!("{} {}", if c.visible { "☑" } else { "☐" }, c.column.title()))
+            .collect::<Vec<_>>();
+        self.column_list.set_collection(items);
+        self.column_list.set_selection(selection);
+    }
+
+    fn toggle_column(&self) {
+        let idx = match self.column_list.selection() {
+            Some(idx) => idx,
+            None => return,
+        };
         {
             let mut state = self.state.borrow_mut();
-            state.capturing = true;
-            state.records.clear();
-            self.stat_records.borrow_mut().clear();
-            state.end_time = None;
-            let now = Local::now();
-            state.start_time = Some(now);
-            self.plot_records.borrow_mut().clear_with_time(now);
+            let draft = &mut state.column_draft;
+            // the table needs at least one column to show anything
+            if draft[idx].visible && draft.iter().filter(|c| c.visible).count() == 1 {
+                return;
+            }
+            draft[idx].visible = !draft[idx].visible;
+        }
+        self.refresh_column_list(Some(idx));
+    }
+
+    fn move_column_up(&self) {
+        match self.column_list.selection() {
+            Some(idx) if idx > 0 => {
+                self.state.borrow_mut().column_draft.swap(idx - 1, idx);
+                self.refresh_column_list(Some(idx - 1));
+            }
+            _ => {}
+        }
+    }
+
+    fn move_column_down(&self) {
+        let len = self.state.borrow().column_draft.len();
+        match self.column_list.selection() {
+            Some(idx) if idx + 1 < len => {
+                self.state.borrow_mut().column_draft.swap(idx, idx + 1);
+                self.refresh_column_list(Some(idx + 1));
+            }
+            _ => {}
+        }
+    }
+
+    fn apply_columns(&self) {
+        self.settings.borrow_mut().record_columns = self.state.borrow().column_draft.clone();
+        self.save_settings();
+        self.build_record_columns();
+        self.rebuild_record_table();
+        self.column_window.set_visible(false);
+    }
+
+    fn close_column_chooser(&self, data: &nwg::EventData) {
+        // closing only discards the draft, the window is reused next time
+        if let nwg::EventData::OnWindowClose(close) = data {
+            close.close(false);
+        }
+        self.column_window.set_visible(false);
+    }
+
+    fn open_repository(&self) {
+        if let Err(err) = open_url(meta::REPOSITORY) {
+            self.set_status(Level::Error, format!("无法打开链接：{}", err).as_str());
+        }
+    }
+
+    fn open_issues(&self) {
+        if let Err(err) = open_url(meta::ISSUES) {
+            self.set_status(Level::Error, format!("无法打开链接：{}", err).as_str());
+        }
+    }
+
+    /// what a bug report needs to know about this machine and build
+    fn diagnostics(&self) -> String {
+        let state = self.state.borrow();
+        let elevated = match is_elevated() {
+            Ok(true) => "是",
+            Ok(false) => "否",
+            Err(_) => "未知",
+        };
+        let mut lines = vec![
+            format!("{} {}（{}，{}）", meta::NAME, meta::VERSION, meta::GIT_HASH, meta::BUILD_DATE),
+            format!("系统：{}", os_version()),
+            format!("管理员权限：{}", elevated),
+            format!("依赖：{}", meta::DEPENDENCIES),
+            format!("网卡（{} 个）：", state.interfaces.len()),
+        ];
+        for interface in &state.interfaces {
+            let connected = if state.connected_interfaces.contains(interface) { "（已连接）" } else { "" };
+            lines.push(format!("  {}{}", interface, connected));
+        }
+        lines.push(format!("捕获计数：{}", self.capture_metrics().to_text().replace('\n', "\r\n  ")));
+        lines.join("\r\n")
+    }
+
+    /// the counters of the capture path, of the running capture or else the last one
+    fn capture_metrics(&self) -> CaptureMetrics {
+        let mut metrics = match self.capture_thread.borrow().as_ref() {
+            Some(thread) => thread.metrics(),
+            None => self.capturer.borrow().metrics(),
+        };
+        metrics.merge_drains(&self.state.borrow().capture_drains);
+        metrics
+    }
+
+    fn display_capture_metrics(&self) {
+        self.about_capture_metrics.set_text(format!("捕获计数：\n{}", self.capture_metrics().to_text()).as_str());
+    }
+
+    fn copy_diagnostics(&self) {
+        nwg::Clipboard::set_data_text(&self.window, &self.diagnostics());
+        self.set_status(Level::Info, "诊断信息已复制到剪贴板");
+    }
+
+    fn show_log_window(&self) {
+        self.refresh_log_text();
+        self.log_window.set_visible(true);
+    }
+
+    fn refresh_log_text(&self) {
+        self.log_text.set_text(&self.log.borrow().to_text());
+        self.log_text.scroll_lastline();
+    }
+
+    fn copy_log(&self) {
+        let log = self.log.borrow();
+        if !log.is_empty() {
+            nwg::Clipboard::set_data_text(&self.log_window, &log.to_text());
+        }
+    }
+
+    fn clear_log(&self) {
+        self.log.borrow_mut().clear();
+        self.refresh_log_text();
+    }
+
+    fn close_log_window(&self, data: &nwg::EventData) {
+        if let nwg::EventData::OnWindowClose(close) = data {
+            close.close(false);
+        }
+        self.log_window.set_visible(false);
+    }
+
+    fn show_interface_chooser(&self) {
+        let state = self.state.borrow();
+        self.interface_list.set_collection(state.interfaces.iter().map(|interface| interface.to_string()).collect());
+        for (idx, interface) in state.interfaces.iter().enumerate() {
+            if state.connected_interfaces.contains(interface) {
+                self.interface_list.multi_add_selection(idx);
+            }
+        }
+        self.interface_window.set_visible(true);
+    }
+
+    fn connect_chosen_interfaces(&self) {
+        let chosen = {
+            let state = self.state.borrow();
+            self.interface_list.multi_selection().into_iter()
+                .filter_map(|idx| state.interfaces.get(idx).cloned())
+                .collect::<Vec<_>>()
+        };
+        if chosen.is_empty() {
+            return;
+        }
+        // the combo box can only show a single interface
+        let single = match chosen.as_slice() {
+            [interface] => self.state.borrow().interfaces.iter().position(|i| i == interface),
+            _ => None,
+        };
+        self.interfaces.set_selection(single);
+        self.interface_window.set_visible(false);
+        self.connect_interfaces(chosen);
+    }
+
+    fn close_interface_chooser(&self, data: &nwg::EventData) {
+        if let nwg::EventData::OnWindowClose(close) = data {
+            close.close(false);
+        }
+        self.interface_window.set_visible(false);
+    }
+
+    fn toggle_schedule(&self) {
+        if self.state.borrow().schedule.is_some() {
+            self.cancel_schedule();
+            self.set_status(Level::Info, "定时捕获已取消");
+        } else {
+            self.schedule_window.set_visible(true);
         }
-        self.capture.set_text("停止捕获");
-        self.reset_status_bar();
-        self.record_table.clear();
-        self.capturing_timer.start();
-        self.plotting_sample_timer.start();
-        self.polling_timer.start();
     }
 
-    fn stop_capture(&self) {
-        self.polling_timer.stop();
-        self.plotting_sample_timer.stop();
-        self.capturing_timer.stop();
-        {
-            let mut state = self.state.borrow_mut();
-            state.capturing = false;
-            state.end_time = Some(Local::now());
+    fn browse_schedule_dir(&self) {
+        if self.schedule_dir_dialog.run(Some(&self.schedule_window)) {
+            if let Ok(dir) = self.schedule_dir_dialog.get_selected_item() {
+                self.schedule_dir.set_text(&dir.to_string_lossy());
+            }
         }
-        self.plot_records.borrow_mut().commit_rest();
-        self.plotting_timer.start();
-        self.capture.set_text("开始捕获");
-        self.reset_status_bar();
     }
 
-    fn toggle_capture(&self) {
+    fn start_schedule(&self) {
+        if !self.connected() {
+            self.set_status(Level::Warn, "请首先选择网卡");
+            return;
+        }
+        if self.state.borrow().capturing {
+            self.set_status(Level::Warn, "请先停止当前的捕获再开始定时捕获");
+            return;
+        }
+        let number = |input: &nwg::TextInput| input.text().trim().parse::<u32>().ok().filter(|&n| n > 0);
+        let (duration, interval, runs) = match (
+            number(&self.schedule_duration),
+            number(&self.schedule_interval),
+            number(&self.schedule_runs),
+        ) {
+            (Some(duration), Some(interval), Some(runs)) => (duration, interval, runs),
+            _ => {
+                self.set_status(Level::Warn, "时长、间隔和重复次数都应为正整数");
+                return;
+            }
+        };
+        let dir = PathBuf::from(self.schedule_dir.text().trim());
+        if !dir.is_dir() {
+            self.set_status(Level::Warn, "导出目录不存在");
+            return;
+        }
+        let schedule = Schedule::new(
+            Duration::seconds(duration as i64),
+            Duration::minutes(interval as i64),
+            runs,
+            dir,
+            Local::now(),
+        );
+        match schedule {
+            Ok(schedule) => {
+                self.state.borrow_mut().schedule = Some(schedule);
+                self.schedule_window.set_visible(false);
+                self.schedule_button.set_text("取消定时");
+                self.schedule_timer.start();
+                self.schedule_tick();
+            }
+            Err(_) => self.set_status(Level::Warn, "间隔不能短于每次捕获的时长"),
+        }
+    }
+
+    /// drive the scheduled runs: start, stop and export them, and show how far along they are
+    fn schedule_tick(&self) {
+        let now = Local::now();
+        let (action, running) = match self.state.borrow().schedule.as_ref() {
+            Some(schedule) => (schedule.action(now), schedule.is_running()),
+            None => return,
+        };
         let capturing = self.state.borrow().capturing;
-        let capturer = self.capturer.borrow();
-        if capturer.connected() {
+        // a run also ends when it is stopped by hand or by the capture timeout
+        if action == ScheduleAction::Stop || (running && !capturing) {
             if capturing {
                 self.stop_capture();
-            } else {
-                self.start_capture();
             }
-        } else {
-            self.status_bar.set_text(0, "请首先选择网卡");
+            if !self.finish_scheduled_run() {
+                return;
+            }
+        } else if action == ScheduleAction::Start && !capturing {
+            if !self.connected() {
+                self.cancel_schedule();
+                self.set_status(Level::Warn, "网卡已断开，定时捕获已取消");
+                return;
+            }
+            // every run is exported on its own, so it starts from nothing
+            self.clear_records();
+            if let Some(schedule) = self.state.borrow_mut().schedule.as_mut() {
+                schedule.start_run(now);
+            }
+            // nothing was captured, so there is nothing to export either; the next run tries again
+            if !self.start_capture() {
+                if let Some(schedule) = self.state.borrow_mut().schedule.as_mut() {
+                    schedule.skip_run();
+                }
+            }
         }
+        self.display_schedule_state(now);
     }
 
-    fn create_filter(&self) {
-        let filter_str = self.filter.text();
-        if filter_str.is_empty() { 
-            self.state.borrow_mut().filter = None;
-            self.rebuild_record_table();
-            self.sync_stat_data();
-            self.sync_plot_data();
-            self.display_stat_table();
-            self.plotting_timer.start();
-        } else {
-            match create_filter(filter_str.as_str()) {
-                Ok(filter) => {
-                    self.state.borrow_mut().filter = Some(Box::new(filter));
-                    self.rebuild_record_table();
-                    self.sync_stat_data();
-                    self.sync_plot_data();
-                    self.display_stat_table();
-                    self.plotting_timer.start();
-                },
-                Err(err) => {
-                    match err {
-                        FilterError::InvalidLiteral(literal) => {
-                            self.status_bar.set_text(0, format!("这里不能用值 \"{}\" 来筛选", literal).as_str())
-                        },
-                        FilterError::InvalidField(field) => {
-                            self.status_bar.set_text(0, format!("名为 \"{}\" 的项目不存在", field).as_str())
-                        },
-                        FilterError::InvalidOperator(op) => {
-                            self.status_bar.set_text(0, format!("\"{}\" 不是一个合法的操作", op).as_str())
-                        },
-                        FilterError::UnsupportedOperator(field, op) => {
-                            self.status_bar.set_text(0, format!("不能在 \"{}\" 项目上使用 \"{}\" 操作筛选", field, op).as_str())
-                        },
-                        FilterError::Failed | FilterError::Nom(_, _) => {
-                            self.status_bar.set_text(0, "筛选器不合法")
-                        }
-                    }
-                    return;
-                },
-            }
+    /// export the run that just stopped and clear it for the next one,
+    /// returns whether the schedule goes on
+    fn finish_scheduled_run(&self) -> bool {
+        let (path, finished) = {
+            let mut state = self.state.borrow_mut();
+            let start_time = state.start_time.unwrap_or_else(Local::now);
+            let schedule = match state.schedule.as_mut() {
+                Some(schedule) => schedule,
+                None => return false,
+            };
+            schedule.finish_run();
+            (timestamped_path(&schedule.dir, start_time), schedule.is_finished())
+        };
+        let columns = self.settings.borrow().visible_columns();
+        let exported = export_csv(&path, &self.state.borrow().records, &columns, self.session_summary().as_deref());
+        self.clear_records();
+        if let Err(err) = exported {
+            self.cancel_schedule();
+            self.set_status(Level::Error, format!("无法导出到 {}：{}，定时捕获已取消", path.display(), err).as_str());
+            return false;
         }
-        self.reset_status_bar();
+        if finished {
+            let (runs, dir) = match self.state.borrow().schedule.as_ref() {
+                Some(schedule) => (schedule.runs, schedule.dir.clone()),
+                None => return false,
+            };
+            self.cancel_schedule();
+            self.set_status(Level::Info, format!("定时捕获已完成，共 {} 次，文件保存在 {}", runs, dir.display()).as_str());
+            return false;
+        }
+        true
     }
 
-    fn sync_stat_data(&self) {
-        let state = self.state.borrow();
-        let mut state_records = self.stat_records.borrow_mut();
-        state_records.clear();
+    fn display_schedule_state(&self, now: DateTime<Local>) {
+        let text = match self.state.borrow().schedule.as_ref() {
+            Some(schedule) if schedule.is_running() => format!(
+                "定时捕获第 {}/{} 次，剩余 {}",
+                schedule.completed() + 1, schedule.runs, format_duration(schedule.countdown(now)),
+            ),
+            Some(schedule) => format!(
+                "定时捕获已完成 {}/{} 次，下次将在 {} 后开始",
+                schedule.completed(), schedule.runs, format_duration(schedule.countdown(now)),
+            ),
+            None => return,
+        };
+        self.status_bar.set_text(0, text.as_str());
+    }
 
-        let id = |_: &Record| true;
-        let f = state.filter.as_ref()
-            .map(|f| f as &dyn Fn(&Record) -> bool)
-            .unwrap_or(&id);
+    /// stop scheduling, a run in progress keeps capturing as a normal capture
+    fn cancel_schedule(&self) {
+        self.schedule_timer.stop();
+        self.state.borrow_mut().schedule = None;
+        self.schedule_button.set_text("定时…");
+    }
 
-        state_records.update_multiple(state.records.iter().filter(|r| f(r)));
+    fn close_schedule_dialog(&self, data: &nwg::EventData) {
+        if let nwg::EventData::OnWindowClose(close) = data {
+            close.close(false);
+        }
+        self.schedule_window.set_visible(false);
     }
 
-    fn sync_plot_data(&self) {
-        let state = self.state.borrow();
-        let mut plot_records = self.plot_records.borrow_mut();
+    // ----- profiles -----
 
-        let id = |_: &Record| true;
-        let f = state.filter.as_ref()
-            .map(|f| f as &dyn Fn(&Record) -> bool)
-            .unwrap_or(&id);
+    /// fill the profile box from the settings, selecting `selection` by name
+    fn display_profiles(&self, selection: Option<&str>) {
+        let settings = self.settings.borrow();
+        self.profiles.set_collection(settings.profiles.iter().map(|profile| profile.name.clone()).collect());
+        self.profiles.set_selection(selection.and_then(|name| settings.profiles.iter().position(|p| p.name == name)));
+    }
 
-        *plot_records = PlotRecord::from_records(
-            state.records.iter().filter(|&r| f(r)), 
-            if state.capturing { None } else { state.start_time }, 
-            if state.capturing { Some(Local::now()) } else { state.end_time },
-        );
+    /// set up the interfaces, filter, timeout and export options of the chosen profile
+    fn apply_profile(&self) {
+        let profile = match self.profiles.selection() {
+            Some(idx) => match self.settings.borrow().profiles.get(idx) {
+                Some(profile) => profile.clone(),
+                None => return,
+            },
+            None => return,
+        };
+        if self.state.borrow().capturing {
+            self.profiles.set_selection(None);
+            self.set_status(Level::Warn, "请先停止捕获再应用配置");
+            return;
+        }
+        // setting the texts applies them through their handlers, just like typing them, the
+        // export folder waits for enter and is applied by hand
+        self.filter.set_text(&profile.filter);
+        self.timeout.set_text(&profile.timeout);
+        let dir = profile.auto_export_dir.as_ref().map_or(String::new(), |dir| dir.to_string_lossy().into_owned());
+        self.auto_export_dir.set_text(&dir);
+        self.set_auto_export_dir();
+        // connecting reports its own failures, missing adapters are told about afterwards
+        let missing = match self.connect_adapters(&profile.adapters) {
+            Some(missing) => missing,
+            None => return,
+        };
+        if missing > 0 {
+            self.set_status(
+                Level::Warn,
+                format!("配置“{}”中有 {} 个网卡不存在或未启用，其余设置已应用", profile.name, missing).as_str(),
+            );
+        }
     }
 
-    fn update_plot_data(&self, record: &Record) {
-        let mut plot_records = self.plot_records.borrow_mut();
+    fn open_profile_dialog(&self) {
+        let name = match self.profiles.selection() {
+            Some(idx) => self.settings.borrow().profiles.get(idx).map(|profile| profile.name.clone()),
+            None => None,
+        };
+        self.profile_name.set_text(name.as_deref().unwrap_or(""));
+        self.profile_window.set_visible(true);
+        self.profile_name.set_focus();
+    }
 
-        plot_records.update_records(
-            iter::once(record), 
-            None
-        );
+    /// save the current interfaces, filter, timeout and export options under the given name
+    fn save_profile(&self) {
+        let name = self.profile_name.text().trim().to_string();
+        if name.is_empty() {
+            self.set_status(Level::Warn, "请输入配置名称");
+            return;
+        }
+        let adapters = self.state.borrow().connected_interfaces.iter().map(Interface::saved).collect();
+        let profile = Profile {
+            name: name.clone(),
+            adapters,
+            filter: self.filter.text(),
+            timeout: self.timeout.text().trim().to_string(),
+            auto_export_dir: self.settings.borrow().auto_export_dir.clone(),
+        };
+        self.settings.borrow_mut().save_profile(profile);
+        self.save_settings();
+        self.display_profiles(Some(&name));
+        self.profile_window.set_visible(false);
+        self.set_status(Level::Info, format!("已保存配置“{}”", name).as_str());
     }
 
-    fn rebuild_record_table(&self) {
-        self.record_table.clear();
-        let state = self.state.borrow();
-        let mut records_iter = state.records.iter();
-        let mut records_filter_iter;
-        let iter: &mut dyn Iterator<Item = &Record> = if let Some(f) = state.filter.as_ref() {
-            records_filter_iter = records_iter.filter(|&r| f(r));
-            &mut records_filter_iter
-        } else {
-            &mut records_iter
+    fn delete_profile(&self) {
+        let idx = match self.profiles.selection() {
+            Some(idx) => idx,
+            None => {
+                self.set_status(Level::Warn, "请先选择要删除的配置");
+                return;
+            }
         };
-        self.record_table.set_redraw(false);
-        for record in iter {
-            self.record_table.insert_items_row(None, &record.to_string_array());
+        let name = {
+            let mut settings = self.settings.borrow_mut();
+            if idx >= settings.profiles.len() {
+                return;
+            }
+            settings.profiles.remove(idx).name
+        };
+        self.save_settings();
+        self.display_profiles(None);
+        self.set_status(Level::Info, format!("已删除配置“{}”", name).as_str());
+    }
+
+    fn close_profile_dialog(&self, data: &nwg::EventData) {
+        if let nwg::EventData::OnWindowClose(close) = data {
+            close.close(false);
         }
-        self.record_table.set_redraw(true);
+        self.profile_window.set_visible(false);
     }
+}
 
+/// diagonal lines `step` pixels apart filling the rectangle, in backend coordinates
+fn hatch_lines(x: Range<i32>, y: Range<i32>, step: i32) -> Vec<Vec<(i32, i32)>> {
+    let (width, height) = (x.end - x.start, y.end - y.start);
+    // each line rises one pixel per pixel to the right, starting at `offset` on the bottom edge
+    (-height..width)
+        .step_by(step.max(1) as usize)
+        .map(|offset| {
+            let from = (x.start + offset.max(0), y.end - (-offset).max(0));
+            let rise = (width - offset.max(0)).min(height - (-offset).max(0));
+            vec![from, (from.0 + rise, from.1 - rise)]
+        })
+        .collect()
+}
 
-    fn refresh_plot_graph(&self) {
-        let mut plot_records = self.plot_records.borrow_mut();
+/// split a polyline into every other segment, which reads as a dashed line
+fn dashed_path<X: Clone, Y: Clone>(points: &[(X, Y)], style: ShapeStyle) -> Vec<PathElement<(X, Y)>> {
+    points
+        .windows(2)
+        .step_by(2)
+        .map(|segment| PathElement::new(segment.to_vec(), style.clone()))
+        .collect()
+}
 
-        plot_records.update_records(
-            iter::empty(), 
-            Some(Local::now())
-        );
+/// an ipv4 address of an adapter, which is what the capture socket binds to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Interface {
+    adapter_name: String,
+    description: String,
+    address: Ipv4Addr,
+}
 
-        self.plotting_timer.start();
+impl Interface {
+    /// what is kept to connect this interface again
+    fn saved(&self) -> SavedAdapter {
+        SavedAdapter {
+            name: self.adapter_name.clone(),
+            address: Some(self.address),
+        }
     }
+}
 
-    fn display_plot_graph(&self) {
-        if let Err(_err) = self.display_plot_graph_with_result() {
-            // print here with no console available could cause program panic
-            // TODO: integrate with logger
-            eprintln!("{:?}", _err);
-        }
+impl Display for Interface {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} — {}", self.description, self.address)
     }
+}
 
-    fn display_plot_graph_with_result(&self) -> Result<()> {
-        let records = self.plot_records.borrow();
+/// how `interfaces` names the adapter of `address`, just the address when it is not among them
+fn interface_name(interfaces: &[Interface], address: Ipv4Addr) -> String {
+    interfaces.iter()
+        .find(|interface| interface.address == address)
+        .map_or_else(|| address.to_string(), |interface| interface.to_string())
+}
 
-        let graph = self.plot_graph.draw()?;
+/// the capturer and capture thread of the app, as `Handover::run` takes them
+struct AppHandover<'a>(&'a App);
 
-        let (max_num, max_len) = records.records.iter().fold(
-            (10u64, 10u64),
-            |(max_num, max_len), r| (
-                max_num.max(r.packet_num),
-                max_len.max(r.byte_num)
-            )
-        );
+impl HandoverSteps for AppHandover<'_> {
+    fn join(&mut self) {
+        for event in self.0.join_capture_thread() {
+            // whatever was lost is bound again or reported once bound
+            if let Some(lost) = self.0.capture_event(event) {
+                self.0.log(Level::Error, format!("{:#}", lost));
+            }
+        }
+    }
 
-        let max_time = if let (Some(start_time), Some(end_time)) = (records.start_time, records.end_time) {
-            end_time - start_time
-        } else {
-            Duration::seconds(10)
+    fn bind(&mut self, targets: &[(Ipv4Addr, String)]) -> Vec<(Ipv4Addr, CaptureError)> {
+        let app = self.0;
+        let (port, recv_mode, protocol) = {
+            let settings = app.settings.borrow();
+            (settings.bind_port, settings.recv_mode, settings.capture_protocol)
         };
+        {
+            let mut state = app.state.borrow_mut();
+            state.bound_protocol = protocol;
+            // the capture goes on with the new sockets, so what it takes in from now on is what counts
+            if state.capturing {
+                state.only_protocol = protocol;
+            }
+        }
+        let mut capturer = app.capturer.borrow_mut();
+        capturer.set_recv_mode(recv_mode);
+        capturer.set_protocol(protocol);
+        capturer.capture_all(targets, port, true)
+    }
 
-        let time_range = if self.state.borrow().capturing && max_time < Duration::seconds(10) {
-            (max_time - Duration::seconds(10)).num_milliseconds()..max_time.num_milliseconds()
-        } else {
-            0..max_time.num_milliseconds()
-        };
+    fn respawn(&mut self) {
+        if let Err(err) = self.0.spawn_capture_thread() {
+            // nothing would read the new sockets
+            self.0.log(Level::Error, format!("无法启动捕获线程：{}", err));
+            self.0.stop_capture();
+        }
+    }
 
-        let mut plot = ChartBuilder::on(&graph)
-            .margin_left(10)
-            .margin_right(10)
-            .x_label_area_size(30)
-            .y_label_area_size(30)
-            .right_y_label_area_size(60)
-            .build_cartesian_2d(time_range.clone(), 0..max_num)?
-            .set_secondary_coord(time_range.clone(), 0..max_len);
+    fn stop(&mut self) {
+        // nothing is left to read from, so the session ends here rather than on the old adapters
+        self.0.stop_capture();
+    }
+}
 
-        let x_formatter_empty ;
-        let x_formatter_with_time;
-        let x_formatter_with_time_long;
-        let x_formatter: &dyn Fn(&i64) -> String;
-        if let Some(start_time) = records.start_time {
-            if max_time <= Duration::seconds(10) {
-                x_formatter_with_time = move |x: &i64| (start_time + Duration::milliseconds(*x)).format("%M:%S%.3f").to_string();
-                x_formatter = &x_formatter_with_time;
-            } else {
-                x_formatter_with_time_long = move |x: &i64| (start_time + Duration::milliseconds(*x)).format("%H:%M:%S%.3f").to_string();
-                x_formatter = &x_formatter_with_time_long;
-            }
-        } else {
-            x_formatter_empty = |_: &i64| String::new();
-            x_formatter = &x_formatter_empty;
+/// notify `sender` from a background thread whenever the ip address table changes,
+/// e.g. when an adapter goes down or comes back
+fn watch_addresses(sender: nwg::NoticeSender) {
+    thread::spawn(move || loop {
+        // blocks until the next change when called without a handle
+        if unsafe { NotifyAddrChange(ptr::null_mut(), ptr::null_mut()) } != 0 {
+            break;
         }
+        sender.notice();
+    });
+}
 
-        let num_color = RGBColor(167, 79, 1);
-        let len_color = RGBColor(17, 125, 187);
+/// every ipv4 address of the adapters that are up, sorted by adapter description;
+/// an adapter with several addresses is listed once per address
+fn usable_interfaces() -> Result<Vec<Interface>> {
+    let mut adapters = ipconfig::get_adapters()?
+        .into_iter()
+        .filter(|adapter| adapter.oper_status() == OperStatus::IfOperStatusUp)
+        .collect::<Vec<_>>();
+    adapters.sort_by(|a1, a2| a1.description().cmp(a2.description()));
+    let interfaces = adapters.iter()
+        .flat_map(|adapter| adapter.ip_addresses().iter().filter_map(move |addr| match addr {
+            IpAddr::V4(address) => Some(Interface {
+                adapter_name: adapter.adapter_name().to_string(),
+                description: adapter.description().to_string(),
+                address: *address,
+            }),
+            IpAddr::V6(_) => None,
+        }))
+        .collect();
+    Ok(interfaces)
+}
 
-        plot.configure_mesh()
-            .light_line_style(ShapeStyle { color: TRANSPARENT, filled: false, stroke_width: 0 })
-            .x_label_formatter(x_formatter)
-            .axis_style(ShapeStyle::from(num_color))
-            .draw()?;
+/// whether `hwnd` is an edit control, where keys are typed rather than used as shortcuts
+fn is_text_input(hwnd: HWND) -> bool {
+    let mut class = [0u16; 16];
+    let len = unsafe { GetClassNameW(hwnd, class.as_mut_ptr(), class.len() as i32) };
+    String::from_utf16_lossy(&class[..len.max(0) as usize]).eq_ignore_ascii_case("Edit")
+}
 
-        plot.configure_secondary_axes()
-            .axis_style(ShapeStyle::from(len_color))
-            .draw()?;
+/// whether the last row of `table` is in view
+fn is_scrolled_to_end(table: &nwg::ListView) -> bool {
+    let hwnd = match table.handle.hwnd() {
+        Some(hwnd) => hwnd,
+        None => return false,
+    };
+    let (top, per_page) = unsafe {
+        (
+            SendMessageW(hwnd, LVM_GETTOPINDEX, 0, 0) as usize,
+            SendMessageW(hwnd, LVM_GETCOUNTPERPAGE, 0, 0) as usize,
+        )
+    };
+    top + per_page >= table.len()
+}
 
-        // let time_samples = (0..records.records.len() as u64).map(|idx| (idx * PLOT_SAMPLING_INTERVAL) as i64);
-        let time_samples = (0..max_time.num_milliseconds()).step_by(PLOT_SAMPLING_INTERVAL as usize);
-        let data = time_samples.clone().zip(records.records.iter().map(|r| r.packet_num));
-
-        plot
-            .draw_series(LineSeries::new(data.clone(),&num_color))?
-            .label("分组/个")
-            .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], &num_color));
-        plot
-            .draw_series(AreaSeries::new(
-                data.clone(),
-                0,
-                num_color.mix(0.2)
-            ))?;
-
-        let data = time_samples.clone().zip(records.records.iter().map(|r| r.byte_num));
-        plot
-            .draw_secondary_series(LineSeries::new(data.clone(),&len_color))?
-            .label("流量/字节")
-            .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], &len_color));
-        plot
-            .draw_secondary_series(AreaSeries::new(
-                data.clone(),
-                0,
-                len_color.mix(0.2)
-            ))?;
-
-        plot
-            .configure_series_labels()
-            .label_font(("Segoe UI", 12))
-            .background_style(&WHITE.mix(0.8))
-            .border_style(&BLACK)
-            .draw()?;
+/// the rows of `table` in view, including one cut off at the bottom; `grouped` rows are not
+/// shown in their order, so each of them is asked whether it is in view
+fn rows_in_view(table: &nwg::ListView, grouped: bool) -> Vec<usize> {
+    let hwnd = match table.handle.hwnd() {
+        Some(hwnd) => hwnd,
+        None => return Vec::new(),
+    };
+    let len = table.len();
+    if grouped {
+        return (0..len)
+            .filter(|&row| unsafe { SendMessageW(hwnd, LVM_ISITEMVISIBLE, row, 0) != 0 })
+            .collect();
+    }
+    let (top, per_page) = unsafe {
+        (
+            SendMessageW(hwnd, LVM_GETTOPINDEX, 0, 0) as usize,
+            SendMessageW(hwnd, LVM_GETCOUNTPERPAGE, 0, 0) as usize,
+        )
+    };
+    (top.min(len)..(top + per_page + 1).min(len)).collect()
+}
 
-        Ok(())
+fn scroll_to_end(table: &nwg::ListView) {
+    let len = table.len();
+    if len > 0 {
+        ensure_visible(table, len - 1);
     }
+}
 
-    fn display_stat_table(&self) {
-        let stat_records = self.stat_records.borrow();
-        self.stat_net_info.set_text(format!(
-            "统计结果：{} 个 IPv4 分组，共 {} 字节", 
-            stat_records.stat_net_table.packet_num, 
-            stat_records.stat_net_table.byte_num
-        ).as_str());
+/// nwg menu items have a fixed text, so change it through win32
+fn set_menu_item_text(item: &nwg::MenuItem, text: &str) {
+    let (hmenu, id) = match item.handle.hmenu_item() {
+        Some(handle) => handle,
+        None => return,
+    };
+    let mut text = OsStr::new(text).encode_wide().chain(iter::once(0)).collect::<Vec<u16>>();
+    let mut info: MENUITEMINFOW = unsafe { mem::zeroed() };
+    info.cbSize = mem::size_of::<MENUITEMINFOW>() as u32;
+    info.fMask = MIIM_STRING;
+    info.dwTypeData = text.as_mut_ptr();
+    info.cch = text.len() as u32 - 1;
+    unsafe {
+        SetMenuItemInfoW(hmenu, id, FALSE, &info);
+    }
+}
 
-        self.stat_trans_table.clear();
-        let mut trans_records = stat_records.stat_trans_table.iter().collect::<Vec<_>>();
-        trans_records.sort_by(|a, b| a.0.cmp(b.0));
-        for (idx, (proto, record)) in trans_records.into_iter().enumerate() {
-            let row = iter::once(proto.clone()).chain(record.to_string_array().into_iter()).collect::<Vec<_>>();
-            self.stat_trans_table.insert_items_row(Some(idx as i32), row.as_slice());
-        }
+/// a group header in a list view, `collapsed` is only applied along with the header when `Some`
+fn group_info(header: &mut Vec<u16>, id: usize, collapsed: Option<bool>) -> LVGROUP {
+    let mut group: LVGROUP = unsafe { mem::zeroed() };
+    group.cbSize = mem::size_of::<LVGROUP>() as u32;
+    group.mask = LVGF_HEADER | LVGF_GROUPID;
+    group.pszHeader = header.as_mut_ptr();
+    group.iGroupId = id as i32;
+    if let Some(collapsed) = collapsed {
+        group.mask |= LVGF_STATE;
+        group.stateMask = LVGS_COLLAPSIBLE | LVGS_COLLAPSED;
+        group.state = LVGS_COLLAPSIBLE | if collapsed { LVGS_COLLAPSED } else { 0 };
+    }
+    group
+}
 
-        self.stat_app_table.clear();
-        let mut app_records = stat_records.stat_app_table.iter().collect::<Vec<_>>();
-        app_records.sort_by(|a, b| a.0.cmp(b.0));
-        for (idx, (proto, record)) in app_records.into_iter().enumerate() {
-            let row = iter::once(proto.clone()).chain(record.to_string_array().into_iter()).collect::<Vec<_>>();
-            self.stat_app_table.insert_items_row(Some(idx as i32), row.as_slice());
+/// append a collapsible group to `table`, which needs group view enabled to show it
+fn insert_group(table: &nwg::ListView, id: usize, header: &str, collapsed: bool) {
+    if let Some(hwnd) = table.handle.hwnd() {
+        let mut header = OsStr::new(header).encode_wide().chain(iter::once(0)).collect::<Vec<u16>>();
+        let group = group_info(&mut header, id, Some(collapsed));
+        unsafe {
+            SendMessageW(hwnd, LVM_INSERTGROUP, -1isize as WPARAM, &group as *const _ as LPARAM);
         }
     }
+}
 
-    fn update_record(&self, record: Record) {
-        self.state.borrow_mut().records.push(record.clone());
+/// change the header of a group, also collapsing it when `collapse`
+fn set_group_header(table: &nwg::ListView, id: usize, header: &str, collapse: bool) {
+    if let Some(hwnd) = table.handle.hwnd() {
+        let mut header = OsStr::new(header).encode_wide().chain(iter::once(0)).collect::<Vec<u16>>();
+        let group = group_info(&mut header, id, if collapse { Some(true) } else { None });
+        unsafe {
+            SendMessageW(hwnd, LVM_SETGROUPINFO, id, &group as *const _ as LPARAM);
+        }
+    }
+}
 
-        if let Some(f) = self.state.borrow().filter.as_ref() {
-            if !f(&record) {
-                return;
-            }
+fn remove_group(table: &nwg::ListView, id: usize) {
+    if let Some(hwnd) = table.handle.hwnd() {
+        unsafe {
+            SendMessageW(hwnd, LVM_REMOVEGROUP, id, 0);
         }
+    }
+}
 
-        self.stat_records.borrow_mut().update(&record);
-        self.update_plot_data(&record);
+fn set_item_group(table: &nwg::ListView, row: usize, id: usize) {
+    if let Some(hwnd) = table.handle.hwnd() {
+        let mut item: LVITEMW = unsafe { mem::zeroed() };
+        item.mask = LVIF_GROUPID;
+        item.iItem = row as i32;
+        item.iGroupId = id as i32;
+        unsafe {
+            SendMessageW(hwnd, LVM_SETITEMW, 0, &item as *const _ as LPARAM);
+        }
+    }
+}
 
-        let mode = self.state.borrow().mode;
+fn set_item_text(table: &nwg::ListView, row: usize, column: usize, text: &str) {
+    if let Some(hwnd) = table.handle.hwnd() {
+        let mut text = OsStr::new(text).encode_wide().chain(iter::once(0)).collect::<Vec<u16>>();
+        let mut item: LVITEMW = unsafe { mem::zeroed() };
+        item.iSubItem = column as i32;
+        item.pszText = text.as_mut_ptr();
+        unsafe {
+            SendMessageW(hwnd, LVM_SETITEMTEXTW, row, &item as *const _ as LPARAM);
+        }
+    }
+}
 
-        match mode {
-            Mode::Record => self.update_record_table(&record),
-            Mode::Plot => {},
-            Mode::Stat => self.display_stat_table(),
-            Mode::About => {},
+/// scroll `table` so that `row` is in view
+fn ensure_visible(table: &nwg::ListView, row: usize) {
+    if let Some(hwnd) = table.handle.hwnd() {
+        unsafe {
+            SendMessageW(hwnd, LVM_ENSUREVISIBLE, row, FALSE as _);
         }
     }
+}
 
-    fn update_record_table(&self, record: &Record) {
-        self.record_table.insert_items_row(None, &record.to_string_array());
+fn rolling_text(secs: u32) -> String {
+    format!("仅显示最近 {} 秒", secs)
+}
+
+fn recv_mode_label(mode: RecvMode) -> &'static str {
+    match mode {
+        RecvMode::On => "网卡收发的全部分组（含其他主机的流量）",
+        RecvMode::IpLevel => "仅本机收发的分组",
+        RecvMode::Off => "关闭（几乎收不到分组）",
     }
+}
 
-    fn tick(&self) {
-        let time = Local::now();
-        let mut capturer = self.capturer.borrow_mut();
-        if let Ok(raw_packet) = capturer.read_mut() {
-            let len = raw_packet.len();
-            if len == 0 {
-                return;
-            }
-            let mut record = Record {
-                time,
-                src_ip: None,
-                src_port: None,
-                dest_ip: None,
-                dest_port: None,
-                len: len as u16,
-                ip_payload_len: None,
-                trans_proto: Protocol::Unknown(0),
-                trans_payload_len: None,
-                app_proto: AppProtocol::Unknown,
-            };
-            if let Ok(mut ip_packet) = v4::Packet::new(&raw_packet[..]) {
-                if ip_packet.length() < 20 {
-                    // corrupted ipv4 packet, try to recover packet
-                    if len > 4 {
-                        // TODO: handle the error, although this is unlikely to happen
-                        let _ = (&mut raw_packet[2..]).write_u16::<NetworkEndian>(len as u16);
-                        ip_packet = v4::Packet::unchecked(raw_packet);
-                    }
-                }
-                let ip_payload_len = ip_packet.payload().len();
-                let have_payload = ip_payload_len != 0;
-
-                record.ip_payload_len = Some(ip_payload_len as u16);
-                record.src_ip = Some(ip_packet.source());
-                record.dest_ip = Some(ip_packet.destination());
-                record.trans_proto = ip_packet.protocol();
-                match ip_packet.protocol() {
-                    Protocol::Tcp if have_payload => {
-                        if let Ok(tcp_packet) = tcp::Packet::new(ip_packet.payload()) {
-                            let src_port = tcp_packet.source();
-                            let dest_port = tcp_packet.destination();
-                            record.trans_payload_len = Some(tcp_packet.payload().len() as u16);
-                            record.src_port = Some(src_port);
-                            record.dest_port = Some(dest_port);
-                            record.app_proto = AppProtocol::from((src_port, dest_port));
-                        }
-                    }
-                    Protocol::Udp if have_payload => {
-                        if let Ok(udp_packet) = udp::Packet::new(ip_packet.payload()) {
-                            let src_port = udp_packet.source();
-                            let dest_port = udp_packet.destination();
-                            record.trans_payload_len = Some(udp_packet.payload().len() as u16);
-                            record.src_port = Some(src_port);
-                            record.dest_port = Some(dest_port);
-                            record.app_proto = AppProtocol::from((src_port, dest_port));
-                        }
-                    }
-                    _ => {},
-                };
-            }
-            self.update_record(record);
-        }
+fn check_state(checked: bool) -> nwg::CheckBoxState {
+    if checked {
+        nwg::CheckBoxState::Checked
+    } else {
+        nwg::CheckBoxState::Unchecked
     }
+}
 
-    fn window_maximize(&self) {
-        if { self.state.borrow().mode } == Mode::Plot {
-            self.plotting_timer.start();
+fn gui_main(debug: bool, file: Option<&Path>, selftest: bool) -> Result<()> {
+    // for the messages shared with the cli
+    set_lang(Lang::Zh);
+    // only the console of a parent shell, the gui is not going to write much to it
+    let _ = attach_console();
+    // reading a file or showing generated traffic needs no administrator rights, otherwise they are
+    // asked for before the adapters are listed, rather than once binding fails
+    let mut declined = false;
+    if file.is_none() && !selftest && matches!(is_elevated(), Ok(false)) {
+        if offer_elevation() {
+            return Ok(());
         }
+        declined = true;
     }
+    nwg::Font::set_global_default(Some(ui_font(Settings::load().font_size)?));
+    let app = App::new(debug)?;
+    app.state.borrow_mut().elevation_declined = declined;
+    let app = App::build_ui(app)?;
+    if selftest {
+        app.load_selftest();
+    } else if let Some(file) = file {
+        app.load_pcap(file);
+    }
+    dispatch_thread_events(&app);
+    Ok(())
+}
 
-    fn window_resize(&self) {
-        if { self.state.borrow().mode } == Mode::Plot {
-            self.plotting_timer.start();
+/// ask to start again as administrator, `true` when the elevated instance takes over
+fn offer_elevation() -> bool {
+    let content = format!("{}。以当前权限运行时所有网卡都将无法捕获。\n\n是否以管理员身份重新启动？", privilege_message());
+    let choice = nwg::message(&nwg::MessageParams {
+        title: "需要管理员权限",
+        content: &content,
+        buttons: nwg::MessageButtons::YesNo,
+        icons: nwg::MessageIcons::Warning,
+    });
+    if choice != nwg::MessageChoice::Yes {
+        return false;
+    }
+    match relaunch_elevated(&env::args().skip(1).collect::<Vec<_>>()) {
+        Ok(()) => true,
+        Err(err) => {
+            nwg::error_message("无法以管理员身份重新启动", &err.to_string());
+            false
         }
     }
+}
 
-    fn window_close(&self) {
-        nwg::stop_thread_dispatch();
+/// `nwg::dispatch_thread_events`, with the keyboard shortcuts taken out before the focused
+/// control gets them, the same way an accelerator table would
+fn dispatch_thread_events(app: &App) {
+    unsafe {
+        let mut msg: MSG = mem::zeroed();
+        while GetMessageW(&mut msg, ptr::null_mut(), 0, 0) > 0 {
+            // bit 30 is set for auto repeated key downs, holding a key should not keep toggling
+            let repeated = (msg.lParam >> 30) & 1 == 1;
+            if msg.message == WM_KEYDOWN && !repeated && app.handle_shortcut(msg.wParam as i32, msg.hwnd) {
+                continue;
+            }
+            if IsDialogMessageW(GetAncestor(msg.hwnd, GA_ROOT), &mut msg) == 0 {
+                TranslateMessage(&msg);
+                DispatchMessageW(&msg);
+            }
+        }
     }
 }
 
-fn gui_main() -> Result<()> {
-    let _ = attach_console();
-    let font = {
-        let mut font = nwg::Font::default();
-        nwg::Font::builder()
-            .family("Segoe UI")
-            .size(22)
-            .build(&mut font)?;
-        font
-    };
-    nwg::Font::set_global_default(Some(font));
-    let _app = App::build_ui(App::new()?)?;
-    nwg::dispatch_thread_events();
-    Ok(())
+/// the global ui font at `size`, scaled for the display
+fn ui_font(size: FontSize) -> Result<nwg::Font> {
+    let mut font = nwg::Font::default();
+    nwg::Font::builder()
+        .family("Segoe UI")
+        .size(scaled(size.pixels() as i32) as u32)
+        .build(&mut font)?;
+    Ok(font)
+}
+
+/// `ui_font` underlined, for the labels that act as links
+fn link_font(size: FontSize) -> Result<nwg::Font> {
+    let mut font = nwg::Font::default();
+    nwg::Font::builder()
+        .family("Segoe UI")
+        .size(scaled(size.pixels() as i32) as u32)
+        .underline(true)
+        .build(&mut font)?;
+    Ok(font)
+}
+
+/// `px` pixels at 100% scaling in physical pixels; nwg scales layouts by itself, but column
+/// widths, fonts and everything drawn on the plots are physical
+fn scaled(px: i32) -> i32 {
+    (px as f64 * scale_factor()).round() as i32
+}
+
+/// list view column width at 100% scaling in physical pixels
+fn scaled_width(width: isize) -> isize {
+    scaled(width as i32) as isize
+}
+
+/// physical list view column width back at 100% scaling
+fn unscaled_width(width: isize) -> isize {
+    (width as f64 / scale_factor()).round() as isize
+}
+
+thread_local! {
+    /// the scaling of the display the window is on, while nwg keeps the one it started with
+    static SCALE: Cell<f64> = Cell::new(nwg::scale_factor());
+}
+
+fn scale_factor() -> f64 {
+    SCALE.with(Cell::get)
+}
+
+/// move `window` into the rectangle windows suggests once it is dragged onto a display of
+/// another scaling, which is put in `pending` before `sender` tells the app
+fn watch_dpi(window: &nwg::Window, sender: nwg::NoticeSender, pending: Rc<Cell<Option<f64>>>) -> Result<nwg::RawEventHandler, nwg::NwgError> {
+    // raw handler ids below 0x10000 are taken by nwg
+    nwg::bind_raw_event_handler(&window.handle, 0x10000, move |hwnd, msg, wparam, lparam| {
+        if msg != WM_DPICHANGED {
+            return None;
+        }
+        // both halves of `wparam` hold the same dpi
+        pending.set(Some(LOWORD(wparam as u32) as f64 / USER_DEFAULT_SCREEN_DPI as f64));
+        unsafe {
+            let rect = &*(lparam as *const RECT);
+            SetWindowPos(hwnd, ptr::null_mut(), rect.left, rect.top, rect.right - rect.left, rect.bottom - rect.top, SWP_NOZORDER | SWP_NOACTIVATE);
+        }
+        sender.notice();
+        Some(0)
+    })
+}
+
+/// tell `sender` whenever an edit control right under `parent` loses the focus, which windows
+/// only reports to the parent
+fn watch_focus_out(parent: &nwg::ControlHandle, sender: nwg::NoticeSender) -> Result<nwg::RawEventHandler, nwg::NwgError> {
+    // raw handler ids below 0x10000 are taken by nwg
+    nwg::bind_raw_event_handler(parent, 0x10001, move |_, msg, wparam, _| {
+        if msg == WM_COMMAND && HIWORD(wparam as u32) == EN_KILLFOCUS {
+            sender.notice();
+        }
+        None
+    })
+}
+
+unsafe extern "system" fn set_child_font(hwnd: HWND, font: LPARAM) -> BOOL {
+    SendMessageW(hwnd, WM_SETFONT, font as WPARAM, TRUE as LPARAM);
+    TRUE
 }
 
-pub fn main() -> Result<()> {
+/// `debug` mirrors the log to a file as well, `file` is a pcap file to show right away
+pub fn main(debug: bool, file: Option<&Path>, selftest: bool) -> Result<()> {
+    // the manifest already asks for per monitor dpi awareness, this tells nwg to scale its layouts
+    unsafe { nwg::set_dpi_awareness() };
     nwg::init()?;
-    match gui_main() {
+    match gui_main(debug, file, selftest) {
         Ok(_) => Ok(()),
         Err(err) => nwg::fatal_message("fatal error", err.to_string().as_str()),
     }
@@ -0,0 +1,108 @@
+use anyhow::{anyhow, Error, Result};
+
+use winapi::um::{winnls::GetUserDefaultLocaleName, winnt::LOCALE_NAME_MAX_LENGTH};
+
+use std::{cell::Cell, env, str::FromStr};
+
+/// the language the cli prints in, the gui is in chinese either way
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lang {
+    En,
+    Zh,
+}
+
+impl FromStr for Lang {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "en" | "english" => Ok(Lang::En),
+            "zh" | "cn" | "chinese" | "中文" => Ok(Lang::Zh),
+            _ => Err(anyhow!("unknown language \"{}\", expected zh or en", s)),
+        }
+    }
+}
+
+thread_local! {
+    // the cli and the gui print from their main thread alone, and tests may pick their own
+    static LANG: Cell<Lang> = Cell::new(Lang::En);
+}
+
+/// print in `lang` from now on, on this thread
+pub fn set_lang(lang: Lang) {
+    LANG.with(|current| current.set(lang));
+}
+
+pub fn lang() -> Lang {
+    LANG.with(Cell::get)
+}
+
+/// the language of a locale such as "zh_CN.UTF-8" or "en-US", `None` for "C" and the like,
+/// which say nothing about it
+pub fn locale_lang(locale: &str) -> Option<Lang> {
+    let language = locale
+        .split(|c| matches!(c, '_' | '-' | '.' | '@'))
+        .next()?
+        .to_ascii_lowercase();
+    match language.as_str() {
+        "" | "c" | "posix" => None,
+        "zh" => Some(Lang::Zh),
+        _ => Some(Lang::En),
+    }
+}
+
+/// the language of LC_ALL, LC_MESSAGES or LANG as a shell may set them, otherwise that of
+/// the windows user; english unless one of them is chinese
+pub fn system_lang() -> Lang {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|name| env::var(name).ok())
+        .find_map(|locale| locale_lang(&locale))
+        .or_else(|| user_locale().as_deref().and_then(locale_lang))
+        .unwrap_or(Lang::En)
+}
+
+fn user_locale() -> Option<String> {
+    let mut name = [0u16; LOCALE_NAME_MAX_LENGTH];
+    // the length counts the terminating null
+    let len = unsafe { GetUserDefaultLocaleName(name.as_mut_ptr(), name.len() as i32) };
+    (len > 1).then(|| String::from_utf16_lossy(&name[..len as usize - 1]))
+}
+
+/// the english or the chinese text, whichever the cli prints in; with arguments, the chosen
+/// one is a format string for them
+#[macro_export]
+macro_rules! tr {
+    ($en:literal, $zh:literal $(,)?) => {
+        match $crate::lang::lang() {
+            $crate::lang::Lang::En => $en,
+            $crate::lang::Lang::Zh => $zh,
+        }
+    };
+    ($en:literal, $zh:literal, $($arg:expr),+ $(,)?) => {
+        match $crate::lang::lang() {
+            $crate::lang::Lang::En => format!($en, $($arg),+),
+            $crate::lang::Lang::Zh => format!($zh, $($arg),+),
+        }
+    };
+}
+
+#[cfg(test)]
+mod lang_test {
+    use super::*;
+
+    #[test]
+    fn test_locale_lang() {
+        assert_eq!(locale_lang("zh_CN.UTF-8"), Some(Lang::Zh));
+        assert_eq!(locale_lang("zh-Hans-CN"), Some(Lang::Zh));
+        assert_eq!(locale_lang("en-US"), Some(Lang::En));
+        assert_eq!(locale_lang("de_DE@euro"), Some(Lang::En));
+        assert_eq!(locale_lang("C"), None);
+        assert_eq!(locale_lang("POSIX"), None);
+        assert_eq!(locale_lang(""), None);
+
+        assert_eq!("ZH".parse::<Lang>().unwrap(), Lang::Zh);
+        assert_eq!(" en ".parse::<Lang>().unwrap(), Lang::En);
+        assert!("fr".parse::<Lang>().is_err());
+    }
+}
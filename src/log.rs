@@ -0,0 +1,167 @@
+use anyhow::{anyhow, Result};
+
+use chrono::{DateTime, Local};
+
+use crate::meta;
+
+use std::{
+    collections::VecDeque,
+    env,
+    fmt::Display,
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::PathBuf,
+};
+
+const TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.3f";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Info,
+    Warn,
+    Error,
+}
+
+impl Level {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Level::Info => "信息",
+            Level::Warn => "警告",
+            Level::Error => "错误",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub time: DateTime<Local>,
+    pub level: Level,
+    pub message: String,
+}
+
+impl Display for Entry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} [{}] {}",
+            self.time.format(TIME_FORMAT),
+            self.level.label(),
+            self.message
+        )
+    }
+}
+
+/// the latest `capacity` entries, older ones are dropped; optionally mirrored to a file
+#[derive(Debug)]
+pub struct Log {
+    entries: VecDeque<Entry>,
+    capacity: usize,
+    file: Option<File>,
+}
+
+impl Default for Log {
+    fn default() -> Self {
+        Self::new(1000)
+    }
+}
+
+impl Log {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            capacity: capacity.max(1),
+            file: None,
+        }
+    }
+
+    /// `debug.log` next to the settings file
+    pub fn file_path() -> Result<PathBuf> {
+        let app_data = env::var_os("APPDATA").ok_or(anyhow!("APPDATA is not set"))?;
+        Ok(PathBuf::from(app_data).join(meta::NAME).join("debug.log"))
+    }
+
+    /// also append every entry to `file_path()`
+    pub fn mirror_to_file(&mut self) -> Result<()> {
+        let path = Self::file_path()?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        self.file = Some(OpenOptions::new().create(true).append(true).open(path)?);
+        Ok(())
+    }
+
+    pub fn push(&mut self, level: Level, message: impl Into<String>) {
+        let entry = Entry {
+            time: Local::now(),
+            level,
+            message: message.into(),
+        };
+        if let Some(file) = &mut self.file {
+            // a broken log file is not worth bothering the user about
+            if writeln!(file, "{}", entry).is_err() {
+                self.file = None;
+            }
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+
+    #[cfg(test)]
+    pub fn entries(&self) -> impl Iterator<Item = &Entry> {
+        self.entries.iter()
+    }
+
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// one entry per line, oldest first, with windows line endings for the edit control
+    pub fn to_text(&self) -> String {
+        self.entries
+            .iter()
+            .map(|entry| entry.to_string())
+            .collect::<Vec<_>>()
+            .join("\r\n")
+    }
+}
+
+#[cfg(test)]
+mod log_test {
+    use super::*;
+
+    #[test]
+    fn test_log() {
+        let mut log = Log::new(2);
+        assert!(log.is_empty());
+        log.push(Level::Info, "first");
+        log.push(Level::Warn, "second");
+        log.push(Level::Error, "third");
+        assert_eq!(log.len(), 2);
+        let messages = log
+            .entries()
+            .map(|entry| (entry.level, entry.message.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(messages, [(Level::Warn, "second"), (Level::Error, "third")]);
+
+        let text = log.to_text();
+        let lines = text.split("\r\n").collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].ends_with(" [警告] second"));
+        assert!(lines[1].ends_with(" [错误] third"));
+
+        log.clear();
+        assert!(log.is_empty());
+        assert_eq!(log.to_text(), "");
+    }
+}
@@ -1,21 +1,39 @@
 #![cfg_attr(not(test), windows_subsystem = "windows")]
 
+mod bench;
+mod capture;
 mod cli;
+mod dns;
+mod export;
 mod filter;
 mod gui;
+mod lang;
+mod log;
 mod meta;
+mod pcap;
 mod record;
+mod report;
+mod resolve;
+mod schedule;
+mod settings;
 mod socket;
+mod synth;
 mod utils;
 
 use anyhow::Result;
 
+use cli::Launch;
+
 use std::env;
 
 fn main() -> Result<()> {
-    if env::args().len() > 1 {
-        cli::main()
-    } else {
-        gui::main()
+    // no subcommand opens the gui, with the pcap file dropped onto the executable if any
+    match cli::launch(env::args_os().collect()) {
+        Launch::Gui {
+            debug,
+            file,
+            selftest,
+        } => gui::main(debug, file.as_deref(), selftest),
+        Launch::Cli { command, lang } => cli::main(&command, lang),
     }
 }
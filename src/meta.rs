@@ -1,3 +1,11 @@
 pub const NAME: &'static str = env!("CARGO_PKG_NAME");
 pub const VERSION: &'static str = env!("CARGO_PKG_VERSION");
 pub const AUTHORS: &'static str = env!("CARGO_PKG_AUTHORS");
+pub const LICENSE: &'static str = env!("CARGO_PKG_LICENSE");
+pub const REPOSITORY: &'static str = env!("CARGO_PKG_REPOSITORY");
+pub const ISSUES: &'static str = concat!(env!("CARGO_PKG_REPOSITORY"), "/issues");
+// the ones below are set by build.rs
+pub const GIT_HASH: &'static str = env!("BUILD_GIT_HASH");
+pub const BUILD_DATE: &'static str = env!("BUILD_DATE");
+/// `name version` of the main dependencies, separated by ", "
+pub const DEPENDENCIES: &'static str = env!("BUILD_DEPENDENCIES");
@@ -0,0 +1,460 @@
+use anyhow::{anyhow, bail, Result};
+
+use chrono::{DateTime, Local, TimeZone};
+
+use crate::capture::{PacketSource, ReadPacket};
+
+use std::{
+    io::{self, Read, Write},
+    str::FromStr,
+    time::Duration,
+};
+
+const MAGIC_MICROS: u32 = 0xa1b2_c3d4;
+const MAGIC_NANOS: u32 = 0xa1b2_3c4d;
+const HEADER_LEN: usize = 24;
+const RECORD_HEADER_LEN: usize = 16;
+/// larger records are taken for a corrupted file rather than allocated
+const MAX_RECORD_LEN: usize = 256 * 1024;
+
+/// the snapshot length written, as long as any ip packet; a packet cut short by the capture
+/// keeps its original length in its record
+const SNAPLEN: u32 = 65535;
+/// the link type written, bare ip packets
+const LINKTYPE_RAW: u32 = 101;
+
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_VLAN: u16 = 0x8100;
+
+/// how the packets of a capture file are framed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkType {
+    Ethernet,
+    /// linux "cooked" captures, as taken on the any device
+    LinuxSll,
+    /// bare ip packets, either version
+    Raw,
+    Ipv4,
+}
+
+impl LinkType {
+    fn from_code(code: u32) -> Option<Self> {
+        match code {
+            1 => Some(Self::Ethernet),
+            113 => Some(Self::LinuxSll),
+            101 => Some(Self::Raw),
+            228 => Some(Self::Ipv4),
+            _ => None,
+        }
+    }
+
+    /// where the ipv4 packet starts in `frame`, `None` when it holds something else
+    fn ipv4_offset(self, frame: &[u8]) -> Option<usize> {
+        let ethertype = |at: usize| {
+            frame
+                .get(at..at + 2)
+                .map(|b| u16::from_be_bytes([b[0], b[1]]))
+        };
+        match self {
+            Self::Ethernet => match ethertype(12)? {
+                ETHERTYPE_IPV4 => Some(14),
+                // a single 802.1q tag, as seen on trunk ports
+                ETHERTYPE_VLAN if ethertype(16)? == ETHERTYPE_IPV4 => Some(18),
+                _ => None,
+            },
+            Self::LinuxSll if ethertype(14)? == ETHERTYPE_IPV4 => Some(16),
+            Self::LinuxSll => None,
+            Self::Raw if frame.first()? >> 4 == 4 => Some(0),
+            Self::Raw => None,
+            Self::Ipv4 => Some(0),
+        }
+    }
+}
+
+/// reads the ipv4 packets of a pcap file one by one, skipping whatever else it holds
+pub struct PcapReader<R> {
+    reader: R,
+    big_endian: bool,
+    nanos: bool,
+    link_type: LinkType,
+    frame: Vec<u8>,
+}
+
+impl<R: Read> PcapReader<R> {
+    /// read the file header, failing when `reader` is not a pcap file of a supported link type
+    pub fn new(mut reader: R) -> Result<Self> {
+        let mut header = [0u8; HEADER_LEN];
+        reader
+            .read_exact(&mut header)
+            .map_err(|_| anyhow!("not a pcap file, it is too short"))?;
+        let magic = [header[0], header[1], header[2], header[3]];
+        let (big_endian, nanos) = match (u32::from_le_bytes(magic), u32::from_be_bytes(magic)) {
+            (MAGIC_MICROS, _) => (false, false),
+            (MAGIC_NANOS, _) => (false, true),
+            (_, MAGIC_MICROS) => (true, false),
+            (_, MAGIC_NANOS) => (true, true),
+            // pcapng starts with a section header block instead
+            _ => bail!("not a pcap file, pcapng files have to be saved as pcap first"),
+        };
+        let mut reader = Self {
+            reader,
+            big_endian,
+            nanos,
+            link_type: LinkType::Raw,
+            frame: Vec::new(),
+        };
+        let code = reader.u32(&header[20..24]) & 0xffff;
+        reader.link_type =
+            LinkType::from_code(code).ok_or(anyhow!("unsupported link type {}", code))?;
+        Ok(reader)
+    }
+
+    #[cfg(test)]
+    pub fn link_type(&self) -> LinkType {
+        self.link_type
+    }
+
+    /// put the next ipv4 packet in `packet`, returning the time it was captured at and whether
+    /// only its start was saved, or `None` at the end of the file
+    pub fn read_packet(&mut self, packet: &mut Vec<u8>) -> Result<Option<(DateTime<Local>, bool)>> {
+        loop {
+            let mut header = [0u8; RECORD_HEADER_LEN];
+            match self.reader.read_exact(&mut header) {
+                Ok(()) => {}
+                // a capture cut short mid-header ends the file just the same
+                Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+                Err(err) => return Err(err.into()),
+            }
+            let secs = self.u32(&header[0..4]);
+            let fraction = self.u32(&header[4..8]);
+            let len = self.u32(&header[8..12]) as usize;
+            let original_len = self.u32(&header[12..16]) as usize;
+            if len > MAX_RECORD_LEN {
+                bail!("corrupted pcap file, a packet claims to be {} bytes", len);
+            }
+            self.frame.resize(len, 0);
+            match self.reader.read_exact(&mut self.frame) {
+                Ok(()) => {}
+                Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+                Err(err) => return Err(err.into()),
+            }
+            let offset = match self.link_type.ipv4_offset(&self.frame) {
+                Some(offset) => offset,
+                None => continue,
+            };
+            let nanos = if self.nanos {
+                fraction
+            } else {
+                fraction.saturating_mul(1000)
+            };
+            let time = Local
+                .timestamp_opt(secs as i64, nanos)
+                .single()
+                .ok_or(anyhow!("corrupted pcap file, a packet has an invalid time"))?;
+            packet.clear();
+            packet.extend_from_slice(&self.frame[offset..]);
+            return Ok(Some((time, original_len > len)));
+        }
+    }
+
+    fn u32(&self, bytes: &[u8]) -> u32 {
+        let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+        if self.big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        }
+    }
+}
+
+/// the packets of a pcap file as a `PacketSource`, at the times they were captured at
+pub struct PcapSource<R> {
+    reader: PcapReader<R>,
+    packet: Vec<u8>,
+    ended: bool,
+}
+
+impl<R: Read> PcapSource<R> {
+    pub fn new(reader: PcapReader<R>) -> Self {
+        Self {
+            reader,
+            packet: Vec::new(),
+            ended: false,
+        }
+    }
+}
+
+impl<R: Read + Send + 'static> PacketSource for PcapSource<R> {
+    /// the next packet is always there without waiting, up to the end of the file
+    fn read_timeout(&mut self, _timeout: Duration) -> Result<Option<ReadPacket<'_>>> {
+        if self.ended {
+            return Ok(None);
+        }
+        match self.reader.read_packet(&mut self.packet)? {
+            Some((time, truncated)) => Ok(Some((time, &mut self.packet, None, truncated))),
+            None => {
+                self.ended = true;
+                Ok(None)
+            }
+        }
+    }
+
+    fn connected(&self) -> bool {
+        !self.ended
+    }
+
+    fn buffer_size(&self) -> usize {
+        MAX_RECORD_LEN
+    }
+}
+
+/// writes ipv4 packets as a pcap stream, which tools such as tcpdump and wireshark can read
+/// from a pipe as well as from a file
+pub struct PcapWriter<W> {
+    writer: W,
+}
+
+impl<W: Write> PcapWriter<W> {
+    /// write the file header, in little endian with microsecond times
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        writer.write_all(&MAGIC_MICROS.to_le_bytes())?;
+        // version 2.4, then the time zone offset and accuracy which are always 0
+        writer.write_all(&2u16.to_le_bytes())?;
+        writer.write_all(&4u16.to_le_bytes())?;
+        writer.write_all(&[0; 8])?;
+        writer.write_all(&SNAPLEN.to_le_bytes())?;
+        writer.write_all(&LINKTYPE_RAW.to_le_bytes())?;
+        Ok(Self { writer })
+    }
+
+    /// write `packet`, `original_len` being how long it was before the capture cut it short
+    pub fn write_packet(
+        &mut self,
+        time: DateTime<Local>,
+        packet: &[u8],
+        original_len: usize,
+    ) -> io::Result<()> {
+        let len = packet.len() as u32;
+        let original_len = original_len.max(packet.len()) as u32;
+        self.writer
+            .write_all(&(time.timestamp() as u32).to_le_bytes())?;
+        self.writer
+            .write_all(&time.timestamp_subsec_micros().to_le_bytes())?;
+        self.writer.write_all(&len.to_le_bytes())?;
+        self.writer.write_all(&original_len.to_le_bytes())?;
+        self.writer.write_all(packet)
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// how packets are framed when passed on raw
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RawFormat {
+    /// each packet after its length, as 4 bytes in little endian
+    Frames,
+    Pcap,
+}
+
+impl FromStr for RawFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "frames" => Ok(Self::Frames),
+            "pcap" => Ok(Self::Pcap),
+            _ => bail!("unknown raw format \"{}\", expected frames or pcap", s),
+        }
+    }
+}
+
+/// writes packets one after another in a `RawFormat`
+pub enum RawWriter<W> {
+    Frames(W),
+    Pcap(PcapWriter<W>),
+}
+
+impl<W: Write> RawWriter<W> {
+    pub fn new(writer: W, format: RawFormat) -> io::Result<Self> {
+        Ok(match format {
+            RawFormat::Frames => Self::Frames(writer),
+            RawFormat::Pcap => Self::Pcap(PcapWriter::new(writer)?),
+        })
+    }
+
+    /// write `packet`, `original_len` being how long it was before the capture cut it short
+    pub fn write_packet(
+        &mut self,
+        time: DateTime<Local>,
+        packet: &[u8],
+        original_len: usize,
+    ) -> io::Result<()> {
+        match self {
+            Self::Frames(writer) => {
+                writer.write_all(&(packet.len() as u32).to_le_bytes())?;
+                writer.write_all(packet)
+            }
+            Self::Pcap(writer) => writer.write_packet(time, packet, original_len),
+        }
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Frames(writer) => writer.flush(),
+            Self::Pcap(writer) => writer.flush(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod pcap_test {
+    use super::*;
+
+    fn pcap(
+        big_endian: bool,
+        magic: u32,
+        link_type: u32,
+        records: &[(u32, u32, &[u8])],
+    ) -> Vec<u8> {
+        let u32_bytes = |n: u32| {
+            if big_endian {
+                n.to_be_bytes()
+            } else {
+                n.to_le_bytes()
+            }
+        };
+        let mut file = u32_bytes(magic).to_vec();
+        // the version, time zone offset and accuracy are not looked at
+        file.extend_from_slice(&[0; 12]);
+        file.extend_from_slice(&u32_bytes(65535));
+        file.extend_from_slice(&u32_bytes(link_type));
+        for &(secs, fraction, data) in records {
+            file.extend_from_slice(&u32_bytes(secs));
+            file.extend_from_slice(&u32_bytes(fraction));
+            file.extend_from_slice(&u32_bytes(data.len() as u32));
+            file.extend_from_slice(&u32_bytes(data.len() as u32));
+            file.extend_from_slice(data);
+        }
+        file
+    }
+
+    const IPV4: [u8; 4] = [0x45, 0, 0, 20];
+
+    #[test]
+    fn test_ethernet() {
+        let frame = |ethertype: &[u8], payload: &[u8]| {
+            let mut frame = vec![0xff; 12];
+            frame.extend_from_slice(ethertype);
+            frame.extend_from_slice(payload);
+            frame
+        };
+        let ipv4 = frame(&[0x08, 0x00], &IPV4);
+        let arp = frame(&[0x08, 0x06], &[0, 1, 8, 0]);
+        let vlan = frame(&[0x81, 0x00, 0, 5, 0x08, 0x00], &IPV4);
+        let file = pcap(
+            false,
+            MAGIC_MICROS,
+            1,
+            &[(100, 250_000, &ipv4), (101, 0, &arp), (102, 1, &vlan)],
+        );
+        let mut reader = PcapReader::new(file.as_slice()).unwrap();
+        assert_eq!(reader.link_type(), LinkType::Ethernet);
+        let mut packet = Vec::new();
+        let (time, truncated) = reader.read_packet(&mut packet).unwrap().unwrap();
+        assert_eq!(
+            (time.timestamp(), time.timestamp_subsec_micros(), truncated),
+            (100, 250_000, false)
+        );
+        assert_eq!(packet, IPV4);
+        // the arp frame is skipped
+        let (time, _) = reader.read_packet(&mut packet).unwrap().unwrap();
+        assert_eq!(
+            (time.timestamp(), time.timestamp_subsec_nanos()),
+            (102, 1000)
+        );
+        assert_eq!(packet, IPV4);
+        assert_eq!(reader.read_packet(&mut packet).unwrap(), None);
+    }
+
+    #[test]
+    fn test_raw() {
+        let ipv6 = [0x60, 0, 0, 0];
+        let file = pcap(true, MAGIC_NANOS, 101, &[(7, 5, &ipv6), (8, 5, &IPV4)]);
+        let mut reader = PcapReader::new(file.as_slice()).unwrap();
+        let mut packet = Vec::new();
+        let (time, _) = reader.read_packet(&mut packet).unwrap().unwrap();
+        assert_eq!((time.timestamp(), time.timestamp_subsec_nanos()), (8, 5));
+        assert_eq!(packet, IPV4);
+        assert_eq!(reader.read_packet(&mut packet).unwrap(), None);
+
+        // a packet cut short by the end of the file is dropped
+        let mut file = pcap(false, MAGIC_MICROS, 228, &[(1, 0, &IPV4)]);
+        file.truncate(file.len() - 1);
+        let mut reader = PcapReader::new(file.as_slice()).unwrap();
+        assert_eq!(reader.read_packet(&mut packet).unwrap(), None);
+    }
+
+    #[test]
+    fn test_source() {
+        let file = pcap(false, MAGIC_MICROS, 228, &[(3, 0, &IPV4), (4, 0, &IPV4)]);
+        let reader = PcapReader::new(io::Cursor::new(file)).unwrap();
+        let mut source = PcapSource::new(reader);
+        for secs in [3, 4] {
+            assert!(source.connected());
+            let (time, packet, local, truncated) =
+                source.read_timeout(Duration::ZERO).unwrap().unwrap();
+            assert_eq!(
+                (time.timestamp(), &*packet, local, truncated),
+                (secs, &IPV4[..], None, false)
+            );
+        }
+        assert!(source.read_timeout(Duration::ZERO).unwrap().is_none());
+        assert!(!source.connected());
+    }
+
+    #[test]
+    fn test_raw_writer() {
+        let time = Local.timestamp_opt(1_636_270_200, 125_000_000).unwrap();
+        let mut file = Vec::new();
+        let mut writer = RawWriter::new(&mut file, RawFormat::Pcap).unwrap();
+        writer.write_packet(time, &IPV4, IPV4.len()).unwrap();
+        // only the start of a longer packet
+        writer.write_packet(time, &IPV4, 1500).unwrap();
+        writer.flush().unwrap();
+        assert_eq!(
+            file.len(),
+            HEADER_LEN + 2 * (RECORD_HEADER_LEN + IPV4.len())
+        );
+        let mut reader = PcapReader::new(file.as_slice()).unwrap();
+        assert_eq!(reader.link_type(), LinkType::Raw);
+        let mut packet = Vec::new();
+        assert_eq!(
+            reader.read_packet(&mut packet).unwrap(),
+            Some((time, false))
+        );
+        assert_eq!(packet, IPV4);
+        assert_eq!(reader.read_packet(&mut packet).unwrap(), Some((time, true)));
+        assert_eq!(packet, IPV4);
+
+        let mut frames = Vec::new();
+        let mut writer = RawWriter::new(&mut frames, RawFormat::Frames).unwrap();
+        writer.write_packet(time, &IPV4, IPV4.len()).unwrap();
+        writer.write_packet(time, &[], 0).unwrap();
+        assert_eq!(frames, [4, 0, 0, 0, 0x45, 0, 0, 20, 0, 0, 0, 0]);
+
+        assert_eq!("PCAP".parse::<RawFormat>().unwrap(), RawFormat::Pcap);
+        assert!("pcapng".parse::<RawFormat>().is_err());
+    }
+
+    #[test]
+    fn test_not_pcap() {
+        assert!(PcapReader::new(&b"short"[..]).is_err());
+        // a pcapng section header block
+        assert!(PcapReader::new([0x0a, 0x0d, 0x0d, 0x0a].repeat(6).as_slice()).is_err());
+        let file = pcap(false, MAGIC_MICROS, 105, &[]);
+        let err = PcapReader::new(file.as_slice()).err().unwrap();
+        assert_eq!(err.to_string(), "unsupported link type 105");
+    }
+}
@@ -1,14 +1,57 @@
+use crate::dns::{DnsInfo, RCODE_NXDOMAIN, RCODE_SERVFAIL};
 use crate::utils::{trans_protocol_name, AppProtocol, TransProtocol};
 use anyhow::{anyhow, Error, Result};
-use chrono::prelude::*;
-use packet::ip::Protocol;
+use byteorder::{NetworkEndian, WriteBytesExt};
+use chrono::{prelude::*, Duration};
+use itertools::Itertools;
+use packet::{
+    ip::{v4, Protocol},
+    tcp, udp, Packet,
+};
+use serde::{Deserialize, Serialize};
 use std::{
-    collections::{hash_map::Entry as HashMapEntry, HashMap},
+    collections::{hash_map::Entry as HashMapEntry, vec_deque, HashMap, VecDeque},
     convert::TryFrom,
-    iter,
+    fmt::Display,
+    iter, mem,
     net::Ipv4Addr,
+    ops::Range,
+    str::FromStr,
 };
 
+/// width of a plot bucket, in milliseconds
+pub const PLOT_SAMPLING_INTERVAL: u64 = 200;
+
+/// direction of a packet relative to the address the capturer is bound to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Inbound,
+    Outbound,
+    Unknown,
+}
+
+impl Direction {
+    pub fn classify(src_ip: Option<Ipv4Addr>, dest_ip: Option<Ipv4Addr>, local: Ipv4Addr) -> Self {
+        if src_ip == Some(local) {
+            Self::Outbound
+        } else if dest_ip == Some(local) {
+            Self::Inbound
+        } else {
+            Self::Unknown
+        }
+    }
+}
+
+impl Display for Direction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Inbound => write!(f, "接收"),
+            Self::Outbound => write!(f, "发送"),
+            Self::Unknown => write!(f, "其他"),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Record {
     pub time: DateTime<Local>,
@@ -21,30 +64,439 @@ pub struct Record {
     pub trans_proto: Protocol,
     pub trans_payload_len: Option<u16>,
     pub app_proto: AppProtocol,
+    pub direction: Direction,
+    pub dns: Option<DnsInfo>,
+    /// the adapter the packet was captured on
+    pub interface: Option<String>,
+    /// only the start of the packet was captured, the lengths still count all of it
+    pub truncated: bool,
 }
 
 impl Record {
-    pub fn to_string_array(&self) -> [String; 10] {
-        [
-            self.time.format("%Y-%m-%d %H:%M:%S%.6f").to_string(),
-            self.src_ip.map_or("".to_string(), |ip| ip.to_string()),
-            self.src_port
-                .map_or("".to_string(), |port| port.to_string()),
-            self.dest_ip.map_or("".to_string(), |ip| ip.to_string()),
-            self.dest_port
-                .map_or("".to_string(), |port| port.to_string()),
-            self.len.to_string(),
-            self.ip_payload_len
-                .map_or("".to_string(), |l| l.to_string()),
-            TransProtocol(self.trans_proto).to_string(),
-            self.trans_payload_len
-                .map_or("".to_string(), |l| l.to_string()),
-            if matches!(self.trans_proto, Protocol::Udp | Protocol::Tcp) {
-                self.app_proto.to_string()
-            } else {
-                "".to_string()
-            },
-        ]
+    /// parse a captured packet, `source` being the address and name of the adapter it was
+    /// captured on and `truncated` whether the read cut it short; whatever can not be parsed
+    /// is left out of the record
+    pub fn from_packet(
+        time: DateTime<Local>,
+        raw_packet: &mut [u8],
+        source: Option<(Ipv4Addr, &str)>,
+        truncated: bool,
+    ) -> Self {
+        let len = raw_packet.len();
+        let mut record = Self {
+            time,
+            src_ip: None,
+            src_port: None,
+            dest_ip: None,
+            dest_port: None,
+            len: len as u16,
+            ip_payload_len: None,
+            trans_proto: Protocol::Unknown(0),
+            trans_payload_len: None,
+            app_proto: AppProtocol::Unknown,
+            direction: Direction::Unknown,
+            dns: None,
+            interface: source.map(|(_, name)| name.to_string()),
+            truncated: false,
+        };
+        // a packet cut short by the read still says how long it is in its header, which is
+        // made to match what was read so that the rest of it can be parsed
+        let total_len = match raw_packet.get(2..4) {
+            Some(field) => u16::from_be_bytes([field[0], field[1]]) as usize,
+            None => 0,
+        };
+        if total_len > len {
+            if truncated {
+                record.len = total_len as u16;
+                record.truncated = true;
+            }
+            let _ = (&mut raw_packet[2..]).write_u16::<NetworkEndian>(len as u16);
+        }
+        if let Ok(mut ip_packet) = v4::Packet::new(&raw_packet[..]) {
+            if ip_packet.length() < 20 {
+                // corrupted ipv4 packet, try to recover packet
+                if len > 4 {
+                    // TODO: handle the error, although this is unlikely to happen
+                    let _ = (&mut raw_packet[2..]).write_u16::<NetworkEndian>(len as u16);
+                    ip_packet = v4::Packet::unchecked(raw_packet);
+                }
+            }
+            let ip_payload_len = ip_packet.payload().len();
+            let have_payload = ip_payload_len != 0;
+
+            record.ip_payload_len = Some(ip_payload_len as u16);
+            record.src_ip = Some(ip_packet.source());
+            record.dest_ip = Some(ip_packet.destination());
+            if let Some((local_ip, _)) = source {
+                record.direction = Direction::classify(record.src_ip, record.dest_ip, local_ip);
+            }
+            record.trans_proto = ip_packet.protocol();
+            match ip_packet.protocol() {
+                Protocol::Tcp if have_payload => {
+                    if let Ok(tcp_packet) = tcp::Packet::new(ip_packet.payload()) {
+                        let src_port = tcp_packet.source();
+                        let dest_port = tcp_packet.destination();
+                        record.trans_payload_len = Some(tcp_packet.payload().len() as u16);
+                        record.src_port = Some(src_port);
+                        record.dest_port = Some(dest_port);
+                        record.app_proto = AppProtocol::from((src_port, dest_port));
+                        if record.app_proto == AppProtocol::Dns {
+                            record.dns = DnsInfo::from_tcp_payload(tcp_packet.payload());
+                        }
+                    }
+                }
+                Protocol::Udp if have_payload => {
+                    if let Ok(udp_packet) = udp::Packet::new(ip_packet.payload()) {
+                        let src_port = udp_packet.source();
+                        let dest_port = udp_packet.destination();
+                        record.trans_payload_len = Some(udp_packet.payload().len() as u16);
+                        record.src_port = Some(src_port);
+                        record.dest_port = Some(dest_port);
+                        record.app_proto = AppProtocol::from((src_port, dest_port));
+                        if record.app_proto == AppProtocol::Dns {
+                            record.dns = Some(DnsInfo::from_udp_payload(udp_packet.payload()));
+                        }
+                    }
+                }
+                _ => {}
+            };
+        }
+        if record.truncated {
+            let missing = (total_len - len) as u16;
+            record.ip_payload_len = record.ip_payload_len.map(|l| l + missing);
+            record.trans_payload_len = record.trans_payload_len.map(|l| l + missing);
+        }
+        record
+    }
+
+    /// the cell of `column`, formatted only when asked for so hidden columns cost nothing
+    pub fn column_string(&self, column: RecordColumn) -> String {
+        fn optional(value: Option<impl ToString>) -> String {
+            value.map_or_else(String::new, |v| v.to_string())
+        }
+        match column {
+            RecordColumn::Time => self.time.format("%Y-%m-%d %H:%M:%S%.6f").to_string(),
+            RecordColumn::SrcIp => optional(self.src_ip),
+            RecordColumn::SrcPort => optional(self.src_port),
+            RecordColumn::DestIp => optional(self.dest_ip),
+            RecordColumn::DestPort => optional(self.dest_port),
+            RecordColumn::Len => self.len.to_string(),
+            RecordColumn::IpPayloadLen => optional(self.ip_payload_len),
+            RecordColumn::TransProto => TransProtocol(self.trans_proto).to_string(),
+            RecordColumn::TransPayloadLen => optional(self.trans_payload_len),
+            RecordColumn::AppProto => {
+                if matches!(self.trans_proto, Protocol::Udp | Protocol::Tcp) {
+                    self.app_proto.name().to_string()
+                } else {
+                    String::new()
+                }
+            }
+            RecordColumn::Direction => self.direction.to_string(),
+            RecordColumn::Interface => self.interface.clone().unwrap_or_default(),
+            RecordColumn::Truncated => if self.truncated { "是" } else { "" }.to_string(),
+        }
+    }
+
+    /// rough number of bytes the record takes in memory
+    pub fn memory_size(&self) -> usize {
+        let dns = match &self.dns {
+            Some(DnsInfo::Query { name }) | Some(DnsInfo::Response { name, .. }) => name.capacity(),
+            _ => 0,
+        };
+        mem::size_of::<Self>() + dns + self.interface.as_ref().map_or(0, |i| i.capacity())
+    }
+
+    /// the cells of `columns`, in the given order
+    pub fn to_column_strings(&self, columns: &[RecordColumn]) -> Vec<String> {
+        columns
+            .iter()
+            .map(|&column| self.column_string(column))
+            .collect()
+    }
+
+    /// whether any of `columns` contains `text`, ignoring case
+    pub fn contains_text(&self, columns: &[RecordColumn], text: &str) -> bool {
+        let text = text.to_lowercase();
+        columns
+            .iter()
+            .any(|&column| self.column_string(column).to_lowercase().contains(&text))
+    }
+
+    /// a record for tests to change what they are about, starting out as a 60 byte http
+    /// packet from 10.0.0.1:52311 to 10.0.0.2:80
+    #[cfg(test)]
+    pub fn builder() -> RecordBuilder {
+        RecordBuilder(Self {
+            time: Local::now(),
+            src_ip: Some(Ipv4Addr::new(10, 0, 0, 1)),
+            src_port: Some(52311),
+            dest_ip: Some(Ipv4Addr::new(10, 0, 0, 2)),
+            dest_port: Some(80),
+            len: 60,
+            ip_payload_len: None,
+            trans_proto: Protocol::Tcp,
+            trans_payload_len: None,
+            app_proto: AppProtocol::Http,
+            direction: Direction::Unknown,
+            dns: None,
+            interface: None,
+            truncated: false,
+        })
+    }
+}
+
+/// see `Record::builder`; the payload lengths follow from the length and the protocol
+#[cfg(test)]
+pub struct RecordBuilder(Record);
+
+#[cfg(test)]
+impl RecordBuilder {
+    pub fn time(mut self, time: DateTime<Local>) -> Self {
+        self.0.time = time;
+        self
+    }
+
+    pub fn src(mut self, ip: impl Into<Ipv4Addr>, port: Option<u16>) -> Self {
+        self.0.src_ip = Some(ip.into());
+        self.0.src_port = port;
+        self
+    }
+
+    pub fn dest(mut self, ip: impl Into<Ipv4Addr>, port: Option<u16>) -> Self {
+        self.0.dest_ip = Some(ip.into());
+        self.0.dest_port = port;
+        self
+    }
+
+    pub fn proto(mut self, trans_proto: Protocol, app_proto: AppProtocol) -> Self {
+        self.0.trans_proto = trans_proto;
+        self.0.app_proto = app_proto;
+        self
+    }
+
+    pub fn len(mut self, len: u16) -> Self {
+        self.0.len = len;
+        self
+    }
+
+    pub fn direction(mut self, direction: Direction) -> Self {
+        self.0.direction = direction;
+        self
+    }
+
+    pub fn interface(mut self, name: &str) -> Self {
+        self.0.interface = Some(name.to_string());
+        self
+    }
+
+    pub fn build(self) -> Record {
+        let mut record = self.0;
+        let header_len = match record.trans_proto {
+            Protocol::Tcp => Some(20),
+            Protocol::Udp => Some(8),
+            _ => None,
+        };
+        record.ip_payload_len = Some(record.len - 20);
+        record.trans_payload_len = header_len.map(|header_len| record.len - 20 - header_len);
+        record
+    }
+}
+
+/// the columns of the record table, in their default order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RecordColumn {
+    Time,
+    SrcIp,
+    SrcPort,
+    DestIp,
+    DestPort,
+    Len,
+    IpPayloadLen,
+    TransProto,
+    TransPayloadLen,
+    AppProto,
+    Direction,
+    Interface,
+    Truncated,
+}
+
+impl RecordColumn {
+    pub const ALL: [Self; 13] = [
+        Self::Time,
+        Self::SrcIp,
+        Self::SrcPort,
+        Self::DestIp,
+        Self::DestPort,
+        Self::Len,
+        Self::IpPayloadLen,
+        Self::TransProto,
+        Self::TransPayloadLen,
+        Self::AppProto,
+        Self::Direction,
+        Self::Interface,
+        Self::Truncated,
+    ];
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            Self::Time => "时间",
+            Self::SrcIp => "源IP",
+            Self::SrcPort => "源端口",
+            Self::DestIp => "目的IP",
+            Self::DestPort => "目的端口",
+            Self::Len => "IP分组长度",
+            Self::IpPayloadLen => "IP数据长度",
+            Self::TransProto => "传输层协议",
+            Self::TransPayloadLen => "报文段数据长度",
+            Self::AppProto => "应用层协议",
+            Self::Direction => "方向",
+            Self::Interface => "网卡",
+            Self::Truncated => "截断",
+        }
+    }
+
+    /// the name of the column on the command line and in json output
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Time => "time",
+            Self::SrcIp => "src_ip",
+            Self::SrcPort => "src_port",
+            Self::DestIp => "dest_ip",
+            Self::DestPort => "dest_port",
+            Self::Len => "len",
+            Self::IpPayloadLen => "ip_payload_len",
+            Self::TransProto => "trans_proto",
+            Self::TransPayloadLen => "trans_payload_len",
+            Self::AppProto => "app_proto",
+            Self::Direction => "direction",
+            Self::Interface => "interface",
+            Self::Truncated => "truncated",
+        }
+    }
+
+    /// column width in pixels until the user resizes it
+    pub fn default_width(&self) -> isize {
+        match self {
+            Self::Time => 220,
+            Self::SrcIp | Self::DestIp => 135,
+            Self::SrcPort | Self::Direction | Self::Truncated => 60,
+            Self::DestPort => 80,
+            Self::TransPayloadLen => 120,
+            Self::Interface => 160,
+            _ => 100,
+        }
+    }
+}
+
+impl FromStr for RecordColumn {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::ALL
+            .iter()
+            .find(|column| column.name() == s.trim())
+            .copied()
+            .ok_or_else(|| {
+                anyhow!(
+                    "unknown field \"{}\", expected one of {}",
+                    s,
+                    Self::ALL.iter().map(|column| column.name()).join(", ")
+                )
+            })
+    }
+}
+
+/// captured records, oldest first; beyond `max_records` records or `max_bytes` bytes the
+/// oldest ones are dropped, a limit of 0 meaning unlimited
+#[derive(Debug, Default)]
+pub struct RecordStore {
+    records: VecDeque<Record>,
+    /// estimated memory taken by `records`
+    bytes: usize,
+    /// number of records dropped since the last clear
+    evicted: u64,
+    max_records: usize,
+    max_bytes: usize,
+}
+
+impl RecordStore {
+    pub fn new(max_records: usize, max_bytes: usize) -> Self {
+        Self {
+            max_records,
+            max_bytes,
+            ..Default::default()
+        }
+    }
+
+    /// append `record`, returning the records dropped to make room for it
+    pub fn push(&mut self, record: Record) -> Vec<Record> {
+        self.bytes += record.memory_size();
+        self.records.push_back(record);
+        self.evict()
+    }
+
+    /// change the limits, returning the records dropped to fit them
+    pub fn set_limits(&mut self, max_records: usize, max_bytes: usize) -> Vec<Record> {
+        self.max_records = max_records;
+        self.max_bytes = max_bytes;
+        self.evict()
+    }
+
+    fn evict(&mut self) -> Vec<Record> {
+        let mut evicted = Vec::new();
+        // the newest record stays whatever the limits are
+        while self.records.len() > 1
+            && ((self.max_records > 0 && self.records.len() > self.max_records)
+                || (self.max_bytes > 0 && self.bytes > self.max_bytes))
+        {
+            if let Some(record) = self.records.pop_front() {
+                self.bytes -= record.memory_size();
+                evicted.push(record);
+            }
+        }
+        self.evicted += evicted.len() as u64;
+        evicted
+    }
+
+    pub fn clear(&mut self) {
+        self.records.clear();
+        self.bytes = 0;
+        self.evicted = 0;
+    }
+
+    pub fn iter(&self) -> vec_deque::Iter<'_, Record> {
+        self.records.iter()
+    }
+
+    /// the record at `idx`, indexed like `iter`
+    pub fn get(&self, idx: usize) -> Option<&Record> {
+        self.records.get(idx)
+    }
+
+    /// the records at `range`, indexed like `iter`
+    pub fn range(&self, range: Range<usize>) -> vec_deque::Iter<'_, Record> {
+        self.records.range(range)
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    pub fn evicted(&self) -> u64 {
+        self.evicted
+    }
+}
+
+impl<'a> IntoIterator for &'a RecordStore {
+    type Item = &'a Record;
+    type IntoIter = vec_deque::Iter<'a, Record>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
     }
 }
 
@@ -73,11 +525,76 @@ impl From<&Record> for NetRecord {
     }
 }
 
-#[derive(Debug)]
+/// the transport protocols the traffic plot breaks bytes down into
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlotProtocol {
+    Tcp,
+    Udp,
+    Icmp,
+    Other,
+}
+
+impl PlotProtocol {
+    pub const ALL: [Self; 4] = [Self::Tcp, Self::Udp, Self::Icmp, Self::Other];
+
+    /// the rgb color standing for the protocol in the plot and in colored cli output; the
+    /// colors for a dark background are brighter
+    pub const fn color(self, dark: bool) -> (u8, u8, u8) {
+        match (self, dark) {
+            (Self::Tcp, false) => (17, 125, 187),
+            (Self::Udp, false) => (76, 175, 80),
+            (Self::Icmp, false) => (229, 57, 53),
+            (Self::Other, false) => (158, 158, 158),
+            (Self::Tcp, true) => (79, 195, 247),
+            (Self::Udp, true) => (129, 199, 132),
+            (Self::Icmp, true) => (239, 83, 80),
+            (Self::Other, true) => (189, 189, 189),
+        }
+    }
+}
+
+impl From<Protocol> for PlotProtocol {
+    fn from(proto: Protocol) -> Self {
+        match proto {
+            Protocol::Tcp => Self::Tcp,
+            Protocol::Udp => Self::Udp,
+            Protocol::Icmp => Self::Icmp,
+            _ => Self::Other,
+        }
+    }
+}
+
+impl Display for PlotProtocol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Tcp => write!(f, "TCP"),
+            Self::Udp => write!(f, "UDP"),
+            Self::Icmp => write!(f, "ICMP"),
+            Self::Other => write!(f, "其他"),
+        }
+    }
+}
+
+/// byte counts of a plot bucket, indexed by `PlotProtocol`
+#[derive(Debug, Default, Clone)]
+pub struct ProtocolBytes([u64; 4]);
+
+impl ProtocolBytes {
+    pub fn add(&mut self, proto: PlotProtocol, byte_num: u64) {
+        self.0[proto as usize] += byte_num;
+    }
+    pub fn get(&self, proto: PlotProtocol) -> u64 {
+        self.0[proto as usize]
+    }
+}
+
+#[derive(Debug, Default)]
 pub struct TransRecord {
     pub packet_num: u64,
     pub byte_num: u64,
     pub byte_num_in_net: u64,
+    pub byte_num_inbound: u64,
+    pub byte_num_outbound: u64,
 }
 
 impl TransRecord {
@@ -85,12 +602,20 @@ impl TransRecord {
         self.packet_num += other.packet_num;
         self.byte_num += other.byte_num;
         self.byte_num_in_net += other.byte_num_in_net;
+        self.byte_num_inbound += other.byte_num_inbound;
+        self.byte_num_outbound += other.byte_num_outbound;
     }
-    pub fn to_string_array(&self) -> [String; 3] {
+    pub fn to_string_array(&self) -> [String; 5] {
+        self.to_display_array(&|n| n.to_string())
+    }
+    /// like `to_string_array`, with byte columns formatted by `bytes`
+    pub fn to_display_array(&self, bytes: &dyn Fn(u64) -> String) -> [String; 5] {
         [
             self.packet_num.to_string(),
-            self.byte_num.to_string(),
-            self.byte_num_in_net.to_string(),
+            bytes(self.byte_num),
+            bytes(self.byte_num_in_net),
+            bytes(self.byte_num_inbound),
+            bytes(self.byte_num_outbound),
         ]
     }
 }
@@ -99,22 +624,29 @@ impl TryFrom<&Record> for TransRecord {
     type Error = Error;
 
     fn try_from(record: &Record) -> Result<Self, Self::Error> {
+        let byte_num = record
+            .ip_payload_len
+            .ok_or_else(|| anyhow!("record does not represent a transport layer packet"))?
+            as u64;
+        let (byte_num_inbound, byte_num_outbound) = split_by_direction(record.direction, byte_num);
         Ok(Self {
             packet_num: 1,
-            byte_num: record.ip_payload_len.ok_or(anyhow!(
-                "record does not represent a transport layer packet"
-            ))? as _,
+            byte_num,
             byte_num_in_net: record.len as _,
+            byte_num_inbound,
+            byte_num_outbound,
         })
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct AppRecord {
     pub packet_num: u64,
     pub byte_num: u64,
     pub byte_num_in_net: u64,
     pub byte_num_in_trans: u64,
+    pub byte_num_inbound: u64,
+    pub byte_num_outbound: u64,
 }
 
 impl AppRecord {
@@ -123,13 +655,21 @@ impl AppRecord {
         self.byte_num += other.byte_num;
         self.byte_num_in_net += other.byte_num_in_net;
         self.byte_num_in_trans += other.byte_num_in_trans;
+        self.byte_num_inbound += other.byte_num_inbound;
+        self.byte_num_outbound += other.byte_num_outbound;
+    }
+    pub fn to_string_array(&self) -> [String; 6] {
+        self.to_display_array(&|n| n.to_string())
     }
-    pub fn to_string_array(&self) -> [String; 4] {
+    /// like `to_string_array`, with byte columns formatted by `bytes`
+    pub fn to_display_array(&self, bytes: &dyn Fn(u64) -> String) -> [String; 6] {
         [
             self.packet_num.to_string(),
-            self.byte_num.to_string(),
-            self.byte_num_in_net.to_string(),
-            self.byte_num_in_trans.to_string(),
+            bytes(self.byte_num),
+            bytes(self.byte_num_in_net),
+            bytes(self.byte_num_in_trans),
+            bytes(self.byte_num_inbound),
+            bytes(self.byte_num_outbound),
         ]
     }
 }
@@ -138,41 +678,118 @@ impl TryFrom<&Record> for AppRecord {
     type Error = Error;
 
     fn try_from(record: &Record) -> Result<Self, Self::Error> {
+        let byte_num = record
+            .trans_payload_len
+            .ok_or_else(|| anyhow!("record does not represent a application layer packet"))?
+            as u64;
+        let (byte_num_inbound, byte_num_outbound) = split_by_direction(record.direction, byte_num);
         Ok(Self {
             packet_num: 1,
-            byte_num: record.trans_payload_len.ok_or(anyhow!(
-                "record does not represent a application layer packet"
-            ))? as _,
+            byte_num,
             byte_num_in_net: record.len as _,
-            byte_num_in_trans: record.ip_payload_len.ok_or(anyhow!(
-                "record does not represent a application layer packet"
-            ))? as _,
+            byte_num_in_trans: record
+                .ip_payload_len
+                .ok_or_else(|| anyhow!("record does not represent a application layer packet"))?
+                as _,
+            byte_num_inbound,
+            byte_num_outbound,
         })
     }
 }
 
+#[derive(Debug, Default)]
+pub struct DnsStats {
+    pub queries: u64,
+    pub responses: u64,
+    pub nxdomain: u64,
+    pub servfail: u64,
+    pub malformed: u64,
+    pub query_names: HashMap<String, u64>,
+}
+
+impl DnsStats {
+    pub fn update(&mut self, dns: &DnsInfo) {
+        match dns {
+            DnsInfo::Query { name } => {
+                self.queries += 1;
+                *self.query_names.entry(name.clone()).or_insert(0) += 1;
+            }
+            DnsInfo::Response { rcode, .. } => {
+                self.responses += 1;
+                match *rcode {
+                    RCODE_NXDOMAIN => self.nxdomain += 1,
+                    RCODE_SERVFAIL => self.servfail += 1,
+                    _ => {}
+                }
+            }
+            DnsInfo::Malformed => self.malformed += 1,
+        }
+    }
+
+    /// fraction of queries that got a response
+    pub fn answered_ratio(&self) -> Option<f64> {
+        if self.queries == 0 {
+            None
+        } else {
+            Some(self.responses.min(self.queries) as f64 / self.queries as f64)
+        }
+    }
+
+    /// the `n` most queried names, most frequent first
+    pub fn top_names(&self, n: usize) -> Vec<(&str, u64)> {
+        let mut names = self
+            .query_names
+            .iter()
+            .map(|(name, &count)| (name.as_str(), count))
+            .collect::<Vec<_>>();
+        names.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        names.truncate(n);
+        names
+    }
+}
+
+/// split `byte_num` into (inbound, outbound) parts according to `direction`
+fn split_by_direction(direction: Direction, byte_num: u64) -> (u64, u64) {
+    match direction {
+        Direction::Inbound => (byte_num, 0),
+        Direction::Outbound => (0, byte_num),
+        Direction::Unknown => (0, 0),
+    }
+}
+
 #[derive(Debug, Default)]
 pub struct StatRecord {
     pub stat_net_table: NetRecord,
-    pub stat_trans_table: HashMap<String, TransRecord>,
-    pub stat_app_table: HashMap<String, AppRecord>,
+    pub stat_net_inbound: NetRecord,
+    pub stat_net_outbound: NetRecord,
+    pub stat_trans_table: HashMap<&'static str, TransRecord>,
+    pub stat_app_table: HashMap<&'static str, AppRecord>,
+    pub stat_dns: DnsStats,
 }
 
 impl StatRecord {
     pub fn clear(&mut self) {
         self.stat_net_table = Default::default();
+        self.stat_net_inbound = Default::default();
+        self.stat_net_outbound = Default::default();
         self.stat_trans_table.clear();
         self.stat_app_table.clear();
+        self.stat_dns = Default::default();
     }
 
     pub fn update(&mut self, record: &Record) {
         let net_record: NetRecord = record.into();
         self.stat_net_table.add_up(&net_record);
+        match record.direction {
+            Direction::Inbound => self.stat_net_inbound.add_up(&net_record),
+            Direction::Outbound => self.stat_net_outbound.add_up(&net_record),
+            Direction::Unknown => {}
+        }
 
         if let Ok(trans_record) = TransRecord::try_from(record) {
             match self
                 .stat_trans_table
-                .entry(trans_protocol_name(record.trans_proto).to_owned())
+                .entry(trans_protocol_name(record.trans_proto))
             {
                 HashMapEntry::Occupied(mut trans) => {
                     trans.get_mut().add_up(&trans_record);
@@ -184,7 +801,7 @@ impl StatRecord {
         }
 
         if let Ok(app_record) = AppRecord::try_from(record) {
-            match self.stat_app_table.entry(record.app_proto.to_string()) {
+            match self.stat_app_table.entry(record.app_proto.name()) {
                 HashMapEntry::Occupied(mut trans) => {
                     trans.get_mut().add_up(&app_record);
                 }
@@ -193,6 +810,30 @@ impl StatRecord {
                 }
             }
         }
+
+        if let Some(dns) = record.dns.as_ref() {
+            self.stat_dns.update(dns);
+        }
+    }
+
+    /// sum of all rows in the transport layer table
+    pub fn trans_total(&self) -> TransRecord {
+        self.stat_trans_table
+            .values()
+            .fold(Default::default(), |mut total, record| {
+                total.add_up(record);
+                total
+            })
+    }
+
+    /// sum of all rows in the application layer table
+    pub fn app_total(&self) -> AppRecord {
+        self.stat_app_table
+            .values()
+            .fold(Default::default(), |mut total, record| {
+                total.add_up(record);
+                total
+            })
     }
 
     pub fn update_multiple<'a>(&mut self, records: impl Iterator<Item = &'a Record>) {
@@ -200,4 +841,930 @@ impl StatRecord {
             self.update(record);
         }
     }
+
+    /// share of network layer bytes per transport protocol, largest first,
+    /// with protocols below `min_share` folded into a trailing "其他" entry
+    pub fn protocol_share(&self, min_share: f64) -> Vec<(String, f64)> {
+        let total = self.trans_total().byte_num_in_net;
+        if total == 0 {
+            return Vec::new();
+        }
+        let mut shares = Vec::new();
+        let mut other = 0.0;
+        for (proto, record) in self.stat_trans_table.iter() {
+            let share = record.byte_num_in_net as f64 / total as f64;
+            if share < min_share {
+                other += share;
+            } else {
+                shares.push((proto.to_string(), share));
+            }
+        }
+        shares.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap().then_with(|| a.0.cmp(&b.0)));
+        if other > 0.0 {
+            shares.push((PlotProtocol::Other.to_string(), other));
+        }
+        shares
+    }
+}
+
+/// ip and port of one side of a conversation
+pub type Endpoint = (Option<Ipv4Addr>, Option<u16>);
+
+/// a conversation, i.e. the protocol and the two endpoints in either direction
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FlowKey {
+    pub trans_proto: &'static str,
+    /// the lesser endpoint, so both directions map to the same key
+    pub a: Endpoint,
+    pub b: Endpoint,
+}
+
+impl FlowKey {
+    pub fn of(record: &Record) -> Self {
+        Self::directed(record).0
+    }
+
+    /// the key of the flow of `record`, and whether the packet went from `b` to `a`
+    pub fn directed(record: &Record) -> (Self, bool) {
+        let src = (record.src_ip, record.src_port);
+        let dest = (record.dest_ip, record.dest_port);
+        let (a, b, reversed) = if src <= dest {
+            (src, dest, false)
+        } else {
+            (dest, src, true)
+        };
+        let key = Self {
+            trans_proto: trans_protocol_name(record.trans_proto),
+            a,
+            b,
+        };
+        (key, reversed)
+    }
+}
+
+impl Display for FlowKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ⇄ {}", endpoint_text(self.a), endpoint_text(self.b))
+    }
+}
+
+/// `ip:port`, or just `ip` for protocols without ports
+pub fn endpoint_text((ip, port): Endpoint) -> String {
+    match (ip, port) {
+        (Some(ip), Some(port)) => format!("{}:{}", ip, port),
+        (Some(ip), None) => ip.to_string(),
+        (None, _) => "?".to_string(),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Flow {
+    /// assigned in order of appearance and never reused until cleared
+    pub id: usize,
+    pub key: FlowKey,
+    pub packet_num: u64,
+    pub byte_num: u64,
+}
+
+/// packets and bytes per conversation, kept up to date one record at a time
+#[derive(Debug, Default)]
+pub struct FlowTable {
+    flows: HashMap<FlowKey, Flow>,
+    next_id: usize,
+}
+
+impl FlowTable {
+    pub fn clear(&mut self) {
+        self.flows.clear();
+        self.next_id = 0;
+    }
+
+    /// count `record` into its flow, creating the flow when it is the first packet
+    pub fn add(&mut self, record: &Record) -> &Flow {
+        let key = FlowKey::of(record);
+        let flow = match self.flows.entry(key.clone()) {
+            HashMapEntry::Occupied(flow) => flow.into_mut(),
+            HashMapEntry::Vacant(flow) => {
+                self.next_id += 1;
+                flow.insert(Flow {
+                    id: self.next_id,
+                    key,
+                    packet_num: 0,
+                    byte_num: 0,
+                })
+            }
+        };
+        flow.packet_num += 1;
+        flow.byte_num += record.len as u64;
+        flow
+    }
+
+    /// take a counted `record` back out, e.g. when it is evicted, returning its flow as it is
+    /// left; a flow without packets is dropped, and comes back under a new id
+    pub fn remove(&mut self, record: &Record) -> Option<Flow> {
+        let key = FlowKey::of(record);
+        let flow = self.flows.get_mut(&key)?;
+        flow.packet_num = flow.packet_num.saturating_sub(1);
+        flow.byte_num = flow.byte_num.saturating_sub(record.len as u64);
+        if flow.packet_num == 0 {
+            return self.flows.remove(&key);
+        }
+        Some(flow.clone())
+    }
+
+    pub fn get(&self, record: &Record) -> Option<&Flow> {
+        self.flows.get(&FlowKey::of(record))
+    }
+
+    /// the flows in order of appearance
+    pub fn flows(&self) -> Vec<&Flow> {
+        let mut flows = self.flows.values().collect::<Vec<_>>();
+        flows.sort_by_key(|flow| flow.id);
+        flows
+    }
+
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        self.flows.len()
+    }
+
+    #[cfg(test)]
+    pub fn is_empty(&self) -> bool {
+        self.flows.is_empty()
+    }
+}
+
+/// packets and bytes per source address, destination address and conversation
+#[derive(Debug, Default)]
+pub struct TalkerTable {
+    pub sources: HashMap<Ipv4Addr, NetRecord>,
+    pub destinations: HashMap<Ipv4Addr, NetRecord>,
+    pub flows: FlowTable,
+}
+
+impl TalkerTable {
+    pub fn update(&mut self, record: &Record) {
+        let net = NetRecord::from(record);
+        if let Some(src_ip) = record.src_ip {
+            self.sources.entry(src_ip).or_default().add_up(&net);
+        }
+        if let Some(dest_ip) = record.dest_ip {
+            self.destinations.entry(dest_ip).or_default().add_up(&net);
+        }
+        self.flows.add(record);
+    }
+}
+
+/// a followed conversation with its traffic split by direction
+#[derive(Debug, Clone)]
+pub struct DirectedFlow {
+    pub key: FlowKey,
+    pub start_time: DateTime<Local>,
+    /// sent from `key.a` to `key.b`, then from `key.b` to `key.a`
+    pub sent: [NetRecord; 2],
+}
+
+/// where a packet stands in its followed conversation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FollowedPacket {
+    /// 1 for the first packet of the conversation
+    pub seq: u64,
+    pub since_start: Duration,
+}
+
+/// the conversations of the followed packets, each counted per direction
+#[derive(Debug, Default)]
+pub struct FollowedFlows {
+    flows: Vec<DirectedFlow>,
+    index: HashMap<FlowKey, usize>,
+}
+
+impl FollowedFlows {
+    /// count `record` into the direction it went in its conversation
+    pub fn add(&mut self, record: &Record) -> FollowedPacket {
+        let (key, reversed) = FlowKey::directed(record);
+        let flows = &mut self.flows;
+        let i = *self.index.entry(key.clone()).or_insert_with(|| {
+            flows.push(DirectedFlow {
+                key,
+                start_time: record.time,
+                sent: Default::default(),
+            });
+            flows.len() - 1
+        });
+        let flow = &mut self.flows[i];
+        flow.sent[reversed as usize].add_up(&record.into());
+        FollowedPacket {
+            seq: flow.sent[0].packet_num + flow.sent[1].packet_num,
+            since_start: record.time - flow.start_time,
+        }
+    }
+
+    /// the conversations in order of appearance
+    pub fn flows(&self) -> &[DirectedFlow] {
+        &self.flows
+    }
+}
+
+/// traffic bucketed by `sample_interval`, backing the plot
+pub struct PlotRecord {
+    pub sample_interval: Duration,
+    pub start_time: Option<DateTime<Local>>,
+    /// start of the uncommitted bucket, i.e. the end of the committed `records`
+    pub end_time: Option<DateTime<Local>>,
+    pub uncommitted_record: NetRecord,
+    pub records: Vec<NetRecord>,
+    /// whether buckets also keep bytes per transport protocol, in `protocol_records`
+    pub by_protocol: bool,
+    pub uncommitted_protocol: ProtocolBytes,
+    pub protocol_records: Vec<ProtocolBytes>,
+    /// periods nothing was sampled in, e.g. while the machine slept; buckets inside them
+    /// hold no data rather than zero traffic
+    pub gaps: Vec<(DateTime<Local>, DateTime<Local>)>,
+}
+
+/// a sample arriving this many intervals after the previous one leaves a gap behind
+pub const PLOT_GAP_INTERVALS: i32 = 5;
+
+impl Default for PlotRecord {
+    fn default() -> Self {
+        Self {
+            sample_interval: Duration::milliseconds(PLOT_SAMPLING_INTERVAL as i64),
+            start_time: Default::default(),
+            end_time: Default::default(),
+            uncommitted_record: Default::default(),
+            records: Default::default(),
+            by_protocol: false,
+            uncommitted_protocol: Default::default(),
+            protocol_records: Default::default(),
+            gaps: Default::default(),
+        }
+    }
+}
+
+impl PlotRecord {
+    pub fn clear(&mut self) {
+        self.start_time = None;
+        self.end_time = None;
+        self.uncommitted_record = Default::default();
+        self.records.clear();
+        self.uncommitted_protocol = Default::default();
+        self.protocol_records.clear();
+        self.gaps.clear();
+    }
+
+    pub fn clear_with_time(&mut self, time: DateTime<Local>) {
+        self.clear();
+        self.start_time = Some(time);
+        self.end_time = Some(time);
+    }
+
+    pub fn commit_rest(&mut self) {
+        if self.uncommitted_record.packet_num != 0 || self.uncommitted_record.byte_num != 0 {
+            self.end_time = self.end_time.map(|t| t + self.sample_interval);
+            self.commit();
+        }
+    }
+
+    fn commit(&mut self) {
+        self.records.push(mem::take(&mut self.uncommitted_record));
+        if self.by_protocol {
+            self.protocol_records
+                .push(mem::take(&mut self.uncommitted_protocol));
+        }
+    }
+
+    /// close the buckets up to `now`, as the periodic sampler does; a sampler that fell far
+    /// behind was not running, so the time it missed is recorded as a gap
+    pub fn sample(&mut self, now: DateTime<Local>) {
+        if let Some(end_time) = self.end_time {
+            let gap_start = end_time + self.sample_interval;
+            if now - end_time > self.sample_interval * PLOT_GAP_INTERVALS {
+                self.gaps.push((gap_start, now));
+            }
+        }
+        self.update_records(iter::empty(), Some(now));
+    }
+
+    /// continue a stopped plot at `now`, the time in between becomes a gap
+    pub fn resume(&mut self, now: DateTime<Local>) {
+        if let Some(end_time) = self.end_time {
+            if now > end_time {
+                self.gaps.push((end_time, now));
+            }
+        }
+        self.update_records(iter::empty(), Some(now));
+    }
+
+    /// whether the bucket at `idx` lies entirely within a gap
+    pub fn is_gap(&self, idx: usize) -> bool {
+        let start_time = match self.start_time {
+            Some(start_time) => start_time,
+            None => return false,
+        };
+        let bucket_start = start_time + self.sample_interval * idx as i32;
+        let bucket_end = bucket_start + self.sample_interval;
+        self.gaps
+            .iter()
+            .any(|&(start, end)| start <= bucket_start && bucket_end <= end)
+    }
+
+    /// total length of the gaps
+    pub fn gap_duration(&self) -> Duration {
+        self.gaps
+            .iter()
+            .fold(Duration::zero(), |sum, &(start, end)| sum + (end - start))
+    }
+
+    /// factor turning a bucket value into a per second rate
+    pub fn per_second(&self) -> f64 {
+        1000.0 / self.sample_interval.num_milliseconds().max(1) as f64
+    }
+
+    /// average (packets/s, bytes/s) over the committed buckets within `window`, not counting gaps
+    pub fn recent_rate(&self, window: Duration) -> (f64, f64) {
+        let interval = self.sample_interval.num_milliseconds().max(1);
+        let n = (window.num_milliseconds() / interval).max(1) as usize;
+        let first = self.records.len().saturating_sub(n);
+        let buckets = self.records[first..]
+            .iter()
+            .enumerate()
+            .filter(|&(idx, _)| !self.is_gap(first + idx))
+            .map(|(_, r)| r)
+            .collect::<Vec<_>>();
+        if buckets.is_empty() {
+            return (0.0, 0.0);
+        }
+        let (packet_num, byte_num) = buckets
+            .iter()
+            .fold((0u64, 0u64), |(p, b), r| (p + r.packet_num, b + r.byte_num));
+        let secs = (buckets.len() as i64 * interval) as f64 / 1000.0;
+        (packet_num as f64 / secs, byte_num as f64 / secs)
+    }
+
+    /// the bucket with the most bytes, as (bucket start time, bytes/s)
+    pub fn peak_rate(&self) -> Option<(DateTime<Local>, f64)> {
+        let start_time = self.start_time?;
+        let (idx, record) = self
+            .records
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, r)| r.byte_num)?;
+        let secs = self.sample_interval.num_milliseconds() as f64 / 1000.0;
+        Some((
+            start_time + self.sample_interval * idx as i32,
+            record.byte_num as f64 / secs,
+        ))
+    }
+
+    pub fn from_records<'a>(
+        iter: impl Iterator<Item = &'a Record>,
+        start_time: Option<DateTime<Local>>,
+        end_time: Option<DateTime<Local>>,
+        by_protocol: bool,
+    ) -> Self {
+        let mut records = Self {
+            start_time,
+            end_time: start_time,
+            by_protocol,
+            ..Default::default()
+        };
+        records.update_records(iter, end_time);
+
+        if let (Some(end_time), Some(record_end_time)) = (end_time, records.end_time) {
+            if end_time > record_end_time {
+                records.end_time = Some(end_time);
+            }
+        }
+
+        records
+    }
+
+    pub fn update_records<'a>(
+        &mut self,
+        iter: impl Iterator<Item = &'a Record>,
+        end_time: Option<DateTime<Local>>,
+    ) {
+        let mut iter = iter.peekable();
+        if let Some(&record) = iter.peek() {
+            if let Some(start_time) = self.start_time {
+                if record.time < start_time {
+                    self.start_time = Some(record.time);
+                }
+            } else {
+                self.start_time = Some(record.time);
+            }
+            if self.end_time.is_none() {
+                self.end_time = Some(record.time);
+            }
+        } else if self.end_time.is_none() {
+            if end_time.is_some() {
+                self.end_time = end_time
+            } else {
+                return;
+            }
+        }
+
+        let mut iter_without_dummy = iter.map(|r| {
+            let nr: NetRecord = r.into();
+            (&r.time, nr, PlotProtocol::from(r.trans_proto))
+        });
+        let mut iter_with_dummy;
+        let dummy_end_time;
+        let iter: &mut dyn Iterator<Item = (&DateTime<Local>, NetRecord, PlotProtocol)>;
+        if let Some(end_time) = end_time {
+            dummy_end_time = end_time;
+            iter_with_dummy = iter_without_dummy.chain(iter::once((
+                &dummy_end_time,
+                NetRecord {
+                    packet_num: 0,
+                    byte_num: 0,
+                },
+                PlotProtocol::Other,
+            )));
+            iter = &mut iter_with_dummy;
+        } else {
+            iter = &mut iter_without_dummy;
+        }
+
+        let mut time = self.end_time.unwrap();
+        let mut next_time = time + self.sample_interval;
+
+        for (record_time, record, proto) in iter {
+            // a record on a bucket boundary belongs to the bucket it starts
+            while record_time >= &next_time {
+                self.commit();
+                time = next_time;
+                next_time = time + self.sample_interval;
+            }
+            self.uncommitted_record.add_up(&record);
+            if self.by_protocol {
+                self.uncommitted_protocol.add(proto, record.byte_num);
+            }
+        }
+
+        self.end_time = Some(time);
+    }
+}
+
+#[cfg(test)]
+mod record_test {
+    use super::*;
+
+    fn record(direction: Direction) -> Record {
+        Record::builder().direction(direction).build()
+    }
+
+    #[test]
+    fn test_column_strings() {
+        let record = record(Direction::Inbound);
+        let cells = record.to_column_strings(&RecordColumn::ALL);
+        let columns = [
+            RecordColumn::Direction,
+            RecordColumn::SrcPort,
+            RecordColumn::Time,
+        ];
+        assert_eq!(
+            record.to_column_strings(&columns),
+            vec!["接收".to_string(), "52311".to_string(), cells[0].clone()]
+        );
+        assert_eq!(cells[RecordColumn::AppProto as usize], "HTTP");
+        assert_eq!(cells[RecordColumn::TransProto as usize], "TCP");
+    }
+
+    #[test]
+    fn test_from_packet_truncated() {
+        // a tcp packet of 128 bytes, of which only the first 48 were read
+        let mut packet = vec![
+            0x45, 0, 0, 128, 0, 0, 0, 0, 64, 6, 0, 0, 10, 0, 0, 1, 10, 0, 0, 2, // ip
+            0x30, 0x39, 0x1f, 0x90, 0, 0, 0, 0, 0, 0, 0, 0, // tcp ports, sequence and ack
+            0x50, 0x18, 0xff, 0xff, 0, 0, 0, 0, // header length, flags, window
+        ];
+        packet.resize(48, 0);
+        let local = Some((Ipv4Addr::new(10, 0, 0, 1), "test"));
+
+        let record = Record::from_packet(Local::now(), &mut packet.clone(), local, true);
+        assert!(record.truncated);
+        assert_eq!(record.len, 128);
+        assert_eq!(record.ip_payload_len, Some(108));
+        assert_eq!(record.trans_payload_len, Some(88));
+        assert_eq!(
+            (record.src_port, record.dest_port),
+            (Some(12345), Some(8080))
+        );
+        assert_eq!(record.column_string(RecordColumn::Truncated), "是");
+
+        // a packet the read did not cut short is taken for as long as it is
+        let record = Record::from_packet(Local::now(), &mut packet, local, false);
+        assert!(!record.truncated);
+        assert_eq!(record.len, 48);
+        assert_eq!(record.ip_payload_len, Some(28));
+        assert_eq!(record.column_string(RecordColumn::Truncated), "");
+    }
+
+    #[test]
+    fn test_column_name() {
+        for column in RecordColumn::ALL {
+            assert_eq!(column.name().parse::<RecordColumn>().unwrap(), column);
+        }
+        assert_eq!(
+            " dest_port".parse::<RecordColumn>().unwrap(),
+            RecordColumn::DestPort
+        );
+        let err = "dst".parse::<RecordColumn>().unwrap_err().to_string();
+        assert!(err.contains("\"dst\""));
+        assert!(err.ends_with("interface, truncated"));
+    }
+
+    #[test]
+    fn test_contains_text() {
+        let record = record(Direction::Inbound);
+        assert!(record.contains_text(&RecordColumn::ALL, "10.0.0.2"));
+        assert!(record.contains_text(&RecordColumn::ALL, "http"));
+        assert!(record.contains_text(&[RecordColumn::AppProto], "HTTP"));
+        // hidden columns are not searched
+        assert!(!record.contains_text(&[RecordColumn::AppProto], "10.0.0.2"));
+        assert!(!record.contains_text(&RecordColumn::ALL, "dns"));
+    }
+
+    #[test]
+    fn test_record_store() {
+        let mut store = RecordStore::new(2, 0);
+        assert!(store.push(record(Direction::Inbound)).is_empty());
+        assert!(store.push(record(Direction::Inbound)).is_empty());
+        let evicted = store.push(record(Direction::Outbound));
+        assert_eq!(evicted.len(), 1);
+        assert_eq!(evicted[0].direction, Direction::Inbound);
+        assert_eq!(store.len(), 2);
+        assert_eq!(store.evicted(), 1);
+        let directions = store.range(1..2).map(|r| r.direction).collect::<Vec<_>>();
+        assert_eq!(directions, [Direction::Outbound]);
+
+        // the byte limit keeps as many records as fit, but never drops the newest one
+        let size = record(Direction::Inbound).memory_size();
+        assert_eq!(store.set_limits(0, size).len(), 1);
+        assert_eq!(store.iter().next().unwrap().direction, Direction::Outbound);
+        assert!(store.set_limits(0, 1).is_empty());
+        assert_eq!(store.len(), 1);
+
+        store.clear();
+        assert!(store.is_empty());
+        assert_eq!(store.evicted(), 0);
+    }
+
+    #[test]
+    fn test_direction_classify() {
+        let a = Ipv4Addr::new(10, 0, 0, 1);
+        let b = Ipv4Addr::new(10, 0, 0, 2);
+        let c = Ipv4Addr::new(10, 0, 0, 3);
+        assert_eq!(
+            Direction::classify(Some(a), Some(b), a),
+            Direction::Outbound
+        );
+        assert_eq!(Direction::classify(Some(b), Some(a), a), Direction::Inbound);
+        assert_eq!(Direction::classify(Some(b), Some(c), a), Direction::Unknown);
+        assert_eq!(Direction::classify(None, None, a), Direction::Unknown);
+    }
+
+    #[test]
+    fn test_direction_stat() {
+        let mut stat = StatRecord::default();
+        stat.update(&record(Direction::Inbound));
+        stat.update(&record(Direction::Inbound));
+        stat.update(&record(Direction::Outbound));
+        stat.update(&record(Direction::Unknown));
+
+        assert_eq!(stat.stat_net_table.packet_num, 4);
+        assert_eq!(stat.stat_net_inbound.packet_num, 2);
+        assert_eq!(stat.stat_net_inbound.byte_num, 120);
+        assert_eq!(stat.stat_net_outbound.packet_num, 1);
+        assert_eq!(stat.stat_net_outbound.byte_num, 60);
+
+        let tcp = &stat.stat_trans_table["TCP"];
+        assert_eq!(tcp.byte_num, 160);
+        assert_eq!(tcp.byte_num_inbound, 80);
+        assert_eq!(tcp.byte_num_outbound, 40);
+
+        let http = &stat.stat_app_table["HTTP"];
+        assert_eq!(http.byte_num, 80);
+        assert_eq!(http.byte_num_inbound, 40);
+        assert_eq!(http.byte_num_outbound, 20);
+    }
+
+    #[test]
+    fn test_totals() {
+        let mut stat = StatRecord::default();
+        stat.update(&record(Direction::Inbound));
+        let mut icmp = record(Direction::Outbound);
+        icmp.trans_proto = Protocol::Icmp;
+        icmp.src_port = None;
+        icmp.dest_port = None;
+        icmp.trans_payload_len = None;
+        icmp.app_proto = AppProtocol::Unknown;
+        stat.update(&icmp);
+
+        let trans_total = stat.trans_total();
+        assert_eq!(trans_total.packet_num, 2);
+        assert_eq!(trans_total.byte_num, 80);
+        assert_eq!(trans_total.byte_num_in_net, 120);
+
+        let app_total = stat.app_total();
+        assert_eq!(app_total.packet_num, 1);
+        assert_eq!(app_total.byte_num_in_trans, 40);
+
+        let empty = StatRecord::default();
+        assert!(empty.stat_dns.answered_ratio().is_none());
+        assert_eq!(empty.trans_total().packet_num, 0);
+        assert_eq!(empty.app_total().byte_num, 0);
+    }
+
+    #[test]
+    fn test_protocol_share() {
+        let mut stat = StatRecord::default();
+        for _ in 0..3 {
+            stat.update(&record(Direction::Inbound));
+        }
+        let mut udp = record(Direction::Outbound);
+        udp.trans_proto = Protocol::Udp;
+        stat.update(&udp);
+
+        let shares = stat.protocol_share(0.01);
+        assert_eq!(
+            shares,
+            vec![("TCP".to_string(), 0.75), ("UDP".to_string(), 0.25)]
+        );
+        // udp falls under the threshold and is reported as other
+        let shares = stat.protocol_share(0.3);
+        assert_eq!(
+            shares,
+            vec![("TCP".to_string(), 0.75), ("其他".to_string(), 0.25)]
+        );
+        assert!(StatRecord::default().protocol_share(0.01).is_empty());
+    }
+
+    #[test]
+    fn test_flow_table() {
+        let mut flows = FlowTable::default();
+        let outbound = record(Direction::Outbound);
+        let mut inbound = record(Direction::Inbound);
+        mem::swap(&mut inbound.src_ip, &mut inbound.dest_ip);
+        mem::swap(&mut inbound.src_port, &mut inbound.dest_port);
+        let mut other = record(Direction::Outbound);
+        other.src_port = Some(52312);
+
+        assert_eq!(flows.add(&outbound).id, 1);
+        assert_eq!(flows.add(&other).id, 2);
+        let flow = flows.add(&inbound);
+        assert_eq!((flow.id, flow.packet_num, flow.byte_num), (1, 2, 120));
+        assert_eq!(flow.key.to_string(), "10.0.0.1:52311 ⇄ 10.0.0.2:80");
+        assert_eq!(flows.len(), 2);
+        let ids = flows.flows().iter().map(|flow| flow.id).collect::<Vec<_>>();
+        assert_eq!(ids, [1, 2]);
+
+        let flow = flows.remove(&outbound).unwrap();
+        assert_eq!((flow.id, flow.packet_num, flow.byte_num), (1, 1, 60));
+        assert_eq!(flows.get(&inbound).map(|flow| flow.packet_num), Some(1));
+        // the last packet takes the flow with it
+        let flow = flows.remove(&inbound).unwrap();
+        assert_eq!((flow.id, flow.packet_num, flow.byte_num), (1, 0, 0));
+        assert!(flows.get(&outbound).is_none());
+        assert_eq!(flows.len(), 1);
+        assert_eq!(flows.add(&outbound).id, 3);
+        flows.clear();
+        assert!(flows.is_empty());
+        assert_eq!(flows.add(&other).id, 1);
+    }
+
+    #[test]
+    fn test_followed_flows() {
+        let mut flows = FollowedFlows::default();
+        let outbound = record(Direction::Outbound);
+        let mut inbound = record(Direction::Inbound);
+        mem::swap(&mut inbound.src_ip, &mut inbound.dest_ip);
+        mem::swap(&mut inbound.src_port, &mut inbound.dest_port);
+        inbound.time = outbound.time + Duration::milliseconds(15);
+        inbound.len = 40;
+
+        let packet = flows.add(&outbound);
+        assert_eq!((packet.seq, packet.since_start), (1, Duration::zero()));
+        let packet = flows.add(&inbound);
+        assert_eq!(
+            (packet.seq, packet.since_start),
+            (2, Duration::milliseconds(15))
+        );
+        flows.add(&inbound);
+        let flow = &flows.flows()[0];
+        assert_eq!(flows.flows().len(), 1);
+        assert_eq!(flow.key, FlowKey::of(&inbound));
+        assert_eq!((flow.sent[0].packet_num, flow.sent[0].byte_num), (1, 60));
+        assert_eq!((flow.sent[1].packet_num, flow.sent[1].byte_num), (2, 80));
+        assert_eq!(endpoint_text(flow.key.a), "10.0.0.1:52311");
+    }
+
+    #[test]
+    fn test_dns_stats() {
+        let query = |name: &str| DnsInfo::Query {
+            name: name.to_string(),
+        };
+        let mut stat = StatRecord::default();
+        for dns in [
+            query("a.com"),
+            query("b.com"),
+            query("b.com"),
+            DnsInfo::Response {
+                name: "b.com".to_string(),
+                rcode: 0,
+            },
+            DnsInfo::Response {
+                name: "a.com".to_string(),
+                rcode: RCODE_NXDOMAIN,
+            },
+            DnsInfo::Malformed,
+        ] {
+            let mut r = record(Direction::Inbound);
+            r.dns = Some(dns);
+            stat.update(&r);
+        }
+        let dns = &stat.stat_dns;
+        assert_eq!(dns.queries, 3);
+        assert_eq!(dns.responses, 2);
+        assert_eq!(dns.nxdomain, 1);
+        assert_eq!(dns.servfail, 0);
+        assert_eq!(dns.malformed, 1);
+        assert_eq!(dns.top_names(10), vec![("b.com", 2), ("a.com", 1)]);
+        assert_eq!(dns.top_names(1), vec![("b.com", 2)]);
+
+        stat.clear();
+        assert_eq!(stat.stat_dns.queries, 0);
+        assert!(stat.stat_dns.query_names.is_empty());
+    }
+
+    fn record_at(start: DateTime<Local>, ms: i64) -> Record {
+        let mut r = record(Direction::Inbound);
+        r.time = start + Duration::milliseconds(ms);
+        r
+    }
+
+    fn packet_nums(plot: &PlotRecord) -> Vec<u64> {
+        plot.records.iter().map(|r| r.packet_num).collect()
+    }
+
+    #[test]
+    fn test_plot_buckets() {
+        let start = Local::now();
+        let records = [0, 50, 250, 450].map(|ms| record_at(start, ms));
+        let plot = PlotRecord::from_records(records.iter(), Some(start), None, false);
+        assert_eq!(packet_nums(&plot), vec![2, 1]);
+        assert_eq!(plot.records[0].byte_num, 120);
+        assert_eq!(plot.uncommitted_record.packet_num, 1);
+        assert_eq!(plot.end_time, Some(start + Duration::milliseconds(400)));
+    }
+
+    #[test]
+    fn test_plot_idle_gap() {
+        let start = Local::now();
+        let records = [0, 1000].map(|ms| record_at(start, ms));
+        let plot = PlotRecord::from_records(records.iter(), Some(start), None, false);
+        assert_eq!(packet_nums(&plot), vec![1, 0, 0, 0, 0]);
+        assert_eq!(plot.uncommitted_record.packet_num, 1);
+        assert_eq!(plot.end_time, Some(start + Duration::milliseconds(1000)));
+
+        // an end time on a bucket boundary opens that bucket and nothing more
+        let records = [record_at(start, 0)];
+        let end_time = start + Duration::milliseconds(400);
+        let plot = PlotRecord::from_records(records.iter(), Some(start), Some(end_time), false);
+        assert_eq!(packet_nums(&plot), vec![1, 0]);
+        assert_eq!(plot.uncommitted_record.packet_num, 0);
+        assert_eq!(plot.end_time, Some(end_time));
+    }
+
+    #[test]
+    fn test_plot_early_end_time() {
+        let start = Local::now();
+        let records = [0, 450].map(|ms| record_at(start, ms));
+        let end_time = start + Duration::milliseconds(100);
+        let plot = PlotRecord::from_records(records.iter(), Some(start), Some(end_time), false);
+        assert_eq!(packet_nums(&plot), vec![1, 0]);
+        assert_eq!(plot.uncommitted_record.packet_num, 1);
+        assert_eq!(plot.end_time, Some(start + Duration::milliseconds(400)));
+    }
+
+    #[test]
+    fn test_plot_commit_rest() {
+        let start = Local::now();
+        let records = [0, 250].map(|ms| record_at(start, ms));
+        let mut plot = PlotRecord::from_records(records.iter(), Some(start), None, true);
+        plot.commit_rest();
+        assert_eq!(packet_nums(&plot), vec![1, 1]);
+        assert_eq!(plot.protocol_records.len(), 2);
+        assert_eq!(plot.protocol_records[1].get(PlotProtocol::Tcp), 60);
+        // the committed buckets and the time axis agree again
+        assert_eq!(plot.end_time, Some(start + Duration::milliseconds(400)));
+
+        // nothing pending, nothing changes
+        plot.commit_rest();
+        assert_eq!(plot.records.len(), 2);
+        assert_eq!(plot.end_time, Some(start + Duration::milliseconds(400)));
+    }
+
+    #[test]
+    fn test_plot_gap() {
+        let start = Local::now();
+        let mut plot = PlotRecord::default();
+        plot.clear_with_time(start);
+        plot.update_records(iter::once(&record_at(start, 50)), None);
+        plot.sample(start + Duration::milliseconds(210));
+        assert!(plot.gaps.is_empty());
+
+        // the sampler stalls for two seconds, then traffic resumes
+        plot.sample(start + Duration::milliseconds(2250));
+        assert_eq!(plot.gaps.len(), 1);
+        assert_eq!(plot.records.len(), 11);
+        assert!(!plot.is_gap(0));
+        assert!(!plot.is_gap(1));
+        assert!((2..11).all(|idx| plot.is_gap(idx)));
+        assert!(!plot.is_gap(11));
+        assert_eq!(plot.gap_duration(), Duration::milliseconds(1850));
+
+        // only the sampled buckets count towards the rate
+        let (packets, _) = plot.recent_rate(Duration::seconds(60));
+        assert_eq!(packets, 2.5);
+    }
+
+    #[test]
+    fn test_plot_resume() {
+        let start = Local::now();
+        let records = [record_at(start, 50)];
+        let mut plot = PlotRecord::from_records(records.iter(), Some(start), None, false);
+        plot.commit_rest();
+
+        // a second session starts a second later and appends to the first
+        plot.resume(start + Duration::milliseconds(1200));
+        assert_eq!(
+            plot.gaps,
+            vec![(
+                start + Duration::milliseconds(200),
+                start + Duration::milliseconds(1200)
+            )]
+        );
+        assert_eq!(plot.records.len(), 6);
+        assert!((1..6).all(|idx| plot.is_gap(idx)));
+        plot.update_records(iter::once(&record_at(start, 1250)), None);
+        plot.commit_rest();
+        assert_eq!(packet_nums(&plot), vec![1, 0, 0, 0, 0, 0, 1]);
+        assert!(!plot.is_gap(6));
+    }
+
+    #[test]
+    #[ignore]
+    fn bench_update_record() {
+        // what the gui does per captured packet; run with --release -- --ignored --nocapture
+        const RECORDS: usize = 1_000_000;
+        let start = Local::now();
+        let apps = [
+            AppProtocol::Http,
+            AppProtocol::Dns,
+            AppProtocol::Https,
+            AppProtocol::Unknown,
+        ];
+        let columns = [
+            RecordColumn::Time,
+            RecordColumn::SrcIp,
+            RecordColumn::DestIp,
+            RecordColumn::TransProto,
+            RecordColumn::AppProto,
+        ];
+        let mut store = RecordStore::new(100_000, 0);
+        let mut stat = StatRecord::default();
+        let mut plot = PlotRecord::from_records(iter::empty(), Some(start), None, true);
+        let mut cells = 0;
+
+        let began = std::time::Instant::now();
+        for idx in 0..RECORDS {
+            let mut record = record_at(start, idx as i64 / 1000);
+            record.trans_proto = if idx % 3 == 0 {
+                Protocol::Udp
+            } else {
+                Protocol::Tcp
+            };
+            record.app_proto = apps[idx % apps.len()].clone();
+            stat.update(&record);
+            plot.update_records(iter::once(&record), None);
+            cells += record.to_column_strings(&columns).len();
+            store.push(record);
+        }
+        let elapsed = began.elapsed();
+        println!(
+            "{} records in {:.3}s, {:.0} ns per record",
+            RECORDS,
+            elapsed.as_secs_f64(),
+            elapsed.as_nanos() as f64 / RECORDS as f64
+        );
+
+        assert_eq!(stat.stat_net_table.packet_num, RECORDS as u64);
+        assert_eq!(stat.stat_trans_table.len(), 2);
+        assert_eq!(stat.stat_app_table.len(), apps.len());
+        assert_eq!(store.len(), 100_000);
+        assert_eq!(cells, RECORDS * columns.len());
+    }
 }
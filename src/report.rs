@@ -0,0 +1,530 @@
+use anyhow::{anyhow, Error, Result};
+use serde_json::{json, Value};
+
+use crate::{
+    record::{endpoint_text, AppRecord, Flow, NetRecord, StatRecord, TalkerTable, TransRecord},
+    tr,
+    utils::text_table,
+};
+
+use std::{cmp::Reverse, collections::HashMap, iter, net::Ipv4Addr, str::FromStr};
+
+/// what the top talkers are ranked by, the other count breaking ties
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TopOrder {
+    Bytes,
+    Packets,
+}
+
+impl TopOrder {
+    fn key(self, packet_num: u64, byte_num: u64) -> Reverse<(u64, u64)> {
+        match self {
+            TopOrder::Bytes => Reverse((byte_num, packet_num)),
+            TopOrder::Packets => Reverse((packet_num, byte_num)),
+        }
+    }
+}
+
+impl FromStr for TopOrder {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "bytes" => Ok(Self::Bytes),
+            "packets" => Ok(Self::Packets),
+            _ => Err(anyhow!(
+                "unknown order \"{}\", expected bytes or packets",
+                s
+            )),
+        }
+    }
+}
+
+/// the columns of a transport layer table, as the stat tab has them
+fn trans_headers() -> [&'static str; 6] {
+    [
+        tr!("transport", "传输层协议"),
+        tr!("packets", "分组数"),
+        tr!("bytes", "字节数"),
+        tr!("in ip", "网络层字节数"),
+        tr!("inbound", "接收字节数"),
+        tr!("outbound", "发送字节数"),
+    ]
+}
+
+/// the columns of an application layer table, as the stat tab has them
+fn app_headers() -> [&'static str; 7] {
+    [
+        tr!("application", "应用层协议"),
+        tr!("packets", "分组数"),
+        tr!("bytes", "字节数"),
+        tr!("in ip", "网络层字节数"),
+        tr!("in transport", "传输层字节数"),
+        tr!("inbound", "接收字节数"),
+        tr!("outbound", "发送字节数"),
+    ]
+}
+
+/// the network, transport and application tables of the stat tab as aligned text, with the
+/// protocols sorted by name and followed by their totals; byte counts are formatted by `bytes`
+pub fn report_text(stat: &StatRecord, bytes: &dyn Fn(u64) -> String) -> String {
+    let net_row = |name: &str, record: &NetRecord| {
+        vec![
+            name.to_string(),
+            record.packet_num.to_string(),
+            bytes(record.byte_num),
+        ]
+    };
+    let mut text = text_table(
+        &[
+            tr!("network", "网络层"),
+            tr!("packets", "分组数"),
+            tr!("bytes", "字节数"),
+        ],
+        &[
+            net_row(tr!("total", "合计"), &stat.stat_net_table),
+            net_row(tr!("inbound", "接收"), &stat.stat_net_inbound),
+            net_row(tr!("outbound", "发送"), &stat.stat_net_outbound),
+        ],
+    );
+
+    let trans_total = stat.trans_total();
+    let mut rows = sorted(&stat.stat_trans_table)
+        .into_iter()
+        .map(|(name, record)| row(name, record.to_display_array(bytes)))
+        .collect::<Vec<_>>();
+    if !rows.is_empty() {
+        rows.push(row(
+            tr!("total", "合计"),
+            trans_total.to_display_array(bytes),
+        ));
+    }
+    text.push('\n');
+    text += &text_table(&trans_headers(), &rows);
+
+    let mut rows = sorted(&stat.stat_app_table)
+        .into_iter()
+        .map(|(name, record)| row(name, record.to_display_array(bytes)))
+        .collect::<Vec<_>>();
+    if !rows.is_empty() {
+        let app_total = stat.app_total();
+        // share of transport layer bytes carried by tcp/udp, the rest is other protocols
+        let total = match trans_total.byte_num {
+            0 => tr!("total", "合计").to_string(),
+            trans_bytes => tr!(
+                "total ({:.1}% of transport)",
+                "合计（占传输层 {:.1}%）",
+                app_total.byte_num_in_trans as f64 * 100.0 / trans_bytes as f64
+            ),
+        };
+        rows.push(row(&total, app_total.to_display_array(bytes)));
+    }
+    text.push('\n');
+    text += &text_table(&app_headers(), &rows);
+    text
+}
+
+/// the tables of `report_text` as json, with plain byte counts
+pub fn report_json(stat: &StatRecord) -> Value {
+    let net =
+        |record: &NetRecord| json!({ "packets": record.packet_num, "bytes": record.byte_num });
+    json!({
+        "network": {
+            "total": net(&stat.stat_net_table),
+            "inbound": net(&stat.stat_net_inbound),
+            "outbound": net(&stat.stat_net_outbound),
+        },
+        "transport": {
+            "protocols": sorted(&stat.stat_trans_table)
+            .into_iter()
+                .map(|(name, record)| trans_json(Some(name), record))
+                .collect::<Vec<_>>(),
+            "total": trans_json(None, &stat.trans_total()),
+        },
+        "application": {
+            "protocols": sorted(&stat.stat_app_table)
+            .into_iter()
+                .map(|(name, record)| app_json(Some(name), record))
+                .collect::<Vec<_>>(),
+            "total": app_json(None, &stat.app_total()),
+        },
+    })
+}
+
+fn trans_json(name: Option<&str>, record: &TransRecord) -> Value {
+    let mut value = json!({
+        "packets": record.packet_num,
+        "bytes": record.byte_num,
+        "bytes_in_ip": record.byte_num_in_net,
+        "bytes_inbound": record.byte_num_inbound,
+        "bytes_outbound": record.byte_num_outbound,
+    });
+    if let Some(name) = name {
+        value["protocol"] = json!(name);
+    }
+    value
+}
+
+fn app_json(name: Option<&str>, record: &AppRecord) -> Value {
+    let mut value = json!({
+        "packets": record.packet_num,
+        "bytes": record.byte_num,
+        "bytes_in_ip": record.byte_num_in_net,
+        "bytes_in_transport": record.byte_num_in_trans,
+        "bytes_inbound": record.byte_num_inbound,
+        "bytes_outbound": record.byte_num_outbound,
+    });
+    if let Some(name) = name {
+        value["protocol"] = json!(name);
+    }
+    value
+}
+
+/// the `n` busiest source addresses, destination addresses and conversations as aligned
+/// text, one table each
+pub fn top_text(
+    talkers: &TalkerTable,
+    n: usize,
+    order: TopOrder,
+    bytes: &dyn Fn(u64) -> String,
+) -> String {
+    let ip_rows = |table| {
+        top_ips(table, n, order)
+            .into_iter()
+            .map(|(ip, record)| {
+                vec![
+                    ip.to_string(),
+                    record.packet_num.to_string(),
+                    bytes(record.byte_num),
+                ]
+            })
+            .collect::<Vec<_>>()
+    };
+    let flow_rows = top_flows(talkers, n, order)
+        .into_iter()
+        .map(|flow| {
+            vec![
+                format!("{} {}", flow.key.trans_proto, flow.key),
+                flow.packet_num.to_string(),
+                bytes(flow.byte_num),
+            ]
+        })
+        .collect::<Vec<_>>();
+    let tables = [
+        (tr!("source", "源地址"), ip_rows(&talkers.sources)),
+        (
+            tr!("destination", "目的地址"),
+            ip_rows(&talkers.destinations),
+        ),
+        (tr!("conversation", "会话"), flow_rows),
+    ];
+    tables
+        .iter()
+        .map(|(name, rows)| {
+            text_table(
+                &[*name, tr!("packets", "分组数"), tr!("bytes", "字节数")],
+                rows,
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// the tables of `top_text` as json, with plain byte counts
+pub fn top_json(talkers: &TalkerTable, n: usize, order: TopOrder) -> Value {
+    let ips = |table| {
+        top_ips(table, n, order)
+            .into_iter()
+            .map(|(ip, record)| {
+                json!({ "address": ip, "packets": record.packet_num, "bytes": record.byte_num })
+            })
+            .collect::<Vec<_>>()
+    };
+    json!({
+        "sort_by": match order {
+            TopOrder::Bytes => "bytes",
+            TopOrder::Packets => "packets",
+        },
+        "sources": ips(&talkers.sources),
+        "destinations": ips(&talkers.destinations),
+        "conversations": top_flows(talkers, n, order)
+            .into_iter()
+            .map(|flow| {
+                json!({
+                    "protocol": flow.key.trans_proto,
+                    "a": endpoint_text(flow.key.a),
+                    "b": endpoint_text(flow.key.b),
+                    "packets": flow.packet_num,
+                    "bytes": flow.byte_num,
+                })
+            })
+            .collect::<Vec<_>>(),
+    })
+}
+
+/// the `n` busiest addresses of `table`, ties by address
+fn top_ips(
+    table: &HashMap<Ipv4Addr, NetRecord>,
+    n: usize,
+    order: TopOrder,
+) -> Vec<(Ipv4Addr, &NetRecord)> {
+    let mut rows = table
+        .iter()
+        .map(|(ip, record)| (*ip, record))
+        .collect::<Vec<_>>();
+    rows.sort_by_key(|(ip, record)| (order.key(record.packet_num, record.byte_num), *ip));
+    rows.truncate(n);
+    rows
+}
+
+/// the `n` busiest conversations, ties in order of appearance
+fn top_flows(talkers: &TalkerTable, n: usize, order: TopOrder) -> Vec<&Flow> {
+    let mut flows = talkers.flows.flows();
+    // stable, so the order of appearance stays among equals
+    flows.sort_by_key(|flow| order.key(flow.packet_num, flow.byte_num));
+    flows.truncate(n);
+    flows
+}
+
+/// table rows sorted by protocol name, like the stat tab lists them
+fn sorted<'a, R>(table: &'a HashMap<&'static str, R>) -> Vec<(&'static str, &'a R)> {
+    let mut rows = table
+        .iter()
+        .map(|(&name, record)| (name, record))
+        .collect::<Vec<_>>();
+    rows.sort_by(|a, b| a.0.cmp(b.0));
+    rows
+}
+
+fn row<const N: usize>(name: &str, cells: [String; N]) -> Vec<String> {
+    iter::once(name.to_string()).chain(cells).collect()
+}
+
+#[cfg(test)]
+mod report_test {
+    use super::*;
+    use crate::{
+        lang::{set_lang, Lang},
+        record::Record,
+        utils::AppProtocol,
+    };
+    use packet::ip::Protocol;
+
+    fn stat() -> StatRecord {
+        let mut stat = StatRecord::default();
+        stat.stat_net_table = NetRecord {
+            packet_num: 3,
+            byte_num: 300,
+        };
+        stat.stat_net_inbound = NetRecord {
+            packet_num: 2,
+            byte_num: 200,
+        };
+        stat.stat_net_outbound = NetRecord {
+            packet_num: 1,
+            byte_num: 100,
+        };
+        stat.stat_trans_table.insert(
+            "UDP",
+            TransRecord {
+                packet_num: 1,
+                byte_num: 80,
+                byte_num_in_net: 100,
+                byte_num_inbound: 0,
+                byte_num_outbound: 80,
+            },
+        );
+        stat.stat_trans_table.insert(
+            "TCP",
+            TransRecord {
+                packet_num: 2,
+                byte_num: 160,
+                byte_num_in_net: 200,
+                byte_num_inbound: 160,
+                byte_num_outbound: 0,
+            },
+        );
+        stat.stat_app_table.insert(
+            "HTTPS",
+            AppRecord {
+                packet_num: 2,
+                byte_num: 120,
+                byte_num_in_net: 200,
+                byte_num_in_trans: 160,
+                byte_num_inbound: 120,
+                byte_num_outbound: 0,
+            },
+        );
+        stat
+    }
+
+    #[test]
+    fn test_report_text() {
+        let text = report_text(&stat(), &|n| n.to_string());
+        let lines = text.lines().collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            [
+                "network   packets  bytes",
+                "total           3    300",
+                "inbound         2    200",
+                "outbound        1    100",
+                "",
+                "transport  packets  bytes  in ip  inbound  outbound",
+                "TCP              2    160    200      160         0",
+                "UDP              1     80    100        0        80",
+                "total            3    240    300      160        80",
+                "",
+                "application                 packets  bytes  in ip  in transport  inbound  outbound",
+                "HTTPS                             2    120    200           160      120         0",
+                "total (66.7% of transport)        2    120    200           160      120         0",
+            ]
+        );
+
+        // no totals rows for empty tables, only the network one
+        let text = report_text(&StatRecord::default(), &|n| n.to_string());
+        assert_eq!(
+            text.lines()
+                .filter(|line| line.starts_with("total"))
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_report_text_zh() {
+        set_lang(Lang::Zh);
+        let text = report_text(&stat(), &|n| n.to_string());
+        let lines = text.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], "网络层  分组数  字节数");
+        assert_eq!(lines[1], "合计         3     300");
+        assert_eq!(
+            lines[5],
+            "传输层协议  分组数  字节数  网络层字节数  接收字节数  发送字节数"
+        );
+        assert_eq!(
+            lines[6],
+            "TCP              2     160           200         160           0"
+        );
+        assert_eq!(lines[12].split("  ").next(), Some("合计（占传输层 66.7%）"));
+    }
+
+    #[test]
+    fn test_report_json() {
+        let json = report_json(&stat());
+        assert_eq!(
+            json["network"]["inbound"],
+            json!({ "packets": 2, "bytes": 200 })
+        );
+        let protocols = json["transport"]["protocols"].as_array().unwrap();
+        assert_eq!(protocols.len(), 2);
+        assert_eq!(protocols[0]["protocol"], "TCP");
+        assert_eq!(protocols[1]["bytes_outbound"], 80);
+        assert_eq!(json["transport"]["total"]["bytes"], 240);
+        assert_eq!(json["transport"]["total"].get("protocol"), None);
+        assert_eq!(
+            json["application"]["protocols"][0],
+            json!({
+                "protocol": "HTTPS",
+                "packets": 2,
+                "bytes": 120,
+                "bytes_in_ip": 200,
+                "bytes_in_transport": 160,
+                "bytes_inbound": 120,
+                "bytes_outbound": 0,
+            })
+        );
+    }
+
+    fn talkers() -> TalkerTable {
+        let packet = |src: [u8; 4], src_port, dest: [u8; 4], dest_port, len| {
+            Record::builder()
+                .src(src, Some(src_port))
+                .dest(dest, Some(dest_port))
+                .proto(Protocol::Tcp, AppProtocol::Unknown)
+                .len(len)
+                .build()
+        };
+        let mut talkers = TalkerTable::default();
+        for record in [
+            // a few large packets one way
+            packet([10, 0, 0, 1], 50000, [10, 0, 0, 9], 443, 1500),
+            packet([10, 0, 0, 1], 50000, [10, 0, 0, 9], 443, 1500),
+            // many small ones, answered once
+            packet([10, 0, 0, 2], 50001, [10, 0, 0, 8], 80, 60),
+            packet([10, 0, 0, 2], 50001, [10, 0, 0, 8], 80, 60),
+            packet([10, 0, 0, 2], 50001, [10, 0, 0, 8], 80, 60),
+            packet([10, 0, 0, 8], 80, [10, 0, 0, 2], 50001, 100),
+            // the same as 10.0.0.8 sends, so the address breaks the tie
+            packet([10, 0, 0, 3], 50002, [10, 0, 0, 9], 22, 100),
+        ] {
+            talkers.update(&record);
+        }
+        talkers
+    }
+
+    #[test]
+    fn test_top_text() {
+        let text = top_text(&talkers(), 2, TopOrder::Bytes, &|n| n.to_string());
+        assert_eq!(
+            text.lines().collect::<Vec<_>>(),
+            [
+                "source    packets  bytes",
+                "10.0.0.1        2   3000",
+                "10.0.0.2        3    180",
+                "",
+                "destination  packets  bytes",
+                "10.0.0.9           3   3100",
+                "10.0.0.8           3    180",
+                "",
+                "conversation                       packets  bytes",
+                "TCP 10.0.0.1:50000 ⇄ 10.0.0.9:443        2   3000",
+                "TCP 10.0.0.2:50001 ⇄ 10.0.0.8:80         4    280",
+            ]
+        );
+
+        let text = top_text(&talkers(), 3, TopOrder::Packets, &|n| n.to_string());
+        let sources = text.lines().skip(1).take(3).collect::<Vec<_>>();
+        assert_eq!(
+            sources,
+            [
+                "10.0.0.2        3    180",
+                "10.0.0.1        2   3000",
+                "10.0.0.3        1    100",
+            ]
+        );
+        // 10.0.0.3 and 10.0.0.8 both sent one packet of 100 bytes
+        let text = top_text(&talkers(), 4, TopOrder::Packets, &|n| n.to_string());
+        assert_eq!(text.lines().nth(4), Some("10.0.0.8        1    100"));
+
+        // the headers stay when nothing was captured
+        let text = top_text(&TalkerTable::default(), 5, TopOrder::Bytes, &|n| {
+            n.to_string()
+        });
+        assert_eq!(text.lines().count(), 5);
+    }
+
+    #[test]
+    fn test_top_json() {
+        let json = top_json(&talkers(), 1, TopOrder::Packets);
+        assert_eq!(json["sort_by"], "packets");
+        assert_eq!(
+            json["sources"],
+            json!([{ "address": "10.0.0.2", "packets": 3, "bytes": 180 }])
+        );
+        assert_eq!(
+            json["conversations"],
+            json!([{
+                "protocol": "TCP",
+                "a": "10.0.0.2:50001",
+                "b": "10.0.0.8:80",
+                "packets": 4,
+                "bytes": 280,
+            }])
+        );
+        assert_eq!(json["destinations"][0]["address"], "10.0.0.9");
+
+        assert_eq!("packets".parse::<TopOrder>().unwrap(), TopOrder::Packets);
+        assert!("flows".parse::<TopOrder>().is_err());
+    }
+}
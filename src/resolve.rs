@@ -0,0 +1,175 @@
+use anyhow::{anyhow, Result};
+
+use ipconfig::{self, OperStatus};
+
+use crate::dns;
+
+use std::{
+    collections::{HashMap, VecDeque},
+    net::{Ipv4Addr, SocketAddr, UdpSocket},
+    sync::{
+        mpsc::{self, Receiver, SyncSender},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/// how many addresses are remembered, resolved or not
+const CACHE_SIZE: usize = 4096;
+/// how many addresses may wait for a lookup; further ones are retried when asked again
+const QUEUE_SIZE: usize = 256;
+/// how long a lookup waits for its answer, so an unreachable server only stalls the queue
+const LOOKUP_TIMEOUT: Duration = Duration::from_secs(2);
+const DNS_PORT: u16 = 53;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Lookup {
+    Pending,
+    /// `None` when the address has no name or the lookup failed
+    Done(Option<String>),
+}
+
+/// the latest `capacity` lookups, the oldest ones are forgotten first
+#[derive(Debug)]
+struct HostCache {
+    entries: HashMap<Ipv4Addr, Lookup>,
+    order: VecDeque<Ipv4Addr>,
+    capacity: usize,
+}
+
+impl HostCache {
+    fn new(capacity: usize) -> Self {
+        Self {
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+            capacity: capacity.max(1),
+        }
+    }
+
+    fn get(&self, ip: &Ipv4Addr) -> Option<&Lookup> {
+        self.entries.get(ip)
+    }
+
+    fn insert(&mut self, ip: Ipv4Addr, lookup: Lookup) {
+        if self.entries.insert(ip, lookup).is_none() {
+            if self.order.len() == self.capacity {
+                if let Some(oldest) = self.order.pop_front() {
+                    self.entries.remove(&oldest);
+                }
+            }
+            self.order.push_back(ip);
+        }
+    }
+}
+
+/// reverse dns lookups on a background thread, one at a time, with the results cached;
+/// clones share the thread and the cache
+#[derive(Debug, Clone)]
+pub struct Resolver {
+    cache: Arc<Mutex<HostCache>>,
+    queue: SyncSender<Ipv4Addr>,
+}
+
+impl Resolver {
+    /// ask the dns `server` for host names; the worker thread ends with the resolver
+    pub fn new(server: SocketAddr) -> Self {
+        let cache = Arc::new(Mutex::new(HostCache::new(CACHE_SIZE)));
+        let (queue, requests) = mpsc::sync_channel(QUEUE_SIZE);
+        let worker_cache = cache.clone();
+        thread::spawn(move || resolve_queued(server, requests, worker_cache));
+        Self { cache, queue }
+    }
+
+    /// ask the first dns server of the adapters that are up
+    pub fn system() -> Result<Self> {
+        Ok(Self::new(system_dns_server()?))
+    }
+
+    /// the host name of `ip` if it is known already, otherwise a lookup is queued
+    pub fn name(&self, ip: Ipv4Addr) -> Option<String> {
+        let mut cache = self.cache.lock().unwrap();
+        match cache.get(&ip) {
+            Some(Lookup::Done(name)) => name.clone(),
+            Some(Lookup::Pending) => None,
+            None => {
+                // a full queue is not remembered, so the address is tried again later
+                if self.queue.try_send(ip).is_ok() {
+                    cache.insert(ip, Lookup::Pending);
+                }
+                None
+            }
+        }
+    }
+
+    /// `host (ip)` when the host name is known, `ip` otherwise
+    pub fn label(&self, ip: Ipv4Addr) -> String {
+        match self.name(ip) {
+            Some(name) => format!("{} ({})", name, ip),
+            None => ip.to_string(),
+        }
+    }
+}
+
+fn system_dns_server() -> Result<SocketAddr> {
+    ipconfig::get_adapters()?
+        .iter()
+        .filter(|adapter| adapter.oper_status() == OperStatus::IfOperStatusUp)
+        .flat_map(|adapter| adapter.dns_servers().iter())
+        .find(|server| server.is_ipv4())
+        .map(|server| SocketAddr::new(*server, DNS_PORT))
+        .ok_or(anyhow!("no dns server is configured"))
+}
+
+fn resolve_queued(server: SocketAddr, requests: Receiver<Ipv4Addr>, cache: Arc<Mutex<HostCache>>) {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))
+        .and_then(|socket| socket.connect(server).map(|_| socket))
+        .ok();
+    let mut id = 0u16;
+    for ip in requests {
+        id = id.wrapping_add(1);
+        let name = socket.as_ref().and_then(|socket| lookup(socket, id, ip));
+        cache.lock().unwrap().insert(ip, Lookup::Done(name));
+    }
+}
+
+fn lookup(socket: &UdpSocket, id: u16, ip: Ipv4Addr) -> Option<String> {
+    socket.send(&dns::ptr_query(id, ip)).ok()?;
+    let deadline = Instant::now() + LOOKUP_TIMEOUT;
+    let mut buf = [0u8; 512];
+    loop {
+        let left = deadline
+            .checked_duration_since(Instant::now())
+            .filter(|left| !left.is_zero())?;
+        socket.set_read_timeout(Some(left)).ok()?;
+        let len = socket.recv(&mut buf).ok()?;
+        // late answers to earlier queries that timed out are skipped
+        if buf[..len.min(2)] == id.to_be_bytes() {
+            return dns::parse_ptr_response(&buf[..len], id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod resolve_test {
+    use super::*;
+
+    #[test]
+    fn test_host_cache() {
+        let ip = |last| Ipv4Addr::new(10, 0, 0, last);
+        let mut cache = HostCache::new(2);
+        cache.insert(ip(1), Lookup::Pending);
+        cache.insert(ip(2), Lookup::Done(None));
+        // finishing a lookup does not make it any younger
+        cache.insert(ip(1), Lookup::Done(Some("one".to_string())));
+        assert_eq!(
+            cache.get(&ip(1)),
+            Some(&Lookup::Done(Some("one".to_string())))
+        );
+        cache.insert(ip(3), Lookup::Pending);
+        assert_eq!(cache.get(&ip(1)), None);
+        assert_eq!(cache.get(&ip(2)), Some(&Lookup::Done(None)));
+        assert_eq!(cache.get(&ip(3)), Some(&Lookup::Pending));
+        assert_eq!(cache.entries.len(), 2);
+    }
+}
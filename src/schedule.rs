@@ -0,0 +1,199 @@
+use anyhow::{anyhow, Result};
+
+use chrono::{DateTime, Duration, Local};
+
+use std::path::PathBuf;
+
+/// repeated captures of `duration` every `interval`, `runs` times
+#[derive(Debug, Clone)]
+pub struct Schedule {
+    pub duration: Duration,
+    pub interval: Duration,
+    pub runs: u32,
+    /// each run is exported into this directory
+    pub dir: PathBuf,
+    completed: u32,
+    next_run: DateTime<Local>,
+    /// start of the run in progress
+    running: Option<DateTime<Local>>,
+}
+
+/// what the schedule wants done at a given time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScheduleAction {
+    Start,
+    Stop,
+    Wait,
+}
+
+impl Schedule {
+    /// a schedule whose first run starts at `now`
+    pub fn new(
+        duration: Duration,
+        interval: Duration,
+        runs: u32,
+        dir: PathBuf,
+        now: DateTime<Local>,
+    ) -> Result<Self> {
+        if duration <= Duration::zero() {
+            return Err(anyhow!("capture duration must be positive"));
+        }
+        if interval < duration {
+            return Err(anyhow!(
+                "interval must not be shorter than the capture duration"
+            ));
+        }
+        if runs == 0 {
+            return Err(anyhow!("at least one run is required"));
+        }
+        Ok(Self {
+            duration,
+            interval,
+            runs,
+            dir,
+            completed: 0,
+            next_run: now,
+            running: None,
+        })
+    }
+
+    pub fn action(&self, now: DateTime<Local>) -> ScheduleAction {
+        match self.running {
+            Some(start) if now - start >= self.duration => ScheduleAction::Stop,
+            Some(_) => ScheduleAction::Wait,
+            None if !self.is_finished() && now >= self.next_run => ScheduleAction::Start,
+            None => ScheduleAction::Wait,
+        }
+    }
+
+    pub fn start_run(&mut self, now: DateTime<Local>) {
+        self.running = Some(now);
+        self.next_run = now + self.interval;
+    }
+
+    /// end the run in progress, also when it was stopped before its duration
+    pub fn finish_run(&mut self) {
+        if self.running.take().is_some() {
+            self.completed += 1;
+        }
+    }
+
+    /// drop the run in progress without counting it, when its capture could not start; the
+    /// next one starts an interval after it all the same
+    pub fn skip_run(&mut self) {
+        self.running = None;
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running.is_some()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.completed >= self.runs
+    }
+
+    pub fn completed(&self) -> u32 {
+        self.completed
+    }
+
+    /// time left of the run in progress, or until the next run
+    pub fn countdown(&self, now: DateTime<Local>) -> Duration {
+        let target = match self.running {
+            Some(start) => start + self.duration,
+            None => self.next_run,
+        };
+        (target - now).max(Duration::zero())
+    }
+}
+
+#[cfg(test)]
+mod schedule_test {
+    use super::*;
+
+    #[test]
+    fn test_schedule() {
+        let now = Local::now();
+        let mut schedule = Schedule::new(
+            Duration::seconds(30),
+            Duration::minutes(10),
+            2,
+            PathBuf::new(),
+            now,
+        )
+        .unwrap();
+        assert_eq!(schedule.action(now), ScheduleAction::Start);
+
+        schedule.start_run(now);
+        assert_eq!(
+            schedule.action(now + Duration::seconds(10)),
+            ScheduleAction::Wait
+        );
+        assert_eq!(
+            schedule.countdown(now + Duration::seconds(10)),
+            Duration::seconds(20)
+        );
+        assert_eq!(
+            schedule.action(now + Duration::seconds(30)),
+            ScheduleAction::Stop
+        );
+
+        schedule.finish_run();
+        assert_eq!(schedule.completed(), 1);
+        assert_eq!(
+            schedule.action(now + Duration::minutes(5)),
+            ScheduleAction::Wait
+        );
+        assert_eq!(
+            schedule.countdown(now + Duration::minutes(5)),
+            Duration::minutes(5)
+        );
+        assert_eq!(
+            schedule.action(now + Duration::minutes(10)),
+            ScheduleAction::Start
+        );
+
+        schedule.start_run(now + Duration::minutes(10));
+        schedule.finish_run();
+        assert!(schedule.is_finished());
+        assert_eq!(
+            schedule.action(now + Duration::minutes(20)),
+            ScheduleAction::Wait
+        );
+    }
+
+    #[test]
+    fn test_skip_run() {
+        let now = Local::now();
+        let mut schedule = Schedule::new(
+            Duration::seconds(30),
+            Duration::minutes(10),
+            2,
+            PathBuf::new(),
+            now,
+        )
+        .unwrap();
+        schedule.start_run(now);
+        schedule.skip_run();
+        assert!(!schedule.is_running());
+        assert_eq!(schedule.completed(), 0);
+        assert_eq!(
+            schedule.action(now + Duration::minutes(5)),
+            ScheduleAction::Wait
+        );
+        assert_eq!(
+            schedule.action(now + Duration::minutes(10)),
+            ScheduleAction::Start
+        );
+    }
+
+    #[test]
+    fn test_invalid_schedule() {
+        let now = Local::now();
+        let new =
+            |duration, interval, runs| Schedule::new(duration, interval, runs, PathBuf::new(), now);
+        assert!(new(Duration::zero(), Duration::minutes(1), 1).is_err());
+        assert!(new(Duration::minutes(2), Duration::minutes(1), 1).is_err());
+        assert!(new(Duration::seconds(30), Duration::minutes(1), 0).is_err());
+        assert!(new(Duration::minutes(1), Duration::minutes(1), 1).is_ok());
+    }
+}
@@ -0,0 +1,436 @@
+use anyhow::{anyhow, Result};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    meta,
+    record::RecordColumn,
+    socket::{CaptureProtocol, RecvMode},
+};
+
+use std::{env, fs, net::Ipv4Addr, path::PathBuf};
+
+/// neither stat table is dragged below this share of the stat tab
+const MIN_STAT_SPLIT_RATIO: f64 = 0.1;
+
+/// user preferences, persisted as json under `%APPDATA%`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// show byte counts as "174.9 MB" instead of raw numbers
+    pub humanize_bytes: bool,
+    /// length of the moving window for the average rate, in seconds
+    pub rate_window_secs: u32,
+    /// overlay a moving average on the traffic plot
+    pub plot_smoothing: bool,
+    /// number of samples the moving average spans
+    pub smoothing_window: usize,
+    /// break the byte series down into stacked per protocol areas
+    pub plot_stacked: bool,
+    /// while capturing, only plot the trailing `plot_window_secs` seconds
+    pub plot_rolling: bool,
+    pub plot_window_secs: u32,
+    /// draw packets and bytes in two stacked charts instead of one dual axis chart
+    pub plot_split: bool,
+    pub plot_series: PlotSeries,
+    /// use the dark color preset for the plot
+    pub plot_dark: bool,
+    /// minimum time between two plot redraws, in milliseconds
+    pub plot_redraw_interval_ms: u64,
+    /// every record table column, in display order
+    pub record_columns: Vec<ColumnSetting>,
+    /// keep the newest record in view while it is scrolled to the bottom
+    pub auto_scroll: bool,
+    /// starting a capture drops the previous records instead of appending to them
+    pub clear_on_start: bool,
+    /// the last connected interfaces
+    pub last_adapters: Vec<SavedAdapter>,
+    /// connect to `last_adapters` on launch
+    pub auto_connect: bool,
+    /// show the host names of the addresses in the record table, looked up in the background
+    pub resolve_hosts: bool,
+    /// read the adapters in a loop instead of waiting for them to signal packets, should
+    /// waiting misbehave on some driver
+    pub poll_capture: bool,
+    /// the port the capture sockets are bound to, which filters nothing; 0 lets the system
+    /// pick one
+    pub bind_port: u16,
+    /// whether the capture takes in everything the adapters see or only this host's traffic,
+    /// from the next connection on
+    pub recv_mode: RecvMode,
+    /// the only protocol captured, from the next connection on, or every protocol for `None`
+    pub capture_protocol: Option<CaptureProtocol>,
+    /// the oldest records are dropped beyond this many, 0 for no limit
+    pub max_records: usize,
+    /// the oldest records are dropped beyond this much memory in MB, 0 for no limit
+    pub max_records_mb: usize,
+    pub font_size: FontSize,
+    /// share of the stat tab height the transport table gets, the rest is for the app table
+    pub stat_split_ratio: f64,
+    /// stat tables folded down to their title
+    pub stat_trans_collapsed: bool,
+    pub stat_app_collapsed: bool,
+    /// how often the stat tab picks up new records while capturing
+    pub stat_refresh: StatRefresh,
+    /// export the records here as csv whenever a capture stops, `None` to leave them be
+    pub auto_export_dir: Option<PathBuf>,
+    pub profiles: Vec<Profile>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            humanize_bytes: true,
+            rate_window_secs: 10,
+            plot_smoothing: false,
+            smoothing_window: 5,
+            plot_stacked: false,
+            plot_rolling: false,
+            plot_window_secs: 30,
+            plot_split: false,
+            plot_series: Default::default(),
+            plot_dark: false,
+            plot_redraw_interval_ms: 100,
+            record_columns: RecordColumn::ALL
+                .iter()
+                .map(|&column| column.into())
+                .collect(),
+            auto_scroll: true,
+            clear_on_start: true,
+            last_adapters: Vec::new(),
+            auto_connect: true,
+            resolve_hosts: false,
+            poll_capture: false,
+            bind_port: 0,
+            recv_mode: RecvMode::On,
+            capture_protocol: None,
+            max_records: 1_000_000,
+            max_records_mb: 1024,
+            font_size: FontSize::Medium,
+            stat_split_ratio: 0.5,
+            stat_trans_collapsed: false,
+            stat_app_collapsed: false,
+            stat_refresh: StatRefresh::Ms250,
+            auto_export_dir: None,
+            profiles: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ColumnSetting {
+    pub column: RecordColumn,
+    pub visible: bool,
+    /// width in pixels, as last left by the user
+    pub width: isize,
+}
+
+impl From<RecordColumn> for ColumnSetting {
+    fn from(column: RecordColumn) -> Self {
+        Self {
+            column,
+            visible: true,
+            width: column.default_width(),
+        }
+    }
+}
+
+/// size of the ui font
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FontSize {
+    Small,
+    Medium,
+    Large,
+}
+
+impl FontSize {
+    pub const ALL: [Self; 3] = [Self::Small, Self::Medium, Self::Large];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Small => "小",
+            Self::Medium => "中",
+            Self::Large => "大",
+        }
+    }
+
+    /// font height in pixels at 100% scaling
+    pub fn pixels(&self) -> u32 {
+        match self {
+            Self::Small => 18,
+            Self::Medium => 22,
+            Self::Large => 26,
+        }
+    }
+}
+
+/// refresh cadence of the stat tab
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StatRefresh {
+    Ms250,
+    Ms500,
+    S1,
+    S2,
+    /// only on the refresh button, switching to the tab or stopping the capture
+    Manual,
+}
+
+impl StatRefresh {
+    pub const ALL: [Self; 5] = [Self::Ms250, Self::Ms500, Self::S1, Self::S2, Self::Manual];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Ms250 => "250 毫秒",
+            Self::Ms500 => "500 毫秒",
+            Self::S1 => "1 秒",
+            Self::S2 => "2 秒",
+            Self::Manual => "手动",
+        }
+    }
+
+    /// `None` when refreshed by hand only
+    pub fn millis(&self) -> Option<u64> {
+        match self {
+            Self::Ms250 => Some(250),
+            Self::Ms500 => Some(500),
+            Self::S1 => Some(1000),
+            Self::S2 => Some(2000),
+            Self::Manual => None,
+        }
+    }
+}
+
+/// an adapter to connect again, along with which of its ipv4 addresses it was
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "SavedAdapterRepr")]
+pub struct SavedAdapter {
+    /// adapter name (guid)
+    pub name: String,
+    /// `None` when saved before the address was, then the first address is taken
+    pub address: Option<Ipv4Addr>,
+}
+
+/// settings saved before the addresses were list the bare adapter names
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SavedAdapterRepr {
+    Name(String),
+    Adapter {
+        name: String,
+        address: Option<Ipv4Addr>,
+    },
+}
+
+impl From<SavedAdapterRepr> for SavedAdapter {
+    fn from(repr: SavedAdapterRepr) -> Self {
+        match repr {
+            SavedAdapterRepr::Name(name) => Self {
+                name,
+                address: None,
+            },
+            SavedAdapterRepr::Adapter { name, address } => Self { name, address },
+        }
+    }
+}
+
+impl SavedAdapter {
+    /// index of this interface among `(adapter name, address)` pairs, falling back to the
+    /// first address of the adapter when the saved one is gone
+    pub fn find(&self, interfaces: &[(&str, Ipv4Addr)]) -> Option<usize> {
+        let saved = self.address.and_then(|address| {
+            interfaces
+                .iter()
+                .position(|&(name, a)| name == self.name && a == address)
+        });
+        saved.or_else(|| interfaces.iter().position(|&(name, _)| name == self.name))
+    }
+}
+
+/// a named capture setup, applied in one go from the toolbar
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    pub name: String,
+    /// the interfaces to connect
+    pub adapters: Vec<SavedAdapter>,
+    pub filter: String,
+    /// text of the capture time field, in milliseconds
+    pub timeout: String,
+    pub auto_export_dir: Option<PathBuf>,
+}
+
+/// which series the plot draws
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlotSeries {
+    pub packets: bool,
+    pub bytes: bool,
+    /// layers of the stacked byte series, indexed by `PlotProtocol`
+    pub protocols: [bool; 4],
+}
+
+impl Default for PlotSeries {
+    fn default() -> Self {
+        Self {
+            packets: true,
+            bytes: true,
+            protocols: [true; 4],
+        }
+    }
+}
+
+impl Settings {
+    fn path() -> Result<PathBuf> {
+        let app_data = env::var_os("APPDATA").ok_or(anyhow!("APPDATA is not set"))?;
+        Ok(PathBuf::from(app_data)
+            .join(meta::NAME)
+            .join("settings.json"))
+    }
+
+    /// load settings from disk, falling back to defaults when missing or broken
+    pub fn load() -> Self {
+        let mut settings: Self = Self::path()
+            .and_then(|path| Ok(fs::read_to_string(path)?))
+            .and_then(|text| Ok(serde_json::from_str(&text)?))
+            .unwrap_or_default();
+        settings.complete_record_columns();
+        settings.stat_split_ratio = clamp_stat_split_ratio(settings.stat_split_ratio);
+        settings
+    }
+
+    /// drop repeated columns and append the ones missing from `record_columns`,
+    /// e.g. columns added after the settings were saved
+    fn complete_record_columns(&mut self) {
+        let mut seen = Vec::new();
+        self.record_columns.retain(|c| {
+            let first = !seen.contains(&c.column);
+            seen.push(c.column);
+            first
+        });
+        for column in RecordColumn::ALL {
+            if !seen.contains(&column) {
+                self.record_columns.push(column.into());
+            }
+        }
+    }
+
+    /// the shown record table columns, in display order
+    pub fn visible_columns(&self) -> Vec<RecordColumn> {
+        self.record_columns
+            .iter()
+            .filter(|c| c.visible)
+            .map(|c| c.column)
+            .collect()
+    }
+
+    /// add `profile`, replacing the one of the same name, and return its index
+    pub fn save_profile(&mut self, profile: Profile) -> usize {
+        match self.profiles.iter().position(|p| p.name == profile.name) {
+            Some(idx) => {
+                self.profiles[idx] = profile;
+                idx
+            }
+            None => {
+                self.profiles.push(profile);
+                self.profiles.len() - 1
+            }
+        }
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = Self::path()?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+/// `ratio` kept within what dragging the stat splitter allows
+pub fn clamp_stat_split_ratio(ratio: f64) -> f64 {
+    ratio.clamp(MIN_STAT_SPLIT_RATIO, 1.0 - MIN_STAT_SPLIT_RATIO)
+}
+
+#[cfg(test)]
+mod settings_test {
+    use super::*;
+
+    #[test]
+    fn test_complete_record_columns() {
+        let mut settings = Settings {
+            record_columns: vec![
+                ColumnSetting {
+                    column: RecordColumn::Direction,
+                    visible: false,
+                    width: 42,
+                },
+                RecordColumn::Time.into(),
+                RecordColumn::Direction.into(),
+            ],
+            ..Default::default()
+        };
+        settings.complete_record_columns();
+        assert_eq!(settings.record_columns.len(), RecordColumn::ALL.len());
+        assert_eq!(settings.record_columns[0].width, 42);
+        assert_eq!(settings.record_columns[1].column, RecordColumn::Time);
+        assert_eq!(settings.record_columns[2].column, RecordColumn::SrcIp);
+
+        let visible = settings.visible_columns();
+        assert_eq!(visible.len(), RecordColumn::ALL.len() - 1);
+        assert_eq!(visible[0], RecordColumn::Time);
+    }
+
+    #[test]
+    fn test_clamp_stat_split_ratio() {
+        assert_eq!(clamp_stat_split_ratio(0.5), 0.5);
+        assert_eq!(clamp_stat_split_ratio(-3.0), 0.1);
+        assert_eq!(clamp_stat_split_ratio(7.0), 0.9);
+    }
+
+    #[test]
+    fn test_save_profile() {
+        let mut settings = Settings::default();
+        let profile = |name: &str, filter: &str| Profile {
+            name: name.to_string(),
+            filter: filter.to_string(),
+            ..Default::default()
+        };
+        assert_eq!(settings.save_profile(profile("voip", "port == 5060")), 0);
+        assert_eq!(settings.save_profile(profile("dns", "port == 53")), 1);
+        assert_eq!(
+            settings.save_profile(profile("voip", "port in (5060, 10000-20000)")),
+            0
+        );
+        assert_eq!(settings.profiles.len(), 2);
+        assert_eq!(settings.profiles[0].filter, "port in (5060, 10000-20000)");
+        assert_eq!(settings.profiles[1].name, "dns");
+    }
+
+    #[test]
+    fn test_saved_adapter() {
+        let first = Ipv4Addr::new(192, 168, 1, 2);
+        let second = Ipv4Addr::new(10, 0, 0, 2);
+        let interfaces = [("{a}", first), ("{a}", second), ("{b}", first)];
+        let saved = |name: &str, address| SavedAdapter {
+            name: name.to_string(),
+            address,
+        };
+        assert_eq!(saved("{a}", Some(second)).find(&interfaces), Some(1));
+        assert_eq!(saved("{b}", Some(first)).find(&interfaces), Some(2));
+        // the saved address is gone, or was never saved
+        assert_eq!(
+            saved("{a}", Some(Ipv4Addr::new(10, 0, 0, 3))).find(&interfaces),
+            Some(0)
+        );
+        assert_eq!(saved("{a}", None).find(&interfaces), Some(0));
+        assert_eq!(saved("{c}", Some(first)).find(&interfaces), None);
+
+        let adapters: Vec<SavedAdapter> =
+            serde_json::from_str(r#"["{a}", {"name": "{b}", "address": "10.0.0.2"}]"#).unwrap();
+        assert_eq!(
+            adapters,
+            vec![saved("{a}", None), saved("{b}", Some(second))]
+        );
+    }
+}
@@ -1,17 +1,146 @@
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, bail, Result};
 
+use chrono::Local;
+use serde::{Deserialize, Serialize};
 use socket2::{Domain, Socket, Type};
 use std::os::windows::prelude::{AsRawSocket, RawSocket};
 use std::{
+    error::Error,
+    fmt::{self, Display},
     io::{self, Read},
     mem,
-    net::SocketAddr,
+    net::{Ipv4Addr, SocketAddr},
     ptr,
+    str::FromStr,
+    sync::Arc,
+    thread,
+    time::Duration,
 };
 use winapi::ctypes::c_int;
-use winapi::shared::{mstcpip, ws2def, ws2ipdef};
+use winapi::shared::{minwindef::FALSE, mstcpip, ws2def, ws2ipdef};
 use winapi::um::winsock2 as sock;
 
+use crate::capture::{CaptureMetrics, PacketSource, ReadPacket, Wake};
+
+// winsock error codes, see https://docs.microsoft.com/en-us/windows/win32/winsock/windows-sockets-error-codes-2
+const WSAEACCES: i32 = 10013;
+const WSAEINVAL: i32 = 10022;
+const WSAEWOULDBLOCK: i32 = 10035;
+const WSAEMSGSIZE: i32 = 10040;
+const WSAEADDRINUSE: i32 = 10048;
+const WSAEADDRNOTAVAIL: i32 = 10049;
+const WSAENETDOWN: i32 = 10050;
+const WSAENETRESET: i32 = 10052;
+const WSAETIMEDOUT: i32 = 10060;
+
+/// how long a blocking read of `ReadMode::Polling` waits before it gives up, so that whoever
+/// reads gets to check whether to stop
+const POLL_READ_TIMEOUT: Duration = Duration::from_millis(100);
+/// how long `Capturer::wait` sleeps in `ReadMode::Polling`
+const POLL_BACKOFF: Duration = Duration::from_millis(1);
+/// the largest ipv4 datagram, which the read buffer grows to once one did not fit
+pub const MAX_DATAGRAM: usize = 65535;
+
+/// why opening, waiting on or reading a capture socket failed, told apart by the winsock
+/// error codes that call for something other than reporting them
+#[derive(Debug)]
+pub enum CaptureError {
+    /// raw sockets take administrator rights
+    AccessDenied,
+    /// there is nothing to read yet, from a non-blocking socket or once a read timed out
+    WouldBlock,
+    /// the datagram did not fit the buffer, which holds its start while the rest is lost
+    MessageTooLong,
+    AddrInUse,
+    /// the network went down
+    NetworkDown,
+    /// the network dropped the connection, e.g. as the adapter was reset
+    NetworkReset,
+    /// the address the socket is bound to is not one of this machine anymore
+    AdapterGone,
+    /// `SIO_RCVALL` refused a socket created for a single protocol, which it is only
+    /// documented for with sockets of every protocol
+    ProtocolRefused,
+    Other(io::Error),
+}
+
+impl CaptureError {
+    /// whether a socket failing with this will not capture anything anymore, e.g. because its
+    /// adapter was disconnected or lost the address the socket is bound to
+    pub fn is_fatal(&self) -> bool {
+        matches!(
+            self,
+            Self::NetworkDown | Self::NetworkReset | Self::AdapterGone
+        )
+    }
+}
+
+impl From<io::Error> for CaptureError {
+    fn from(err: io::Error) -> Self {
+        match err.raw_os_error() {
+            Some(WSAEACCES) => Self::AccessDenied,
+            Some(WSAEWOULDBLOCK | WSAETIMEDOUT) => Self::WouldBlock,
+            Some(WSAEMSGSIZE) => Self::MessageTooLong,
+            Some(WSAEADDRINUSE) => Self::AddrInUse,
+            Some(WSAENETDOWN) => Self::NetworkDown,
+            Some(WSAENETRESET) => Self::NetworkReset,
+            Some(WSAEADDRNOTAVAIL) => Self::AdapterGone,
+            _ => Self::Other(err),
+        }
+    }
+}
+
+impl Display for CaptureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::AccessDenied => write!(f, "access denied, raw sockets require administrator"),
+            Self::WouldBlock => write!(f, "nothing to read yet"),
+            Self::MessageTooLong => write!(f, "the datagram is larger than the buffer"),
+            Self::AddrInUse => write!(f, "the address is already in use"),
+            Self::NetworkDown => write!(f, "the network is down"),
+            Self::NetworkReset => write!(f, "the connection was reset by the network"),
+            Self::AdapterGone => write!(f, "the address is not available anymore"),
+            Self::ProtocolRefused => {
+                write!(f, "receiving all packets is refused for a single protocol")
+            }
+            Self::Other(err) => err.fmt(f),
+        }
+    }
+}
+
+impl Error for CaptureError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Other(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// an adapter that stopped capturing for good, returned by `Capturer::read_from`
+#[derive(Debug)]
+pub struct AdapterLost {
+    pub ip: Ipv4Addr,
+    pub name: String,
+    pub source: CaptureError,
+}
+
+impl Display for AdapterLost {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "capturing on {} ({}) failed: {}",
+            self.name, self.ip, self.source
+        )
+    }
+}
+
+impl Error for AdapterLost {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
 macro_rules! syscall {
     ($fn: ident ( $($arg: expr),* $(,)* ), $err_test: path, $err_value: expr) => {{
         #[allow(unused_unsafe)]
@@ -47,7 +176,7 @@ unsafe fn setsockopt<T>(
 pub trait SocketExt {
     fn set_recv_ip_header(&self, recv_ip_header: bool) -> io::Result<()>;
     fn set_recv_ip_header_v6(&self, recv_ip_header: bool) -> io::Result<()>;
-    fn set_recv_all_packets(&self, recv_all_packets: bool) -> io::Result<()>;
+    fn set_recv_mode(&self, mode: RecvMode) -> io::Result<()>;
 }
 
 impl SocketExt for Socket {
@@ -74,11 +203,11 @@ impl SocketExt for Socket {
         }
     }
 
-    fn set_recv_all_packets(&self, recv_all_packets: bool) -> io::Result<()> {
-        let mut in_buf: mstcpip::RCVALL_VALUE = if recv_all_packets {
-            mstcpip::RCVALL_ON
-        } else {
-            mstcpip::RCVALL_OFF
+    fn set_recv_mode(&self, mode: RecvMode) -> io::Result<()> {
+        let mut in_buf: mstcpip::RCVALL_VALUE = match mode {
+            RecvMode::Off => mstcpip::RCVALL_OFF,
+            RecvMode::On => mstcpip::RCVALL_ON,
+            RecvMode::IpLevel => mstcpip::RCVALL_IPLEVEL,
         };
         let mut out = 0;
         syscall!(
@@ -100,61 +229,853 @@ impl SocketExt for Socket {
     }
 }
 
-pub fn ipv4_capturer(address: SocketAddr, nonblocking: bool) -> io::Result<Socket> {
-    let socket = Socket::new(Domain::IPV4, Type::RAW, Some(ws2def::IPPROTO_IP.into()))?;
+/// a winsock event object, closed on drop
+struct WsaEvent(sock::WSAEVENT);
+
+// an event object may be signaled and waited on from any thread
+unsafe impl Send for WsaEvent {}
+unsafe impl Sync for WsaEvent {}
+
+impl WsaEvent {
+    fn new() -> io::Result<Self> {
+        let event = unsafe { sock::WSACreateEvent() };
+        if event == sock::WSA_INVALID_EVENT {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self(event))
+    }
+    fn set(&self) {
+        unsafe { sock::WSASetEvent(self.0) };
+    }
+    fn reset(&self) {
+        unsafe { sock::WSAResetEvent(self.0) };
+    }
+}
+
+impl Drop for WsaEvent {
+    fn drop(&mut self) {
+        unsafe { sock::WSACloseEvent(self.0) };
+    }
+}
+
+/// signal `event` whenever `socket` has something to read, or stop signaling with `None`;
+/// a socket signaling an event is non-blocking
+fn select_read_event(socket: &Socket, event: Option<&WsaEvent>) -> io::Result<()> {
+    let (event, network_events) = match event {
+        Some(event) => (event.0, sock::FD_READ),
+        None => (ptr::null_mut(), 0),
+    };
+    syscall!(
+        WSAEventSelect(socket.as_raw_socket() as usize, event, network_events),
+        PartialEq::eq,
+        sock::SOCKET_ERROR
+    )
+    .map(|_| ())
+}
+
+/// how `Capturer::wait` waits for packets
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadMode {
+    /// sleep until a socket signals it has something to read, or a waker cuts the wait short
+    Events,
+    /// a blocking read with a timeout on a single socket, non-blocking reads in turn on
+    /// several, with a short sleep whenever none of them had anything
+    Polling,
+}
+
+impl Default for ReadMode {
+    fn default() -> Self {
+        Self::Polling
+    }
+}
+
+/// which packets a capture socket receives, as `SIO_RCVALL` sets it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RecvMode {
+    /// only what a raw socket gets anyway, which for one bound to an address is next to
+    /// nothing: tcp and udp are never handed to raw sockets
+    Off,
+    /// every ipv4 packet the adapter receives or sends, with the adapter switched to
+    /// promiscuous mode where its driver allows, so that on a hub or a mirrored port the
+    /// traffic of other hosts comes along
+    On,
+    /// every ipv4 packet to or from this host as the ip stack sees it, with the adapter
+    /// left as it is; others' traffic stays out even on a mirrored port
+    IpLevel,
+}
+
+impl RecvMode {
+    pub const ALL: [Self; 3] = [Self::On, Self::IpLevel, Self::Off];
+}
+
+impl Default for RecvMode {
+    fn default() -> Self {
+        Self::On
+    }
+}
+
+impl FromStr for RecvMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "off" => Ok(Self::Off),
+            "on" => Ok(Self::On),
+            "ip-level" => Ok(Self::IpLevel),
+            _ => bail!(
+                "unknown receive mode \"{}\", expected on, ip-level or off",
+                s
+            ),
+        }
+    }
+}
+
+/// the one protocol a capture may be restricted to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CaptureProtocol {
+    Tcp,
+    Udp,
+    Icmp,
+}
+
+impl CaptureProtocol {
+    pub const ALL: [Self; 3] = [Self::Tcp, Self::Udp, Self::Icmp];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Tcp => "TCP",
+            Self::Udp => "UDP",
+            Self::Icmp => "ICMP",
+        }
+    }
+
+    /// the protocol field of the ip header
+    pub fn number(self) -> u8 {
+        match self {
+            Self::Tcp => 6,
+            Self::Udp => 17,
+            Self::Icmp => 1,
+        }
+    }
+
+    /// whether `packet`, an ipv4 packet along with its header, carries this protocol
+    pub fn matches(self, packet: &[u8]) -> bool {
+        packet.get(9) == Some(&self.number())
+    }
+}
+
+impl FromStr for CaptureProtocol {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "tcp" => Ok(Self::Tcp),
+            "udp" => Ok(Self::Udp),
+            "icmp" => Ok(Self::Icmp),
+            _ => bail!("unknown protocol \"{}\", expected tcp, udp or icmp", s),
+        }
+    }
+}
+
+/// a raw socket capturing on `address`, created for `protocol` alone if given
+pub fn ipv4_capturer(
+    address: SocketAddr,
+    recv_mode: RecvMode,
+    protocol: Option<CaptureProtocol>,
+    nonblocking: bool,
+) -> Result<Socket, CaptureError> {
+    let ipproto = match protocol {
+        None => ws2def::IPPROTO_IP,
+        Some(CaptureProtocol::Tcp) => ws2def::IPPROTO_TCP,
+        Some(CaptureProtocol::Udp) => ws2def::IPPROTO_UDP,
+        Some(CaptureProtocol::Icmp) => ws2def::IPPROTO_ICMP,
+    };
+    let socket = Socket::new(Domain::IPV4, Type::RAW, Some(ipproto.into()))?;
     socket.set_recv_ip_header(true)?;
     socket.set_nonblocking(nonblocking)?;
     socket.bind(&address.into())?;
-    socket.set_recv_all_packets(true)?;
+    socket
+        .set_recv_mode(recv_mode)
+        .map_err(|err| match (protocol, err.raw_os_error()) {
+            (Some(_), Some(WSAEINVAL)) => CaptureError::ProtocolRefused,
+            _ => err.into(),
+        })?;
     Ok(socket)
 }
 
+/// a raw socket capturing on `ip`, bound to `port`; a raw socket receives the packets of
+/// every port all the same, so when `port` is taken the system picks another one instead
+///
+/// `SIO_RCVALL` is only documented for sockets of every protocol, so where a socket for
+/// `protocol` alone is refused, one for every protocol is bound instead, leaving the reads to
+/// pick out `protocol`; `true` along with the socket when that happened
+fn bind_capturer(
+    ip: Ipv4Addr,
+    port: u16,
+    recv_mode: RecvMode,
+    protocol: Option<CaptureProtocol>,
+    nonblocking: bool,
+) -> Result<(Socket, bool), CaptureError> {
+    bind_with_fallback(protocol, |protocol| {
+        match ipv4_capturer(
+            SocketAddr::from((ip, port)),
+            recv_mode,
+            protocol,
+            nonblocking,
+        ) {
+            Err(CaptureError::AddrInUse) if port != 0 => {
+                ipv4_capturer(SocketAddr::from((ip, 0)), recv_mode, protocol, nonblocking)
+            }
+            result => result,
+        }
+    })
+}
+
+/// `bind` for `protocol`, and for every protocol once that is refused as `ProtocolRefused`;
+/// any other failure is the one reported
+fn bind_with_fallback<S>(
+    protocol: Option<CaptureProtocol>,
+    bind: impl Fn(Option<CaptureProtocol>) -> Result<S, CaptureError>,
+) -> Result<(S, bool), CaptureError> {
+    match bind(protocol) {
+        Err(CaptureError::ProtocolRefused) => bind(None).map(|socket| (socket, true)),
+        result => result.map(|socket| (socket, false)),
+    }
+}
+
+/// a packet read by the capturer, along with the local address and name of its adapter and
+/// whether it was cut short by the buffer
+pub type SocketPacket<'a> = (&'a mut [u8], Option<(Ipv4Addr, &'a str)>, bool);
+
 #[derive(Default)]
 pub struct Capturer {
-    socket: Option<Socket>,
+    /// one socket per captured adapter, with its local address and adapter name
+    sockets: Vec<(Socket, Ipv4Addr, String)>,
     buffer: Vec<u8>,
+    /// the socket to read from first next time, so a busy adapter can not starve the others
+    next: usize,
+    /// what the sockets bound from now on receive
+    recv_mode: RecvMode,
+    /// the only protocol the sockets bound from now on are created for, and the reads keep
+    protocol: Option<CaptureProtocol>,
+    /// sockets bound for every protocol since the one for `protocol` alone was refused
+    protocol_fallbacks: u64,
+    metrics: CaptureMetrics,
+    mode: ReadMode,
+    /// signaled by the sockets in `ReadMode::Events`, and by the wakers handed out to cut
+    /// waiting short, created along with the first switch to it
+    events: Option<(WsaEvent, Arc<WsaEvent>)>,
 }
 
 impl Capturer {
     pub fn new() -> Self {
         Default::default()
     }
-    pub fn capture(&mut self, address: SocketAddr, nonblocking: bool) -> io::Result<()> {
-        drop(self.socket.take());
-        let socket = ipv4_capturer(address, nonblocking)?;
-        let buffer_size = socket.recv_buffer_size()?;
-        if self.buffer.len() < buffer_size {
-            self.buffer.resize(buffer_size, 0u8);
+    pub fn capture(
+        &mut self,
+        ip: Ipv4Addr,
+        port: u16,
+        nonblocking: bool,
+    ) -> Result<(), CaptureError> {
+        match self
+            .capture_all(&[(ip, ip.to_string())], port, nonblocking)
+            .pop()
+        {
+            Some((_, err)) => Err(err),
+            None => Ok(()),
+        }
+    }
+    /// capture on every `(address, adapter name)` that can be bound to `port`, 0 for any,
+    /// returning the addresses that failed; blocking sockets would be waited on in turn, so
+    /// use `nonblocking` for more than one
+    pub fn capture_all(
+        &mut self,
+        targets: &[(Ipv4Addr, String)],
+        port: u16,
+        nonblocking: bool,
+    ) -> Vec<(Ipv4Addr, CaptureError)> {
+        self.disconnect();
+        let mut failures = Vec::new();
+        for (ip, name) in targets {
+            let socket = match bind_capturer(*ip, port, self.recv_mode, self.protocol, nonblocking)
+            {
+                Ok((socket, fallback)) => {
+                    self.protocol_fallbacks += fallback as u64;
+                    socket
+                }
+                Err(err) => {
+                    failures.push((*ip, err));
+                    continue;
+                }
+            };
+            match socket.recv_buffer_size() {
+                Ok(buffer_size) => {
+                    if self.buffer.len() < buffer_size {
+                        self.buffer.resize(buffer_size, 0u8);
+                    }
+                    self.sockets.push((socket, *ip, name.clone()));
+                }
+                Err(err) => failures.push((*ip, err.into())),
+            }
+        }
+        // the new sockets signal events like the ones before them, or are all polled instead
+        if self.mode == ReadMode::Events && self.set_read_mode(ReadMode::Events).is_err() {
+            let _ = self.set_read_mode(ReadMode::Polling);
+        }
+        failures
+    }
+    /// switch how reads wait for packets; events carry over to the sockets bound later
+    pub fn set_read_mode(&mut self, mode: ReadMode) -> Result<(), CaptureError> {
+        self.mode = mode;
+        match mode {
+            ReadMode::Events => {
+                if self.events.is_none() {
+                    self.events = Some((WsaEvent::new()?, Arc::new(WsaEvent::new()?)));
+                }
+                let (read, stop) = self.events.as_ref().unwrap();
+                // a waker may have been used on an earlier capture
+                stop.reset();
+                for (socket, _, _) in self.sockets.iter() {
+                    select_read_event(socket, Some(read))?;
+                }
+            }
+            ReadMode::Polling if !self.sockets.is_empty() => {
+                // sockets stay non-blocking until they no longer signal events
+                if self.events.is_some() {
+                    for (socket, _, _) in self.sockets.iter() {
+                        select_read_event(socket, None)?;
+                    }
+                }
+                // blocking sockets would be waited on in turn
+                let single = self.sockets.len() == 1;
+                for (socket, _, _) in self.sockets.iter() {
+                    socket.set_nonblocking(!single)?;
+                    socket.set_read_timeout(single.then(|| POLL_READ_TIMEOUT))?;
+                }
+            }
+            ReadMode::Polling => {}
+        }
+        Ok(())
+    }
+    /// wait until a socket may have something to read: in `ReadMode::Events` until one
+    /// signals, a waker cuts the wait short or `timeout` passes, otherwise for a moment
+    pub fn wait(&self, timeout: Option<Duration>) -> Result<(), CaptureError> {
+        match (&self.events, self.mode) {
+            (Some((read, stop)), ReadMode::Events) => {
+                let events = [read.0, stop.0];
+                let result = unsafe {
+                    sock::WSAWaitForMultipleEvents(
+                        events.len() as _,
+                        events.as_ptr(),
+                        FALSE,
+                        timeout.map_or(sock::WSA_INFINITE, |timeout| timeout.as_millis() as _),
+                        FALSE,
+                    )
+                };
+                if result == sock::WSA_WAIT_FAILED {
+                    return Err(io::Error::last_os_error().into());
+                }
+                // before reading, so that a packet arriving meanwhile signals it again
+                read.reset();
+            }
+            _ => thread::sleep(POLL_BACKOFF),
         }
-        self.socket = Some(socket);
         Ok(())
     }
+    /// what cuts `wait` short from another thread, until the read mode is set anew
+    pub fn waker(&self) -> Option<Wake> {
+        match (&self.events, self.mode) {
+            (Some((_, stop)), ReadMode::Events) => {
+                let stop = stop.clone();
+                Some(Box::new(move || stop.set()))
+            }
+            _ => None,
+        }
+    }
+    /// the counters of the reads since the capturer was created or they were reset, along
+    /// with how many of the sockets bound now fell back to every protocol
+    pub fn metrics(&self) -> CaptureMetrics {
+        CaptureMetrics {
+            protocol_fallbacks: self.protocol_fallbacks,
+            ..self.metrics
+        }
+    }
+    /// start counting anew, for a new capture
+    pub fn reset_metrics(&mut self) {
+        self.metrics = CaptureMetrics::default();
+    }
+    /// choose which packets the sockets bound from now on receive, `RecvMode::On` unless set
+    pub fn set_recv_mode(&mut self, mode: RecvMode) {
+        self.recv_mode = mode;
+    }
+    /// capture `protocol` alone, or every protocol for `None`, the default; the sockets bound
+    /// from now on are created for it where the system allows, and the packets of other
+    /// protocols read all the same are left out
+    pub fn set_protocol(&mut self, protocol: Option<CaptureProtocol>) {
+        self.protocol = protocol;
+    }
+    /// stop capturing, turning receive-all off on each socket before it is closed, which is
+    /// what binding to other adapters does first too
+    pub fn disconnect(&mut self) {
+        for (socket, _, _) in self.sockets.drain(..) {
+            // closed all the same when the adapter is already gone
+            let _ = socket.set_recv_mode(RecvMode::Off);
+        }
+        self.next = 0;
+        self.protocol_fallbacks = 0;
+    }
     pub fn connected(&self) -> bool {
-        self.socket.is_some()
+        !self.sockets.is_empty()
     }
-    pub fn set_nonblocking(&self, nonblocking: bool) -> Result<()> {
-        if let Some(socket) = self.socket.as_ref() {
-            socket.set_nonblocking(nonblocking)?;
-            Ok(())
-        } else {
-            Err(anyhow!("no socket connection, capture an ip address first"))
+    /// the size reads are made with, the largest receive buffer of the sockets unless a larger
+    /// datagram made it grow
+    pub fn recv_buffer_size(&self) -> usize {
+        self.buffer.len()
+    }
+    /// read a packet from whichever socket has one, along with the local address and name of
+    /// its adapter; an empty packet means none of them had anything to read
+    ///
+    /// a socket failing with a fatal error is dropped and reported as `AdapterLost`, the
+    /// others keep capturing
+    pub fn read_from(&mut self) -> Result<SocketPacket<'_>> {
+        if self.sockets.is_empty() {
+            return Err(anyhow!("no socket connection, capture an ip address first"));
+        }
+        let len = self.sockets.len();
+        for offset in 0..len {
+            let idx = (self.next + offset) % len;
+            let (bytes, truncated) = self.read_socket(idx)?;
+            if bytes != 0 {
+                return Ok(self.packet(idx, bytes, truncated));
+            }
         }
+        Ok((&mut self.buffer[..0], None, false))
     }
-    pub fn read_mut(&mut self) -> Result<&mut [u8]> {
-        if let Some(socket) = self.socket.as_mut() {
-            let bytes = match socket.read(self.buffer.as_mut_slice()) {
-                Ok(bytes) => bytes,
-                Err(err) => match err.raw_os_error() {
-                    Some(10035) => 0,
-                    _ => return Err(anyhow!(err)),
-                },
-            };
-            Ok(&mut self.buffer[..bytes])
-        } else {
-            Err(anyhow!("no socket connection, capture an ip address first"))
+    /// read a packet from whichever socket has one first, waiting for `timeout` at most;
+    /// `None` when none did, while a zero timeout only checks
+    ///
+    /// only sockets with something to read are read, so that a blocking one does not hold
+    /// the read past the timeout, and a packet arriving just as it passes stays queued for
+    /// the next read
+    pub fn read_timeout(&mut self, timeout: Duration) -> Result<Option<SocketPacket<'_>>> {
+        if self.sockets.is_empty() {
+            return Err(anyhow!("no socket connection, capture an ip address first"));
+        }
+        let ready = match self.ready(Duration::ZERO)? {
+            None if timeout.is_zero() => None,
+            // the events are what a waker cuts short, a poll is not
+            None if self.mode == ReadMode::Events && self.events.is_some() => {
+                self.wait(Some(timeout))?;
+                self.ready(Duration::ZERO)?
+            }
+            None => self.ready(timeout)?,
+            ready => ready,
+        };
+        let idx = match ready {
+            Some(idx) => idx,
+            None => return Ok(None),
+        };
+        match self.read_socket(idx)? {
+            (0, _) => Ok(None),
+            (bytes, truncated) => Ok(Some(self.packet(idx, bytes, truncated))),
+        }
+    }
+    /// the first socket from the one read next on that has something to read, or an error
+    /// to report, waiting for `timeout` at most
+    fn ready(&self, timeout: Duration) -> Result<Option<usize>, CaptureError> {
+        let mut fds = self
+            .sockets
+            .iter()
+            .map(|(socket, _, _)| sock::WSAPOLLFD {
+                fd: socket.as_raw_socket() as usize,
+                events: sock::POLLRDNORM,
+                revents: 0,
+            })
+            .collect::<Vec<_>>();
+        // rounded up, so that a timeout below a millisecond still waits
+        let millis = ((timeout.as_micros() + 999) / 1000).min(c_int::MAX as u128) as c_int;
+        syscall!(
+            WSAPoll(fds.as_mut_ptr(), fds.len() as _, millis),
+            PartialEq::eq,
+            sock::SOCKET_ERROR
+        )?;
+        let len = fds.len();
+        Ok((0..len)
+            .map(|offset| (self.next + offset) % len)
+            .find(|&idx| fds[idx].revents != 0))
+    }
+    /// read from the socket at `idx` into the buffer, returning the bytes read and whether the
+    /// packet was cut short; 0 bytes when it had nothing to read or only a packet of a protocol
+    /// left out
+    ///
+    /// a socket failing with a fatal error is dropped and reported as `AdapterLost`
+    fn read_socket(&mut self, idx: usize) -> Result<(usize, bool)> {
+        let mut socket = &self.sockets[idx].0;
+        let read = read_counted(
+            &mut self.buffer,
+            &mut self.metrics,
+            self.protocol,
+            |buffer| socket.read(buffer),
+        );
+        match read {
+            Ok(read) => Ok(read),
+            Err(err) if err.is_fatal() => {
+                let (_, ip, name) = self.sockets.remove(idx);
+                self.next = 0;
+                Err(AdapterLost {
+                    ip,
+                    name,
+                    source: err,
+                }
+                .into())
+            }
+            Err(err) => Err(err.into()),
         }
     }
+    /// the packet just read from the socket at `idx`, which the socket after it gets to
+    /// follow up on
+    fn packet(&mut self, idx: usize, bytes: usize, truncated: bool) -> SocketPacket<'_> {
+        self.next = (idx + 1) % self.sockets.len();
+        let (_, ip, name) = &self.sockets[idx];
+        (
+            &mut self.buffer[..bytes],
+            Some((*ip, name.as_str())),
+            truncated,
+        )
+    }
+    pub fn read_mut(&mut self) -> Result<&mut [u8]> {
+        self.read_from().map(|(packet, _, _)| packet)
+    }
     pub fn read(&mut self) -> Result<&[u8]> {
         self.read_mut().map(|s| &s[..])
     }
 }
+
+/// read a datagram into `buffer` with `read`, returning its length and whether it was cut
+/// short; a datagram that did not fit grows the buffer to `MAX_DATAGRAM` for the ones after it,
+/// there is no reading the rest of it again
+fn read_datagram(
+    buffer: &mut Vec<u8>,
+    read: impl FnOnce(&mut [u8]) -> io::Result<usize>,
+) -> Result<(usize, bool), CaptureError> {
+    match read(buffer.as_mut_slice()).map_err(CaptureError::from) {
+        Ok(bytes) => Ok((bytes, false)),
+        Err(CaptureError::MessageTooLong) => {
+            let bytes = buffer.len();
+            if bytes < MAX_DATAGRAM {
+                buffer.resize(MAX_DATAGRAM, 0u8);
+            }
+            Ok((bytes, true))
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// read a datagram with `read` like `read_datagram` and count it in `metrics`, returning the
+/// bytes read and whether the packet was cut short; 0 bytes when there was nothing to read or
+/// only a packet of another protocol than `protocol`
+fn read_counted(
+    buffer: &mut Vec<u8>,
+    metrics: &mut CaptureMetrics,
+    protocol: Option<CaptureProtocol>,
+    read: impl FnOnce(&mut [u8]) -> io::Result<usize>,
+) -> Result<(usize, bool), CaptureError> {
+    match read_datagram(buffer, read) {
+        Ok((bytes, truncated)) => {
+            metrics.read(bytes);
+            if truncated {
+                metrics.truncated += 1;
+            }
+            // left out before anything is parsed, as if there had been nothing to read
+            match protocol {
+                Some(protocol) if bytes != 0 && !protocol.matches(&buffer[..bytes]) => {
+                    metrics.filtered += 1;
+                    Ok((0, false))
+                }
+                _ => Ok((bytes, truncated)),
+            }
+        }
+        Err(CaptureError::WouldBlock) => {
+            metrics.read(0);
+            Ok((0, false))
+        }
+        Err(err) => Err(err),
+    }
+}
+
+impl Drop for Capturer {
+    fn drop(&mut self) {
+        self.disconnect();
+    }
+}
+
+impl PacketSource for Capturer {
+    fn read_timeout(&mut self, timeout: Duration) -> Result<Option<ReadPacket<'_>>> {
+        let read = Capturer::read_timeout(self, timeout)?;
+        Ok(read.map(|(packet, local, truncated)| (Local::now(), packet, local, truncated)))
+    }
+
+    fn connected(&self) -> bool {
+        Capturer::connected(self)
+    }
+
+    fn buffer_size(&self) -> usize {
+        self.recv_buffer_size()
+    }
+
+    fn waker(&self) -> Option<Wake> {
+        Capturer::waker(self)
+    }
+
+    fn metrics(&self) -> CaptureMetrics {
+        Capturer::metrics(self)
+    }
+}
+
+#[cfg(test)]
+mod socket_test {
+    use super::*;
+
+    #[test]
+    fn test_capture_error() {
+        let error = |code| CaptureError::from(io::Error::from_raw_os_error(code));
+        assert!(matches!(error(10013), CaptureError::AccessDenied));
+        assert!(matches!(error(10035), CaptureError::WouldBlock));
+        assert!(matches!(error(10060), CaptureError::WouldBlock));
+        assert!(matches!(error(10048), CaptureError::AddrInUse));
+        assert!(matches!(error(10050), CaptureError::NetworkDown));
+        assert!(matches!(error(10052), CaptureError::NetworkReset));
+        assert!(matches!(error(10049), CaptureError::AdapterGone));
+        assert!(matches!(error(10040), CaptureError::MessageTooLong));
+        // WSAECONNRESET is reported as it is
+        assert!(matches!(
+            error(10054),
+            CaptureError::Other(err) if err.raw_os_error() == Some(10054)
+        ));
+
+        // refused by SIO_RCVALL for a single protocol, WSAEINVAL is otherwise reported as it is
+        assert!(matches!(error(10022), CaptureError::Other(_)));
+
+        assert!(error(10049).is_fatal());
+        assert!(error(10050).is_fatal());
+        assert!(error(10052).is_fatal());
+        assert!(!error(10013).is_fatal());
+        assert!(!error(10035).is_fatal());
+    }
+
+    #[test]
+    fn test_bind_with_fallback() {
+        let tried = std::cell::RefCell::new(Vec::new());
+        // refuses a socket for a single protocol the way SIO_RCVALL does
+        let refusing = |protocol: Option<CaptureProtocol>| {
+            tried.borrow_mut().push(protocol);
+            match protocol {
+                Some(_) => Err(CaptureError::ProtocolRefused),
+                None => Ok("ip"),
+            }
+        };
+        let bound = bind_with_fallback(Some(CaptureProtocol::Udp), refusing).unwrap();
+        assert_eq!(bound, ("ip", true));
+        assert_eq!(*tried.borrow(), [Some(CaptureProtocol::Udp), None]);
+
+        tried.borrow_mut().clear();
+        let bound = bind_with_fallback(Some(CaptureProtocol::Tcp), |protocol| {
+            tried.borrow_mut().push(protocol);
+            Ok::<_, CaptureError>(protocol)
+        });
+        assert_eq!(bound.unwrap(), (Some(CaptureProtocol::Tcp), false));
+        assert_eq!(tried.borrow().len(), 1);
+
+        // any other failure is not worked around
+        tried.borrow_mut().clear();
+        let bound = bind_with_fallback(Some(CaptureProtocol::Icmp), |protocol| {
+            tried.borrow_mut().push(protocol);
+            Err::<(), _>(CaptureError::AccessDenied)
+        });
+        assert!(matches!(bound, Err(CaptureError::AccessDenied)));
+        assert_eq!(tried.borrow().len(), 1);
+    }
+
+    #[test]
+    fn test_read_datagram() {
+        // fills what fits and fails with WSAEMSGSIZE when the datagram is larger, like winsock
+        let recv = |datagram: &[u8]| {
+            let datagram = datagram.to_vec();
+            move |buffer: &mut [u8]| {
+                let bytes = datagram.len().min(buffer.len());
+                buffer[..bytes].copy_from_slice(&datagram[..bytes]);
+                if datagram.len() > buffer.len() {
+                    Err(io::Error::from_raw_os_error(10040))
+                } else {
+                    Ok(bytes)
+                }
+            }
+        };
+        let large = (0..4000).map(|n| n as u8).collect::<Vec<_>>();
+        let mut buffer = vec![0; 1500];
+        assert_eq!(
+            read_datagram(&mut buffer, recv(&large[..100])).unwrap(),
+            (100, false)
+        );
+        assert_eq!(buffer.len(), 1500);
+
+        // the start of the datagram is kept, and the buffer grows for the ones after it
+        assert_eq!(
+            read_datagram(&mut buffer, recv(&large)).unwrap(),
+            (1500, true)
+        );
+        assert_eq!(&buffer[..1500], &large[..1500]);
+        assert_eq!(buffer.len(), MAX_DATAGRAM);
+        assert_eq!(
+            read_datagram(&mut buffer, recv(&large)).unwrap(),
+            (4000, false)
+        );
+
+        let would_block = |_: &mut [u8]| Err(io::Error::from_raw_os_error(10035));
+        assert!(matches!(
+            read_datagram(&mut buffer, would_block),
+            Err(CaptureError::WouldBlock)
+        ));
+    }
+
+    #[test]
+    fn test_read_counted() {
+        let recv = |datagram: &[u8]| {
+            let datagram = datagram.to_vec();
+            move |buffer: &mut [u8]| {
+                let bytes = datagram.len().min(buffer.len());
+                buffer[..bytes].copy_from_slice(&datagram[..bytes]);
+                if datagram.len() > buffer.len() {
+                    Err(io::Error::from_raw_os_error(10040))
+                } else {
+                    Ok(bytes)
+                }
+            }
+        };
+        let mut udp = vec![0; 100];
+        udp[..12].copy_from_slice(&[0x45, 0, 0, 100, 0, 0, 0, 0, 64, 17, 0, 0]);
+        let mut tcp = udp.clone();
+        tcp[9] = 6;
+        let mut large = vec![0; 3000];
+        large[..100].copy_from_slice(&udp);
+        let would_block = |_: &mut [u8]| Err(io::Error::from_raw_os_error(10035));
+
+        let mut buffer = vec![0; 1500];
+        let mut metrics = CaptureMetrics::default();
+        let udp_only = Some(CaptureProtocol::Udp);
+        let mut read = |read: &dyn Fn(&mut [u8]) -> io::Result<usize>| {
+            read_counted(&mut buffer, &mut metrics, udp_only, read)
+        };
+        assert_eq!(read(&recv(&udp)).unwrap(), (100, false));
+        assert_eq!(read(&recv(&large)).unwrap(), (1500, true));
+        // read and counted, but left out
+        assert_eq!(read(&recv(&tcp)).unwrap(), (0, false));
+        assert_eq!(read(&would_block).unwrap(), (0, false));
+        let reset = |_: &mut [u8]| Err(io::Error::from_raw_os_error(10052));
+        assert!(matches!(read(&reset), Err(CaptureError::NetworkReset)));
+
+        assert_eq!(metrics.reads, 4);
+        assert_eq!(metrics.would_block, 1);
+        assert_eq!(metrics.bytes, 1700);
+        assert_eq!(metrics.max_datagram, 1500);
+        assert_eq!(metrics.truncated, 1);
+        assert_eq!(metrics.filtered, 1);
+    }
+
+    #[test]
+    fn test_recv_mode() {
+        assert_eq!("on".parse::<RecvMode>().unwrap(), RecvMode::On);
+        assert_eq!("IP-Level".parse::<RecvMode>().unwrap(), RecvMode::IpLevel);
+        assert_eq!("off".parse::<RecvMode>().unwrap(), RecvMode::Off);
+        assert!("promiscuous".parse::<RecvMode>().is_err());
+    }
+
+    #[test]
+    fn test_protocol() {
+        assert_eq!(
+            "UDP".parse::<CaptureProtocol>().unwrap(),
+            CaptureProtocol::Udp
+        );
+        assert_eq!(
+            "icmp".parse::<CaptureProtocol>().unwrap(),
+            CaptureProtocol::Icmp
+        );
+        assert!("igmp".parse::<CaptureProtocol>().is_err());
+        let mut packet = [0x45, 0, 0, 20, 0, 0, 0, 0, 64, 17, 0, 0];
+        assert!(CaptureProtocol::Udp.matches(&packet));
+        assert!(!CaptureProtocol::Tcp.matches(&packet));
+        packet[9] = 6;
+        assert!(CaptureProtocol::Tcp.matches(&packet));
+        // too short to tell is not kept
+        assert!(!CaptureProtocol::Tcp.matches(&packet[..9]));
+    }
+
+    #[test]
+    #[ignore]
+    fn test_recv_mode_capture() {
+        // needs administrator, run with --ignored
+        let ip = Ipv4Addr::LOCALHOST;
+        for (mode, captured) in [
+            (RecvMode::On, true),
+            (RecvMode::IpLevel, true),
+            (RecvMode::Off, false),
+        ] {
+            let mut capturer = Capturer::new();
+            capturer.set_recv_mode(mode);
+            capturer.capture(ip, 0, true).unwrap();
+            // known traffic of this host: a datagram from one local socket to another
+            let receiver = std::net::UdpSocket::bind((ip, 0)).unwrap();
+            let sender = std::net::UdpSocket::bind((ip, 0)).unwrap();
+            let port = receiver.local_addr().unwrap().port();
+            sender.send_to(b"recv mode", (ip, port)).unwrap();
+            let mut seen = false;
+            let started = std::time::Instant::now();
+            while !seen && started.elapsed() < Duration::from_secs(1) {
+                let read = PacketSource::read_timeout(&mut capturer, Duration::from_millis(100));
+                if let Some((_, packet, _, _)) = read.unwrap() {
+                    let header_len = (packet[0] & 0xf) as usize * 4;
+                    seen = packet[9] == 17
+                        && packet[header_len + 2..header_len + 4] == port.to_be_bytes()
+                        && packet.ends_with(b"recv mode");
+                }
+            }
+            assert_eq!(seen, captured, "{:?}", mode);
+        }
+    }
+
+    #[test]
+    #[ignore]
+    fn test_protocol_capture() {
+        // needs administrator, run with --ignored
+        let ip = Ipv4Addr::LOCALHOST;
+        let mut capturer = Capturer::new();
+        capturer.set_protocol(Some(CaptureProtocol::Udp));
+        capturer.capture(ip, 0, true).unwrap();
+        // tcp along with the datagram, which has to be left out whether or not the socket
+        // was created for udp alone
+        let listener = std::net::TcpListener::bind((ip, 0)).unwrap();
+        let mut stream = std::net::TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        std::io::Write::write_all(&mut stream, b"not udp").unwrap();
+        let receiver = std::net::UdpSocket::bind((ip, 0)).unwrap();
+        let sender = std::net::UdpSocket::bind((ip, 0)).unwrap();
+        let port = receiver.local_addr().unwrap().port();
+        sender.send_to(b"protocol", (ip, port)).unwrap();
+        let mut seen = false;
+        let started = std::time::Instant::now();
+        while started.elapsed() < Duration::from_secs(1) {
+            let read = PacketSource::read_timeout(&mut capturer, Duration::from_millis(100));
+            if let Some((_, packet, _, _)) = read.unwrap() {
+                assert_eq!(packet[9], 17);
+                seen |= packet.ends_with(b"protocol");
+            }
+        }
+        assert!(seen);
+        // which of the two the system went with, shown with --nocapture
+        let metrics = capturer.metrics();
+        println!(
+            "udp socket refused: {}, other protocols left out: {}",
+            metrics.protocol_fallbacks == 1,
+            metrics.filtered
+        );
+    }
+}
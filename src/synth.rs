@@ -0,0 +1,490 @@
+use anyhow::Result;
+
+use chrono::{DateTime, Local};
+use packet::ip::Protocol;
+
+use crate::{
+    capture::{PacketSource, ReadPacket},
+    socket::MAX_DATAGRAM,
+};
+
+use std::{
+    net::Ipv4Addr,
+    thread,
+    time::{Duration, Instant},
+};
+
+/// the adapter synthetic packets are captured on
+pub const SYNTHETIC_ADAPTER: &str = "synthetic";
+/// the address of this machine in `demo_flows`
+pub const DEMO_LOCAL_IP: Ipv4Addr = Ipv4Addr::new(192, 168, 1, 10);
+
+const IPV4_HEADER_LEN: usize = 20;
+const TCP_HEADER_LEN: usize = 20;
+const UDP_HEADER_LEN: usize = 8;
+const ICMP_HEADER_LEN: usize = 8;
+
+/// packets of one protocol from one endpoint to another, all of the same length and evenly
+/// spaced; the ports of icmp are left out, it sends echo requests
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SynthFlow {
+    pub protocol: Protocol,
+    pub src: (Ipv4Addr, u16),
+    pub dest: (Ipv4Addr, u16),
+    /// bytes after the transport header
+    pub payload_len: u16,
+    pub count: u32,
+    /// when the first packet is sent, from the start of the traffic
+    pub start: Duration,
+    /// between two packets of the flow
+    pub interval: Duration,
+}
+
+impl SynthFlow {
+    /// a single packet at the start of the traffic
+    pub fn new(
+        protocol: Protocol,
+        src: (Ipv4Addr, u16),
+        dest: (Ipv4Addr, u16),
+        payload_len: u16,
+    ) -> Self {
+        Self {
+            protocol,
+            src,
+            dest,
+            payload_len,
+            count: 1,
+            start: Duration::ZERO,
+            interval: Duration::ZERO,
+        }
+    }
+
+    pub fn repeat(mut self, count: u32, interval: Duration) -> Self {
+        self.count = count;
+        self.interval = interval;
+        self
+    }
+
+    pub fn starting(mut self, start: Duration) -> Self {
+        self.start = start;
+        self
+    }
+
+    /// the packet number `seq` of the flow, an ipv4 packet with valid checksums whose payload
+    /// counts up from its number
+    pub fn packet(&self, seq: u32) -> Vec<u8> {
+        let payload = (0..self.payload_len)
+            .map(|idx| b'a' + ((seq + idx as u32) % 26) as u8)
+            .collect::<Vec<_>>();
+        let (src_ip, src_port) = self.src;
+        let (dest_ip, dest_port) = self.dest;
+        let mut segment = match self.protocol {
+            Protocol::Tcp => {
+                let mut header = Vec::with_capacity(TCP_HEADER_LEN);
+                header.extend_from_slice(&src_port.to_be_bytes());
+                header.extend_from_slice(&dest_port.to_be_bytes());
+                header.extend_from_slice(&(seq * self.payload_len as u32).to_be_bytes());
+                header.extend_from_slice(&0u32.to_be_bytes());
+                // a header of 5 words, with psh and ack set
+                header.extend_from_slice(&[0x50, 0x18, 0xff, 0xff, 0, 0, 0, 0]);
+                header
+            }
+            Protocol::Udp => {
+                let len = (UDP_HEADER_LEN + payload.len()) as u16;
+                let mut header = Vec::with_capacity(UDP_HEADER_LEN);
+                header.extend_from_slice(&src_port.to_be_bytes());
+                header.extend_from_slice(&dest_port.to_be_bytes());
+                header.extend_from_slice(&len.to_be_bytes());
+                header.extend_from_slice(&[0, 0]);
+                header
+            }
+            Protocol::Icmp => {
+                let mut header = vec![8, 0, 0, 0];
+                header.extend_from_slice(&src_port.to_be_bytes());
+                header.extend_from_slice(&(seq as u16).to_be_bytes());
+                debug_assert_eq!(header.len(), ICMP_HEADER_LEN);
+                header
+            }
+            _ => Vec::new(),
+        };
+        segment.extend_from_slice(&payload);
+        let proto = u8::from(self.protocol);
+        // tcp and udp sum a pseudo header of the addresses along with the segment
+        let checksum_at = match self.protocol {
+            Protocol::Tcp => Some(16),
+            Protocol::Udp => Some(6),
+            Protocol::Icmp => Some(2),
+            _ => None,
+        };
+        if let Some(at) = checksum_at {
+            let mut summed = Vec::with_capacity(12 + segment.len());
+            if self.protocol != Protocol::Icmp {
+                summed.extend_from_slice(&src_ip.octets());
+                summed.extend_from_slice(&dest_ip.octets());
+                summed.extend_from_slice(&[0, proto]);
+                summed.extend_from_slice(&(segment.len() as u16).to_be_bytes());
+            }
+            summed.extend_from_slice(&segment);
+            segment[at..at + 2].copy_from_slice(&checksum(&summed).to_be_bytes());
+        }
+
+        let total_len = (IPV4_HEADER_LEN + segment.len()) as u16;
+        let mut packet = Vec::with_capacity(total_len as usize);
+        packet.extend_from_slice(&[0x45, 0]);
+        packet.extend_from_slice(&total_len.to_be_bytes());
+        packet.extend_from_slice(&(seq as u16).to_be_bytes());
+        // don't fragment, a ttl of 64
+        packet.extend_from_slice(&[0x40, 0, 64, proto, 0, 0]);
+        packet.extend_from_slice(&src_ip.octets());
+        packet.extend_from_slice(&dest_ip.octets());
+        let header_checksum = checksum(&packet);
+        packet[10..12].copy_from_slice(&header_checksum.to_be_bytes());
+        packet.extend_from_slice(&segment);
+        packet
+    }
+}
+
+/// the internet checksum of `bytes`, an odd last byte padded with zero
+fn checksum(bytes: &[u8]) -> u16 {
+    let mut sum = bytes
+        .chunks(2)
+        .map(|word| u16::from_be_bytes([word[0], *word.get(1).unwrap_or(&0)]) as u32)
+        .sum::<u32>();
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+/// some ten seconds of what a machine at `DEMO_LOCAL_IP` browsing the web might see: https,
+/// http, dns and ntp both ways, pings, an ssh session and ssdp announcements
+pub fn demo_flows() -> Vec<SynthFlow> {
+    let web = (Ipv4Addr::new(93, 184, 216, 34), 443);
+    let router = Ipv4Addr::new(192, 168, 1, 1);
+    let ms = Duration::from_millis;
+    vec![
+        SynthFlow::new(Protocol::Tcp, (DEMO_LOCAL_IP, 50123), web, 120).repeat(200, ms(50)),
+        SynthFlow::new(Protocol::Tcp, web, (DEMO_LOCAL_IP, 50123), 1400)
+            .repeat(400, ms(25))
+            .starting(ms(10)),
+        SynthFlow::new(
+            Protocol::Tcp,
+            (DEMO_LOCAL_IP, 50200),
+            (Ipv4Addr::new(151, 101, 1, 69), 80),
+            400,
+        )
+        .repeat(20, ms(400))
+        .starting(ms(2000)),
+        SynthFlow::new(
+            Protocol::Tcp,
+            (Ipv4Addr::new(151, 101, 1, 69), 80),
+            (DEMO_LOCAL_IP, 50200),
+            1200,
+        )
+        .repeat(60, ms(130))
+        .starting(ms(2040)),
+        SynthFlow::new(Protocol::Udp, (DEMO_LOCAL_IP, 53001), (router, 53), 32).repeat(20, ms(500)),
+        SynthFlow::new(Protocol::Udp, (router, 53), (DEMO_LOCAL_IP, 53001), 96)
+            .repeat(20, ms(500))
+            .starting(ms(15)),
+        SynthFlow::new(
+            Protocol::Udp,
+            (DEMO_LOCAL_IP, 123),
+            (Ipv4Addr::new(162, 159, 200, 1), 123),
+            48,
+        )
+        .repeat(2, ms(5000))
+        .starting(ms(300)),
+        SynthFlow::new(
+            Protocol::Icmp,
+            (DEMO_LOCAL_IP, 1),
+            (Ipv4Addr::new(8, 8, 8, 8), 0),
+            32,
+        )
+        .repeat(10, ms(1000)),
+        SynthFlow::new(
+            Protocol::Tcp,
+            (Ipv4Addr::new(192, 168, 1, 20), 51515),
+            (DEMO_LOCAL_IP, 22),
+            64,
+        )
+        .repeat(50, ms(200))
+        .starting(ms(500)),
+        SynthFlow::new(
+            Protocol::Udp,
+            (Ipv4Addr::new(192, 168, 1, 30), 1900),
+            (Ipv4Addr::new(239, 255, 255, 250), 1900),
+            300,
+        )
+        .repeat(5, ms(2000))
+        .starting(ms(700)),
+    ]
+}
+
+/// the packets of some flows in the order they are sent, all at once or as they fall due
+pub struct SyntheticSource {
+    flows: Vec<SynthFlow>,
+    /// when each packet is sent from the start of the traffic, with its flow and number in it
+    schedule: Vec<(Duration, usize, u32)>,
+    next: usize,
+    start: DateTime<Local>,
+    /// when the first packet was read, of a source handing them out in real time
+    paced: Option<Option<Instant>>,
+    local_ip: Ipv4Addr,
+    packet: Vec<u8>,
+}
+
+impl SyntheticSource {
+    /// the packets of `flows` captured on `local_ip`, the traffic starting at `start`
+    pub fn new(flows: Vec<SynthFlow>, local_ip: Ipv4Addr, start: DateTime<Local>) -> Self {
+        let mut schedule = flows
+            .iter()
+            .enumerate()
+            .flat_map(|(idx, flow)| {
+                (0..flow.count).map(move |seq| (flow.start + flow.interval * seq, idx, seq))
+            })
+            .collect::<Vec<_>>();
+        // packets sent at the same time go in the order of their flows
+        schedule.sort();
+        Self {
+            flows,
+            schedule,
+            next: 0,
+            start,
+            paced: None,
+            local_ip,
+            packet: Vec::new(),
+        }
+    }
+
+    /// hand each packet out once its time has come, counting from the first read, rather
+    /// than all of them right away
+    pub fn paced(mut self) -> Self {
+        self.paced = Some(None);
+        self
+    }
+}
+
+impl PacketSource for SyntheticSource {
+    fn read_timeout(&mut self, timeout: Duration) -> Result<Option<ReadPacket<'_>>> {
+        let (offset, flow, seq) = match self.schedule.get(self.next) {
+            Some(&next) => next,
+            None => return Ok(None),
+        };
+        if let Some(paced) = &mut self.paced {
+            let started = match paced {
+                Some(started) => *started,
+                None => {
+                    self.start = Local::now();
+                    *paced.insert(Instant::now())
+                }
+            };
+            let due = offset.saturating_sub(started.elapsed());
+            if due > timeout {
+                thread::sleep(timeout);
+                return Ok(None);
+            }
+            thread::sleep(due);
+        }
+        self.next += 1;
+        self.packet = self.flows[flow].packet(seq);
+        let time = self.start + chrono::Duration::from_std(offset)?;
+        Ok(Some((
+            time,
+            &mut self.packet,
+            Some((self.local_ip, SYNTHETIC_ADAPTER)),
+            false,
+        )))
+    }
+
+    fn connected(&self) -> bool {
+        self.next < self.schedule.len()
+    }
+
+    fn buffer_size(&self) -> usize {
+        MAX_DATAGRAM
+    }
+}
+
+#[cfg(test)]
+mod synth_test {
+    use super::*;
+    use crate::{
+        record::{PlotRecord, Record, StatRecord},
+        report::report_text,
+    };
+    use chrono::TimeZone;
+
+    fn start() -> DateTime<Local> {
+        Local.ymd(2024, 6, 1).and_hms(12, 0, 0)
+    }
+
+    /// a few packets of each protocol, both ways
+    fn fixture() -> Vec<SynthFlow> {
+        let local = (Ipv4Addr::new(10, 0, 0, 2), 50000);
+        let web = (Ipv4Addr::new(10, 0, 0, 80), 443);
+        let dns = (Ipv4Addr::new(10, 0, 0, 1), 53);
+        let ms = Duration::from_millis;
+        vec![
+            SynthFlow::new(Protocol::Tcp, local, web, 100).repeat(3, ms(100)),
+            SynthFlow::new(Protocol::Tcp, web, local, 500)
+                .repeat(2, ms(300))
+                .starting(ms(50)),
+            SynthFlow::new(Protocol::Udp, local, dns, 30).starting(ms(250)),
+            SynthFlow::new(Protocol::Udp, dns, local, 60).starting(ms(260)),
+            SynthFlow::new(Protocol::Icmp, local, (Ipv4Addr::new(10, 0, 0, 9), 0), 8)
+                .starting(ms(900)),
+        ]
+    }
+
+    fn records(mut source: SyntheticSource) -> Vec<Record> {
+        let mut records = Vec::new();
+        while let Some((time, packet, local, truncated)) =
+            source.read_timeout(Duration::ZERO).unwrap()
+        {
+            records.push(Record::from_packet(time, packet, local, truncated));
+        }
+        assert!(!source.connected());
+        records
+    }
+
+    #[test]
+    fn test_packet() {
+        let flow = SynthFlow::new(
+            Protocol::Udp,
+            (Ipv4Addr::new(10, 0, 0, 1), 1234),
+            (Ipv4Addr::new(10, 0, 0, 2), 53),
+            4,
+        );
+        #[rustfmt::skip]
+        let golden = [
+            0x45, 0, 0, 32, 0, 1, 0x40, 0, 64, 17, 0x26, 0xca, 10, 0, 0, 1, 10, 0, 0, 2,
+            0x04, 0xd2, 0, 53, 0, 12, 0x20, 0x04, b'b', b'c', b'd', b'e',
+        ];
+        assert_eq!(flow.packet(1), golden);
+        // a header summed along with its checksum comes to zero
+        assert_eq!(checksum(&golden[..20]), 0);
+
+        let tcp = SynthFlow {
+            protocol: Protocol::Tcp,
+            ..flow.clone()
+        }
+        .packet(0);
+        assert_eq!(tcp.len(), 20 + 20 + 4);
+        let mut pseudo = vec![10, 0, 0, 1, 10, 0, 0, 2, 0, 6, 0, 24];
+        pseudo.extend_from_slice(&tcp[20..]);
+        assert_eq!(checksum(&pseudo), 0);
+
+        let icmp = SynthFlow {
+            protocol: Protocol::Icmp,
+            ..flow
+        }
+        .packet(0);
+        assert_eq!(&icmp[20..22], &[8, 0]);
+        assert_eq!(checksum(&icmp[20..]), 0);
+    }
+
+    #[test]
+    fn test_schedule() {
+        let records = records(SyntheticSource::new(
+            fixture(),
+            Ipv4Addr::new(10, 0, 0, 2),
+            start(),
+        ));
+        let offsets = records
+            .iter()
+            .map(|r| (r.time - start()).num_milliseconds())
+            .collect::<Vec<_>>();
+        assert_eq!(offsets, vec![0, 50, 100, 200, 250, 260, 350, 900]);
+        assert!(records.iter().all(|r| !r.truncated));
+        assert_eq!(records[0].interface.as_deref(), Some(SYNTHETIC_ADAPTER));
+    }
+
+    #[test]
+    fn test_paced() {
+        let ms = Duration::from_millis;
+        let flow = SynthFlow::new(
+            Protocol::Udp,
+            (Ipv4Addr::new(10, 0, 0, 1), 1234),
+            (Ipv4Addr::new(10, 0, 0, 2), 53),
+            4,
+        )
+        .repeat(2, ms(40));
+        let mut source =
+            SyntheticSource::new(vec![flow], Ipv4Addr::new(10, 0, 0, 2), start()).paced();
+        assert!(source.read_timeout(Duration::ZERO).unwrap().is_some());
+        // the second one is not due yet
+        assert!(source.read_timeout(Duration::ZERO).unwrap().is_none());
+        assert!(source.read_timeout(ms(200)).unwrap().is_some());
+        assert!(!source.connected());
+    }
+
+    #[test]
+    fn test_pipeline() {
+        let records = records(SyntheticSource::new(
+            fixture(),
+            Ipv4Addr::new(10, 0, 0, 2),
+            start(),
+        ));
+        let mut stat = StatRecord::default();
+        stat.update_multiple(records.iter());
+
+        assert_eq!(
+            (stat.stat_net_table.packet_num, stat.stat_net_table.byte_num),
+            (8, 1682)
+        );
+        assert_eq!(stat.stat_net_inbound.packet_num, 3);
+        assert_eq!(stat.stat_net_outbound.packet_num, 5);
+        let tcp = &stat.stat_trans_table["TCP"];
+        assert_eq!(
+            (tcp.packet_num, tcp.byte_num, tcp.byte_num_in_net),
+            (5, 1400, 1500)
+        );
+        let udp = &stat.stat_trans_table["UDP"];
+        assert_eq!(
+            (udp.packet_num, udp.byte_num, udp.byte_num_in_net),
+            (2, 106, 146)
+        );
+        let icmp = &stat.stat_trans_table["ICMP"];
+        assert_eq!((icmp.packet_num, icmp.byte_num), (1, 16));
+        let https = &stat.stat_app_table["HTTPS"];
+        assert_eq!(
+            (https.packet_num, https.byte_num, https.byte_num_inbound),
+            (5, 1300, 1000)
+        );
+        let dns = &stat.stat_app_table["DNS"];
+        assert_eq!((dns.packet_num, dns.byte_num), (2, 90));
+        assert_eq!(stat.stat_app_table.len(), 2);
+
+        assert_eq!(
+            report_text(&stat, &|n| n.to_string()),
+            "\
+network   packets  bytes
+total           8   1682
+inbound         3   1168
+outbound        5    514
+
+transport  packets  bytes  in ip  inbound  outbound
+ICMP             1     16     36        0        16
+TCP              5   1400   1500     1040       360
+UDP              2    106    146       68        38
+total            8   1522   1682     1108       414
+
+application                 packets  bytes  in ip  in transport  inbound  outbound
+DNS                               2     90    146           106       60        30
+HTTPS                             5   1300   1500          1400     1000       300
+total (98.9% of transport)        7   1390   1646          1506     1060       330
+"
+        );
+
+        let mut plot = PlotRecord::from_records(records.iter(), Some(start()), None, true);
+        plot.commit_rest();
+        let buckets = plot
+            .records
+            .iter()
+            .map(|r| (r.packet_num, r.byte_num))
+            .collect::<Vec<_>>();
+        // 200ms buckets, nothing is sent between 350ms and the ping at 900ms
+        assert_eq!(buckets, vec![(3, 820), (4, 826), (0, 0), (0, 0), (1, 36)]);
+    }
+}
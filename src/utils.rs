@@ -1,56 +1,226 @@
 use anyhow::{anyhow, Error, Result};
 
-use std::{fmt::Display, io, str::FromStr};
+use std::{
+    env,
+    ffi::OsStr,
+    fmt::Display,
+    io, iter, mem,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    os::windows::ffi::OsStrExt,
+    path::Path,
+    process, ptr,
+    str::FromStr,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use crate::{socket::CaptureError, tr};
 
 use ipconfig::{self, Adapter};
 use itertools::Itertools;
 
+use serde::Serialize;
+
 use packet::ip::Protocol;
 
-use winapi::um::{consoleapi::AllocConsole, wincon};
+use winapi::{
+    shared::{
+        ipmib::MIB_IPSTATS,
+        minwindef::{BOOL, DWORD, FALSE, FILETIME, TRUE},
+        winerror::ERROR_SUCCESS,
+    },
+    um::{
+        consoleapi::{AllocConsole, GetConsoleMode, SetConsoleCtrlHandler, SetConsoleMode},
+        handleapi::CloseHandle,
+        iphlpapi::GetIpStatistics,
+        processenv::GetStdHandle,
+        processthreadsapi::{GetCurrentProcess, GetProcessTimes, OpenProcessToken},
+        securitybaseapi::GetTokenInformation,
+        shellapi::ShellExecuteW,
+        winbase::STD_OUTPUT_HANDLE,
+        wincon,
+        winnt::{TokenElevation, TOKEN_ELEVATION, TOKEN_QUERY},
+        winreg::{RegGetValueW, HKEY_LOCAL_MACHINE, RRF_RT_REG_SZ},
+        winuser::SW_SHOWNORMAL,
+    },
+};
+
+/// print the adapters as a table, or as a json array of `InterfaceInfo` when `json`
+pub fn print_interfaces<'a>(
+    nfs: impl Iterator<Item = &'a Adapter>,
+    list_number: bool,
+    json: bool,
+) -> Result<()> {
+    if json {
+        let infos = nfs
+            .enumerate()
+            .map(|(i, nf)| interface_info(i, nf))
+            .collect::<Vec<_>>();
+        println!("{}", interfaces_json(&infos)?);
+        return Ok(());
+    }
 
-pub fn print_interfaces<'a>(nfs: impl Iterator<Item = &'a Adapter>, list_number: bool) {
     if list_number {
         print!(" # ");
     }
-    print!("{:width$}", "name", width = 40);
-    print!("{:width$}", "description", width = 45);
-    print!("{:width$}", "up", width = 6);
-    print!("ip list");
+    print!("{}", pad_end(tr!("name", "名称"), 40));
+    print!("{}", pad_end(tr!("description", "描述"), 45));
+    print!("{}", pad_end(tr!("up", "启用"), 6));
+    print!("{}", tr!("ip list", "IP 列表"));
     println!();
 
     for (i, nf) in nfs.enumerate() {
         if list_number {
             print!("{:2} ", i);
         }
-        print!("{:width$}", nf.adapter_name(), width = 40);
-        print!("{:width$}", nf.description(), width = 45);
+        print!("{}", pad_end(&nf.adapter_name(), 40));
+        // descriptions are localized, chinese ones take two columns a character
+        print!("{}", pad_end(nf.description(), 45));
         print!(
-            "{:width$}",
-            nf.oper_status() == ipconfig::OperStatus::IfOperStatusUp,
-            width = 6
+            "{}",
+            pad_end(
+                &(nf.oper_status() == ipconfig::OperStatus::IfOperStatusUp).to_string(),
+                6
+            )
         );
         print!("[{}]", nf.ip_addresses().iter().format(", "));
         println!();
     }
+    Ok(())
+}
+
+/// an adapter as listed by `print_interfaces` in json
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct InterfaceInfo {
+    /// position in the listing, which `--interface` accepts
+    pub index: usize,
+    /// the adapter guid
+    pub name: String,
+    pub description: String,
+    pub up: bool,
+    pub ipv4: Vec<Ipv4Addr>,
+    pub ipv6: Vec<Ipv6Addr>,
+}
+
+fn interface_info(index: usize, adapter: &Adapter) -> InterfaceInfo {
+    let (mut ipv4, mut ipv6) = (Vec::new(), Vec::new());
+    for address in adapter.ip_addresses() {
+        match address {
+            IpAddr::V4(address) => ipv4.push(*address),
+            IpAddr::V6(address) => ipv6.push(*address),
+        }
+    }
+    InterfaceInfo {
+        index,
+        name: adapter.adapter_name().to_string(),
+        description: adapter.description().to_string(),
+        up: adapter.oper_status() == ipconfig::OperStatus::IfOperStatusUp,
+        ipv4,
+        ipv6,
+    }
 }
 
+pub fn interfaces_json(infos: &[InterfaceInfo]) -> Result<String> {
+    Ok(serde_json::to_string_pretty(infos)?)
+}
+
+/// what `match_interface` found
+#[derive(Debug, PartialEq, Eq)]
+pub enum InterfaceMatch {
+    Found(usize),
+    /// positions of every adapter that fits
+    Ambiguous(Vec<usize>),
+    NotFound,
+}
+
+/// pick an adapter, given as its description and ipv4 addresses in listing order, by `query`:
+/// a number is the position in the listing, an ipv4 address is one of the adapter's, anything
+/// else is part of the description, ignoring case, where a full match beats partial ones
+pub fn match_interface<'a>(
+    query: &str,
+    adapters: impl IntoIterator<Item = (&'a str, &'a [Ipv4Addr])>,
+) -> InterfaceMatch {
+    let adapters = adapters.into_iter().collect::<Vec<_>>();
+    let query = query.trim();
+    let matches = if let Ok(idx) = query.parse::<usize>() {
+        (idx < adapters.len()).then(|| idx).into_iter().collect()
+    } else if let Ok(ip) = query.parse::<Ipv4Addr>() {
+        adapters
+            .iter()
+            .positions(|(_, addresses)| addresses.contains(&ip))
+            .collect()
+    } else {
+        let query = query.to_lowercase();
+        let descriptions = adapters
+            .iter()
+            .map(|(description, _)| description.to_lowercase())
+            .collect::<Vec<_>>();
+        let exact = descriptions
+            .iter()
+            .positions(|d| *d == query)
+            .collect::<Vec<_>>();
+        if exact.is_empty() {
+            descriptions
+                .iter()
+                .positions(|d| d.contains(&query))
+                .collect()
+        } else {
+            exact
+        }
+    };
+    match matches.as_slice() {
+        [] => InterfaceMatch::NotFound,
+        [idx] => InterfaceMatch::Found(*idx),
+        _ => InterfaceMatch::Ambiguous(matches),
+    }
+}
+
+/// a hex dump of `bytes`, `width` of them per row behind their offset, grouped by 8 and
+/// followed by their printable ascii characters when `ascii` is set
 #[derive(Debug)]
-pub struct Bytes<'a>(pub &'a [u8]);
+pub struct HexDump<'a> {
+    pub bytes: &'a [u8],
+    pub width: usize,
+    pub ascii: bool,
+}
+
+impl<'a> HexDump<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self {
+            bytes,
+            width: 16,
+            ascii: false,
+        }
+    }
+}
 
-impl<'a> Display for Bytes<'a> {
+impl<'a> Display for HexDump<'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let len = self.0.len();
-        for (i, b) in self.0.iter().enumerate() {
-            write!(f, "{:02x} ", b)?;
-            match i % 16 {
-                7 => write!(f, " ")?,
-                15 => writeln!(f)?,
-                _ => {}
+        let width = self.width.max(1);
+        for (row, chunk) in self.bytes.chunks(width).enumerate() {
+            let mut line = format!("{:04x} ", row * width);
+            for i in 0..width {
+                if i % 8 == 0 {
+                    line.push(' ');
+                }
+                match chunk.get(i) {
+                    Some(b) => line += &format!("{:02x} ", b),
+                    // keep the ascii column of a short last row in line with the others
+                    None if self.ascii => line += "   ",
+                    None => break,
+                }
             }
-        }
-        if len % 16 != 0 {
-            writeln!(f)?;
+            if self.ascii {
+                line.push('|');
+                line.extend(chunk.iter().map(|&b| {
+                    if b.is_ascii_graphic() || b == b' ' {
+                        b as char
+                    } else {
+                        '.'
+                    }
+                }));
+                line.push('|');
+            }
+            writeln!(f, "{}", line.trim_end())?;
         }
         Ok(())
     }
@@ -507,27 +677,485 @@ impl From<(u16, u16)> for AppProtocol {
     }
 }
 
-impl Display for AppProtocol {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl AppProtocol {
+    /// display name, also the key of the application layer stat table
+    pub fn name(&self) -> &'static str {
         use AppProtocol::*;
         match self {
-            Ftp => write!(f, "FTP"),
-            Ssh => write!(f, "SSH"),
-            Telnet => write!(f, "Telnet"),
-            Smtp => write!(f, "SMTP"),
-            Dns => write!(f, "DNS"),
-            Dhcp => write!(f, "DHCP"),
-            Http => write!(f, "HTTP"),
-            Pop3 => write!(f, "POP3"),
-            Nntp => write!(f, "NNTP"),
-            Ntp => write!(f, "NTP"),
-            Imap => write!(f, "IMAP"),
-            Snmp => write!(f, "SNMP"),
-            Irc => write!(f, "IRC"),
-            Https => write!(f, "HTTPS"),
-            Unknown => write!(f, "Unknown"),
+            Ftp => "FTP",
+            Ssh => "SSH",
+            Telnet => "Telnet",
+            Smtp => "SMTP",
+            Dns => "DNS",
+            Dhcp => "DHCP",
+            Http => "HTTP",
+            Pop3 => "POP3",
+            Nntp => "NNTP",
+            Ntp => "NTP",
+            Imap => "IMAP",
+            Snmp => "SNMP",
+            Irc => "IRC",
+            Https => "HTTPS",
+            Unknown => "Unknown",
+        }
+    }
+}
+
+impl Display for AppProtocol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+const BYTE_UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+
+fn humanize(n: f64) -> String {
+    let mut n = n;
+    let mut unit = 0;
+    while n >= 1024.0 && unit < BYTE_UNITS.len() - 1 {
+        n /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{:.0} {}", n, BYTE_UNITS[unit])
+    } else {
+        format!("{:.1} {}", n, BYTE_UNITS[unit])
+    }
+}
+
+/// a unit shared by every tick of a plot axis, picked from the axis maximum
+/// so that the ticks stay comparable with each other
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AxisUnit {
+    /// e.g. "MB" or "k"
+    pub symbol: &'static str,
+    divisor: f64,
+    precision: usize,
+}
+
+impl AxisUnit {
+    fn pick(max: f64, base: f64, symbols: &[&'static str]) -> Self {
+        let mut unit = 0;
+        let mut divisor = 1.0;
+        while max / divisor >= base && unit < symbols.len() - 1 {
+            divisor *= base;
+            unit += 1;
+        }
+        Self {
+            symbol: symbols[unit],
+            divisor,
+            precision: if unit > 0 && max / divisor < 10.0 {
+                1
+            } else {
+                0
+            },
+        }
+    }
+
+    pub fn bytes(max: f64) -> Self {
+        Self::pick(max, 1024.0, &BYTE_UNITS)
+    }
+
+    pub fn packets(max: f64) -> Self {
+        Self::pick(max, 1000.0, &["", "k", "M"])
+    }
+
+    /// `value` in this unit, without the symbol
+    pub fn scale(&self, value: f64) -> String {
+        format!("{:.*}", self.precision, value / self.divisor)
+    }
+}
+
+/// format a byte count as "174.9 MB" style text
+pub fn format_bytes(bytes: u64) -> String {
+    humanize(bytes as f64)
+}
+
+/// format a count with thousands separators, as "12,483"
+pub fn format_count(n: u64) -> String {
+    let digits = n.to_string();
+    let mut text = String::with_capacity(digits.len() * 4 / 3);
+    for (i, c) in digits.chars().enumerate() {
+        if i != 0 && (digits.len() - i) % 3 == 0 {
+            text.push(',');
+        }
+        text.push(c);
+    }
+    text
+}
+
+/// format a byte rate, switching between B/s, KB/s and MB/s automatically
+pub fn format_byte_rate(bytes_per_sec: f64) -> String {
+    format!("{}/s", humanize(bytes_per_sec))
+}
+
+/// format a duration as hh:mm:ss, negative durations are treated as zero
+pub fn format_duration(duration: chrono::Duration) -> String {
+    let secs = duration.num_seconds().max(0);
+    format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+/// how the cli prints the time a packet was read
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeFormat {
+    /// rfc 3339 with milliseconds
+    Iso,
+    /// seconds since the unix epoch, with microseconds
+    Epoch,
+    /// seconds since the previous packet
+    Delta,
+    /// seconds since the capture started
+    Elapsed,
+}
+
+impl FromStr for TimeFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "iso" => Ok(Self::Iso),
+            "epoch" => Ok(Self::Epoch),
+            "delta" => Ok(Self::Delta),
+            "elapsed" => Ok(Self::Elapsed),
+            _ => Err(anyhow!(
+                "unknown time format \"{}\", expected iso, epoch, delta or elapsed",
+                s
+            )),
+        }
+    }
+}
+
+/// formats the times of consecutive packets, keeping what the relative formats count from
+pub struct Timestamps {
+    format: TimeFormat,
+    start: chrono::DateTime<chrono::Local>,
+    previous: Option<chrono::DateTime<chrono::Local>>,
+}
+
+impl Timestamps {
+    pub fn new(format: TimeFormat, start: chrono::DateTime<chrono::Local>) -> Self {
+        Self {
+            format,
+            start,
+            previous: None,
+        }
+    }
+
+    /// the time of the next packet, the first one being 0 seconds after its previous
+    pub fn format(&mut self, time: chrono::DateTime<chrono::Local>) -> String {
+        let text = match self.format {
+            TimeFormat::Iso => time.to_rfc3339_opts(chrono::SecondsFormat::Millis, false),
+            TimeFormat::Epoch => {
+                format!("{}.{:06}", time.timestamp(), time.timestamp_subsec_micros())
+            }
+            TimeFormat::Delta => format_seconds(time - self.previous.unwrap_or(time)),
+            TimeFormat::Elapsed => format_seconds(time - self.start),
+        };
+        self.previous = Some(time);
+        text
+    }
+}
+
+/// format a duration as seconds with microseconds, negative durations are treated as zero
+pub fn format_seconds(duration: chrono::Duration) -> String {
+    let micros = duration.num_microseconds().unwrap_or(i64::MAX).max(0);
+    format!("{}.{:06}", micros / 1_000_000, micros % 1_000_000)
+}
+
+/// terminal columns taken by `s`
+pub fn display_width(s: &str) -> usize {
+    s.chars().map(char_width).sum()
+}
+
+/// terminal columns taken by `c`, two for the east asian wide ones such as chinese
+pub fn char_width(c: char) -> usize {
+    match c as u32 {
+        0x1100..=0x115f
+        | 0x2e80..=0x303e
+        | 0x3041..=0x33ff
+        | 0x3400..=0x4dbf
+        | 0x4e00..=0x9fff
+        | 0xa000..=0xa4cf
+        | 0xac00..=0xd7a3
+        | 0xf900..=0xfaff
+        | 0xfe30..=0xfe4f
+        | 0xff00..=0xff60
+        | 0xffe0..=0xffe6
+        | 0x20000..=0x3fffd => 2,
+        _ => 1,
+    }
+}
+
+/// `s` followed by the spaces that make it `width` columns wide, which `{:width$}` gets wrong
+/// for wide characters as it counts them as one
+pub fn pad_end(s: &str, width: usize) -> String {
+    let padding = width.saturating_sub(display_width(s));
+    format!("{}{}", s, " ".repeat(padding))
+}
+
+/// `s` after the spaces that make it `width` columns wide
+pub fn pad_start(s: &str, width: usize) -> String {
+    let padding = width.saturating_sub(display_width(s));
+    format!("{}{}", " ".repeat(padding), s)
+}
+
+/// lay `rows` out under `headers` in plain text columns, the first left aligned and the rest,
+/// which hold numbers, right aligned
+pub fn text_table(headers: &[&str], rows: &[Vec<String>]) -> String {
+    let mut widths = headers
+        .iter()
+        .map(|header| display_width(header))
+        .collect::<Vec<_>>();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(display_width(cell));
+        }
+    }
+    let header = headers.iter().map(|header| header.to_string()).collect();
+    let mut text = String::new();
+    for row in iter::once(&header).chain(rows) {
+        let line = row
+            .iter()
+            .zip(&widths)
+            .enumerate()
+            .map(|(i, (cell, &width))| {
+                if i == 0 {
+                    pad_end(cell, width)
+                } else {
+                    pad_start(cell, width)
+                }
+            })
+            .join("  ");
+        text += line.trim_end();
+        text.push('\n');
+    }
+    text
+}
+
+/// parse a positive, possibly fractional, number of seconds such as "2.5"
+pub fn parse_seconds(s: &str) -> Result<std::time::Duration> {
+    let secs: f64 = s.trim().parse()?;
+    if !secs.is_finite() || secs <= 0.0 {
+        return Err(anyhow!("{} is not a positive number of seconds", s));
+    }
+    std::time::Duration::try_from_secs_f64(secs).map_err(|_| anyhow!("{} seconds is too long", s))
+}
+
+/// parse a positive byte count such as "50M", "1.5GB" or "4096", in the units of
+/// `format_bytes`, i.e. powers of 1024
+pub fn parse_size(s: &str) -> Result<u64> {
+    let s = s.trim();
+    let split = s.find(|c: char| c.is_ascii_alphabetic()).unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let shift = match unit.to_ascii_uppercase().trim_end_matches('B') {
+        "" => 0,
+        "K" => 10,
+        "M" => 20,
+        "G" => 30,
+        "T" => 40,
+        _ => return Err(anyhow!("unknown unit in {}, expected K, M, G or T", s)),
+    };
+    let number: f64 = number.trim().parse()?;
+    let bytes = number * (1u64 << shift) as f64;
+    if !bytes.is_finite() || bytes < 1.0 {
+        return Err(anyhow!("{} is not a positive size", s));
+    }
+    Ok(bytes as u64)
+}
+
+/// parse a positive duration such as "30m", "1h", "1.5d" or "90s", seconds without a unit
+pub fn parse_interval(s: &str) -> Result<std::time::Duration> {
+    let s = s.trim();
+    let (number, unit) = match s.char_indices().last() {
+        Some((i, 's')) => (&s[..i], 1.0),
+        Some((i, 'm')) => (&s[..i], 60.0),
+        Some((i, 'h')) => (&s[..i], 3600.0),
+        Some((i, 'd')) => (&s[..i], 86400.0),
+        _ => (s, 1.0),
+    };
+    // the unit is applied before the duration is built, which then fails rather than overflows
+    number
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|number| number.is_finite() && *number > 0.0)
+        .and_then(|number| std::time::Duration::try_from_secs_f64(number * unit).ok())
+        .ok_or_else(|| anyhow!("{} is not a positive duration such as 30m, 1h or 1d", s))
+}
+
+/// parse an endpoint such as "10.0.0.5:52311", or "10.0.0.5" for any port
+pub fn parse_endpoint(s: &str) -> Result<(Ipv4Addr, Option<u16>)> {
+    let s = s.trim();
+    let (ip, port) = match s.split_once(':') {
+        Some((ip, port)) => (ip, Some(port)),
+        None => (s, None),
+    };
+    let ip = ip
+        .parse()
+        .map_err(|_| anyhow!("{} is not an ipv4 address", ip))?;
+    let port = port
+        .map(|port| port.parse().map_err(|_| anyhow!("{} is not a port", port)))
+        .transpose()?;
+    Ok((ip, port))
+}
+
+/// parse the two endpoints of a conversation separated by a comma, such as
+/// "10.0.0.5:52311,93.184.216.34:443"
+pub fn parse_conversation(s: &str) -> Result<((Ipv4Addr, Option<u16>), (Ipv4Addr, Option<u16>))> {
+    let (a, b) = s
+        .split_once(',')
+        .ok_or_else(|| anyhow!("expected two endpoints separated by a comma"))?;
+    Ok((parse_endpoint(a)?, parse_endpoint(b)?))
+}
+
+/// exit codes of the cli, besides 0 for success
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitCode {
+    /// no packet matched under --quiet or --count-only
+    NoMatch = 1,
+    /// bad arguments or filter
+    Usage = 2,
+    /// not run as administrator
+    Privilege = 3,
+    /// the capture socket could not be opened or read
+    Socket = 4,
+    /// the output file could not be opened or written
+    Output = 5,
+}
+
+/// what both front ends tell when capturing needs administrator rights, found upfront by
+/// `is_elevated` or late by a socket refusing to open
+pub fn privilege_message() -> &'static str {
+    tr!(
+        "raw socket capture requires Administrator; re-run from an elevated prompt",
+        "捕获 IP 分组需要管理员权限，请以管理员身份重新运行"
+    )
+}
+
+/// an error the cli exits on with its own exit code
+#[derive(Debug)]
+pub struct CliError {
+    pub code: ExitCode,
+    pub message: String,
+}
+
+impl CliError {
+    pub fn new(code: ExitCode, message: impl Display) -> Self {
+        Self {
+            code,
+            message: message.to_string(),
         }
     }
+
+    pub fn privilege() -> Self {
+        Self::new(ExitCode::Privilege, privilege_message())
+    }
+
+    /// a failure to open or read the capture socket bound to `addr`
+    pub fn socket(err: &CaptureError, addr: Ipv4Addr) -> Self {
+        match err {
+            CaptureError::AccessDenied => Self::privilege(),
+            CaptureError::AdapterGone => Self::new(
+                ExitCode::Socket,
+                tr!(
+                    "{} is not an address of this machine",
+                    "{} 不是本机的地址",
+                    addr
+                ),
+            ),
+            _ => Self::new(
+                ExitCode::Socket,
+                tr!(
+                    "can not capture on {}: {}",
+                    "无法在 {} 上捕获：{}",
+                    addr,
+                    err
+                ),
+            ),
+        }
+    }
+
+    pub fn output(path: &Path, err: impl Display) -> Self {
+        Self::new(
+            ExitCode::Output,
+            tr!(
+                "writing to {} failed: {}",
+                "写入 {} 失败：{}",
+                path.display(),
+                err
+            ),
+        )
+    }
+}
+
+impl Display for CliError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for CliError {}
+
+/// the code to exit with on `err`: its own for a `CliError`, 1 for anything unexpected
+pub fn exit_code(err: &Error) -> i32 {
+    err.downcast_ref::<CliError>()
+        .map_or(1, |err| err.code as i32)
+}
+
+/// strftime format for the labels of a time axis spanning `span`, precise enough to
+/// tell adjacent ticks apart without printing digits that never change
+pub fn time_label_format(span: chrono::Duration) -> &'static str {
+    if span <= chrono::Duration::seconds(10) {
+        "%M:%S%.3f"
+    } else if span <= chrono::Duration::hours(1) {
+        "%H:%M:%S"
+    } else {
+        "%H:%M"
+    }
+}
+
+/// horizontal space kept free between two axis labels, in pixels
+const AXIS_LABEL_GAP: u32 = 20;
+
+/// how many labels to ask plotters for on an axis `width` pixels long, so that labels
+/// `label_width` pixels wide never overlap
+pub fn max_axis_labels(width: u32, label_width: u32) -> usize {
+    // plotters may return one key point more than requested, spaced at least width / (n + 1)
+    ((width / (label_width + AXIS_LABEL_GAP)) as usize)
+        .saturating_sub(1)
+        .max(1)
+}
+
+/// trailing moving average over `window` values, using a shorter window at the start
+/// so the result lines up with the input instead of being shifted
+pub fn moving_average(values: &[f64], window: usize) -> Vec<f64> {
+    let window = window.max(1);
+    let mut sum = 0.0;
+    values
+        .iter()
+        .enumerate()
+        .map(|(i, v)| {
+            sum += v;
+            if i >= window {
+                sum -= values[i - window];
+            }
+            sum / (i + 1).min(window) as f64
+        })
+        .collect()
+}
+
+/// how many adjacent values to merge so that `len` values shrink to at most `max_points`
+pub fn downsample_chunk(len: usize, max_points: usize) -> usize {
+    let max_points = max_points.max(1);
+    ((len + max_points - 1) / max_points).max(1)
+}
+
+/// merge every `chunk` adjacent values into their maximum, so short spikes stay visible
+pub fn downsample_max(values: &[f64], chunk: usize) -> Vec<f64> {
+    values
+        .chunks(chunk.max(1))
+        .map(|c| c.iter().copied().fold(f64::MIN, f64::max))
+        .collect()
 }
 
 pub fn alloc_console() -> io::Result<()> {
@@ -546,6 +1174,305 @@ pub fn attach_console() -> io::Result<()> {
     }
 }
 
+/// raised by the console ctrl handler on the first Ctrl+C
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+unsafe extern "system" fn ctrl_handler(ctrl_type: DWORD) -> BOOL {
+    match ctrl_type {
+        wincon::CTRL_C_EVENT | wincon::CTRL_BREAK_EVENT => {
+            // the second time, whatever was holding the first one up is not worth waiting for
+            if INTERRUPTED.swap(true, Ordering::SeqCst) {
+                process::exit(130);
+            }
+            TRUE
+        }
+        _ => FALSE,
+    }
+}
+
+/// turn Ctrl+C into a request to stop, see `interrupted`; a second Ctrl+C still terminates
+pub fn catch_interrupt() -> io::Result<()> {
+    if unsafe { SetConsoleCtrlHandler(Some(ctrl_handler), TRUE) } == 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+/// whether Ctrl+C was pressed since `catch_interrupt`
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// let the console interpret ansi escape sequences, which windows 10 supports but leaves off
+pub fn enable_ansi() -> io::Result<()> {
+    unsafe {
+        let handle = GetStdHandle(STD_OUTPUT_HANDLE);
+        let mut mode = 0;
+        if GetConsoleMode(handle, &mut mode) == 0
+            || SetConsoleMode(handle, mode | wincon::ENABLE_VIRTUAL_TERMINAL_PROCESSING) == 0
+        {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// when to color cli output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorChoice {
+    /// only when writing to a console
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// whether to color what goes to stdout, turning escape sequences on in the console if so
+    pub fn enabled(self) -> bool {
+        match self {
+            Self::Auto => atty::is(atty::Stream::Stdout) && enable_ansi().is_ok(),
+            Self::Always => {
+                // a pipe has no console mode to set, whoever reads it wanted the sequences
+                let _ = enable_ansi();
+                true
+            }
+            Self::Never => false,
+        }
+    }
+}
+
+impl FromStr for ColorChoice {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "auto" => Ok(Self::Auto),
+            "always" => Ok(Self::Always),
+            "never" => Ok(Self::Never),
+            _ => Err(anyhow!(
+                "unknown color choice \"{}\", expected auto, always or never",
+                s
+            )),
+        }
+    }
+}
+
+/// `text` in the 24 bit color `rgb` with ansi escape sequences
+pub fn ansi_color(text: impl Display, (r, g, b): (u8, u8, u8)) -> String {
+    format!("\x1b[38;2;{};{};{}m{}\x1b[0m", r, g, b, text)
+}
+
+/// `text` in bold with ansi escape sequences
+pub fn ansi_bold(text: impl Display) -> String {
+    format!("\x1b[1m{}\x1b[0m", text)
+}
+
+extern "C" {
+    fn _kbhit() -> i32;
+    fn _getch() -> i32;
+}
+
+/// the key pressed in the console since the last call, without waiting for one
+pub fn read_key() -> Option<char> {
+    unsafe {
+        if _kbhit() == 0 {
+            return None;
+        }
+        match _getch() {
+            // function and arrow keys come as a prefix and a second code, neither a character
+            0 | 0xe0 => {
+                _getch();
+                None
+            }
+            key => char::from_u32(key as u32),
+        }
+    }
+}
+
+/// the packet counters of the ip stack since boot, across all adapters; they wrap
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IpCounts {
+    pub received: u32,
+    pub sent: u32,
+    /// received packets the stack threw away, for want of buffer space among others
+    pub discarded: u32,
+}
+
+impl IpCounts {
+    /// packets received and sent since `before` was taken
+    pub fn packets_since(&self, before: &IpCounts) -> u64 {
+        self.received.wrapping_sub(before.received) as u64
+            + self.sent.wrapping_sub(before.sent) as u64
+    }
+
+    /// packets discarded since `before` was taken
+    pub fn discarded_since(&self, before: &IpCounts) -> u64 {
+        self.discarded.wrapping_sub(before.discarded) as u64
+    }
+}
+
+/// the counters of the ip stack as they are now, held against a later reading to tell how
+/// many packets went by while capturing
+pub fn ip_packet_counts() -> io::Result<IpCounts> {
+    unsafe {
+        let mut stats: MIB_IPSTATS = mem::zeroed();
+        match GetIpStatistics(&mut stats) {
+            ERROR_SUCCESS => Ok(IpCounts {
+                received: stats.dwInReceives,
+                sent: stats.dwOutRequests,
+                discarded: stats.dwInDiscards,
+            }),
+            err => Err(io::Error::from_raw_os_error(err as i32)),
+        }
+    }
+}
+
+/// the processor time the process has used so far, in kernel and user mode together
+pub fn process_cpu_time() -> io::Result<std::time::Duration> {
+    unsafe {
+        let mut times: [FILETIME; 4] = mem::zeroed();
+        let [creation, exit, kernel, user] = &mut times;
+        if GetProcessTimes(GetCurrentProcess(), creation, exit, kernel, user) == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let ticks = filetime_ticks(kernel) + filetime_ticks(user);
+        Ok(std::time::Duration::from_nanos(ticks * 100))
+    }
+}
+
+/// a `FILETIME` in its 100 ns units
+fn filetime_ticks(time: &FILETIME) -> u64 {
+    (time.dwHighDateTime as u64) << 32 | time.dwLowDateTime as u64
+}
+
+/// whether the process token is elevated, raw sockets can not be opened otherwise
+pub fn is_elevated() -> io::Result<bool> {
+    unsafe {
+        let mut token = ptr::null_mut();
+        if OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token) == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut elevation: TOKEN_ELEVATION = mem::zeroed();
+        let mut size = 0;
+        let result = GetTokenInformation(
+            token,
+            TokenElevation,
+            &mut elevation as *mut _ as *mut _,
+            mem::size_of::<TOKEN_ELEVATION>() as DWORD,
+            &mut size,
+        );
+        let err = io::Error::last_os_error();
+        CloseHandle(token);
+        if result == 0 {
+            Err(err)
+        } else {
+            Ok(elevation.TokenIsElevated != 0)
+        }
+    }
+}
+
+fn wide(s: &OsStr) -> Vec<u16> {
+    s.encode_wide().chain(iter::once(0)).collect()
+}
+
+/// `ShellExecuteW` with `verb` on `file`
+fn shell_execute(verb: &str, file: &OsStr, params: Option<&str>) -> Result<()> {
+    let params = params.map(|params| wide(OsStr::new(params)));
+    let result = unsafe {
+        ShellExecuteW(
+            ptr::null_mut(),
+            wide(OsStr::new(verb)).as_ptr(),
+            wide(file).as_ptr(),
+            params
+                .as_ref()
+                .map_or(ptr::null(), |params| params.as_ptr()),
+            ptr::null(),
+            SW_SHOWNORMAL,
+        )
+    };
+    // anything up to 32 is an error code, e.g. when the uac prompt is declined
+    if result as usize <= 32 {
+        return Err(anyhow!("ShellExecute failed (code {})", result as usize));
+    }
+    Ok(())
+}
+
+/// start this executable again with `args` through the uac prompt
+pub fn relaunch_elevated(args: &[String]) -> Result<()> {
+    let exe = env::current_exe()?;
+    let params = args.iter().map(|arg| quote_arg(arg)).join(" ");
+    shell_execute("runas", exe.as_os_str(), Some(&params))
+}
+
+/// open `url` in the default browser
+pub fn open_url(url: &str) -> Result<()> {
+    shell_execute("open", OsStr::new(url), None)
+}
+
+fn registry_string(key: &str, value: &str) -> Option<String> {
+    let mut buffer = [0u16; 256];
+    let mut size = mem::size_of_val(&buffer) as DWORD;
+    let result = unsafe {
+        RegGetValueW(
+            HKEY_LOCAL_MACHINE,
+            wide(OsStr::new(key)).as_ptr(),
+            wide(OsStr::new(value)).as_ptr(),
+            RRF_RT_REG_SZ,
+            ptr::null_mut(),
+            buffer.as_mut_ptr() as *mut _,
+            &mut size,
+        )
+    };
+    if result != ERROR_SUCCESS as i32 {
+        return None;
+    }
+    // the size is in bytes and counts the terminating null
+    let len = (size as usize / 2).saturating_sub(1);
+    Some(String::from_utf16_lossy(&buffer[..len]))
+}
+
+/// e.g. `Windows 10 Pro 21H2 (build 19044)`; the registry is read since `GetVersionExW`
+/// reports whatever the manifest declares support for
+pub fn os_version() -> String {
+    const KEY: &str = r"SOFTWARE\Microsoft\Windows NT\CurrentVersion";
+    let mut version = registry_string(KEY, "ProductName").unwrap_or_else(|| "Windows".to_string());
+    if let Some(display) = registry_string(KEY, "DisplayVersion") {
+        version += &format!(" {}", display);
+    }
+    if let Some(build) = registry_string(KEY, "CurrentBuild") {
+        version += &format!(" (build {})", build);
+    }
+    version
+}
+
+/// quote `arg` so that `CommandLineToArgvW` splits it back unchanged
+fn quote_arg(arg: &str) -> String {
+    if !arg.is_empty() && !arg.contains(&[' ', '\t', '"'][..]) {
+        return arg.to_string();
+    }
+    let mut quoted = String::from('"');
+    let mut backslashes = 0;
+    for c in arg.chars() {
+        if c == '\\' {
+            backslashes += 1;
+            continue;
+        }
+        // backslashes only escape when a quote follows them
+        let escapes = if c == '"' {
+            backslashes * 2 + 1
+        } else {
+            backslashes
+        };
+        quoted.extend(iter::repeat('\\').take(escapes));
+        quoted.push(c);
+        backslashes = 0;
+    }
+    quoted.extend(iter::repeat('\\').take(backslashes * 2));
+    quoted.push('"');
+    quoted
+}
+
 /// macro to specify dimensions in gui
 #[macro_export]
 macro_rules! dim {
@@ -622,3 +1549,429 @@ macro_rules! size {
         }
     };
 }
+
+#[cfg(test)]
+mod utils_test {
+    use super::*;
+
+    #[test]
+    fn test_format_bytes() {
+        let table = [
+            (0, "0 B"),
+            (1, "1 B"),
+            (1023, "1023 B"),
+            (1024, "1.0 KB"),
+            (1536, "1.5 KB"),
+            (1024 * 1024, "1.0 MB"),
+            (183429113, "174.9 MB"),
+            (5 * 1024 * 1024 * 1024, "5.0 GB"),
+            (3 * 1024 * 1024 * 1024 * 1024, "3.0 TB"),
+            (2048 * 1024 * 1024 * 1024 * 1024, "2048.0 TB"),
+        ];
+        for (bytes, text) in table {
+            assert_eq!(format_bytes(bytes), text);
+        }
+    }
+
+    #[test]
+    fn test_format_count() {
+        assert_eq!(format_count(0), "0");
+        assert_eq!(format_count(999), "999");
+        assert_eq!(format_count(1_052), "1,052");
+        assert_eq!(format_count(12_483), "12,483");
+        assert_eq!(format_count(1_234_567), "1,234,567");
+    }
+
+    #[test]
+    fn test_format_duration() {
+        use chrono::Duration;
+        assert_eq!(format_duration(Duration::zero()), "00:00:00");
+        assert_eq!(format_duration(Duration::milliseconds(151_900)), "00:02:31");
+        assert_eq!(
+            format_duration(Duration::seconds(100 * 3600 + 61)),
+            "100:01:01"
+        );
+        assert_eq!(format_duration(Duration::seconds(-5)), "00:00:00");
+    }
+
+    #[test]
+    fn test_moving_average() {
+        assert_eq!(moving_average(&[], 5), Vec::<f64>::new());
+        assert_eq!(
+            moving_average(&[2.0, 4.0, 6.0, 8.0], 2),
+            vec![2.0, 3.0, 5.0, 7.0]
+        );
+        assert_eq!(moving_average(&[3.0, 6.0, 9.0], 5), vec![3.0, 4.5, 6.0]);
+        assert_eq!(moving_average(&[1.0, 5.0], 0), vec![1.0, 5.0]);
+    }
+
+    #[test]
+    fn test_axis_unit() {
+        let unit = AxisUnit::bytes(1.5 * 1024.0 * 1024.0);
+        assert_eq!(unit.symbol, "MB");
+        assert_eq!(unit.scale(1.2 * 1024.0 * 1024.0), "1.2");
+        // ticks below the unit still use it
+        assert_eq!(unit.scale(350.0 * 1024.0), "0.3");
+
+        let unit = AxisUnit::bytes(500.0 * 1024.0);
+        assert_eq!(unit.symbol, "KB");
+        assert_eq!(unit.scale(350.0 * 1024.0), "350");
+
+        let unit = AxisUnit::bytes(10.0);
+        assert_eq!(unit.symbol, "B");
+        assert_eq!(unit.scale(7.0), "7");
+
+        let unit = AxisUnit::packets(1500.0);
+        assert_eq!(unit.symbol, "k");
+        assert_eq!(unit.scale(1200.0), "1.2");
+        assert_eq!(AxisUnit::packets(999.0).symbol, "");
+    }
+
+    #[test]
+    fn test_downsample() {
+        assert_eq!(downsample_chunk(0, 2000), 1);
+        assert_eq!(downsample_chunk(2000, 2000), 1);
+        assert_eq!(downsample_chunk(2001, 2000), 2);
+        assert_eq!(downsample_chunk(10, 0), 10);
+        assert_eq!(
+            downsample_max(&[1.0, 5.0, 2.0, 0.0, 3.0], 2),
+            vec![5.0, 2.0, 3.0]
+        );
+        assert_eq!(downsample_max(&[1.0, 5.0], 1), vec![1.0, 5.0]);
+
+        // an hour of 200ms buckets, with a single spike that has to survive
+        let mut values = vec![1.0; 18000];
+        values[12345] = 100.0;
+        for max_points in [1, 640, 1920, 2000] {
+            let points = downsample_max(&values, downsample_chunk(values.len(), max_points));
+            assert!(points.len() <= max_points);
+            assert_eq!(points.iter().copied().fold(f64::MIN, f64::max), 100.0);
+        }
+    }
+
+    #[test]
+    fn test_axis_labels() {
+        assert_eq!(
+            time_label_format(chrono::Duration::milliseconds(800)),
+            "%M:%S%.3f"
+        );
+        assert_eq!(time_label_format(chrono::Duration::minutes(5)), "%H:%M:%S");
+        assert_eq!(time_label_format(chrono::Duration::hours(3)), "%H:%M");
+
+        // roughly the width of a 12px Segoe UI digit
+        const CHAR_WIDTH: u32 = 7;
+        let time = chrono::NaiveDate::from_ymd(2021, 11, 8).and_hms_milli(23, 59, 58, 123);
+        for span in [
+            chrono::Duration::seconds(2),
+            chrono::Duration::minutes(5),
+            chrono::Duration::hours(2),
+        ] {
+            let label = time.format(time_label_format(span)).to_string();
+            let label_width = label.len() as u32 * CHAR_WIDTH;
+            for width in (600..=3840).step_by(40) {
+                let labels = max_axis_labels(width, label_width);
+                assert!(labels >= 1);
+                assert!(width / (labels as u32 + 1) >= label_width + AXIS_LABEL_GAP);
+            }
+        }
+        assert_eq!(max_axis_labels(0, 80), 1);
+    }
+
+    #[test]
+    fn test_format_byte_rate() {
+        assert_eq!(format_byte_rate(0.0), "0 B/s");
+        assert_eq!(format_byte_rate(512.4), "512 B/s");
+        assert_eq!(format_byte_rate(102.0 * 1024.0), "102.0 KB/s");
+        assert_eq!(format_byte_rate(1.3 * 1024.0 * 1024.0), "1.3 MB/s");
+    }
+
+    #[test]
+    fn test_protocol_name_round_trip() {
+        // stat rows are keyed by these names and mapped back on double click
+        for proto in [Protocol::Icmp, Protocol::Tcp, Protocol::Udp, Protocol::Igmp] {
+            assert_eq!(
+                str_to_trans_protocol(trans_protocol_name(proto)).unwrap(),
+                proto
+            );
+        }
+        assert_eq!(
+            str_to_trans_protocol(trans_protocol_name(Protocol::Unknown(253))).unwrap(),
+            Protocol::Unknown(0)
+        );
+        for proto in [AppProtocol::Dns, AppProtocol::Https, AppProtocol::Unknown] {
+            assert_eq!(proto.to_string().parse::<AppProtocol>().unwrap(), proto);
+        }
+        assert!(str_to_trans_protocol("合计").is_err());
+    }
+
+    #[test]
+    fn test_timestamps() {
+        use chrono::TimeZone;
+
+        let start = chrono::Local.timestamp(1636299000, 0);
+        let times = [
+            chrono::Local.timestamp(1636299000, 250_000_000),
+            chrono::Local.timestamp(1636299002, 1_000),
+            // clocks may step back, which must not print a negative time
+            chrono::Local.timestamp(1636299001, 0),
+        ];
+        let format = |format: TimeFormat| {
+            let mut timestamps = Timestamps::new(format, start);
+            times
+                .iter()
+                .map(|&time| timestamps.format(time))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            format(TimeFormat::Epoch),
+            [
+                "1636299000.250000",
+                "1636299002.000001",
+                "1636299001.000000"
+            ]
+        );
+        assert_eq!(
+            format(TimeFormat::Delta),
+            ["0.000000", "1.750001", "0.000000"]
+        );
+        assert_eq!(
+            format(TimeFormat::Elapsed),
+            ["0.250000", "2.000001", "1.000000"]
+        );
+        let iso = format(TimeFormat::Iso);
+        assert_eq!(
+            chrono::DateTime::parse_from_rfc3339(&iso[0]).unwrap(),
+            times[0]
+        );
+        assert!(iso[1].contains(":02.000"));
+        assert_eq!("delta".parse::<TimeFormat>().unwrap(), TimeFormat::Delta);
+        assert!("unix".parse::<TimeFormat>().is_err());
+    }
+
+    #[test]
+    fn test_ansi() {
+        assert_eq!(
+            ansi_color("TCP", (17, 125, 187)),
+            "\x1b[38;2;17;125;187mTCP\x1b[0m"
+        );
+        assert_eq!(ansi_bold(80), "\x1b[1m80\x1b[0m");
+        assert_eq!("never".parse::<ColorChoice>().unwrap(), ColorChoice::Never);
+        assert!(!ColorChoice::Never.enabled());
+        assert!("yes".parse::<ColorChoice>().is_err());
+    }
+
+    #[test]
+    fn test_hex_dump() {
+        let bytes = b"GET / HTTP/1.1\r\n\x00\xff~";
+        assert_eq!(
+            HexDump::new(bytes).to_string(),
+            "0000  47 45 54 20 2f 20 48 54  54 50 2f 31 2e 31 0d 0a\n\
+             0010  00 ff 7e\n"
+        );
+        let dump = HexDump {
+            bytes,
+            width: 8,
+            ascii: true,
+        };
+        assert_eq!(
+            dump.to_string(),
+            "0000  47 45 54 20 2f 20 48 54 |GET / HT|\n\
+             0008  54 50 2f 31 2e 31 0d 0a |TP/1.1..|\n\
+             0010  00 ff 7e                |..~|\n"
+        );
+        let dump = HexDump {
+            bytes: &bytes[..10],
+            width: 12,
+            ascii: true,
+        };
+        assert_eq!(
+            dump.to_string(),
+            "0000  47 45 54 20 2f 20 48 54  54 50       |GET / HTTP|\n"
+        );
+        assert_eq!(HexDump::new(&[]).to_string(), "");
+    }
+
+    #[test]
+    fn test_text_table() {
+        let rows = vec![
+            vec!["TCP".to_string(), "1,024".to_string()],
+            vec!["ICMP".to_string(), "3".to_string()],
+        ];
+        assert_eq!(
+            text_table(&["protocol", "packets"], &rows),
+            "protocol  packets\nTCP         1,024\nICMP            3\n"
+        );
+        assert_eq!(
+            text_table(&["protocol", "packets"], &[]),
+            "protocol  packets\n"
+        );
+        // chinese takes two columns a character
+        let rows = vec![vec!["网络层".to_string(), "12".to_string()]];
+        assert_eq!(
+            text_table(&["协议", "分组数"], &rows),
+            "协议    分组数\n网络层      12\n"
+        );
+    }
+
+    #[test]
+    fn test_pad() {
+        assert_eq!(display_width("Intel(R) Wi-Fi 6 AX201"), 22);
+        assert_eq!(display_width("以太网 2"), 8);
+        assert_eq!(display_width("ｗｉｄｅ"), 8);
+        assert_eq!(pad_end("以太网", 8), "以太网  ");
+        assert_eq!(pad_start("以太网", 8), "  以太网");
+        assert_eq!(pad_end("eth0", 2), "eth0");
+        // adapters listed one under the other line up however their descriptions are written
+        let width = display_width(&pad_end("Realtek PCIe GbE 系列控制器", 45));
+        assert_eq!(width, 45);
+        assert_eq!(display_width(&pad_end("Intel(R) Ethernet", 45)), width);
+    }
+
+    #[test]
+    fn test_parse_seconds() {
+        assert_eq!(
+            parse_seconds("3").unwrap(),
+            std::time::Duration::from_secs(3)
+        );
+        assert_eq!(
+            parse_seconds(" 2.5 ").unwrap(),
+            std::time::Duration::from_millis(2500)
+        );
+        assert!(parse_seconds("0").is_err());
+        assert!(parse_seconds("-1").is_err());
+        assert!(parse_seconds("inf").is_err());
+        assert!(parse_seconds("soon").is_err());
+        assert!(parse_seconds("1e30").is_err());
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("4096").unwrap(), 4096);
+        assert_eq!(parse_size("50M").unwrap(), 50 << 20);
+        assert_eq!(parse_size(" 1.5 gb ").unwrap(), 3 << 29);
+        assert_eq!(parse_size("2KB").unwrap(), 2048);
+        assert!(parse_size("0").is_err());
+        assert!(parse_size("10X").is_err());
+        assert!(parse_size("M").is_err());
+
+        let secs = |s| parse_interval(s).unwrap().as_secs_f64();
+        assert_eq!(secs("1h"), 3600.0);
+        assert_eq!(secs("30m"), 1800.0);
+        assert_eq!(secs("1.5d"), 129600.0);
+        assert_eq!(secs("90"), 90.0);
+        assert_eq!(secs("2s"), 2.0);
+        assert!(parse_interval("0h").is_err());
+        assert!(parse_interval("1w").is_err());
+        assert!(parse_interval("1e15d").is_err());
+    }
+
+    #[test]
+    fn test_parse_conversation() {
+        let client = Ipv4Addr::new(10, 0, 0, 5);
+        let server = Ipv4Addr::new(93, 184, 216, 34);
+        assert_eq!(parse_endpoint("10.0.0.5").unwrap(), (client, None));
+        assert_eq!(
+            parse_conversation("10.0.0.5:52311, 93.184.216.34:443").unwrap(),
+            ((client, Some(52311)), (server, Some(443)))
+        );
+        assert!(parse_endpoint("10.0.0.5:https").is_err());
+        assert!(parse_endpoint("example.com:443").is_err());
+        assert!(parse_conversation("10.0.0.5:52311").is_err());
+    }
+
+    #[test]
+    fn test_cli_error() {
+        let addr = Ipv4Addr::new(10, 0, 3, 4);
+        let err = CliError::socket(&CaptureError::AccessDenied, addr);
+        assert_eq!(err.code, ExitCode::Privilege);
+        assert_eq!(
+            err.to_string(),
+            "raw socket capture requires Administrator; re-run from an elevated prompt"
+        );
+        // the late refusal tells the same as the upfront check
+        assert_eq!(err.message, CliError::privilege().message);
+        assert_eq!(err.message, privilege_message());
+        let err = CliError::socket(&CaptureError::AdapterGone, addr);
+        assert_eq!(err.code, ExitCode::Socket);
+        assert_eq!(
+            err.to_string(),
+            "10.0.3.4 is not an address of this machine"
+        );
+        let err = io::Error::new(io::ErrorKind::Other, "busy");
+        let err = CliError::socket(&CaptureError::Other(err), addr);
+        assert_eq!(err.code, ExitCode::Socket);
+        assert_eq!(err.to_string(), "can not capture on 10.0.3.4: busy");
+        let err = CliError::output(Path::new("out.csv"), "disk full");
+        assert_eq!(err.code, ExitCode::Output);
+        assert_eq!(err.to_string(), "writing to out.csv failed: disk full");
+
+        assert_eq!(exit_code(&CliError::privilege().into()), 3);
+        assert_eq!(
+            exit_code(&Error::from(CliError::output(Path::new("a"), "b"))),
+            5
+        );
+        // context added on the way up does not hide the code
+        let err = Error::from(CliError::new(ExitCode::Usage, "bad")).context("while starting");
+        assert_eq!(exit_code(&err), 2);
+        assert_eq!(exit_code(&anyhow!("something else")), 1);
+    }
+
+    #[test]
+    fn test_interfaces_json() {
+        let infos = [InterfaceInfo {
+            index: 0,
+            name: "{4D36E972-E325-11CE-BFC1-08002BE10318}".to_string(),
+            description: "Intel(R) Wi-Fi 6 AX201".to_string(),
+            up: true,
+            ipv4: vec![Ipv4Addr::new(192, 168, 1, 5)],
+            ipv6: vec!["fe80::1".parse().unwrap()],
+        }];
+        let json: serde_json::Value =
+            serde_json::from_str(&interfaces_json(&infos).unwrap()).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!([{
+                "index": 0,
+                "name": "{4D36E972-E325-11CE-BFC1-08002BE10318}",
+                "description": "Intel(R) Wi-Fi 6 AX201",
+                "up": true,
+                "ipv4": ["192.168.1.5"],
+                "ipv6": ["fe80::1"],
+            }])
+        );
+        assert_eq!(interfaces_json(&[]).unwrap(), "[]");
+    }
+
+    #[test]
+    fn test_match_interface() {
+        let wifi = [Ipv4Addr::new(192, 168, 1, 5)];
+        let ethernet = [Ipv4Addr::new(10, 0, 0, 5), Ipv4Addr::new(10, 0, 1, 5)];
+        let adapters = [
+            ("Intel(R) Wi-Fi 6 AX201", &wifi[..]),
+            ("Realtek PCIe GbE Family Controller", &ethernet[..]),
+            ("Realtek PCIe GbE Family Controller #2", &[][..]),
+        ];
+        let find = |query: &str| match_interface(query, adapters.iter().copied());
+        assert_eq!(find("1"), InterfaceMatch::Found(1));
+        assert_eq!(find("3"), InterfaceMatch::NotFound);
+        assert_eq!(find("10.0.1.5"), InterfaceMatch::Found(1));
+        assert_eq!(find("10.0.0.6"), InterfaceMatch::NotFound);
+        assert_eq!(find("wi-fi"), InterfaceMatch::Found(0));
+        assert_eq!(find("realtek"), InterfaceMatch::Ambiguous(vec![1, 2]));
+        assert_eq!(
+            find("realtek pcie gbe family controller"),
+            InterfaceMatch::Found(1)
+        );
+        assert_eq!(find("bluetooth"), InterfaceMatch::NotFound);
+    }
+
+    #[test]
+    fn test_quote_arg() {
+        assert_eq!(quote_arg("--debug"), "--debug");
+        assert_eq!(quote_arg(""), "\"\"");
+        assert_eq!(quote_arg("a b"), "\"a b\"");
+        assert_eq!(quote_arg(r#"say "hi""#), r#""say \"hi\"""#);
+        assert_eq!(quote_arg(r"C:\some dir\"), r#""C:\some dir\\""#);
+        assert_eq!(quote_arg(r#"a\"b c"#), r#""a\\\"b c""#);
+    }
+}